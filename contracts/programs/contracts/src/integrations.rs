@@ -1,4 +1,5 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
 use anchor_spl::token::{Token, TokenAccount};
 
 // Jupiter Aggregator Integration
@@ -6,11 +7,14 @@ use anchor_spl::token::{Token, TokenAccount};
 pub mod jupiter {
     use super::*;
     
-    // Jupiter program ID - using actual program ID in byte array format
-    pub const JUPITER_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        1, 16, 106, 110, 107, 91, 33, 106, 72, 198, 34, 156, 221, 205, 112, 238, 
-        188, 163, 65, 120, 50, 91, 190, 150, 162, 145, 196, 146, 158, 246, 87, 78
-    ]);
+    // Jupiter v6 aggregator program ID (same on every cluster)
+    pub const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+    
+    // Maximum route labels recorded per execution event
+    pub const MAX_ROUTE_LABELS: usize = 4;
+    
+    // Maximum route steps accepted, bounds compute cost and transaction size
+    pub const MAX_ROUTE_STEPS: usize = 8;
     
     #[derive(Clone)]
     pub struct JupiterSwapParams {
@@ -19,6 +23,8 @@ pub mod jupiter {
         pub amount: u64,
         pub slippage_bps: u16,
         pub platform_fee_bps: u16, // Our 0.3% fee
+        pub max_route_fee: Option<u64>, // Cap on summed AMM fees across the route
+        pub max_price_impact: Option<u16>, // Cap on the quote's price impact in bps, buy intents only
     }
     
     // Jupiter swap instruction data structure
@@ -29,6 +35,7 @@ pub mod jupiter {
         pub quoted_out_amount: u64,
         pub slippage_bps: u16,
         pub platform_fee_bps: u16,
+        pub price_impact_bps: u16, // Quote's priceImpactPct converted to bps
     }
     
     #[derive(AnchorSerialize, AnchorDeserialize, Clone)]
@@ -57,6 +64,13 @@ pub mod jupiter {
     ) -> Result<u64> {
         msg!("🚀 Executing Jupiter swap with route optimization");
         
+        require!(
+            jupiter_swap_data.route_plan.len() <= MAX_ROUTE_STEPS,
+            crate::IntentError::RouteTooLong
+        );
+        check_route_fee(&jupiter_swap_data, swap_params.max_route_fee)?;
+        check_price_impact(&jupiter_swap_data, swap_params.max_price_impact)?;
+        
         // Validate Jupiter route matches our parameters
         require!(
             jupiter_swap_data.in_amount == swap_params.amount,
@@ -107,6 +121,46 @@ pub mod jupiter {
         Ok(estimated_output)
     }
 
+    /// Reject routes that meet slippage but pay excessive AMM fees along the way
+    pub fn check_route_fee(jupiter_swap_data: &JupiterSwapData, max_route_fee: Option<u64>) -> Result<()> {
+        let Some(max_route_fee) = max_route_fee else {
+            return Ok(());
+        };
+        let route_fee = jupiter_swap_data
+            .route_plan
+            .iter()
+            .try_fold(0u64, |total, step| total.checked_add(step.swap_info.fee_amount))
+            .ok_or(crate::IntentError::RouteFeeTooHigh)?;
+        
+        msg!("💸 Route AMM fees: {} (max {})", route_fee, max_route_fee);
+        require!(route_fee <= max_route_fee, crate::IntentError::RouteFeeTooHigh);
+        Ok(())
+    }
+
+    /// Reject quotes that would move the price more than the intent allows
+    pub fn check_price_impact(jupiter_swap_data: &JupiterSwapData, max_price_impact: Option<u16>) -> Result<()> {
+        let Some(max_price_impact) = max_price_impact else {
+            return Ok(());
+        };
+        
+        msg!("📉 Quote price impact: {} bps (max {})", jupiter_swap_data.price_impact_bps, max_price_impact);
+        require!(
+            jupiter_swap_data.price_impact_bps <= max_price_impact,
+            crate::IntentError::PriceImpactTooHigh
+        );
+        Ok(())
+    }
+
+    /// Venue labels of the route taken, bounded to MAX_ROUTE_LABELS
+    pub fn route_labels(jupiter_swap_data: &JupiterSwapData) -> Vec<String> {
+        jupiter_swap_data
+            .route_plan
+            .iter()
+            .take(MAX_ROUTE_LABELS)
+            .map(|step| step.swap_info.label.clone())
+            .collect()
+    }
+
     /// Simplified Jupiter swap execution without full Context
     pub fn execute_jupiter_swap_simple(
         _user: &AccountInfo,
@@ -115,8 +169,15 @@ pub mod jupiter {
         _jupiter_program: &AccountInfo,
         _token_program: &AccountInfo,
         params: JupiterSwapParams,
-        _swap_data: JupiterSwapData,
+        swap_data: JupiterSwapData,
     ) -> Result<u64> {
+        require!(
+            swap_data.route_plan.len() <= MAX_ROUTE_STEPS,
+            crate::IntentError::RouteTooLong
+        );
+        check_route_fee(&swap_data, params.max_route_fee)?;
+        check_price_impact(&swap_data, params.max_price_impact)?;
+        
         msg!("🚀 Executing Jupiter aggregated swap...");
         msg!("From: {} → To: {}", params.from_mint, params.to_mint);
         msg!("Amount: {} tokens", params.amount);
@@ -147,11 +208,116 @@ pub mod jupiter {
 pub mod raydium {
     use super::*;
     
-    // Raydium AMM program ID - using actual program ID in byte array format
-    pub const RAYDIUM_AMM_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        4, 41, 205, 89, 168, 95, 71, 139, 14, 36, 134, 74, 68, 217, 73, 219,
-        89, 151, 58, 27, 154, 213, 207, 160, 154, 134, 135, 163, 204, 126, 230, 245
-    ]);
+    // Raydium AMM v4 program ID
+    #[cfg(not(feature = "devnet"))]
+    pub const RAYDIUM_AMM_PROGRAM_ID: Pubkey = pubkey!("675kPX9MHTjS2zt1qfr1NYHuzeLXfQM9H24wFSUt1Mp8");
+    #[cfg(feature = "devnet")]
+    pub const RAYDIUM_AMM_PROGRAM_ID: Pubkey = pubkey!("HWy1jotHpo6UqeQxx49dpYYdQB8wj9Qk9MdxwjLvDHB8");
+    
+    // Byte offsets of the coin/pc vault pubkeys inside Raydium's on-chain AmmInfo
+    pub const AMM_COIN_VAULT_OFFSET: usize = 336;
+    pub const AMM_PC_VAULT_OFFSET: usize = 368;
+    
+    // AmmInstruction tags
+    pub const DEPOSIT_TAG: u8 = 3;
+    pub const WITHDRAW_TAG: u8 = 4;
+    
+    // Accounts of an AMM v4 Deposit. Raydium checks each one against the pool's own state.
+    pub struct RaydiumDeposit {
+        pub amm: Pubkey,
+        pub amm_authority: Pubkey,
+        pub amm_open_orders: Pubkey,
+        pub amm_target_orders: Pubkey,
+        pub lp_mint: Pubkey,
+        pub pool_coin_token_account: Pubkey,
+        pub pool_pc_token_account: Pubkey,
+        pub serum_market: Pubkey,
+        pub user_coin_token_account: Pubkey,
+        pub user_pc_token_account: Pubkey,
+        pub user_lp_token_account: Pubkey,
+        pub user_owner: Pubkey,
+        pub serum_event_queue: Pubkey,
+    }
+    
+    impl RaydiumDeposit {
+        // Deposit sized off `base_side` (0 = coin, 1 = pc), spending at most the given maximums
+        pub fn instruction(&self, max_coin_amount: u64, max_pc_amount: u64, base_side: u64) -> Instruction {
+            let accounts = vec![
+                AccountMeta::new_readonly(anchor_spl::token::ID, false),
+                AccountMeta::new(self.amm, false),
+                AccountMeta::new_readonly(self.amm_authority, false),
+                AccountMeta::new_readonly(self.amm_open_orders, false),
+                AccountMeta::new(self.amm_target_orders, false),
+                AccountMeta::new(self.lp_mint, false),
+                AccountMeta::new(self.pool_coin_token_account, false),
+                AccountMeta::new(self.pool_pc_token_account, false),
+                AccountMeta::new_readonly(self.serum_market, false),
+                AccountMeta::new(self.user_coin_token_account, false),
+                AccountMeta::new(self.user_pc_token_account, false),
+                AccountMeta::new(self.user_lp_token_account, false),
+                AccountMeta::new_readonly(self.user_owner, true),
+                AccountMeta::new_readonly(self.serum_event_queue, false),
+            ];
+            let mut data = vec![DEPOSIT_TAG];
+            data.extend_from_slice(&max_coin_amount.to_le_bytes());
+            data.extend_from_slice(&max_pc_amount.to_le_bytes());
+            data.extend_from_slice(&base_side.to_le_bytes());
+            Instruction { program_id: RAYDIUM_AMM_PROGRAM_ID, accounts, data }
+        }
+    }
+    
+    pub struct RaydiumWithdraw {
+        pub amm: Pubkey,
+        pub amm_authority: Pubkey,
+        pub amm_open_orders: Pubkey,
+        pub amm_target_orders: Pubkey,
+        pub lp_mint: Pubkey,
+        pub pool_coin_token_account: Pubkey,
+        pub pool_pc_token_account: Pubkey,
+        pub serum_program: Pubkey,
+        pub serum_market: Pubkey,
+        pub serum_coin_vault: Pubkey,
+        pub serum_pc_vault: Pubkey,
+        pub serum_vault_signer: Pubkey,
+        pub user_lp_token_account: Pubkey,
+        pub user_coin_token_account: Pubkey,
+        pub user_pc_token_account: Pubkey,
+        pub user_owner: Pubkey,
+        pub serum_event_queue: Pubkey,
+        pub serum_bids: Pubkey,
+        pub serum_asks: Pubkey,
+    }
+    
+    impl RaydiumWithdraw {
+        // Burns `amount` LP and pays both sides out pro rata
+        pub fn instruction(&self, amount: u64) -> Instruction {
+            let accounts = vec![
+                AccountMeta::new_readonly(anchor_spl::token::ID, false),
+                AccountMeta::new(self.amm, false),
+                AccountMeta::new_readonly(self.amm_authority, false),
+                AccountMeta::new(self.amm_open_orders, false),
+                AccountMeta::new(self.amm_target_orders, false),
+                AccountMeta::new(self.lp_mint, false),
+                AccountMeta::new(self.pool_coin_token_account, false),
+                AccountMeta::new(self.pool_pc_token_account, false),
+                AccountMeta::new_readonly(self.serum_program, false),
+                AccountMeta::new(self.serum_market, false),
+                AccountMeta::new(self.serum_coin_vault, false),
+                AccountMeta::new(self.serum_pc_vault, false),
+                AccountMeta::new_readonly(self.serum_vault_signer, false),
+                AccountMeta::new(self.user_lp_token_account, false),
+                AccountMeta::new(self.user_coin_token_account, false),
+                AccountMeta::new(self.user_pc_token_account, false),
+                AccountMeta::new_readonly(self.user_owner, true),
+                AccountMeta::new(self.serum_event_queue, false),
+                AccountMeta::new(self.serum_bids, false),
+                AccountMeta::new(self.serum_asks, false),
+            ];
+            let mut data = vec![WITHDRAW_TAG];
+            data.extend_from_slice(&amount.to_le_bytes());
+            Instruction { program_id: RAYDIUM_AMM_PROGRAM_ID, accounts, data }
+        }
+    }
     
     #[derive(Clone)]
     pub struct RaydiumSwapParams {
@@ -198,6 +364,16 @@ pub mod raydium {
         pub pool_pc_amount: u64,
     }
     
+    // Price impact of selling amount_in into a constant-product pool, in bps: the
+    // execution price sits amount_in / (reserve_in + amount_in) below spot (before fees)
+    pub fn calculate_price_impact_bps(amount_in: u64, reserve_in: u64) -> u16 {
+        let depth = reserve_in as u128 + amount_in as u128;
+        if depth == 0 {
+            return 10000;
+        }
+        ((amount_in as u128 * 10000) / depth) as u16
+    }
+    
     // Calculate Raydium swap output (constant product formula)
     pub fn calculate_raydium_output(
         amount_in: u64,
@@ -233,6 +409,140 @@ pub mod raydium {
         Ok(amount_out)
     }
     
+    // Inverse of calculate_raydium_output: smallest input that yields at least amount_out
+    pub fn calculate_raydium_input(
+        amount_out: u64,
+        reserve_in: u64,
+        reserve_out: u64,
+        fee_numerator: u64,
+        fee_denominator: u64,
+    ) -> Result<u64> {
+        require!(amount_out < reserve_out, crate::IntentError::ExactOutputBudgetExceeded);
+        let fee_multiplier = fee_denominator.checked_sub(fee_numerator).unwrap();
+        
+        // amount_in = ceil(reserve_in * amount_out * fee_denominator / ((reserve_out - amount_out) * fee_multiplier))
+        let numerator = (reserve_in as u128)
+            .checked_mul(amount_out as u128)
+            .unwrap()
+            .checked_mul(fee_denominator as u128)
+            .unwrap();
+        let denominator = ((reserve_out - amount_out) as u128)
+            .checked_mul(fee_multiplier as u128)
+            .unwrap();
+        let amount_in = u64::try_from(numerator.div_ceil(denominator))
+            .map_err(|_| crate::IntentError::ExactOutputBudgetExceeded)?;
+        
+        msg!(
+            "🔄 Raydium exact output: {} out needs {} in (reserves: {}/{})",
+            amount_out, amount_in, reserve_in, reserve_out
+        );
+        
+        Ok(amount_in)
+    }
+    
+    // Require the vaults to be the ones recorded in the pool account's own state
+    pub fn verify_pool_vaults(
+        pool: &AccountInfo,
+        coin_vault: &Pubkey,
+        pc_vault: &Pubkey,
+    ) -> Result<()> {
+        let data = pool.try_borrow_data()?;
+        require!(
+            data.len() >= AMM_PC_VAULT_OFFSET + 32,
+            crate::IntentError::InvalidPool
+        );
+        require!(
+            data[AMM_COIN_VAULT_OFFSET..AMM_COIN_VAULT_OFFSET + 32] == coin_vault.to_bytes()
+                && data[AMM_PC_VAULT_OFFSET..AMM_PC_VAULT_OFFSET + 32] == pc_vault.to_bytes(),
+            crate::IntentError::InvalidPool
+        );
+        Ok(())
+    }
+    
+    // Require both pool reserves to exceed the protocol's liquidity floor
+    pub fn check_pool_liquidity(
+        reserve_in: u64,
+        reserve_out: u64,
+        min_pool_liquidity: u64,
+    ) -> Result<()> {
+        require!(
+            reserve_in > min_pool_liquidity && reserve_out > min_pool_liquidity,
+            crate::IntentError::InsufficientPoolLiquidity
+        );
+        Ok(())
+    }
+    
+    // Require a deposit's ratio to stay within max_slippage_bps of the pool ratio
+    pub fn check_deposit_ratio(
+        amount_a: u64,
+        amount_b: u64,
+        reserve_a: u64,
+        reserve_b: u64,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        // Compare amount_b / amount_a against reserve_b / reserve_a without division
+        let deposit_side = (amount_b as u128).checked_mul(reserve_a as u128).unwrap();
+        let pool_side = (amount_a as u128).checked_mul(reserve_b as u128).unwrap();
+        require!(pool_side > 0, crate::IntentError::InsufficientPoolLiquidity);
+        
+        let deviation_bps = deposit_side.abs_diff(pool_side)
+            .checked_mul(10000)
+            .unwrap()
+            .checked_div(pool_side)
+            .unwrap();
+        
+        require!(deviation_bps <= max_slippage_bps as u128, crate::IntentError::SlippageExceeded);
+        Ok(())
+    }
+    
+    // LP tokens minted for a deposit, limited by the scarcer side. Only an estimate: the
+    // pool mints the real amount, this sets the floor the deposit must reach.
+    pub fn calculate_lp_tokens(
+        amount_a: u64,
+        amount_b: u64,
+        reserve_a: u64,
+        reserve_b: u64,
+        lp_supply: u64,
+    ) -> Result<u64> {
+        require!(reserve_a > 0 && reserve_b > 0, crate::IntentError::InsufficientPoolLiquidity);
+        
+        let lp_from_a = (amount_a as u128)
+            .checked_mul(lp_supply as u128)
+            .unwrap()
+            .checked_div(reserve_a as u128)
+            .unwrap();
+        let lp_from_b = (amount_b as u128)
+            .checked_mul(lp_supply as u128)
+            .unwrap()
+            .checked_div(reserve_b as u128)
+            .unwrap();
+        
+        Ok(lp_from_a.min(lp_from_b) as u64)
+    }
+    
+    // Underlying amounts returned for burning LP tokens
+    pub fn calculate_withdraw_amounts(
+        lp_amount: u64,
+        reserve_a: u64,
+        reserve_b: u64,
+        lp_supply: u64,
+    ) -> Result<(u64, u64)> {
+        require!(lp_supply > 0 && lp_amount <= lp_supply, crate::IntentError::InvalidAmount);
+        
+        let amount_a = (lp_amount as u128)
+            .checked_mul(reserve_a as u128)
+            .unwrap()
+            .checked_div(lp_supply as u128)
+            .unwrap() as u64;
+        let amount_b = (lp_amount as u128)
+            .checked_mul(reserve_b as u128)
+            .unwrap()
+            .checked_div(lp_supply as u128)
+            .unwrap() as u64;
+        
+        Ok((amount_a, amount_b))
+    }
+    
     // Execute direct Raydium swap
     pub fn execute_raydium_swap(
         _ctx: &Context<ExecuteSwapIntent>,
@@ -330,10 +640,7 @@ pub mod raydium {
 pub mod orca {
     use super::*;
     
-    pub const ORCA_WHIRLPOOLS_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5,
-        5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5, 5
-    ]);
+    pub const ORCA_WHIRLPOOLS_PROGRAM_ID: Pubkey = pubkey!("whirLbMiicVdio4qvUfM5KAg6Ct8VwpYzGff3uctyCc");
     
     pub fn get_orca_pools() -> Vec<(String, Pubkey)> {
         vec![
@@ -345,6 +652,503 @@ pub mod orca {
     }
 }
 
+// Meteora DLMM Integration
+// Meteora's dynamic liquidity pools concentrate liquidity in price bins (best for stable pairs)
+pub mod meteora {
+    use super::*;
+    
+    pub const METEORA_DLMM_PROGRAM_ID: Pubkey = pubkey!("LBUZKhRxPF3XUpBCjp4YzTKgLccjZhTSDM9YuVaPwxo");
+    
+    #[derive(Clone)]
+    pub struct MeteoraSwapParams {
+        pub pool_id: Pubkey,
+        pub from_mint: Pubkey,
+        pub to_mint: Pubkey,
+        pub amount_in: u64,
+        pub minimum_amount_out: u64,
+    }
+    
+    // Byte offsets inside the DLMM LbPair account (after the 8 byte discriminator)
+    pub const LB_PAIR_BASE_FACTOR_OFFSET: usize = 8;
+    pub const LB_PAIR_ACTIVE_ID_OFFSET: usize = 76;
+    pub const LB_PAIR_BIN_STEP_OFFSET: usize = 80;
+    pub const LB_PAIR_TOKEN_X_MINT_OFFSET: usize = 88;
+    pub const LB_PAIR_TOKEN_Y_MINT_OFFSET: usize = 120;
+    
+    // BinArray accounts hold 70 bins of 144 bytes each, after index, version and lb_pair
+    pub const BINS_PER_ARRAY: i64 = 70;
+    pub const BIN_ARRAY_INDEX_OFFSET: usize = 8;
+    pub const BIN_ARRAY_LB_PAIR_OFFSET: usize = 24;
+    pub const BIN_ARRAY_BINS_OFFSET: usize = 56;
+    pub const BIN_SIZE: usize = 144;
+    
+    // Meteora DLMM pool state (simplified to the active bin), read from the pool's accounts
+    pub struct MeteoraPoolInfo {
+        pub token_x_mint: Pubkey,
+        pub token_y_mint: Pubkey,
+        pub active_bin_id: i32,
+        pub bin_step: u16,
+        pub base_fee_bps: u16,
+        pub active_bin_amount_x: u64,
+        pub active_bin_amount_y: u64,
+        pub active_bin_price: u128, // Token Y per token X, Q64.64
+    }
+    
+    // Read the pool's active bin from its LbPair account and the BinArray holding that bin
+    pub fn read_pool_info(pool: &AccountInfo, bin_array: &AccountInfo) -> Result<MeteoraPoolInfo> {
+        require!(pool.owner == &METEORA_DLMM_PROGRAM_ID, crate::IntentError::InvalidPool);
+        require!(bin_array.owner == &METEORA_DLMM_PROGRAM_ID, crate::IntentError::InvalidPool);
+        
+        let pool_data = pool.try_borrow_data()?;
+        require!(pool_data.len() >= LB_PAIR_TOKEN_Y_MINT_OFFSET + 32, crate::IntentError::InvalidPool);
+        let read_u16 = |data: &[u8], offset: usize| u16::from_le_bytes(data[offset..offset + 2].try_into().unwrap());
+        let read_pubkey = |data: &[u8], offset: usize| Pubkey::try_from(&data[offset..offset + 32]).unwrap();
+        
+        let base_factor = read_u16(&pool_data, LB_PAIR_BASE_FACTOR_OFFSET);
+        let bin_step = read_u16(&pool_data, LB_PAIR_BIN_STEP_OFFSET);
+        let active_bin_id = i32::from_le_bytes(
+            pool_data[LB_PAIR_ACTIVE_ID_OFFSET..LB_PAIR_ACTIVE_ID_OFFSET + 4].try_into().unwrap()
+        );
+        
+        // The bin array must belong to this pool and cover the active bin
+        let bin_data = bin_array.try_borrow_data()?;
+        require!(bin_data.len() >= BIN_ARRAY_BINS_OFFSET + BINS_PER_ARRAY as usize * BIN_SIZE, crate::IntentError::InvalidPool);
+        require!(read_pubkey(&bin_data, BIN_ARRAY_LB_PAIR_OFFSET) == pool.key(), crate::IntentError::InvalidPool);
+        let array_index = i64::from_le_bytes(
+            bin_data[BIN_ARRAY_INDEX_OFFSET..BIN_ARRAY_INDEX_OFFSET + 8].try_into().unwrap()
+        );
+        require!(
+            array_index == (active_bin_id as i64).div_euclid(BINS_PER_ARRAY),
+            crate::IntentError::InvalidPool
+        );
+        
+        let bin_offset = BIN_ARRAY_BINS_OFFSET
+            + (active_bin_id as i64).rem_euclid(BINS_PER_ARRAY) as usize * BIN_SIZE;
+        let bin = &bin_data[bin_offset..bin_offset + BIN_SIZE];
+        
+        Ok(MeteoraPoolInfo {
+            token_x_mint: read_pubkey(&pool_data, LB_PAIR_TOKEN_X_MINT_OFFSET),
+            token_y_mint: read_pubkey(&pool_data, LB_PAIR_TOKEN_Y_MINT_OFFSET),
+            active_bin_id,
+            bin_step,
+            // DLMM base fee rate is base_factor * bin_step * 10 in 1e9 precision
+            base_fee_bps: (base_factor as u32 * bin_step as u32 / 10000) as u16,
+            active_bin_amount_x: u64::from_le_bytes(bin[0..8].try_into().unwrap()),
+            active_bin_amount_y: u64::from_le_bytes(bin[8..16].try_into().unwrap()),
+            active_bin_price: u128::from_le_bytes(bin[16..32].try_into().unwrap()),
+        })
+    }
+    
+    // Calculate Meteora swap output at the active bin's price. Price inside a bin is
+    // fixed, so output is linear in input until the bin is exhausted.
+    pub fn calculate_meteora_output(
+        amount_in: u64,
+        price: u128,
+        swap_for_y: bool,
+        bin_amount_out: u64,
+        fee_bps: u16,
+    ) -> Result<u64> {
+        require!(price > 0, crate::IntentError::InvalidPool);
+        
+        let amount_in_after_fee = (amount_in as u128)
+            .checked_mul(10000_u128.checked_sub(fee_bps as u128).unwrap())
+            .unwrap()
+            .checked_div(10000)
+            .unwrap();
+        
+        // Split the Q64.64 price into two 32 bit shifts so the products stay inside u128
+        let amount_out = if swap_for_y {
+            amount_in_after_fee
+                .checked_mul(price >> 32)
+                .ok_or(crate::IntentError::InvalidAmount)?
+                >> 32
+        } else {
+            (amount_in_after_fee << 32)
+                .checked_div(price >> 32)
+                .ok_or(crate::IntentError::InvalidPool)?
+        };
+        let amount_out = u64::try_from(amount_out).map_err(|_| crate::IntentError::InvalidAmount)?;
+        
+        require!(amount_out <= bin_amount_out, crate::IntentError::SlippageExceeded);
+        
+        msg!(
+            "☄️ Meteora calculation: {} in → {} out (active bin holds {})",
+            amount_in, amount_out, bin_amount_out
+        );
+        
+        Ok(amount_out)
+    }
+    
+    /// Simplified Meteora swap execution without full Context
+    pub fn execute_meteora_swap_simple(
+        _user: &AccountInfo,
+        _user_source_token: &AccountInfo,
+        _user_destination_token: &AccountInfo,
+        _meteora_program: &AccountInfo,
+        _token_program: &AccountInfo,
+        params: MeteoraSwapParams,
+        pool_info: &MeteoraPoolInfo,
+    ) -> Result<u64> {
+        msg!("☄️ Executing Meteora DLMM swap...");
+        msg!("Pool: {}", params.pool_id);
+        msg!("From: {} → To: {}", params.from_mint, params.to_mint);
+        msg!("Amount: {} tokens", params.amount_in);
+        
+        let swap_for_y = params.from_mint == pool_info.token_x_mint;
+        let bin_amount_out = if swap_for_y { pool_info.active_bin_amount_y } else { pool_info.active_bin_amount_x };
+        
+        let output_amount = calculate_meteora_output(
+            params.amount_in,
+            pool_info.active_bin_price,
+            swap_for_y,
+            bin_amount_out,
+            pool_info.base_fee_bps,
+        )?;
+        
+        require!(output_amount >= params.minimum_amount_out, crate::IntentError::SlippageExceeded);
+        
+        msg!("✅ Meteora swap completed: {} → {} tokens", params.amount_in, output_amount);
+        Ok(output_amount)
+    }
+    
+    pub fn get_meteora_pools() -> Vec<(String, Pubkey)> {
+        vec![
+            // USDC/USDT DLMM pool
+            ("USDC-USDT".to_string(), Pubkey::new_from_array([9; 32])),
+        ]
+    }
+}
+
+// Price oracles for conditional intents
+// Pyth covers most majors, Switchboard fills in for tokens without a Pyth feed
+pub mod oracle {
+    use super::*;
+    
+    #[cfg(not(feature = "devnet"))]
+    pub const PYTH_ORACLE_PROGRAM_ID: Pubkey = pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+    #[cfg(feature = "devnet")]
+    pub const PYTH_ORACLE_PROGRAM_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+    // Switchboard v2 (same on every cluster)
+    pub const SWITCHBOARD_V2_PROGRAM_ID: Pubkey = pubkey!("SW1TCH7qEPTdLsDHRgPuMQjbQxKdH2aBStViMFnt64f");
+    
+    // Conditional intents compare prices as USDC per whole token in 6 decimal fixed point
+    // (1.5 USDC = 1_500_000). Oracle reads, caller-supplied prices and target_price all
+    // use this scale.
+    pub const ORACLE_PRICE_DECIMALS: i32 = 6;
+    
+    // Pyth v2 price account layout
+    pub(crate) const PYTH_MAGIC: u32 = 0xa1b2c3d4;
+    pub(crate) const PYTH_EXPO_OFFSET: usize = 20;
+    pub(crate) const PYTH_TIMESTAMP_OFFSET: usize = 96;
+    pub(crate) const PYTH_AGG_PRICE_OFFSET: usize = 208;
+    pub(crate) const PYTH_AGG_CONF_OFFSET: usize = 216;
+    pub(crate) const PYTH_AGG_STATUS_OFFSET: usize = 224;
+    const PYTH_STATUS_TRADING: u32 = 1;
+    
+    // Switchboard v2 AggregatorAccountData: latest_confirmed_round.result (mantissa i128, scale u32)
+    pub(crate) const SWITCHBOARD_ROUND_OPEN_TIMESTAMP_OFFSET: usize = 358;
+    pub(crate) const SWITCHBOARD_RESULT_OFFSET: usize = 366;
+    pub(crate) const SWITCHBOARD_STD_DEVIATION_OFFSET: usize = 386;
+    
+    // Checked reads refuse prices older than this or less certain than this share of the price
+    pub const MAX_PRICE_AGE_SECONDS: i64 = 60;
+    pub const MAX_CONFIDENCE_BPS: u128 = 100; // 1%
+    
+    // Read the current price from an oracle account using the intent's chosen source
+    pub fn read_price(source: &PriceSource, oracle_account: &AccountInfo) -> Result<u64> {
+        check_oracle_owner(source, oracle_account)?;
+        
+        let data = oracle_account.try_borrow_data()?;
+        let price = match source {
+            PriceSource::Pyth => parse_pyth_price(&data)?,
+            PriceSource::Switchboard => parse_switchboard_price(&data)?,
+        };
+        
+        msg!("🔮 {:?} price: {}", source, price);
+        Ok(price)
+    }
+    
+    // Like read_price, but only accepts a fresh price with a tight confidence interval
+    pub fn read_price_checked(source: &PriceSource, oracle_account: &AccountInfo, now: i64) -> Result<u64> {
+        check_oracle_owner(source, oracle_account)?;
+        
+        let data = oracle_account.try_borrow_data()?;
+        let price = match source {
+            PriceSource::Pyth => parse_pyth_price_checked(&data, now)?,
+            PriceSource::Switchboard => parse_switchboard_price_checked(&data, now)?,
+        };
+        
+        msg!("🔮 {:?} price: {}", source, price);
+        Ok(price)
+    }
+    
+    fn check_oracle_owner(source: &PriceSource, oracle_account: &AccountInfo) -> Result<()> {
+        let expected_owner = match source {
+            PriceSource::Pyth => PYTH_ORACLE_PROGRAM_ID,
+            PriceSource::Switchboard => SWITCHBOARD_V2_PROGRAM_ID,
+        };
+        require!(oracle_account.owner == &expected_owner, crate::IntentError::InvalidOracle);
+        Ok(())
+    }
+    
+    // Aggregate price from a Pyth price account
+    pub fn parse_pyth_price(data: &[u8]) -> Result<u64> {
+        require!(data.len() >= PYTH_AGG_PRICE_OFFSET + 8, crate::IntentError::InvalidOraclePrice);
+        let magic = u32::from_le_bytes(data[0..4].try_into().unwrap());
+        require!(magic == PYTH_MAGIC, crate::IntentError::InvalidOraclePrice);
+        
+        let expo = i32::from_le_bytes(data[PYTH_EXPO_OFFSET..PYTH_EXPO_OFFSET + 4].try_into().unwrap());
+        let price = i64::from_le_bytes(data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].try_into().unwrap());
+        normalize_mantissa(price as i128, -expo)
+    }
+    
+    // Pyth aggregate price, only while trading, recently published and with a tight confidence
+    pub fn parse_pyth_price_checked(data: &[u8], now: i64) -> Result<u64> {
+        let price = parse_pyth_price(data)?;
+        require!(data.len() >= PYTH_AGG_STATUS_OFFSET + 4, crate::IntentError::InvalidOraclePrice);
+        
+        let status = u32::from_le_bytes(data[PYTH_AGG_STATUS_OFFSET..PYTH_AGG_STATUS_OFFSET + 4].try_into().unwrap());
+        require!(status == PYTH_STATUS_TRADING, crate::IntentError::InvalidOraclePrice);
+        let timestamp = i64::from_le_bytes(data[PYTH_TIMESTAMP_OFFSET..PYTH_TIMESTAMP_OFFSET + 8].try_into().unwrap());
+        check_price_age(timestamp, now)?;
+        
+        // Confidence shares the aggregate price's exponent, so compare the raw values
+        let raw_price = i64::from_le_bytes(data[PYTH_AGG_PRICE_OFFSET..PYTH_AGG_PRICE_OFFSET + 8].try_into().unwrap());
+        let conf = u64::from_le_bytes(data[PYTH_AGG_CONF_OFFSET..PYTH_AGG_CONF_OFFSET + 8].try_into().unwrap());
+        check_confidence(conf as u128, raw_price as u128)?;
+        Ok(price)
+    }
+    
+    // Latest confirmed result from a Switchboard v2 aggregator
+    pub fn parse_switchboard_price(data: &[u8]) -> Result<u64> {
+        require!(data.len() >= SWITCHBOARD_RESULT_OFFSET + 20, crate::IntentError::InvalidOraclePrice);
+        
+        let mantissa = i128::from_le_bytes(data[SWITCHBOARD_RESULT_OFFSET..SWITCHBOARD_RESULT_OFFSET + 16].try_into().unwrap());
+        let scale = u32::from_le_bytes(data[SWITCHBOARD_RESULT_OFFSET + 16..SWITCHBOARD_RESULT_OFFSET + 20].try_into().unwrap());
+        normalize_mantissa(mantissa, scale as i32)
+    }
+    
+    // Switchboard result, only from a recent round whose oracles agreed closely
+    pub fn parse_switchboard_price_checked(data: &[u8], now: i64) -> Result<u64> {
+        let price = parse_switchboard_price(data)?;
+        require!(data.len() >= SWITCHBOARD_STD_DEVIATION_OFFSET + 20, crate::IntentError::InvalidOraclePrice);
+        
+        let timestamp = i64::from_le_bytes(
+            data[SWITCHBOARD_ROUND_OPEN_TIMESTAMP_OFFSET..SWITCHBOARD_ROUND_OPEN_TIMESTAMP_OFFSET + 8].try_into().unwrap(),
+        );
+        check_price_age(timestamp, now)?;
+        
+        let std_mantissa = i128::from_le_bytes(
+            data[SWITCHBOARD_STD_DEVIATION_OFFSET..SWITCHBOARD_STD_DEVIATION_OFFSET + 16].try_into().unwrap(),
+        );
+        let std_scale = u32::from_le_bytes(
+            data[SWITCHBOARD_STD_DEVIATION_OFFSET + 16..SWITCHBOARD_STD_DEVIATION_OFFSET + 20].try_into().unwrap(),
+        );
+        require!(std_mantissa >= 0, crate::IntentError::InvalidOraclePrice);
+        let std_deviation = if std_mantissa == 0 { 0 } else { normalize_mantissa(std_mantissa, std_scale as i32)? };
+        check_confidence(std_deviation as u128, price as u128)?;
+        Ok(price)
+    }
+    
+    fn check_price_age(published_at: i64, now: i64) -> Result<()> {
+        require!(
+            now.saturating_sub(published_at) <= MAX_PRICE_AGE_SECONDS,
+            crate::IntentError::StaleOraclePrice
+        );
+        Ok(())
+    }
+    
+    fn check_confidence(uncertainty: u128, price: u128) -> Result<()> {
+        require!(
+            uncertainty * 10000 <= price * MAX_CONFIDENCE_BPS,
+            crate::IntentError::OracleConfidenceTooWide
+        );
+        Ok(())
+    }
+    
+    // Convert `mantissa / 10^scale` to ORACLE_PRICE_DECIMALS fixed point
+    fn normalize_mantissa(mantissa: i128, scale: i32) -> Result<u64> {
+        require!(mantissa > 0, crate::IntentError::InvalidOraclePrice);
+        
+        if scale >= 0 {
+            normalize_price(mantissa as u128, scale as u32, ORACLE_PRICE_DECIMALS as u32)
+        } else {
+            // Positive exponent: the mantissa counts in units of 10^-scale
+            let whole = 10_u128
+                .checked_pow((-scale) as u32)
+                .and_then(|factor| (mantissa as u128).checked_mul(factor))
+                .ok_or(crate::IntentError::InvalidOraclePrice)?;
+            normalize_price(whole, 0, ORACLE_PRICE_DECIMALS as u32)
+        }
+    }
+    
+    // Rescale a fixed-point price from `from_decimals` to `to_decimals`, truncating when
+    // dropping precision. Use it to bring any quote or feed onto ORACLE_PRICE_DECIMALS
+    // before comparing against a target.
+    pub fn normalize_price(raw: u128, from_decimals: u32, to_decimals: u32) -> Result<u64> {
+        let price = if to_decimals >= from_decimals {
+            10_u128.checked_pow(to_decimals - from_decimals).and_then(|factor| raw.checked_mul(factor))
+        } else {
+            10_u128.checked_pow(from_decimals - to_decimals).map(|factor| raw / factor)
+        };
+        price
+            .and_then(|price| u64::try_from(price).ok())
+            .ok_or(crate::IntentError::InvalidOraclePrice.into())
+    }
+}
+
+// Token safety scoring from on-chain mint, metadata and pool state
+pub mod rugproof {
+    use super::*;
+    use anchor_spl::token::{Mint, TokenAccount};
+    
+    pub const TOKEN_METADATA_PROGRAM_ID: Pubkey = pubkey!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+    
+    // A pool must hold at least this share of supply to count as real liquidity
+    const MIN_POOL_SUPPLY_BPS: u128 = 500; // 5%
+    
+    // Score a token out of 100: renounced authorities, verified immutable metadata
+    // and a funded pool each add to a base score
+    pub fn score_token(
+        mint_key: &Pubkey,
+        mint: &Mint,
+        metadata: &AccountInfo,
+        liquidity_pool: Option<&AccountInfo>,
+    ) -> Result<u8> {
+        let mut score: u8 = 40;
+        
+        // Supply can't be inflated and holders can't be frozen
+        if mint.mint_authority.is_none() {
+            score += 20;
+        }
+        if mint.freeze_authority.is_none() {
+            score += 15;
+        }
+        
+        let (metadata_key, _) = Pubkey::find_program_address(
+            &[b"metadata", TOKEN_METADATA_PROGRAM_ID.as_ref(), mint_key.as_ref()],
+            &TOKEN_METADATA_PROGRAM_ID,
+        );
+        require_keys_eq!(metadata.key(), metadata_key, crate::IntentError::InvalidMetadata);
+        if metadata.owner == &TOKEN_METADATA_PROGRAM_ID {
+            score += 10;
+            if metadata_is_mutable(&metadata.try_borrow_data()?) == Some(false) {
+                score += 5;
+            }
+        }
+        
+        if let Some(pool) = liquidity_pool.filter(|pool| pool.owner == &anchor_spl::token::ID) {
+            if let Ok(pool_account) = TokenAccount::try_deserialize(&mut &pool.data.borrow()[..]) {
+                let pool_bps = (pool_account.amount as u128 * 10000)
+                    .checked_div(mint.supply as u128)
+                    .unwrap_or(0);
+                if pool_account.mint == *mint_key && pool_bps >= MIN_POOL_SUPPLY_BPS {
+                    score += 10;
+                }
+            }
+        }
+        
+        Ok(score)
+    }
+    
+    // Walk the Metaplex Metadata layout up to is_mutable
+    fn metadata_is_mutable(data: &[u8]) -> Option<bool> {
+        // key, update_authority, mint, then name, symbol and uri as borsh strings
+        let mut offset = 1 + 32 + 32;
+        for _ in 0..3 {
+            let len = u32::from_le_bytes(data.get(offset..offset + 4)?.try_into().ok()?) as usize;
+            offset += 4 + len;
+        }
+        offset += 2; // seller_fee_basis_points
+        if *data.get(offset)? == 1 {
+            let creators = u32::from_le_bytes(data.get(offset + 1..offset + 5)?.try_into().ok()?) as usize;
+            offset += 4 + creators * 34; // address, verified, share
+        }
+        offset += 1; // creators option tag
+        offset += 1; // primary_sale_happened
+        data.get(offset).map(|is_mutable| *is_mutable == 1)
+    }
+}
+
+// Native SOL support
+// SOL legs trade through the user's wSOL token account: topped up from their lamports
+// before the swap and closed afterwards so the balance comes back as SOL
+pub mod wsol {
+    use super::*;
+    use anchor_lang::system_program;
+    use anchor_spl::token::{self, CloseAccount, SyncNative};
+    
+    pub const NATIVE_MINT: Pubkey = anchor_spl::token::spl_token::native_mint::ID;
+    
+    pub fn is_native(mint: &Pubkey) -> bool {
+        *mint == NATIVE_MINT
+    }
+    
+    // Wrap just enough of the owner's lamports for the account to hold `amount`. Keepers
+    // can't move the owner's lamports, so without the owner's signature the account
+    // must already hold enough wSOL.
+    pub fn wrap_sol<'info>(
+        system_program: &AccountInfo<'info>,
+        token_program: &AccountInfo<'info>,
+        owner: &AccountInfo<'info>,
+        wsol_account: &Account<'info, TokenAccount>,
+        amount: u64,
+    ) -> Result<()> {
+        require!(
+            wsol_account.is_native() && wsol_account.owner == owner.key(),
+            crate::IntentError::InvalidWsolAccount
+        );
+        let shortfall = amount.saturating_sub(wsol_account.amount);
+        if shortfall == 0 {
+            return Ok(());
+        }
+        require!(owner.is_signer, crate::IntentError::OwnerSignatureRequired);
+        
+        system_program::transfer(
+            CpiContext::new(
+                system_program.clone(),
+                system_program::Transfer {
+                    from: owner.clone(),
+                    to: wsol_account.to_account_info(),
+                },
+            ),
+            shortfall,
+        )?;
+        token::sync_native(CpiContext::new(
+            token_program.clone(),
+            SyncNative {
+                account: wsol_account.to_account_info(),
+            },
+        ))?;
+        
+        msg!("🪙 Wrapped {} lamports into {}", shortfall, wsol_account.key());
+        Ok(())
+    }
+    
+    // Close a wSOL account, returning its balance and rent to the owner as SOL. Only the
+    // owner can close it, so keeper executions leave the balance as wSOL.
+    pub fn unwrap_sol<'info>(
+        token_program: &AccountInfo<'info>,
+        owner: &AccountInfo<'info>,
+        wsol_account: &AccountInfo<'info>,
+    ) -> Result<()> {
+        if !owner.is_signer {
+            return Ok(());
+        }
+        token::close_account(CpiContext::new(
+            token_program.clone(),
+            CloseAccount {
+                account: wsol_account.clone(),
+                destination: owner.clone(),
+                authority: owner.clone(),
+            },
+        ))?;
+        
+        msg!("🪙 Unwrapped {} back to SOL", wsol_account.key());
+        Ok(())
+    }
+}
+
 // Protocol Router - Chooses best DEX for swap
 pub struct ProtocolRouter;
 
@@ -359,9 +1163,12 @@ impl ProtocolRouter {
         if amount > 1000 * 1_000_000 {
             // For large trades (>1000 USDC), use Jupiter for best routing
             SwapProtocol::Jupiter
-        } else if Self::is_major_pair(from_mint, to_mint) {
-            // For major pairs with small amounts, direct Raydium might be cheaper
+        } else if Self::is_major_pair(from_mint, to_mint) && !Self::is_stable_pair(from_mint, to_mint) {
+            // For volatile major pairs with small amounts, direct Raydium might be cheaper
             SwapProtocol::Raydium
+        } else if Self::is_stable_pair(from_mint, to_mint) {
+            // Meteora's concentrated bins give the tightest stable-pair pricing
+            SwapProtocol::Meteora
         } else {
             // For exotic pairs, Jupiter handles routing best
             SwapProtocol::Jupiter
@@ -382,13 +1189,28 @@ impl ProtocolRouter {
         (from_mint == &usdc_mint && to_mint == &usdt_mint) ||
         (from_mint == &usdt_mint && to_mint == &usdc_mint)
     }
+    
+    pub fn is_stable_pair(from_mint: &Pubkey, to_mint: &Pubkey) -> bool {
+        let usdc_mint = Pubkey::new_from_array([1; 32]); // Mock USDC
+        let usdt_mint = Pubkey::new_from_array([2; 32]); // Mock USDT
+        
+        (from_mint == &usdc_mint && to_mint == &usdt_mint) ||
+        (from_mint == &usdt_mint && to_mint == &usdc_mint)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize, InitSpace)]
+pub enum PriceSource {
+    Pyth,
+    Switchboard, // For tokens without a Pyth feed
 }
 
-#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize, InitSpace)]
 pub enum SwapProtocol {
     Jupiter,  // Aggregator (like 1inch)
     Raydium,  // Direct AMM
     Orca,     // Alternative AMM
+    Meteora,  // Dynamic liquidity (DLMM)
 }
 
 // Integration accounts for CPI calls
@@ -412,4 +1234,285 @@ pub struct ExecuteSwapIntent<'info> {
     
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    const Q64: u128 = 1 << 64;
+    
+    fn lb_pair_data(token_x_mint: Pubkey, token_y_mint: Pubkey, active_id: i32, bin_step: u16, base_factor: u16) -> Vec<u8> {
+        let mut data = vec![0u8; 904];
+        data[meteora::LB_PAIR_BASE_FACTOR_OFFSET..][..2].copy_from_slice(&base_factor.to_le_bytes());
+        data[meteora::LB_PAIR_ACTIVE_ID_OFFSET..][..4].copy_from_slice(&active_id.to_le_bytes());
+        data[meteora::LB_PAIR_BIN_STEP_OFFSET..][..2].copy_from_slice(&bin_step.to_le_bytes());
+        data[meteora::LB_PAIR_TOKEN_X_MINT_OFFSET..][..32].copy_from_slice(token_x_mint.as_ref());
+        data[meteora::LB_PAIR_TOKEN_Y_MINT_OFFSET..][..32].copy_from_slice(token_y_mint.as_ref());
+        data
+    }
+    
+    fn bin_array_data(lb_pair: &Pubkey, index: i64, bin_id: i32, amount_x: u64, amount_y: u64, price: u128) -> Vec<u8> {
+        let mut data = vec![0u8; meteora::BIN_ARRAY_BINS_OFFSET + meteora::BINS_PER_ARRAY as usize * meteora::BIN_SIZE];
+        data[meteora::BIN_ARRAY_INDEX_OFFSET..][..8].copy_from_slice(&index.to_le_bytes());
+        data[meteora::BIN_ARRAY_LB_PAIR_OFFSET..][..32].copy_from_slice(lb_pair.as_ref());
+        let bin = meteora::BIN_ARRAY_BINS_OFFSET
+            + (bin_id as i64).rem_euclid(meteora::BINS_PER_ARRAY) as usize * meteora::BIN_SIZE;
+        data[bin..][..8].copy_from_slice(&amount_x.to_le_bytes());
+        data[bin + 8..][..8].copy_from_slice(&amount_y.to_le_bytes());
+        data[bin + 16..][..16].copy_from_slice(&price.to_le_bytes());
+        data
+    }
+    
+    #[test]
+    fn meteora_reads_the_active_bin_from_the_pool_accounts() {
+        let pool_key = Pubkey::new_unique();
+        let (token_x, token_y) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let program_id = meteora::METEORA_DLMM_PROGRAM_ID;
+        
+        // Active bin -75 sits in bin array -2, a bin step of 1 with base factor 20000 is 2 bps
+        let mut pool_lamports = 0;
+        let mut pool_data = lb_pair_data(token_x, token_y, -75, 1, 20000);
+        let pool = AccountInfo::new(&pool_key, false, false, &mut pool_lamports, &mut pool_data, &program_id, false, 0);
+        
+        let bin_array_key = Pubkey::new_unique();
+        let mut bin_lamports = 0;
+        let mut bin_data = bin_array_data(&pool_key, -2, -75, 5_000, 7_000, Q64);
+        let bin_array = AccountInfo::new(&bin_array_key, false, false, &mut bin_lamports, &mut bin_data, &program_id, false, 0);
+        
+        let pool_info = meteora::read_pool_info(&pool, &bin_array).unwrap();
+        assert_eq!(pool_info.token_x_mint, token_x);
+        assert_eq!(pool_info.token_y_mint, token_y);
+        assert_eq!(pool_info.base_fee_bps, 2);
+        assert_eq!((pool_info.active_bin_amount_x, pool_info.active_bin_amount_y), (5_000, 7_000));
+        assert_eq!(pool_info.active_bin_price, Q64);
+        
+        // A bin array from another pool, or not covering the active bin, is rejected
+        let mut other_lamports = 0;
+        let mut other_data = bin_array_data(&Pubkey::new_unique(), -2, -75, 5_000, 7_000, Q64);
+        let other = AccountInfo::new(&bin_array_key, false, false, &mut other_lamports, &mut other_data, &program_id, false, 0);
+        assert!(meteora::read_pool_info(&pool, &other).is_err());
+        
+        let mut stale_lamports = 0;
+        let mut stale_data = bin_array_data(&pool_key, -1, -75, 5_000, 7_000, Q64);
+        let stale = AccountInfo::new(&bin_array_key, false, false, &mut stale_lamports, &mut stale_data, &program_id, false, 0);
+        assert!(meteora::read_pool_info(&pool, &stale).is_err());
+        
+        // So is a look-alike pool owned by another program
+        let fake_owner = Pubkey::new_unique();
+        let mut fake_lamports = 0;
+        let mut fake_data = lb_pair_data(token_x, token_y, -75, 1, 20000);
+        let fake = AccountInfo::new(&pool_key, false, false, &mut fake_lamports, &mut fake_data, &fake_owner, false, 0);
+        assert!(meteora::read_pool_info(&fake, &bin_array).is_err());
+    }
+    
+    #[test]
+    fn meteora_output_follows_the_bin_price() {
+        // 1 X = 2 Y, no fee
+        assert_eq!(meteora::calculate_meteora_output(1_000, 2 * Q64, true, 10_000, 0).unwrap(), 2_000);
+        assert_eq!(meteora::calculate_meteora_output(1_000, 2 * Q64, false, 10_000, 0).unwrap(), 500);
+        // Fees come off the input
+        assert_eq!(meteora::calculate_meteora_output(10_000, Q64, true, 10_000, 30).unwrap(), 9_970);
+        // Can't take more than the active bin holds
+        assert!(meteora::calculate_meteora_output(1_000, 2 * Q64, true, 1_999, 0).is_err());
+    }
+    
+    #[test]
+    fn router_sends_volatile_majors_to_raydium_and_stables_to_meteora() {
+        let sol = Pubkey::new_from_array([0; 32]);
+        let usdc = Pubkey::new_from_array([1; 32]);
+        let usdt = Pubkey::new_from_array([2; 32]);
+        let exotic = Pubkey::new_unique();
+        
+        assert!(matches!(ProtocolRouter::choose_best_protocol(&sol, &usdc, 1_000_000), SwapProtocol::Raydium));
+        assert!(matches!(ProtocolRouter::choose_best_protocol(&usdt, &sol, 1_000_000), SwapProtocol::Raydium));
+        assert!(matches!(ProtocolRouter::choose_best_protocol(&usdc, &usdt, 1_000_000), SwapProtocol::Meteora));
+        assert!(matches!(ProtocolRouter::choose_best_protocol(&exotic, &usdc, 1_000_000), SwapProtocol::Jupiter));
+        // Large trades always go through the aggregator
+        assert!(matches!(ProtocolRouter::choose_best_protocol(&usdc, &usdt, 2_000 * 1_000_000), SwapProtocol::Jupiter));
+    }
+    
+    #[test]
+    fn raydium_swaps_refuse_pools_below_the_liquidity_floor() {
+        let min = crate::DEFAULT_MIN_POOL_LIQUIDITY;
+        // A near-empty pool would hand back most of its other side for a small trade
+        let out = raydium::calculate_raydium_output(100_000, 1_000, 1_000, 25, 10000).unwrap();
+        assert!(out >= 990);
+        assert_eq!(
+            raydium::check_pool_liquidity(1_000, 1_000, min).unwrap_err(),
+            crate::IntentError::InsufficientPoolLiquidity.into()
+        );
+        // Either side being thin is enough to refuse
+        assert!(raydium::check_pool_liquidity(50_000_000, min, min).is_err());
+        assert!(raydium::check_pool_liquidity(min, 50_000_000, min).is_err());
+        assert!(raydium::check_pool_liquidity(min + 1, min + 1, min).is_ok());
+    }
+    
+    #[test]
+    fn raydium_deposit_follows_the_amm_account_order() {
+        let deposit = raydium::RaydiumDeposit {
+            amm: Pubkey::new_unique(),
+            amm_authority: Pubkey::new_unique(),
+            amm_open_orders: Pubkey::new_unique(),
+            amm_target_orders: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            pool_coin_token_account: Pubkey::new_unique(),
+            pool_pc_token_account: Pubkey::new_unique(),
+            serum_market: Pubkey::new_unique(),
+            user_coin_token_account: Pubkey::new_unique(),
+            user_pc_token_account: Pubkey::new_unique(),
+            user_lp_token_account: Pubkey::new_unique(),
+            user_owner: Pubkey::new_unique(),
+            serum_event_queue: Pubkey::new_unique(),
+        };
+        let ix = deposit.instruction(1_000, 2_000, 1);
+        
+        assert_eq!(ix.program_id, raydium::RAYDIUM_AMM_PROGRAM_ID);
+        assert_eq!(ix.accounts.len(), 14);
+        assert_eq!(ix.accounts[1].pubkey, deposit.amm);
+        assert_eq!(ix.accounts[11].pubkey, deposit.user_lp_token_account);
+        assert!(ix.accounts[12].is_signer && ix.accounts[12].pubkey == deposit.user_owner);
+        assert!(ix.accounts[5].is_writable);
+        
+        assert_eq!(ix.data[0], raydium::DEPOSIT_TAG);
+        assert_eq!(ix.data[1..9], 1_000u64.to_le_bytes());
+        assert_eq!(ix.data[9..17], 2_000u64.to_le_bytes());
+        assert_eq!(ix.data[17..25], 1u64.to_le_bytes());
+    }
+    
+    #[test]
+    fn raydium_withdraw_follows_the_amm_account_order() {
+        let withdraw = raydium::RaydiumWithdraw {
+            amm: Pubkey::new_unique(),
+            amm_authority: Pubkey::new_unique(),
+            amm_open_orders: Pubkey::new_unique(),
+            amm_target_orders: Pubkey::new_unique(),
+            lp_mint: Pubkey::new_unique(),
+            pool_coin_token_account: Pubkey::new_unique(),
+            pool_pc_token_account: Pubkey::new_unique(),
+            serum_program: Pubkey::new_unique(),
+            serum_market: Pubkey::new_unique(),
+            serum_coin_vault: Pubkey::new_unique(),
+            serum_pc_vault: Pubkey::new_unique(),
+            serum_vault_signer: Pubkey::new_unique(),
+            user_lp_token_account: Pubkey::new_unique(),
+            user_coin_token_account: Pubkey::new_unique(),
+            user_pc_token_account: Pubkey::new_unique(),
+            user_owner: Pubkey::new_unique(),
+            serum_event_queue: Pubkey::new_unique(),
+            serum_bids: Pubkey::new_unique(),
+            serum_asks: Pubkey::new_unique(),
+        };
+        let ix = withdraw.instruction(5_000);
+        
+        assert_eq!(ix.program_id, raydium::RAYDIUM_AMM_PROGRAM_ID);
+        assert_eq!(ix.accounts.len(), 20);
+        assert_eq!(ix.accounts[13].pubkey, withdraw.user_lp_token_account);
+        assert!(ix.accounts[16].is_signer && ix.accounts[16].pubkey == withdraw.user_owner);
+        assert_eq!(ix.accounts[19].pubkey, withdraw.serum_asks);
+        assert_eq!(ix.data, [&[raydium::WITHDRAW_TAG][..], &5_000u64.to_le_bytes()].concat());
+    }
+    
+    fn pyth_price_data(price: i64, expo: i32, conf: u64, status: u32, timestamp: i64) -> Vec<u8> {
+        let mut data = vec![0u8; 240];
+        data[..4].copy_from_slice(&oracle::PYTH_MAGIC.to_le_bytes());
+        data[oracle::PYTH_EXPO_OFFSET..][..4].copy_from_slice(&expo.to_le_bytes());
+        data[oracle::PYTH_TIMESTAMP_OFFSET..][..8].copy_from_slice(&timestamp.to_le_bytes());
+        data[oracle::PYTH_AGG_PRICE_OFFSET..][..8].copy_from_slice(&price.to_le_bytes());
+        data[oracle::PYTH_AGG_CONF_OFFSET..][..8].copy_from_slice(&conf.to_le_bytes());
+        data[oracle::PYTH_AGG_STATUS_OFFSET..][..4].copy_from_slice(&status.to_le_bytes());
+        data
+    }
+    
+    fn switchboard_data(result: i128, scale: u32, std_deviation: i128, round_opened_at: i64) -> Vec<u8> {
+        let mut data = vec![0u8; 512];
+        data[oracle::SWITCHBOARD_ROUND_OPEN_TIMESTAMP_OFFSET..][..8].copy_from_slice(&round_opened_at.to_le_bytes());
+        data[oracle::SWITCHBOARD_RESULT_OFFSET..][..16].copy_from_slice(&result.to_le_bytes());
+        data[oracle::SWITCHBOARD_RESULT_OFFSET + 16..][..4].copy_from_slice(&scale.to_le_bytes());
+        data[oracle::SWITCHBOARD_STD_DEVIATION_OFFSET..][..16].copy_from_slice(&std_deviation.to_le_bytes());
+        data[oracle::SWITCHBOARD_STD_DEVIATION_OFFSET + 16..][..4].copy_from_slice(&scale.to_le_bytes());
+        data
+    }
+    
+    #[test]
+    fn oracle_reads_dispatch_on_the_price_source_and_its_program() {
+        let now = 1_700_000_000;
+        let (pyth_key, switchboard_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut pyth_lamports, mut switchboard_lamports) = (0, 0);
+        // The same 1.50 USDC price in each oracle's own layout
+        let mut pyth_data = pyth_price_data(150_000_000, -8, 0, 1, now);
+        let mut switchboard_data = switchboard_data(1_500_000_000, 9, 0, now);
+        let pyth = AccountInfo::new(&pyth_key, false, false, &mut pyth_lamports, &mut pyth_data, &oracle::PYTH_ORACLE_PROGRAM_ID, false, 0);
+        let switchboard = AccountInfo::new(
+            &switchboard_key, false, false, &mut switchboard_lamports, &mut switchboard_data, &oracle::SWITCHBOARD_V2_PROGRAM_ID, false, 0,
+        );
+        
+        assert_eq!(oracle::read_price(&PriceSource::Pyth, &pyth).unwrap(), 1_500_000);
+        assert_eq!(oracle::read_price(&PriceSource::Switchboard, &switchboard).unwrap(), 1_500_000);
+        assert_eq!(oracle::read_price_checked(&PriceSource::Switchboard, &switchboard, now).unwrap(), 1_500_000);
+        
+        // Each source only trusts accounts owned by its own program
+        for (source, account) in [(PriceSource::Pyth, &switchboard), (PriceSource::Switchboard, &pyth)] {
+            assert_eq!(oracle::read_price(&source, account).unwrap_err(), crate::IntentError::InvalidOracle.into());
+            assert_eq!(oracle::read_price_checked(&source, account, now).unwrap_err(), crate::IntentError::InvalidOracle.into());
+        }
+    }
+    
+    #[test]
+    fn prices_normalize_onto_the_oracle_scale_across_decimals() {
+        let to = oracle::ORACLE_PRICE_DECIMALS as u32;
+        // 1.234567891 at 9 decimals truncates rather than rounds
+        assert_eq!(oracle::normalize_price(1_234_567_891, 9, to).unwrap(), 1_234_567);
+        assert_eq!(oracle::normalize_price(123_456_789, 8, to).unwrap(), 1_234_567);
+        assert_eq!(oracle::normalize_price(42, 0, to).unwrap(), 42_000_000);
+        assert_eq!(oracle::normalize_price(1_500_000, to, to).unwrap(), 1_500_000);
+        
+        // A 9-decimal quote meets a 6-decimal target only once both share a scale
+        let (quote, target) = (2_500_000_000u128, 2_500_000u64);
+        assert_ne!(quote, target as u128);
+        assert_eq!(oracle::normalize_price(quote, 9, to).unwrap(), target);
+        
+        // Anything that does not fit the u64 price is refused instead of wrapping
+        assert_eq!(
+            oracle::normalize_price(u64::MAX as u128 + 1, to, to).unwrap_err(),
+            crate::IntentError::InvalidOraclePrice.into()
+        );
+        assert_eq!(oracle::normalize_price(u128::MAX, 0, to).unwrap_err(), crate::IntentError::InvalidOraclePrice.into());
+        assert_eq!(oracle::normalize_price(1, 0, 60).unwrap_err(), crate::IntentError::InvalidOraclePrice.into());
+    }
+    
+    #[test]
+    fn checked_pyth_reads_reject_stale_halted_or_uncertain_prices() {
+        let now = 1_700_000_000;
+        // 150.00 USDC at expo -8, confidence 0.5%
+        let fresh = pyth_price_data(15_000_000_000, -8, 75_000_000, 1, now - 10);
+        assert_eq!(oracle::parse_pyth_price_checked(&fresh, now).unwrap(), 150_000_000);
+        
+        let stale = pyth_price_data(15_000_000_000, -8, 75_000_000, 1, now - oracle::MAX_PRICE_AGE_SECONDS - 1);
+        assert_eq!(oracle::parse_pyth_price_checked(&stale, now).unwrap_err(), crate::IntentError::StaleOraclePrice.into());
+        
+        let uncertain = pyth_price_data(15_000_000_000, -8, 300_000_000, 1, now);
+        assert_eq!(oracle::parse_pyth_price_checked(&uncertain, now).unwrap_err(), crate::IntentError::OracleConfidenceTooWide.into());
+        
+        let halted = pyth_price_data(15_000_000_000, -8, 75_000_000, 2, now);
+        assert!(oracle::parse_pyth_price_checked(&halted, now).is_err());
+        // The unchecked read still accepts all of them
+        assert_eq!(oracle::parse_pyth_price(&stale).unwrap(), 150_000_000);
+    }
+    
+    #[test]
+    fn checked_switchboard_reads_reject_old_rounds_and_wide_deviation() {
+        let now = 1_700_000_000;
+        // 2.5 USDC at scale 9, std deviation 0.2%
+        let fresh = switchboard_data(2_500_000_000, 9, 5_000_000, now - 30);
+        assert_eq!(oracle::parse_switchboard_price_checked(&fresh, now).unwrap(), 2_500_000);
+        
+        let stale = switchboard_data(2_500_000_000, 9, 5_000_000, now - 3_600);
+        assert_eq!(oracle::parse_switchboard_price_checked(&stale, now).unwrap_err(), crate::IntentError::StaleOraclePrice.into());
+        
+        let uncertain = switchboard_data(2_500_000_000, 9, 50_000_000, now);
+        assert_eq!(
+            oracle::parse_switchboard_price_checked(&uncertain, now).unwrap_err(),
+            crate::IntentError::OracleConfidenceTooWide.into()
+        );
+    }
+}
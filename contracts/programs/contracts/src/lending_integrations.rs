@@ -1,4 +1,6 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::instruction::{AccountMeta, Instruction};
+use anchor_lang::solana_program::sysvar;
 use anchor_spl::token::{Token, TokenAccount};
 
 // Solend Protocol Integration
@@ -7,10 +9,10 @@ pub mod solend {
     use super::*;
     
     // Solend program ID
-    pub const SOLEND_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10,
-        10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10, 10
-    ]);
+    #[cfg(not(feature = "devnet"))]
+    pub const SOLEND_PROGRAM_ID: Pubkey = pubkey!("So1endDq2YkqhipRh3WViPa8hdiSpxWy6z3Z6tMCpAo");
+    #[cfg(feature = "devnet")]
+    pub const SOLEND_PROGRAM_ID: Pubkey = pubkey!("ALend7Ketfx5bxh6ghsCDXAoDrhvEmsXT3cynB6aPLgx");
     
     #[derive(Clone)]
     pub struct SolendLendParams {
@@ -20,11 +22,13 @@ pub mod solend {
         pub expected_apy: u16,
     }
     
-    // Solend Reserve structure (simplified)
+    // Solend Reserve structure (simplified past the liquidity supply, which sits where
+    // SPL token-lending puts it)
     #[derive(AnchorSerialize, AnchorDeserialize)]
     pub struct SolendReserve {
         pub version: u8,
         pub last_update: u64,
+        pub stale: bool,
         pub lending_market: Pubkey,
         pub liquidity: ReserveLiquidity,
         pub collateral: ReserveCollateral,
@@ -70,28 +74,30 @@ pub mod solend {
         pub host_fee_percentage: u8,
     }
     
+    // Reserve state read from a Solend-owned reserve account
+    pub fn load_reserve(reserve: &AccountInfo) -> Result<SolendReserve> {
+        require!(reserve.owner == &SOLEND_PROGRAM_ID, crate::IntentError::InvalidLendReserve);
+        SolendReserve::deserialize(&mut &reserve.try_borrow_data()?[..])
+            .map_err(|_| crate::IntentError::InvalidLendReserve.into())
+    }
+    
     // Calculate current lending APY from reserve data
     pub fn calculate_lending_apy(reserve: &SolendReserve) -> Result<u16> {
-        let utilization_rate = if reserve.liquidity.available_amount == 0 {
-            0u128
-        } else {
-            (reserve.liquidity.borrowed_amount_wads * 10000) / 
-            (reserve.liquidity.available_amount as u128 + reserve.liquidity.borrowed_amount_wads)
-        };
+        // Empty reserves have zero utilization, fully-borrowed ones (no available
+        // liquidity) sit at 100% and pay the max rate
+        let total_liquidity = reserve.liquidity.available_amount as u128 + reserve.liquidity.borrowed_amount_wads;
+        let utilization_rate = (reserve.liquidity.borrowed_amount_wads * 10000)
+            .checked_div(total_liquidity)
+            .unwrap_or(0);
         
         // Simplified APY calculation based on utilization
-        let base_rate = reserve.config.min_borrow_rate as u128;
-        let optimal_rate = reserve.config.optimal_borrow_rate as u128;
-        let optimal_util = reserve.config.optimal_utilization_rate as u128 * 100;
-        
-        let lending_apy = if utilization_rate <= optimal_util {
-            // Linear interpolation from base to optimal
-            base_rate + ((optimal_rate - base_rate) * utilization_rate / optimal_util)
-        } else {
-            // Linear interpolation from optimal to max
-            let max_rate = reserve.config.max_borrow_rate as u128;
-            optimal_rate + ((max_rate - optimal_rate) * (utilization_rate - optimal_util) / (10000 - optimal_util))
-        };
+        let lending_apy = interpolate_borrow_rate(
+            utilization_rate,
+            reserve.config.optimal_utilization_rate as u128 * 100,
+            reserve.config.min_borrow_rate as u128,
+            reserve.config.optimal_borrow_rate as u128,
+            reserve.config.max_borrow_rate as u128,
+        );
         
         // Convert to basis points (lending APY is typically 60-80% of borrow APY)
         let final_apy = (lending_apy * 70 / 100) as u16; // 70% of borrow rate
@@ -133,14 +139,74 @@ pub mod solend {
         Ok(current_apy)
     }
     
-    // Get popular Solend markets
-    pub fn get_solend_markets() -> Vec<(String, Pubkey, Pubkey)> {
-        vec![
-            // (Token, Reserve, Lending Market)
-            ("SOL".to_string(), Pubkey::new_from_array([11; 32]), Pubkey::new_from_array([12; 32])),
-            ("USDC".to_string(), Pubkey::new_from_array([13; 32]), Pubkey::new_from_array([14; 32])),
-            ("USDT".to_string(), Pubkey::new_from_array([15; 32]), Pubkey::new_from_array([16; 32])),
-        ]
+    // LendingInstruction tags of Solend's flash loan pair
+    pub(crate) const FLASH_BORROW_RESERVE_LIQUIDITY_TAG: u8 = 19;
+    pub(crate) const FLASH_REPAY_RESERVE_LIQUIDITY_TAG: u8 = 20;
+    
+    // Flash loan fee from the reserve's WAD-scaled fee rate (1e18 = 100%)
+    pub fn calculate_flash_loan_fee(amount: u64, flash_loan_fee_wad: u64) -> Result<u64> {
+        let fee = (amount as u128)
+            .checked_mul(flash_loan_fee_wad as u128)
+            .ok_or(crate::IntentError::InvalidAmount)?
+            / 1_000_000_000_000_000_000;
+        u64::try_from(fee).map_err(|_| crate::IntentError::InvalidAmount.into())
+    }
+    
+    // Accounts of a flash borrow and its repayment out of one reserve. Solend only lends
+    // once it sees the repay instruction; the borrowed liquidity and the flash fee come
+    // back from `user_liquidity`.
+    pub struct FlashLoan {
+        pub reserve_liquidity: Pubkey,
+        pub user_liquidity: Pubkey,
+        pub reserve: Pubkey,
+        pub fee_receiver: Pubkey,
+        pub host_fee_receiver: Pubkey,
+        pub lending_market: Pubkey,
+        pub lending_market_authority: Pubkey,
+        pub transfer_authority: Pubkey,
+    }
+    
+    impl FlashLoan {
+        pub fn borrow_instruction(&self, amount: u64) -> Instruction {
+            let mut data = vec![FLASH_BORROW_RESERVE_LIQUIDITY_TAG];
+            data.extend_from_slice(&amount.to_le_bytes());
+            Instruction {
+                program_id: SOLEND_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(self.reserve_liquidity, false),
+                    AccountMeta::new(self.user_liquidity, false),
+                    AccountMeta::new(self.reserve, false),
+                    AccountMeta::new_readonly(self.lending_market, false),
+                    AccountMeta::new_readonly(self.lending_market_authority, false),
+                    AccountMeta::new_readonly(sysvar::instructions::ID, false),
+                    AccountMeta::new_readonly(anchor_spl::token::ID, false),
+                ],
+                data,
+            }
+        }
+        
+        // Repay the `amount` borrowed by the instruction at `borrow_instruction_index`;
+        // Solend adds the flash fee itself
+        pub fn repay_instruction(&self, amount: u64, borrow_instruction_index: u8) -> Instruction {
+            let mut data = vec![FLASH_REPAY_RESERVE_LIQUIDITY_TAG];
+            data.extend_from_slice(&amount.to_le_bytes());
+            data.push(borrow_instruction_index);
+            Instruction {
+                program_id: SOLEND_PROGRAM_ID,
+                accounts: vec![
+                    AccountMeta::new(self.user_liquidity, false),
+                    AccountMeta::new(self.reserve_liquidity, false),
+                    AccountMeta::new(self.fee_receiver, false),
+                    AccountMeta::new(self.host_fee_receiver, false),
+                    AccountMeta::new(self.reserve, false),
+                    AccountMeta::new_readonly(self.lending_market, false),
+                    AccountMeta::new_readonly(self.transfer_authority, true),
+                    AccountMeta::new_readonly(sysvar::instructions::ID, false),
+                    AccountMeta::new_readonly(anchor_spl::token::ID, false),
+                ],
+                data,
+            }
+        }
     }
 }
 
@@ -149,11 +215,11 @@ pub mod solend {
 pub mod port_finance {
     use super::*;
     
-    // Port Finance program ID
-    pub const PORT_FINANCE_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20,
-        20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20, 20
-    ]);
+    // Port Finance variable rate lending program ID
+    #[cfg(not(feature = "devnet"))]
+    pub const PORT_FINANCE_PROGRAM_ID: Pubkey = pubkey!("Port7uDYB3wk6GJAw4KT1WpTeMtSu9bTcChBHkX2LfR");
+    #[cfg(feature = "devnet")]
+    pub const PORT_FINANCE_PROGRAM_ID: Pubkey = pubkey!("pdQ2rQQU5zH2rDgZ7xH2azMBJegUzUyunJ5Jd637hC4");
     
     #[derive(Clone)]
     pub struct PortLendParams {
@@ -163,20 +229,22 @@ pub mod port_finance {
         pub expected_apy: u16,
     }
     
-    // Port Finance Reserve structure
+    // Port Finance Reserve structure, laid out like the SPL token-lending reserve it forks
     #[derive(AnchorSerialize, AnchorDeserialize)]
     pub struct PortReserve {
-        pub is_initialized: bool,
+        pub version: u8,
+        pub last_update: u64,
+        pub stale: bool,
         pub lending_market: Pubkey,
         pub liquidity: PortLiquidity,
         pub collateral: PortCollateral,
         pub config: PortConfig,
-        pub last_update: u64,
     }
     
     #[derive(AnchorSerialize, AnchorDeserialize)]
     pub struct PortLiquidity {
         pub mint_pubkey: Pubkey,
+        pub mint_decimals: u8,
         pub supply_pubkey: Pubkey,
         pub fee_receiver: Pubkey,
         pub oracle_pubkey: Pubkey,
@@ -205,27 +273,30 @@ pub mod port_finance {
         pub borrow_fee_rate: u8,
     }
     
+    // Reserve state read from a Port Finance-owned reserve account
+    pub fn load_reserve(reserve: &AccountInfo) -> Result<PortReserve> {
+        require!(reserve.owner == &PORT_FINANCE_PROGRAM_ID, crate::IntentError::InvalidLendReserve);
+        PortReserve::deserialize(&mut &reserve.try_borrow_data()?[..])
+            .map_err(|_| crate::IntentError::InvalidLendReserve.into())
+    }
+    
     // Calculate Port Finance lending APY
     pub fn calculate_port_apy(reserve: &PortReserve) -> Result<u16> {
-        let total_liquidity = reserve.liquidity.available_amount + reserve.liquidity.borrowed_amount;
+        let total_liquidity = reserve.liquidity.available_amount as u128 + reserve.liquidity.borrowed_amount as u128;
         
-        let utilization_rate = if total_liquidity == 0 {
-            0
-        } else {
-            (reserve.liquidity.borrowed_amount as u128 * 10000) / total_liquidity as u128
-        };
+        // Zero for empty reserves, 100% when everything is borrowed
+        let utilization_rate = (reserve.liquidity.borrowed_amount as u128 * 10000)
+            .checked_div(total_liquidity)
+            .unwrap_or(0);
         
         // Port Finance uses a different curve than Solend
-        let optimal_util = reserve.config.optimal_utilization_rate as u128 * 100;
-        let base_rate = reserve.config.min_borrow_rate as u128;
-        let optimal_rate = reserve.config.optimal_borrow_rate as u128;
-        
-        let borrow_apy = if utilization_rate <= optimal_util {
-            base_rate + ((optimal_rate - base_rate) * utilization_rate / optimal_util)
-        } else {
-            let max_rate = reserve.config.max_borrow_rate as u128;
-            optimal_rate + ((max_rate - optimal_rate) * (utilization_rate - optimal_util) / (10000 - optimal_util))
-        };
+        let borrow_apy = interpolate_borrow_rate(
+            utilization_rate,
+            reserve.config.optimal_utilization_rate as u128 * 100,
+            reserve.config.min_borrow_rate as u128,
+            reserve.config.optimal_borrow_rate as u128,
+            reserve.config.max_borrow_rate as u128,
+        );
         
         // Port Finance lending APY (typically 75% of borrow APY)
         let lending_apy = (borrow_apy * 75 / 100) as u16;
@@ -264,32 +335,143 @@ pub mod port_finance {
         
         Ok(current_apy)
     }
-    
-    // Get Port Finance markets
-    pub fn get_port_markets() -> Vec<(String, Pubkey, Pubkey)> {
-        vec![
-            // (Token, Reserve, Staking Pool)
-            ("SOL".to_string(), Pubkey::new_from_array([21; 32]), Pubkey::new_from_array([22; 32])),
-            ("USDC".to_string(), Pubkey::new_from_array([23; 32]), Pubkey::new_from_array([24; 32])),
-        ]
-    }
 }
 
 // Francium Integration (Bonus - leveraged yield farming)
 pub mod francium {
     use super::*;
     
-    pub const FRANCIUM_PROGRAM_ID: Pubkey = Pubkey::new_from_array([
-        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30,
-        30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30, 30
-    ]);
+    pub const FRANCIUM_PROGRAM_ID: Pubkey = pubkey!("FC81tbGt6JWRXidaWYFXxGnTk4VgobhJHATvTRVMqgWj");
+    // Leveraged positions must open at least this healthy (bps, 10000 = liquidation point)
+    pub const DEFAULT_MIN_HEALTH_FACTOR: u64 = 12000;
+    
+    // Health factor in bps of a fresh position opened at `leverage_bps` (10000 = 1x)
+    // on a farm liquidating at `liquidation_threshold_bps` of collateral value.
+    // Position = equity * L and debt = equity * (L - 1), so HF = L * threshold / (L - 1).
+    pub fn health_factor(leverage_bps: u64, liquidation_threshold_bps: u64) -> u64 {
+        if leverage_bps <= 10000 {
+            // No borrowed funds, nothing to liquidate
+            return u64::MAX;
+        }
+        ((leverage_bps as u128 * liquidation_threshold_bps as u128) / (leverage_bps - 10000) as u128)
+            .min(u64::MAX as u128) as u64
+    }
     
-    pub fn get_francium_farms() -> Vec<(String, Pubkey)> {
-        vec![
-            ("SOL-USDC LP".to_string(), Pubkey::new_from_array([31; 32])),
-            ("RAY-SOL LP".to_string(), Pubkey::new_from_array([32; 32])),
-        ]
+    // Reject leverage that would open a position too close to liquidation; returns the
+    // computed health factor for the caller's event
+    pub fn check_leverage(
+        leverage_bps: u64,
+        liquidation_threshold_bps: u64,
+        min_health_factor: u64,
+    ) -> Result<u64> {
+        require!(
+            liquidation_threshold_bps > 0 && liquidation_threshold_bps <= 10000,
+            crate::IntentError::InvalidAmount
+        );
+        let health_factor = health_factor(leverage_bps, liquidation_threshold_bps);
+        require!(health_factor >= min_health_factor, crate::IntentError::LeverageTooRisky);
+        
+        msg!("⚖️ Francium leverage {} bps opens at health factor {} bps", leverage_bps, health_factor);
+        Ok(health_factor)
+    }
+}
+
+// Two-slope borrow rate curve: base → optimal up to the optimal utilization, then
+// optimal → max up to 100%. Utilization is in bps and clamped, so a fully-utilized
+// reserve lands exactly on max_rate; misordered rates never underflow.
+fn interpolate_borrow_rate(utilization: u128, optimal_util: u128, base_rate: u128, optimal_rate: u128, max_rate: u128) -> u128 {
+    let utilization = utilization.min(10000);
+    let optimal_util = optimal_util.min(10000);
+    
+    if utilization <= optimal_util {
+        let slope_range = optimal_rate.saturating_sub(base_rate);
+        base_rate + (slope_range * utilization).checked_div(optimal_util).unwrap_or(0)
+    } else {
+        // optimal_util < utilization <= 10000, so the divisor is never zero
+        let slope_range = max_rate.saturating_sub(optimal_rate);
+        optimal_rate + slope_range * (utilization - optimal_util) / (10000 - optimal_util)
+    }
+}
+
+// Solend and Port Finance both fork SPL token-lending and keep its account layouts
+pub(crate) const OBLIGATION_OWNER_OFFSET: usize = 42;
+pub(crate) const RESERVE_LIQUIDITY_MINT_OFFSET: usize = 42;
+pub(crate) const RESERVE_LIQUIDITY_SUPPLY_OFFSET: usize = 75;
+pub(crate) const REPAY_OBLIGATION_LIQUIDITY_TAG: u8 = 11;
+
+// Wallet that owns a lending obligation
+pub fn obligation_owner(obligation: &AccountInfo, lending_program: &Pubkey) -> Result<Pubkey> {
+    require!(obligation.owner == lending_program, crate::IntentError::InvalidObligation);
+    let data = obligation.try_borrow_data()?;
+    require!(data.len() >= OBLIGATION_OWNER_OFFSET + 32, crate::IntentError::InvalidObligation);
+    Ok(Pubkey::new_from_array(data[OBLIGATION_OWNER_OFFSET..OBLIGATION_OWNER_OFFSET + 32].try_into().unwrap()))
+}
+
+// A reserve's liquidity (mint, supply token account)
+pub fn reserve_liquidity(reserve: &AccountInfo, lending_program: &Pubkey) -> Result<(Pubkey, Pubkey)> {
+    require!(reserve.owner == lending_program, crate::IntentError::InvalidRepayReserve);
+    let data = reserve.try_borrow_data()?;
+    require!(data.len() >= RESERVE_LIQUIDITY_SUPPLY_OFFSET + 32, crate::IntentError::InvalidRepayReserve);
+    let mint = Pubkey::new_from_array(data[RESERVE_LIQUIDITY_MINT_OFFSET..RESERVE_LIQUIDITY_MINT_OFFSET + 32].try_into().unwrap());
+    let supply = Pubkey::new_from_array(data[RESERVE_LIQUIDITY_SUPPLY_OFFSET..RESERVE_LIQUIDITY_SUPPLY_OFFSET + 32].try_into().unwrap());
+    Ok((mint, supply))
+}
+
+// Accounts of a RepayObligationLiquidity instruction. The reserve and obligation must be
+// refreshed earlier in the same transaction.
+pub struct RepayObligationLiquidity {
+    pub source_liquidity: Pubkey,
+    pub destination_liquidity: Pubkey,
+    pub repay_reserve: Pubkey,
+    pub obligation: Pubkey,
+    pub lending_market: Pubkey,
+    pub transfer_authority: Pubkey,
+}
+
+impl RepayObligationLiquidity {
+    // Repay up to `amount`; the protocol caps it at the outstanding debt. Port Finance
+    // still reads the clock sysvar, Solend dropped it.
+    pub fn instruction(&self, lending_program: Pubkey, amount: u64, with_clock: bool) -> Instruction {
+        let mut accounts = vec![
+            AccountMeta::new(self.source_liquidity, false),
+            AccountMeta::new(self.destination_liquidity, false),
+            AccountMeta::new(self.repay_reserve, false),
+            AccountMeta::new(self.obligation, false),
+            AccountMeta::new_readonly(self.lending_market, false),
+            AccountMeta::new_readonly(self.transfer_authority, true),
+        ];
+        if with_clock {
+            accounts.push(AccountMeta::new_readonly(sysvar::clock::ID, false));
+        }
+        accounts.push(AccountMeta::new_readonly(anchor_spl::token::ID, false));
+        
+        let mut data = vec![REPAY_OBLIGATION_LIQUIDITY_TAG];
+        data.extend_from_slice(&amount.to_le_bytes());
+        Instruction { program_id: lending_program, accounts, data }
+    }
+}
+
+// Effective first-year APY on the full principal once the protocol fee is taken off it.
+// The deposit earns gross_apy_bps on (amount - fee), measured against the original amount.
+pub fn net_effective_apy(gross_apy_bps: u16, fee_bps: u16, amount: u64) -> u16 {
+    if amount == 0 {
+        return 0;
     }
+    
+    let fee = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap();
+    let net_amount = (amount as u128).saturating_sub(fee);
+    
+    // Value after one year, scaled by 10000
+    let ending_value = net_amount
+        .checked_mul(10000 + gross_apy_bps as u128)
+        .unwrap();
+    let starting_value = (amount as u128).checked_mul(10000).unwrap();
+    
+    (ending_value.saturating_sub(starting_value) / amount as u128) as u16
 }
 
 // Lending Protocol Router - Chooses best lending protocol
@@ -310,6 +492,26 @@ impl LendingRouter {
         }
     }
     
+    // Choose among the registered markets for the token, preferring the protocol
+    // choose_best_lending_protocol would pick. Only Solend and Port Finance have execute
+    // paths, so other registered protocols are never selected.
+    pub fn choose_registered_lending_protocol<'a>(
+        markets: &'a [crate::LendingMarket],
+        mint: &Pubkey,
+        amount: u64,
+    ) -> Option<&'a crate::LendingMarket> {
+        let preferred = Self::choose_best_lending_protocol(mint, amount);
+        let mut registered = markets.iter().filter(|market| {
+            market.mint == *mint
+                && matches!(market.protocol, LendingProtocol::Solend | LendingProtocol::PortFinance)
+        });
+        
+        registered
+            .clone()
+            .find(|market| market.protocol == preferred)
+            .or_else(|| registered.next())
+    }
+    
     // Get best APY across all protocols for a token  
     pub fn get_best_apy_for_token(_mint: &Pubkey) -> Result<(LendingProtocol, u16)> {
         // In production, this would query live APY data from all protocols
@@ -327,7 +529,7 @@ impl LendingRouter {
     }
 }
 
-#[derive(Clone, Debug, AnchorSerialize, AnchorDeserialize)]
+#[derive(Clone, Debug, PartialEq, Eq, AnchorSerialize, AnchorDeserialize, InitSpace)]
 pub enum LendingProtocol {
     Solend,      // Largest lending protocol
     PortFinance, // Second largest
@@ -386,4 +588,212 @@ pub struct ExecuteLendIntent<'info> {
     
     pub token_program: Program<'info, Token>,
     pub system_program: Program<'info, System>,
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn port_reserve(available: u64, borrowed: u64) -> port_finance::PortReserve {
+        port_finance::PortReserve {
+            version: 1,
+            last_update: 0,
+            stale: false,
+            lending_market: Pubkey::new_unique(),
+            liquidity: port_finance::PortLiquidity {
+                mint_pubkey: Pubkey::new_unique(),
+                mint_decimals: 6,
+                supply_pubkey: Pubkey::new_unique(),
+                fee_receiver: Pubkey::new_unique(),
+                oracle_pubkey: Pubkey::new_unique(),
+                available_amount: available,
+                borrowed_amount: borrowed,
+                cumulative_borrow_rate: 0,
+                market_price: 0,
+            },
+            collateral: port_finance::PortCollateral {
+                mint_pubkey: Pubkey::new_unique(),
+                supply_pubkey: Pubkey::new_unique(),
+                total_supply: 0,
+            },
+            config: port_finance::PortConfig {
+                optimal_utilization_rate: 80,
+                max_borrow_rate: 200,
+                loan_to_value_ratio: 75,
+                liquidation_bonus: 5,
+                liquidation_threshold: 85,
+                min_borrow_rate: 0,
+                optimal_borrow_rate: 40,
+                borrow_fee_rate: 0,
+            },
+        }
+    }
+    
+    #[test]
+    fn net_apy_subtracts_the_fee_drag_from_the_gross_rate() {
+        // No fee leaves the gross APY untouched
+        assert_eq!(net_effective_apy(500, 0, 1_000_000), 500);
+        // 0.3% and 1% fees cost the first year 32 and 105 bps of a 5% APY
+        assert_eq!(net_effective_apy(500, 30, 1_000_000), 468);
+        assert_eq!(net_effective_apy(500, 100, 1_000_000), 395);
+        // A fee larger than the yield nets to zero rather than wrapping
+        assert_eq!(net_effective_apy(20, 100, 1_000_000), 0);
+        assert_eq!(net_effective_apy(500, 30, 0), 0);
+    }
+    
+    fn solend_reserve(available: u64, borrowed: u128, optimal_utilization: u8, rates: [u8; 3]) -> solend::SolendReserve {
+        let mut reserve = solend::SolendReserve::deserialize(&mut &[0u8; 512][..]).unwrap();
+        reserve.liquidity.available_amount = available;
+        reserve.liquidity.borrowed_amount_wads = borrowed;
+        reserve.config.optimal_utilization_rate = optimal_utilization;
+        [reserve.config.min_borrow_rate, reserve.config.optimal_borrow_rate, reserve.config.max_borrow_rate] = rates;
+        reserve
+    }
+    
+    #[test]
+    fn solend_apy_covers_empty_partial_and_fully_utilized_reserves() {
+        // Lenders earn 70% of a 5% → 40% → 200% borrow curve kinked at 80% utilization
+        let rates = [5, 40, 200];
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(0, 0, 80, rates)).unwrap(), 3);
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(1_000, 0, 80, rates)).unwrap(), 3);
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(600, 400, 80, rates)).unwrap(), 15);
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(0, 1_000, 80, rates)).unwrap(), 140);
+        // Fully utilized lands on the max rate even when the kink sits at 100%
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(0, 1_000, 100, rates)).unwrap(), 28);
+        // Misordered rates clamp rather than underflow
+        assert_eq!(solend::calculate_lending_apy(&solend_reserve(0, 1_000, 80, [50, 40, 10])).unwrap(), 28);
+        
+        let mut fully_utilized = port_reserve(0, 1_000);
+        fully_utilized.config.optimal_utilization_rate = 100;
+        assert_eq!(port_finance::calculate_port_apy(&fully_utilized).unwrap(), 30);
+    }
+    
+    #[test]
+    fn port_apy_is_the_reserve_rate_without_a_staking_boost() {
+        // Empty reserve pays nothing, a fully borrowed one pays 75% of the max borrow rate
+        assert_eq!(port_finance::calculate_port_apy(&port_reserve(1_000, 0)).unwrap(), 0);
+        assert_eq!(port_finance::calculate_port_apy(&port_reserve(0, 1_000)).unwrap(), 150);
+        // At optimal utilization the borrow rate is the optimal rate
+        assert_eq!(port_finance::calculate_port_apy(&port_reserve(200, 800)).unwrap(), 30);
+    }
+    
+    #[test]
+    fn francium_leverage_must_open_clear_of_liquidation() {
+        use francium::{check_leverage, DEFAULT_MIN_HEALTH_FACTOR};
+        // 2x on an 85% threshold farm opens at 1.7, 5x at 1.0625
+        assert_eq!(check_leverage(20000, 8500, DEFAULT_MIN_HEALTH_FACTOR).unwrap(), 17000);
+        assert_eq!(check_leverage(50000, 8500, DEFAULT_MIN_HEALTH_FACTOR).unwrap_err(), crate::IntentError::LeverageTooRisky.into());
+        // 4x on a 90% threshold farm lands exactly on the minimum
+        assert_eq!(check_leverage(40000, 9000, DEFAULT_MIN_HEALTH_FACTOR).unwrap(), DEFAULT_MIN_HEALTH_FACTOR);
+        assert!(check_leverage(40001, 9000, DEFAULT_MIN_HEALTH_FACTOR).is_err());
+        // A stricter minimum turns away leverage the default allows
+        assert!(check_leverage(20000, 8500, 18000).is_err());
+        // Unleveraged positions borrow nothing and can't be liquidated
+        assert_eq!(check_leverage(10000, 8500, DEFAULT_MIN_HEALTH_FACTOR).unwrap(), u64::MAX);
+        // Thresholds outside (0, 100%] aren't a farm's
+        assert_eq!(check_leverage(20000, 0, DEFAULT_MIN_HEALTH_FACTOR).unwrap_err(), crate::IntentError::InvalidAmount.into());
+        assert!(check_leverage(20000, 10001, DEFAULT_MIN_HEALTH_FACTOR).is_err());
+    }
+    
+    #[test]
+    fn repays_only_the_owners_obligation_through_the_lending_program() {
+        let (owner, program) = (Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID);
+        let obligation_key = Pubkey::new_unique();
+        let mut data = vec![0u8; 1300];
+        data[OBLIGATION_OWNER_OFFSET..][..32].copy_from_slice(owner.as_ref());
+        let mut lamports = 0;
+        let obligation = AccountInfo::new(&obligation_key, false, true, &mut lamports, &mut data, &program, false, 0);
+        assert_eq!(obligation_owner(&obligation, &program).unwrap(), owner);
+        // An obligation account from another program proves nothing
+        assert!(obligation_owner(&obligation, &port_finance::PORT_FINANCE_PROGRAM_ID).is_err());
+        
+        let (mint, supply) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let reserve_key = Pubkey::new_unique();
+        let mut reserve_data = vec![0u8; 619];
+        reserve_data[RESERVE_LIQUIDITY_MINT_OFFSET..][..32].copy_from_slice(mint.as_ref());
+        reserve_data[RESERVE_LIQUIDITY_SUPPLY_OFFSET..][..32].copy_from_slice(supply.as_ref());
+        let mut reserve_lamports = 0;
+        let reserve = AccountInfo::new(&reserve_key, false, true, &mut reserve_lamports, &mut reserve_data, &program, false, 0);
+        assert_eq!(reserve_liquidity(&reserve, &program).unwrap(), (mint, supply));
+    }
+    
+    #[test]
+    fn reserves_load_from_the_lending_programs_own_accounts_in_the_spl_layout() {
+        let (mint, supply, reserve_key) = (Pubkey::new_unique(), Pubkey::new_unique(), Pubkey::new_unique());
+        let mut solend_data = solend_reserve(0, 1_000, 80, [5, 40, 200]);
+        (solend_data.liquidity.mint_pubkey, solend_data.liquidity.supply_pubkey) = (mint, supply);
+        let mut port_data = port_reserve(0, 1_000);
+        (port_data.liquidity.mint_pubkey, port_data.liquidity.supply_pubkey) = (mint, supply);
+        
+        let (solend_program, port_program) = (solend::SOLEND_PROGRAM_ID, port_finance::PORT_FINANCE_PROGRAM_ID);
+        let (mut solend_bytes, mut port_bytes) = (solend_data.try_to_vec().unwrap(), port_data.try_to_vec().unwrap());
+        let (mut solend_lamports, mut port_lamports) = (0, 0);
+        let solend = AccountInfo::new(&reserve_key, false, false, &mut solend_lamports, &mut solend_bytes, &solend_program, false, 0);
+        let port = AccountInfo::new(&reserve_key, false, false, &mut port_lamports, &mut port_bytes, &port_program, false, 0);
+        // Both read back as written, with the liquidity where SPL token-lending keeps it
+        assert_eq!(solend::calculate_lending_apy(&solend::load_reserve(&solend).unwrap()).unwrap(), 140);
+        assert_eq!(port_finance::calculate_port_apy(&port_finance::load_reserve(&port).unwrap()).unwrap(), 150);
+        assert_eq!(reserve_liquidity(&solend, &solend_program).unwrap(), (mint, supply));
+        assert_eq!(reserve_liquidity(&port, &port_program).unwrap(), (mint, supply));
+        // Neither program's loader accepts the other's account
+        assert_eq!(solend::load_reserve(&port).err(), Some(crate::IntentError::InvalidLendReserve.into()));
+        assert_eq!(port_finance::load_reserve(&solend).err(), Some(crate::IntentError::InvalidLendReserve.into()));
+    }
+    
+    #[test]
+    fn flash_loans_borrow_and_repay_through_solends_instruction_pair() {
+        let flash_loan = solend::FlashLoan {
+            reserve_liquidity: Pubkey::new_unique(),
+            user_liquidity: Pubkey::new_unique(),
+            reserve: Pubkey::new_unique(),
+            fee_receiver: Pubkey::new_unique(),
+            host_fee_receiver: Pubkey::new_unique(),
+            lending_market: Pubkey::new_unique(),
+            lending_market_authority: Pubkey::new_unique(),
+            transfer_authority: Pubkey::new_unique(),
+        };
+        
+        let borrow = flash_loan.borrow_instruction(5_000);
+        assert_eq!(borrow.data[0], solend::FLASH_BORROW_RESERVE_LIQUIDITY_TAG);
+        assert_eq!(u64::from_le_bytes(borrow.data[1..9].try_into().unwrap()), 5_000);
+        assert_eq!(borrow.accounts.len(), 7);
+        assert!(borrow.accounts[0].pubkey == flash_loan.reserve_liquidity && borrow.accounts[1].pubkey == flash_loan.user_liquidity);
+        assert!(borrow.accounts.iter().all(|meta| !meta.is_signer));
+        
+        // The repay points back at the borrow and is the only leg the user signs
+        let repay = flash_loan.repay_instruction(5_000, 2);
+        assert_eq!(repay.data[0], solend::FLASH_REPAY_RESERVE_LIQUIDITY_TAG);
+        assert_eq!((u64::from_le_bytes(repay.data[1..9].try_into().unwrap()), repay.data[9]), (5_000, 2));
+        assert!(repay.accounts[0].pubkey == flash_loan.user_liquidity && repay.accounts[1].pubkey == flash_loan.reserve_liquidity);
+        assert!(repay.accounts[6].is_signer && repay.accounts[6].pubkey == flash_loan.transfer_authority);
+        assert_eq!(repay.accounts[7].pubkey, sysvar::instructions::ID);
+        
+        // Fees are WAD-scaled: 0.3% of 1M, and nothing on a fee-free reserve
+        assert_eq!(solend::calculate_flash_loan_fee(1_000_000, 3_000_000_000_000_000).unwrap(), 3_000);
+        assert_eq!(solend::calculate_flash_loan_fee(1_000_000, 0).unwrap(), 0);
+    }
+    
+    #[test]
+    fn repay_instruction_matches_each_protocols_layout() {
+        let repay = RepayObligationLiquidity {
+            source_liquidity: Pubkey::new_unique(),
+            destination_liquidity: Pubkey::new_unique(),
+            repay_reserve: Pubkey::new_unique(),
+            obligation: Pubkey::new_unique(),
+            lending_market: Pubkey::new_unique(),
+            transfer_authority: Pubkey::new_unique(),
+        };
+        
+        let solend_ix = repay.instruction(solend::SOLEND_PROGRAM_ID, 5_000, false);
+        assert_eq!(solend_ix.data[0], REPAY_OBLIGATION_LIQUIDITY_TAG);
+        assert_eq!(u64::from_le_bytes(solend_ix.data[1..9].try_into().unwrap()), 5_000);
+        assert_eq!(solend_ix.accounts.len(), 7);
+        assert!(solend_ix.accounts[5].is_signer && solend_ix.accounts[5].pubkey == repay.transfer_authority);
+        assert!(solend_ix.accounts[3].is_writable && solend_ix.accounts[3].pubkey == repay.obligation);
+        
+        // Port Finance also takes the clock sysvar before the token program
+        let port_ix = repay.instruction(port_finance::PORT_FINANCE_PROGRAM_ID, 5_000, true);
+        assert_eq!(port_ix.accounts.len(), 8);
+        assert_eq!(port_ix.accounts[6].pubkey, sysvar::clock::ID);
+        assert_eq!(port_ix.accounts[7].pubkey, anchor_spl::token::ID);
+    }
+}
@@ -1,13 +1,22 @@
 use anchor_lang::prelude::*;
+use anchor_lang::solana_program::hash::hashv;
+use anchor_lang::solana_program::instruction::Instruction;
+use anchor_lang::solana_program::program::invoke_signed;
+use anchor_lang::solana_program::sysvar::instructions::load_current_index_checked;
+use anchor_lang::system_program;
 use anchor_spl::{
-    token::{self, Token, TokenAccount, Transfer},
+    associated_token::get_associated_token_address,
+    token::{self, Approve, Mint, Revoke, Token, TokenAccount, Transfer},
 };
 
 // Import our protocol integrations
 pub mod integrations;
 pub mod lending_integrations;
-use integrations::{jupiter, raydium, ProtocolRouter, SwapProtocol};
-use lending_integrations::{solend, port_finance, LendingRouter, LendingProtocol};
+use integrations::{jupiter, raydium, meteora, oracle, rugproof, wsol, PriceSource, ProtocolRouter, SwapProtocol};
+use lending_integrations::{
+    obligation_owner, port_finance, reserve_liquidity, solend, net_effective_apy, LendingProtocol, LendingRouter,
+    RepayObligationLiquidity,
+};
 
 declare_id!("7opSCrXjWAC5cjMdSJiFjHGY2ncWiyQyHZEbmjiUA3Ax");
 
@@ -15,7 +24,27 @@ declare_id!("7opSCrXjWAC5cjMdSJiFjHGY2ncWiyQyHZEbmjiUA3Ax");
 pub const PROTOCOL_FEE_BPS: u16 = 30; // 0.3% = 30 basis points
 pub const MAX_INTENTS_PER_USER: u8 = 50;
 pub const INTENT_EXPIRY_SECONDS: i64 = 86400 * 7; // 7 days
+pub const MIN_INTENT_LIFETIME: i64 = 60; // Intents must outlive creation by at least a minute
+pub const DEFAULT_EXPIRY_WARNING_WINDOW: i64 = 86400; // Warn during an intent's last day
+pub const DEFAULT_MAX_EXECUTION_ATTEMPTS: u8 = 10; // Missed conditions before an intent fails out
 pub const MIN_RUGPROOF_SCORE: u8 = 70;
+pub const RUGPROOF_CACHE_TTL: i64 = 3600; // Cached scores older than 1 hour are recomputed
+pub const MAX_FEE_BENEFICIARIES: usize = 4;
+pub const DEFAULT_MIN_POOL_LIQUIDITY: u64 = 1_000_000; // Minimum reserve on each side of a pool
+pub const MAX_BULK_CANCELS: usize = 10; // Intents cancellable per cancel_all_intents call
+pub const MAX_FEE_EXEMPT: usize = 8; // Whitelisted fee-free accounts (protocol market making)
+pub const MAX_LENDING_MARKETS: usize = 16; // Entries in the lending market registry
+pub const MAX_SLIPPAGE_BPS: u16 = 5000; // 50%, ceiling on any intent's slippage tolerance
+pub const DEFAULT_SLIPPAGE_BPS: u16 = 50; // 0.5%, new users' default slippage tolerance
+pub const MAX_STABLE_PAIR_SLIPPAGE_BPS: u16 = 100; // 1%, anything looser on a stable pair is a mistake
+pub const DEFAULT_MIN_APY_SPREAD_BPS: u16 = 50; // Reselect only for at least 0.5% more APY
+pub const DEFAULT_MAX_REALISTIC_APY: u16 = 5000; // 50%, no real reserve pays more than this
+pub const MAX_TYPE_FEE_BPS: u16 = 100; // 1% ceiling on any per-intent-type fee
+pub const MAX_REBALANCE_ASSETS: usize = 4; // Assets a single rebalance intent can weight
+pub const INTENT_TYPE_COUNT: usize = 7; // Entries in ProtocolState::fee_bps_by_type
+pub const TIP_SWEEP_GRACE_PERIOD: i64 = 86400 * 30; // Tips still locked 30 days past expiry can be swept to treasury
+pub const CONFIG_VERSION: u8 = 4; // Bumped whenever the ProtocolState layout changes
+pub const PRICE_SCALE: u128 = 1_000_000_000; // Fixed-point scale for execution and reference prices
 
 #[program]
 pub mod intentfi {
@@ -34,6 +63,17 @@ pub mod intentfi {
         protocol_state.total_intents_created = 0;
         protocol_state.total_intents_executed = 0;
         protocol_state.is_paused = false;
+        protocol_state.min_pool_liquidity = DEFAULT_MIN_POOL_LIQUIDITY;
+        protocol_state.min_fee_tokens = 0;
+        protocol_state.expiry_bounty_lamports = 0;
+        protocol_state.fee_exempt = Vec::new();
+        protocol_state.max_realistic_apy = DEFAULT_MAX_REALISTIC_APY;
+        protocol_state.expiry_warning_window = DEFAULT_EXPIRY_WARNING_WINDOW;
+        protocol_state.max_execution_attempts = DEFAULT_MAX_EXECUTION_ATTEMPTS;
+        protocol_state.fee_bps_by_type = [PROTOCOL_FEE_BPS; INTENT_TYPE_COUNT];
+        protocol_state.min_apy_spread_bps = DEFAULT_MIN_APY_SPREAD_BPS;
+        protocol_state.flash_loans_enabled = false;
+        protocol_state.config_version = CONFIG_VERSION;
         protocol_state.bump = ctx.bumps.protocol_state;
         
         msg!("🚀 IntentFI Protocol initialized with Jupiter + Raydium + Solend + Port Finance");
@@ -49,13 +89,111 @@ pub mod intentfi {
         user_account.total_intents_created = 0;
         user_account.total_volume = 0;
         user_account.rugproof_enabled = true;
+        user_account.delegate = None;
+        user_account.rugproof_min_score = MIN_RUGPROOF_SCORE;
+        user_account.default_slippage_bps = DEFAULT_SLIPPAGE_BPS;
         user_account.bump = ctx.bumps.user_account;
         
         msg!("👤 User account initialized for: {}", ctx.accounts.authority.key());
         Ok(())
     }
 
+    /// Register (or clear) a delegate allowed to create intents on the user's behalf
+    pub fn set_delegate(ctx: Context<SetDelegate>, delegate: Option<Pubkey>) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        user_account.delegate = delegate;
+        
+        match delegate {
+            Some(delegate) => msg!("🤝 Delegate {} set for: {}", delegate, user_account.authority),
+            None => msg!("🤝 Delegate cleared for: {}", user_account.authority),
+        }
+        Ok(())
+    }
+
+    /// Approve the protocol PDA to move up to `amount` from the user's token account,
+    /// so keepers can execute intents funded from it without the user signing. Execution
+    /// spends the allowance through `OwnerAuthority` whenever the owner hasn't signed.
+    pub fn delegate_for_execution(ctx: Context<ManageExecutionDelegation>, amount: u64) -> Result<()> {
+        require!(amount > 0, IntentError::InvalidAmount);
+        
+        let approve_accounts = Approve {
+            to: ctx.accounts.user_token_account.to_account_info(),
+            delegate: ctx.accounts.protocol_state.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token::approve(CpiContext::new(ctx.accounts.token_program.to_account_info(), approve_accounts), amount)?;
+        
+        emit!(ExecutionDelegationUpdated {
+            user: ctx.accounts.user.key(),
+            token_account: ctx.accounts.user_token_account.key(),
+            amount,
+        });
+        
+        msg!("🔑 Delegated {} tokens from {} for keeper execution", amount, ctx.accounts.user_token_account.key());
+        Ok(())
+    }
+
+    /// Revoke any delegation on the user's token account
+    pub fn revoke_delegation(ctx: Context<ManageExecutionDelegation>) -> Result<()> {
+        let revoke_accounts = Revoke {
+            source: ctx.accounts.user_token_account.to_account_info(),
+            authority: ctx.accounts.user.to_account_info(),
+        };
+        token::revoke(CpiContext::new(ctx.accounts.token_program.to_account_info(), revoke_accounts))?;
+        
+        emit!(ExecutionDelegationUpdated {
+            user: ctx.accounts.user.key(),
+            token_account: ctx.accounts.user_token_account.key(),
+            amount: 0,
+        });
+        
+        msg!("🔒 Delegation revoked on {}", ctx.accounts.user_token_account.key());
+        Ok(())
+    }
+
+    /// Set the user's personal minimum rugproof score, at least the protocol's MIN_RUGPROOF_SCORE
+    pub fn set_rugproof_threshold(ctx: Context<SetRugproofThreshold>, min_score: u8) -> Result<()> {
+        require!(
+            (MIN_RUGPROOF_SCORE..=100).contains(&min_score),
+            IntentError::InvalidRugproofThreshold
+        );
+        
+        let user_account = &mut ctx.accounts.user_account;
+        let old_min_score = user_account.rugproof_min_score;
+        user_account.rugproof_min_score = min_score;
+        
+        emit!(RugproofThresholdUpdated {
+            user: user_account.authority,
+            old_min_score,
+            new_min_score: min_score,
+        });
+        
+        msg!("🛡️ Rugproof threshold for {} set to {}", user_account.authority, min_score);
+        Ok(())
+    }
+
+    /// Set the slippage tolerance used by swap intents created without one
+    pub fn set_default_slippage(ctx: Context<SetDefaultSlippage>, slippage_bps: u16) -> Result<()> {
+        require!(slippage_bps <= MAX_SLIPPAGE_BPS, IntentError::SlippageTooHigh);
+        
+        let user_account = &mut ctx.accounts.user_account;
+        let old_slippage_bps = user_account.default_slippage_bps;
+        user_account.default_slippage_bps = slippage_bps;
+        
+        emit!(DefaultSlippageUpdated {
+            user: user_account.authority,
+            old_slippage_bps,
+            new_slippage_bps: slippage_bps,
+        });
+        
+        msg!("🎚️ Default slippage for {} set to {} bps", user_account.authority, slippage_bps);
+        Ok(())
+    }
+
     /// Create a swap intent with protocol selection
+    ///
+    /// With `has_liquidity`, remaining accounts must include a pool's two token vaults
+    /// for the pair, each holding at least the protocol's minimum pool liquidity.
     pub fn create_swap_intent(
         ctx: Context<CreateSwapIntent>,
         params: SwapIntentParams,
@@ -70,31 +208,65 @@ pub mod intentfi {
         // Validate protocol is not paused
         require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
         
-        // Validate intent parameters
+        // Validate intent parameters; omitted slippage falls back to the user's default
+        let max_slippage = params.max_slippage.unwrap_or(user_account.default_slippage_bps);
         require!(params.amount > 0, IntentError::InvalidAmount);
-        require!(params.max_slippage <= 5000, IntentError::SlippageTooHigh); // Max 50%
+        require!(max_slippage <= MAX_SLIPPAGE_BPS, IntentError::SlippageTooHigh);
+        require!(
+            !ProtocolRouter::is_stable_pair(&params.from_mint, &params.to_mint)
+                || max_slippage <= MAX_STABLE_PAIR_SLIPPAGE_BPS,
+            IntentError::SlippageTooHighForStablePair
+        );
+        require!(!params.exact_output || params.max_input > 0, IntentError::InvalidAmount);
+        require!(params.min_fill_amount <= params.amount, IntentError::InvalidAmount);
+        if params.auto_lend {
+            let min_apy = params.min_apy.unwrap_or(0);
+            require!(min_apy <= 10000, IntentError::InvalidAPY); // Max 100%
+            require!(min_apy <= protocol_state.max_realistic_apy, IntentError::UnrealisticApy);
+        }
         
-        // Calculate protocol fee (0.3%)
-        let protocol_fee = (params.amount as u128)
-            .checked_mul(PROTOCOL_FEE_BPS as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        // The router falls back to Jupiter for any pair, so prove a venue exists when asked
+        if params.has_liquidity {
+            require!(
+                find_pool_reserves(
+                    ctx.remaining_accounts,
+                    &params.from_mint,
+                    &params.to_mint,
+                    protocol_state.min_pool_liquidity,
+                ).is_some(),
+                IntentError::NoLiquidity
+            );
+        }
+        
+        // Calculate the swap protocol fee. Exact-output intents are charged on the input actually
+        // spent at execution, so this is only the upper bound from the budget.
+        let input_amount = if params.exact_output { params.max_input } else { params.amount };
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::Swap);
+        let protocol_fee = calculate_protocol_fee(input_amount, fee_bps, protocol_state.min_fee_tokens)?;
         
         // Perform rugproof check if enabled
         if params.rugproof_enabled {
-            let rugproof_score = perform_rugproof_check(&params.to_mint)?;
-            require!(rugproof_score >= MIN_RUGPROOF_SCORE, IntentError::RugproofCheckFailed);
+            let rugproof_score = cached_rugproof_score(&params.to_mint, ctx.accounts.rugproof_cache.as_deref())?;
+            require!(
+                rugproof_score >= user_account.rugproof_min_score.max(MIN_RUGPROOF_SCORE),
+                IntentError::RugproofCheckFailed
+            );
             
             msg!("🛡️ Rugproof check passed with score: {}", rugproof_score);
         }
         
         // Choose best DEX protocol for this swap
-        let selected_protocol = ProtocolRouter::choose_best_protocol(
+        let selected_protocol = match ProtocolRouter::choose_best_protocol(
             &params.from_mint,
             &params.to_mint,
-            params.amount,
-        );
+            input_amount,
+        ) {
+            // Meteora bins can't be solved for an exact output, Jupiter can quote ExactOut
+            SwapProtocol::Meteora if params.exact_output => SwapProtocol::Jupiter,
+            // Only the Jupiter execution carries the lend leg's accounts
+            _ if params.auto_lend => SwapProtocol::Jupiter,
+            protocol => protocol,
+        };
         
         msg!(
             "🎯 Selected protocol: {:?} for {}/{} swap",
@@ -104,21 +276,58 @@ pub mod intentfi {
         );
         
         // Initialize intent account
-        intent_account.authority = ctx.accounts.authority.key();
+        intent_account.authority = user_account.authority;
         intent_account.intent_type = IntentType::Swap;
         intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
         intent_account.from_mint = params.from_mint;
         intent_account.to_mint = params.to_mint;
         intent_account.amount = params.amount;
         intent_account.protocol_fee = protocol_fee;
-        intent_account.max_slippage = params.max_slippage;
+        intent_account.max_slippage = max_slippage;
+        intent_account.min_apy = if params.auto_lend { params.min_apy } else { None };
         intent_account.rugproof_enabled = params.rugproof_enabled;
         intent_account.selected_swap_protocol = selected_protocol.clone();
         intent_account.selected_lending_protocol = None;
         intent_account.created_at = Clock::get()?.unix_timestamp;
         intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
+        intent_account.pool_id = None;
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = PriceSource::Pyth;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = params.max_route_fee;
+        intent_account.exact_output = params.exact_output;
+        intent_account.max_input = if params.exact_output { params.max_input } else { 0 };
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = params.min_fill_amount;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = params.auto_lend;
         intent_account.bump = ctx.bumps.intent_account;
         
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
         // Update counters
         user_account.active_intents += 1;
         user_account.total_intents_created += 1;
@@ -138,39 +347,164 @@ pub mod intentfi {
     }
 
     /// Execute a swap intent through selected DEX protocol
-    pub fn execute_swap_intent_jupiter(
-        ctx: Context<ExecuteSwapIntentJupiter>,
+    pub fn execute_swap_intent_jupiter<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteSwapIntentJupiter<'info>>,
         jupiter_swap_data: jupiter::JupiterSwapData,
     ) -> Result<()> {
         // Validate intent can be executed
         require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
-        require!(Clock::get()?.unix_timestamp < ctx.accounts.intent_account.expires_at, IntentError::IntentExpired);
-        require!(matches!(ctx.accounts.intent_account.selected_swap_protocol, SwapProtocol::Jupiter), IntentError::WrongProtocol);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        // A buy whose price check deadline has lapsed is as stale as an expired one
+        let now = Clock::get()?.unix_timestamp;
+        if now >= ctx.accounts.intent_account.expires_at
+            || ctx.accounts.intent_account.price_check_deadline.is_some_and(|deadline| now >= deadline)
+        {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        // A Raydium intent may fall back to Jupiter once its pool can no longer fill the
+        // trade within slippage, proven from the pool's own vaults
+        let fallback_from = match ctx.accounts.intent_account.selected_swap_protocol {
+            SwapProtocol::Jupiter => None,
+            SwapProtocol::Raydium => {
+                let (Some(primary_pool), Some(coin_vault), Some(pc_vault)) = (
+                    ctx.accounts.primary_pool.as_ref(),
+                    ctx.accounts.primary_pool_coin_vault.as_ref(),
+                    ctx.accounts.primary_pool_pc_vault.as_ref(),
+                ) else {
+                    msg!("❌ Raydium intent needs its pool and vaults to fall back to Jupiter");
+                    return err!(IntentError::WrongProtocol);
+                };
+                require!(primary_pool.owner == &raydium::RAYDIUM_AMM_PROGRAM_ID, IntentError::InvalidPool);
+                if let Some(pool_id) = ctx.accounts.intent_account.pool_id {
+                    require!(primary_pool.key() == pool_id, IntentError::InvalidPool);
+                }
+                raydium::verify_pool_vaults(primary_pool, &coin_vault.key(), &pc_vault.key())?;
+                
+                let from_mint = ctx.accounts.intent_account.from_mint;
+                let to_mint = ctx.accounts.intent_account.to_mint;
+                let (reserve_in, reserve_out) = if coin_vault.mint == from_mint && pc_vault.mint == to_mint {
+                    (coin_vault.amount, pc_vault.amount)
+                } else if pc_vault.mint == from_mint && coin_vault.mint == to_mint {
+                    (pc_vault.amount, coin_vault.amount)
+                } else {
+                    return err!(IntentError::InvalidPool);
+                };
+                
+                // The floor comes from the intent itself and the pair's reference price, never
+                // from the caller's route quote: an exact-output intent needs its amount, any
+                // other its net input at the reference price less slippage
+                let intent_account = &ctx.accounts.intent_account;
+                let trade_input = if intent_account.exact_output { intent_account.max_input } else { intent_account.amount }
+                    .checked_sub(intent_account.protocol_fee)
+                    .ok_or(IntentError::InvalidAmount)?;
+                let fallback_floor = if intent_account.exact_output {
+                    intent_account.amount
+                } else {
+                    let Some(reference_price) = pair_reference_price(&ctx.accounts.circuit_breaker.to_account_info())? else {
+                        msg!("❌ Raydium intent needs its pair's reference price to fall back to Jupiter");
+                        return err!(IntentError::WrongProtocol);
+                    };
+                    ((trade_input as u128) * reference_price as u128 / PRICE_SCALE)
+                        .checked_mul(10000 - intent_account.max_slippage as u128)
+                        .map(|floor| (floor / 10000).min(u64::MAX as u128) as u64)
+                        .ok_or(IntentError::InvalidAmount)?
+                };
+                let primary_output = raydium::calculate_raydium_output(
+                    trade_input,
+                    reserve_in,
+                    reserve_out,
+                    25,    // Raydium fee: 0.25%
+                    10000,
+                )?;
+                // Only re-route when the pool can't give the floor the fallback must then meet
+                require!(primary_output < fallback_floor, IntentError::WrongProtocol);
+                Some((primary_output, fallback_floor))
+            }
+            _ => {
+                msg!("❌ execute_swap_intent_jupiter called on an intent routed to {:?}", ctx.accounts.intent_account.selected_swap_protocol);
+                return err!(IntentError::WrongProtocol);
+            }
+        };
+        require!(!ctx.accounts.protocol_state.is_paused, IntentError::ProtocolPaused);
         
         msg!("🚀 Executing Jupiter aggregated swap...");
         
-        // Calculate amounts
-        let protocol_fee = ctx.accounts.intent_account.protocol_fee;
-        let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        // Pending buy intents only fill once the oracle price reaches the target. A miss is
+        // recorded instead of reverted, so perpetually-failing intents eventually fail out.
+        if let (IntentType::Buy, Some(_)) = (
+            &ctx.accounts.intent_account.intent_type,
+            ctx.accounts.intent_account.target_price,
+        ) {
+            let price_oracle = ctx.accounts.price_oracle.as_ref().ok_or(IntentError::MissingExecutionAccounts)?;
+            let current_price = oracle::read_price(&ctx.accounts.intent_account.price_source, price_oracle)?;
+            if !buy_condition_met(&ctx.accounts.intent_account, current_price) {
+                return record_failed_attempt(
+                    &mut ctx.accounts.intent_account,
+                    &mut ctx.accounts.user_account,
+                    &ctx.accounts.protocol_state,
+                    &ctx.accounts.user.to_account_info(),
+                );
+            }
+        }
         
-        // Transfer protocol fee to treasury first
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_source_token.to_account_info(),
-            to: ctx.accounts.treasury_fee_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+        // The route quote is caller-supplied, so it never moves the pair's reference
+        // price; a pair tripped by a pool-priced execution still halts Jupiter fills
+        check_circuit_breaker(
+            &ctx.accounts.circuit_breaker.to_account_info(),
+            &mut ctx.accounts.intent_account,
+            None,
+        )?;
+        
+        // Calculate amounts; exact-output intents spend only the route's quoted input
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let (protocol_fee, net_amount) = if ctx.accounts.intent_account.exact_output {
+            require!(
+                jupiter_swap_data.quoted_out_amount >= ctx.accounts.intent_account.amount,
+                IntentError::SlippageExceeded
+            );
+            exact_output_amounts(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state, jupiter_swap_data.in_amount)?
+        } else {
+            (protocol_fee, ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap())
         };
-        let cpi_program = ctx.accounts.token_program.to_account_info();
-        let cpi_ctx = CpiContext::new(cpi_program, cpi_accounts);
-        token::transfer(cpi_ctx, protocol_fee)?;
+        
+        // Native SOL input trades through the user's wSOL account, topped up from lamports
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::wrap_sol(
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token,
+                net_amount + protocol_fee,
+            )?;
+        }
+        
+        // Transfer protocol fee to treasury (or split beneficiaries) first
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
         
         // Execute Jupiter swap with our integration
-        let swap_params = jupiter::JupiterSwapParams {
-            from_mint: ctx.accounts.intent_account.from_mint,
-            to_mint: ctx.accounts.intent_account.to_mint,
-            amount: net_amount,
-            slippage_bps: ctx.accounts.intent_account.max_slippage,
-            platform_fee_bps: 0, // We already collected our fee
-        };
+        let swap_params = jupiter_swap_params(&ctx.accounts.intent_account, net_amount);
+        
+        let route_labels = jupiter::route_labels(&jupiter_swap_data);
         
         // Execute Jupiter swap with simplified integration call
         let estimated_output = jupiter::execute_jupiter_swap_simple(
@@ -183,19 +517,81 @@ pub mod intentfi {
             jupiter_swap_data,
         )?;
         
+        // A fallback has to do better than the primary venue could, or it isn't one
+        if let Some((_, fallback_floor)) = fallback_from {
+            require!(estimated_output >= fallback_floor, IntentError::SlippageExceeded);
+        }
+        
+        // Auto-lend intents deposit the whole output into the to_mint's registered reserve,
+        // reverting the swap with it when the reserve pays less than min_apy
+        let auto_lent = if ctx.accounts.intent_account.auto_lend {
+            Some(auto_lend_swap_output(
+                &ctx.accounts.intent_account,
+                ctx.accounts.lending_market_registry.as_deref(),
+                ctx.accounts.auto_lend_reserve.as_ref(),
+                ctx.accounts.auto_lend_destination.as_ref(),
+                &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user_destination_token.to_account_info(),
+                estimated_output,
+            )?)
+        } else {
+            None
+        };
+        
+        // Close wSOL legs so the user ends up holding native SOL
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token.to_account_info(),
+            )?;
+        }
+        if wsol::is_native(&ctx.accounts.intent_account.to_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_destination_token.to_account_info(),
+            )?;
+        }
+        
+        if let Some((primary_output, _)) = fallback_from {
+            ctx.accounts.intent_account.selected_swap_protocol = SwapProtocol::Jupiter;
+            emit!(VenueFallback {
+                intent_id: ctx.accounts.intent_account.key(),
+                old_protocol: SwapProtocol::Raydium,
+                new_protocol: SwapProtocol::Jupiter,
+                primary_output,
+                fallback_output: estimated_output,
+            });
+        }
+        
         // Update intent status
         ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
         ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
-        ctx.accounts.intent_account.execution_price = Some(estimated_output);
+        record_fill(&mut ctx.accounts.intent_account, net_amount, estimated_output)?;
+        if let Some((lending_protocol, apy)) = &auto_lent {
+            ctx.accounts.intent_account.selected_lending_protocol = Some(lending_protocol.clone());
+            ctx.accounts.intent_account.execution_apy = Some(*apy);
+        }
         
         // Update counters
         ctx.accounts.user_account.active_intents -= 1;
-        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
         ctx.accounts.protocol_state.total_intents_executed += 1;
         ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
         
         emit!(SwapIntentExecuted {
             intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
             user: ctx.accounts.user.key(),
             protocol: SwapProtocol::Jupiter,
             from_mint: ctx.accounts.intent_account.from_mint,
@@ -203,8 +599,25 @@ pub mod intentfi {
             amount_in: net_amount,
             amount_out: estimated_output,
             protocol_fee,
+            route_labels,
         });
         
+        // The protocol fee was taken on the swap leg, the deposit itself is free
+        if let Some((lending_protocol, apy)) = auto_lent {
+            emit!(LendIntentExecuted {
+                intent_id: ctx.accounts.intent_account.key(),
+                client_order_id: ctx.accounts.intent_account.client_order_id,
+                keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+                user: ctx.accounts.user.key(),
+                mint: ctx.accounts.intent_account.to_mint,
+                amount: estimated_output,
+                apy,
+                net_apy: apy,
+                protocol: lending_protocol,
+                protocol_fee: 0,
+            });
+        }
+        
         msg!(
             "✅ Jupiter swap completed: {} → {} tokens (Fee: {})",
             net_amount,
@@ -215,55 +628,318 @@ pub mod intentfi {
         Ok(())
     }
 
+    /// Execute a Jupiter swap intent on the user's amount plus `borrow_amount` flash-borrowed
+    /// from the registered Solend reserve of the input token, repaying the loan and its fee
+    /// out of the user's source account before the instruction ends
+    pub fn execute_swap_with_flash_loan<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteSwapWithFlashLoan<'info>>,
+        borrow_amount: u64,
+        jupiter_swap_data: jupiter::JupiterSwapData,
+    ) -> Result<()> {
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        require!(ctx.accounts.protocol_state.flash_loans_enabled, IntentError::FlashLoansDisabled);
+        require!(!ctx.accounts.protocol_state.is_paused, IntentError::ProtocolPaused);
+        if ctx.accounts.intent_account.intent_type != IntentType::Swap
+            || !matches!(ctx.accounts.intent_account.selected_swap_protocol, SwapProtocol::Jupiter)
+        {
+            msg!("❌ execute_swap_with_flash_loan called on a {:?} intent", ctx.accounts.intent_account.intent_type);
+            return err!(IntentError::WrongProtocol);
+        }
+        // The lend leg of an auto-lend intent only runs through execute_swap_intent_jupiter
+        require!(!ctx.accounts.intent_account.auto_lend, IntentError::WrongProtocol);
+        require!(borrow_amount > 0, IntentError::InvalidAmount);
+        
+        // Borrow only from the registered Solend reserve of the input token, into the
+        // user's source account and back out of the reserve's own liquidity supply
+        let flash_reserve = &ctx.accounts.flash_reserve;
+        require!(
+            flash_reserve.key() == expected_lend_reserve(&ctx.accounts.lending_market_registry, &ctx.accounts.intent_account, &LendingProtocol::Solend)?,
+            IntentError::LendingMarketNotRegistered
+        );
+        let reserve = solend::load_reserve(flash_reserve)?;
+        require!(
+            reserve.liquidity.mint_pubkey == ctx.accounts.intent_account.from_mint
+                && reserve.liquidity.supply_pubkey == ctx.accounts.flash_reserve_liquidity.key(),
+            IntentError::InvalidLendReserve
+        );
+        let flash_fee = solend::calculate_flash_loan_fee(borrow_amount, reserve.config.fees.flash_loan_fee_wad)?;
+        
+        check_circuit_breaker(
+            &ctx.accounts.circuit_breaker.to_account_info(),
+            &mut ctx.accounts.intent_account,
+            None,
+        )?;
+        
+        msg!("⚡ Executing flash-loan-funded swap...");
+        
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
+        let owner_authority = OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state);
+        let flash_loan = solend::FlashLoan {
+            reserve_liquidity: ctx.accounts.flash_reserve_liquidity.key(),
+            user_liquidity: ctx.accounts.user_source_token.key(),
+            reserve: flash_reserve.key(),
+            fee_receiver: ctx.accounts.flash_fee_receiver.key(),
+            host_fee_receiver: ctx.accounts.host_fee_receiver.key(),
+            lending_market: ctx.accounts.lending_market.key(),
+            lending_market_authority: ctx.accounts.lending_market_authority.key(),
+            transfer_authority: owner_authority.authority().key(),
+        };
+        let flash_accounts = [
+            ctx.accounts.flash_reserve_liquidity.to_account_info(),
+            ctx.accounts.user_source_token.to_account_info(),
+            flash_reserve.to_account_info(),
+            ctx.accounts.flash_fee_receiver.to_account_info(),
+            ctx.accounts.host_fee_receiver.to_account_info(),
+            ctx.accounts.lending_market.to_account_info(),
+            ctx.accounts.lending_market_authority.to_account_info(),
+            owner_authority.authority(),
+            ctx.accounts.instructions.to_account_info(),
+            ctx.accounts.token_program.to_account_info(),
+            ctx.accounts.solend_program.to_account_info(),
+        ];
+        let supply_before = ctx.accounts.flash_reserve_liquidity.amount;
+        owner_authority.invoke(&flash_loan.borrow_instruction(borrow_amount), &flash_accounts)?;
+        
+        // Swap the user's amount plus the borrowed liquidity through the router
+        let amount_in = net_amount.checked_add(borrow_amount).ok_or(IntentError::InvalidAmount)?;
+        let swap_params = jupiter_swap_params(&ctx.accounts.intent_account, amount_in);
+        let estimated_output = jupiter::execute_jupiter_swap_simple(
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &ctx.accounts.user_destination_token.to_account_info(),
+            &ctx.accounts.jupiter_program.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+            swap_params,
+            jupiter_swap_data,
+        )?;
+        
+        // Repay within this same instruction, and only count it once the reserve holds
+        // at least what it lent out
+        ctx.accounts.user_source_token.reload()?;
+        let source_before_repay = ctx.accounts.user_source_token.amount;
+        let borrow_index = load_current_index_checked(&ctx.accounts.instructions.to_account_info())?;
+        owner_authority.invoke(
+            &flash_loan.repay_instruction(borrow_amount, u8::try_from(borrow_index).map_err(|_| IntentError::FlashLoanNotRepaid)?),
+            &flash_accounts,
+        )?;
+        ctx.accounts.flash_reserve_liquidity.reload()?;
+        ctx.accounts.user_source_token.reload()?;
+        require!(ctx.accounts.flash_reserve_liquidity.amount >= supply_before, IntentError::FlashLoanNotRepaid);
+        let repaid = source_before_repay
+            .checked_sub(ctx.accounts.user_source_token.amount)
+            .ok_or(IntentError::FlashLoanNotRepaid)?;
+        require!(repaid >= borrow_amount.saturating_add(flash_fee), IntentError::FlashLoanNotRepaid);
+        
+        // Update intent status
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        record_fill(&mut ctx.accounts.intent_account, amount_in, estimated_output)?;
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(FlashSwapExecuted {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user: ctx.accounts.user.key(),
+            from_mint: ctx.accounts.intent_account.from_mint,
+            to_mint: ctx.accounts.intent_account.to_mint,
+            amount_in: net_amount,
+            borrowed: borrow_amount,
+            repaid,
+            flash_fee,
+            amount_out: estimated_output,
+            protocol_fee,
+        });
+        
+        msg!(
+            "✅ Flash swap completed: borrowed {}, repaid {} (flash fee: {}), {} → {} tokens",
+            borrow_amount,
+            repaid,
+            flash_fee,
+            amount_in,
+            estimated_output
+        );
+        Ok(())
+    }
+
     /// Execute a swap intent through Raydium AMM
-    pub fn execute_swap_intent_raydium(
-        ctx: Context<ExecuteSwapIntentRaydium>,
+    pub fn execute_swap_intent_raydium<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteSwapIntentRaydium<'info>>,
         pool_info: raydium::RaydiumPoolInfo,
     ) -> Result<()> {
         // Validate intent can be executed
         require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
-        require!(Clock::get()?.unix_timestamp < ctx.accounts.intent_account.expires_at, IntentError::IntentExpired);
-        require!(matches!(ctx.accounts.intent_account.selected_swap_protocol, SwapProtocol::Raydium), IntentError::WrongProtocol);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if !matches!(ctx.accounts.intent_account.selected_swap_protocol, SwapProtocol::Raydium) {
+            msg!("❌ execute_swap_intent_raydium called on an intent routed to {:?}", ctx.accounts.intent_account.selected_swap_protocol);
+            return err!(IntentError::WrongProtocol);
+        }
+        require!(!ctx.accounts.protocol_state.is_paused, IntentError::ProtocolPaused);
         
         msg!("🌊 Executing direct Raydium AMM swap...");
         
-        // Calculate amounts
-        let protocol_fee = ctx.accounts.intent_account.protocol_fee;
-        let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        // Pool info is caller-supplied: bind it to the real pool account and read
+        // reserves from the pool's actual vaults instead of trusting it
+        if let Some(pool_id) = ctx.accounts.intent_account.pool_id {
+            require!(ctx.accounts.raydium_pool.key() == pool_id, IntentError::InvalidPool);
+        }
+        raydium::verify_pool_vaults(
+            &ctx.accounts.raydium_pool.to_account_info(),
+            &ctx.accounts.pool_coin_vault.key(),
+            &ctx.accounts.pool_pc_vault.key(),
+        )?;
+        let mut pool_info = pool_info;
+        pool_info.pool_coin_token_account = ctx.accounts.pool_coin_vault.key();
+        pool_info.pool_pc_token_account = ctx.accounts.pool_pc_vault.key();
+        pool_info.coin_mint_address = ctx.accounts.pool_coin_vault.mint;
+        pool_info.pc_mint_address = ctx.accounts.pool_pc_vault.mint;
+        pool_info.pool_coin_amount = ctx.accounts.pool_coin_vault.amount;
+        pool_info.pool_pc_amount = ctx.accounts.pool_pc_vault.amount;
         
-        // Transfer protocol fee to treasury
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_source_token.to_account_info(),
-            to: ctx.accounts.treasury_fee_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
+        let from_mint = ctx.accounts.intent_account.from_mint;
+        let to_mint = ctx.accounts.intent_account.to_mint;
+        let (reserve_in, reserve_out) = if from_mint == pool_info.coin_mint_address && to_mint == pool_info.pc_mint_address {
+            (pool_info.pool_coin_amount, pool_info.pool_pc_amount)
+        } else if from_mint == pool_info.pc_mint_address && to_mint == pool_info.coin_mint_address {
+            (pool_info.pool_pc_amount, pool_info.pool_coin_amount)
+        } else {
+            return err!(IntentError::InvalidPool);
+        };
+        
+        // Refuse to trade against freshly-created or drained pools
+        raydium::check_pool_liquidity(
+            reserve_in,
+            reserve_out,
+            ctx.accounts.protocol_state.min_pool_liquidity,
+        )?;
+        
+        // Halt the pair instead of trading at an abnormal price, read from the pool's vaults
+        if check_circuit_breaker(
+            &ctx.accounts.circuit_breaker.to_account_info(),
+            &mut ctx.accounts.intent_account,
+            Some((reserve_in, reserve_out)),
+        )? {
+            return Ok(());
+        }
+        
+        // Calculate amounts; exact-output intents spend only the input the pool needs
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let (protocol_fee, net_amount) = if ctx.accounts.intent_account.exact_output {
+            let required_input = raydium::calculate_raydium_input(
+                ctx.accounts.intent_account.amount,
+                reserve_in,
+                reserve_out,
+                25,    // Raydium fee: 0.25%
+                10000,
+            )?;
+            exact_output_amounts(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state, required_input)?
+        } else {
+            (protocol_fee, ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap())
         };
-        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-        token::transfer(cpi_ctx, protocol_fee)?;
+        
+        // Buy intents cap how far the trade may move the pool's price
+        if let Some(max_price_impact) = ctx.accounts.intent_account.max_price_impact {
+            let price_impact = raydium::calculate_price_impact_bps(net_amount, reserve_in);
+            msg!("📉 Pool price impact: {} bps (max {})", price_impact, max_price_impact);
+            require!(price_impact <= max_price_impact, IntentError::PriceImpactTooHigh);
+        }
+        
+        // Native SOL input trades through the user's wSOL account, topped up from lamports
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::wrap_sol(
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token,
+                net_amount + protocol_fee,
+            )?;
+        }
+        
+        // Transfer protocol fee to treasury (or split beneficiaries)
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
         
         // Calculate minimum amount out with slippage
         let base_output = raydium::calculate_raydium_output(
             net_amount,
-            if ctx.accounts.intent_account.from_mint == pool_info.coin_mint_address {
-                pool_info.pool_coin_amount
-            } else {
-                pool_info.pool_pc_amount
-            },
-            if ctx.accounts.intent_account.from_mint == pool_info.coin_mint_address {
-                pool_info.pool_pc_amount
-            } else {
-                pool_info.pool_coin_amount
-            },
+            reserve_in,
+            reserve_out,
             25,    // Raydium fee: 0.25%
             10000,
         )?;
         
-        // Apply slippage protection
+        // Apply slippage protection, exact-output intents must receive the full amount
         let slippage_multiplier = 10000_u64.checked_sub(ctx.accounts.intent_account.max_slippage as u64).unwrap();
-        let minimum_amount_out = (base_output as u128)
-            .checked_mul(slippage_multiplier as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        let minimum_amount_out = if ctx.accounts.intent_account.exact_output {
+            ctx.accounts.intent_account.amount
+        } else {
+            (base_output as u128)
+                .checked_mul(slippage_multiplier as u128)
+                .unwrap()
+                .checked_div(10000)
+                .unwrap() as u64
+        };
         
         // Execute Raydium swap
         let swap_params = raydium::RaydiumSwapParams {
@@ -285,19 +961,44 @@ pub mod intentfi {
             pool_info,
         )?;
         
+        // Close wSOL legs so the user ends up holding native SOL
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token.to_account_info(),
+            )?;
+        }
+        if wsol::is_native(&ctx.accounts.intent_account.to_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_destination_token.to_account_info(),
+            )?;
+        }
+        
         // Update intent status
         ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
         ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
-        ctx.accounts.intent_account.execution_price = Some(estimated_output);
+        record_fill(&mut ctx.accounts.intent_account, net_amount, estimated_output)?;
         
         // Update counters
         ctx.accounts.user_account.active_intents -= 1;
-        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
         ctx.accounts.protocol_state.total_intents_executed += 1;
         ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
         
         emit!(SwapIntentExecuted {
             intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
             user: ctx.accounts.user.key(),
             protocol: SwapProtocol::Raydium,
             from_mint: ctx.accounts.intent_account.from_mint,
@@ -305,6 +1006,7 @@ pub mod intentfi {
             amount_in: net_amount,
             amount_out: estimated_output,
             protocol_fee,
+            route_labels: Vec::new(),
         });
         
         msg!(
@@ -317,47 +1019,229 @@ pub mod intentfi {
         Ok(())
     }
 
-    /// Create a lending intent with protocol selection
-    pub fn create_lend_intent(
-        ctx: Context<CreateLendIntent>,
-        params: LendIntentParams,
+    /// Execute a swap intent through a Meteora DLMM pool
+    pub fn execute_swap_intent_meteora<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteSwapIntentMeteora<'info>>,
     ) -> Result<()> {
-        let user_account = &mut ctx.accounts.user_account;
-        let intent_account = &mut ctx.accounts.intent_account;
-        let protocol_state = &mut ctx.accounts.protocol_state;
+        // Validate intent can be executed
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if !matches!(ctx.accounts.intent_account.selected_swap_protocol, SwapProtocol::Meteora) {
+            msg!("❌ execute_swap_intent_meteora called on an intent routed to {:?}", ctx.accounts.intent_account.selected_swap_protocol);
+            return err!(IntentError::WrongProtocol);
+        }
+        require!(!ctx.accounts.protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(!ctx.accounts.intent_account.exact_output, IntentError::ExactOutputUnsupported);
         
-        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
-        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
-        require!(params.amount > 0, IntentError::InvalidAmount);
-        require!(params.min_apy > 0 && params.min_apy <= 10000, IntentError::InvalidAPY); // Max 100%
+        msg!("☄️ Executing Meteora DLMM swap...");
+        
+        // Calculate amounts
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        
+        // Price and liquidity come from the pool's own active bin, never from the caller
+        let pool_info = meteora::read_pool_info(&ctx.accounts.meteora_pool, &ctx.accounts.meteora_bin_array)?;
+        let from_mint = ctx.accounts.intent_account.from_mint;
+        let to_mint = ctx.accounts.intent_account.to_mint;
+        let swap_for_y = if from_mint == pool_info.token_x_mint && to_mint == pool_info.token_y_mint {
+            true
+        } else if from_mint == pool_info.token_y_mint && to_mint == pool_info.token_x_mint {
+            false
+        } else {
+            return err!(IntentError::InvalidPool);
+        };
+        let bin_amount_out = if swap_for_y { pool_info.active_bin_amount_y } else { pool_info.active_bin_amount_x };
+        
+        let base_output = meteora::calculate_meteora_output(
+            net_amount,
+            pool_info.active_bin_price,
+            swap_for_y,
+            bin_amount_out,
+            pool_info.base_fee_bps,
+        )?;
+        
+        // Halt the pair instead of trading at an abnormal price, read from the active bin
+        if check_circuit_breaker(
+            &ctx.accounts.circuit_breaker.to_account_info(),
+            &mut ctx.accounts.intent_account,
+            Some((net_amount, base_output)),
+        )? {
+            return Ok(());
+        }
+        
+        // Native SOL input trades through the user's wSOL account, topped up from lamports
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::wrap_sol(
+                &ctx.accounts.system_program.to_account_info(),
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token,
+                net_amount + protocol_fee,
+            )?;
+        }
+        
+        // Transfer protocol fee to treasury (or split beneficiaries)
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
         
-        let protocol_fee = (params.amount as u128)
-            .checked_mul(PROTOCOL_FEE_BPS as u128)
+        // Calculate minimum amount out with slippage
+        let slippage_multiplier = 10000_u64.checked_sub(ctx.accounts.intent_account.max_slippage as u64).unwrap();
+        let minimum_amount_out = (base_output as u128)
+            .checked_mul(slippage_multiplier as u128)
             .unwrap()
             .checked_div(10000)
             .unwrap() as u64;
         
-        // Choose best lending protocol for this token
-        let selected_protocol = LendingRouter::choose_best_lending_protocol(&params.mint, params.amount);
+        let swap_params = meteora::MeteoraSwapParams {
+            pool_id: ctx.accounts.meteora_pool.key(),
+            from_mint: ctx.accounts.intent_account.from_mint,
+            to_mint: ctx.accounts.intent_account.to_mint,
+            amount_in: net_amount,
+            minimum_amount_out,
+        };
         
-        msg!(
-            "🎯 Selected lending protocol: {:?} for {} (min APY: {}%)",
-            selected_protocol,
-            params.mint,
-            params.min_apy
-        );
+        // Execute Meteora swap with simplified integration call
+        let estimated_output = meteora::execute_meteora_swap_simple(
+            &ctx.accounts.user.to_account_info(),
+            &ctx.accounts.user_source_token.to_account_info(),
+            &ctx.accounts.user_destination_token.to_account_info(),
+            &ctx.accounts.meteora_program.to_account_info(),
+            &ctx.accounts.token_program.to_account_info(),
+            swap_params,
+            &pool_info,
+        )?;
         
-        intent_account.authority = ctx.accounts.authority.key();
-        intent_account.intent_type = IntentType::Lend;
-        intent_account.status = IntentStatus::Pending;
-        intent_account.from_mint = params.mint;
-        intent_account.to_mint = params.mint; // Same for lending
-        intent_account.amount = params.amount;
-        intent_account.protocol_fee = protocol_fee;
-        intent_account.max_slippage = 0;
-        intent_account.min_apy = Some(params.min_apy);
-        intent_account.target_price = None;
-        intent_account.max_price_impact = None;
+        // Close wSOL legs so the user ends up holding native SOL
+        if wsol::is_native(&ctx.accounts.intent_account.from_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_source_token.to_account_info(),
+            )?;
+        }
+        if wsol::is_native(&ctx.accounts.intent_account.to_mint) {
+            wsol::unwrap_sol(
+                &ctx.accounts.token_program.to_account_info(),
+                &ctx.accounts.user.to_account_info(),
+                &ctx.accounts.user_destination_token.to_account_info(),
+            )?;
+        }
+        
+        // Update intent status
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        record_fill(&mut ctx.accounts.intent_account, net_amount, estimated_output)?;
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(SwapIntentExecuted {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user: ctx.accounts.user.key(),
+            protocol: SwapProtocol::Meteora,
+            from_mint: ctx.accounts.intent_account.from_mint,
+            to_mint: ctx.accounts.intent_account.to_mint,
+            amount_in: net_amount,
+            amount_out: estimated_output,
+            protocol_fee,
+            route_labels: Vec::new(),
+        });
+        
+        msg!(
+            "✅ Meteora swap completed: {} → {} tokens (Fee: {})",
+            net_amount,
+            estimated_output,
+            protocol_fee
+        );
+        
+        Ok(())
+    }
+
+    /// Create a lending intent with protocol selection
+    pub fn create_lend_intent(
+        ctx: Context<CreateLendIntent>,
+        params: LendIntentParams,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let intent_account = &mut ctx.accounts.intent_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        
+        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(params.amount > 0, IntentError::InvalidAmount);
+        require!(params.min_apy > 0 && params.min_apy <= 10000, IntentError::InvalidAPY); // Max 100%
+        // No reserve will ever meet an unrealistic floor, so the intent would only waste a slot
+        require!(params.min_apy <= protocol_state.max_realistic_apy, IntentError::UnrealisticApy);
+        
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::Lend);
+        let protocol_fee = calculate_protocol_fee(params.amount, fee_bps, protocol_state.min_fee_tokens)?;
+        
+        // Reserve capacity against the per-mint lending cap (if one is configured)
+        let lending_cap_reserved = reserve_lending_cap(&ctx.accounts.lending_cap, params.amount)?;
+        
+        // Choose best lending protocol for this token among its registered markets
+        let selected_market = LendingRouter::choose_registered_lending_protocol(
+            &ctx.accounts.lending_market_registry.markets,
+            &params.mint,
+            params.amount,
+        ).ok_or(IntentError::LendingMarketNotRegistered)?;
+        let selected_protocol = selected_market.protocol.clone();
+        let selected_reserve = selected_market.reserve;
+        
+        msg!(
+            "🎯 Selected lending protocol: {:?} for {} (min APY: {}%)",
+            selected_protocol,
+            params.mint,
+            params.min_apy
+        );
+        
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::Lend;
+        intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.mint;
+        intent_account.to_mint = params.mint; // Same for lending
+        intent_account.amount = params.amount;
+        intent_account.protocol_fee = protocol_fee;
+        intent_account.max_slippage = 0;
+        intent_account.min_apy = Some(params.min_apy);
+        intent_account.target_price = None;
+        intent_account.max_price_impact = None;
         intent_account.execution_price = None;
         intent_account.execution_apy = None;
         intent_account.rugproof_enabled = false;
@@ -365,10 +1249,44 @@ pub mod intentfi {
         intent_account.selected_lending_protocol = Some(selected_protocol.clone());
         intent_account.created_at = Clock::get()?.unix_timestamp;
         intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
         intent_account.executed_at = None;
         intent_account.cancelled_at = None;
+        intent_account.pool_id = Some(selected_reserve); // Registered reserve to deposit into
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = PriceSource::Pyth;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = None;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = lending_cap_reserved;
+        intent_account.auto_lend = false;
         intent_account.bump = ctx.bumps.intent_account;
         
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
         user_account.active_intents += 1;
         user_account.total_intents_created += 1;
         protocol_state.total_intents_created += 1;
@@ -385,67 +1303,155 @@ pub mod intentfi {
     }
 
     /// Execute a lending intent through Solend
-    pub fn execute_lend_intent_solend(
-        ctx: Context<ExecuteLendIntentSolend>,
-        reserve_data: solend::SolendReserve,
+    pub fn execute_lend_intent_solend<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteLendIntentSolend<'info>>,
     ) -> Result<()> {
         require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
-        require!(Clock::get()?.unix_timestamp < ctx.accounts.intent_account.expires_at, IntentError::IntentExpired);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                Some(&ctx.accounts.lending_cap),
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        
+        // A Port Finance intent may fall back to Solend once Port's registered reserve can no
+        // longer meet min APY
+        let reselected_from = match ctx.accounts.intent_account.selected_lending_protocol {
+            Some(LendingProtocol::Solend) => None,
+            Some(LendingProtocol::PortFinance) => {
+                let original_reserve = ctx.accounts.port_reserve.as_ref().ok_or(IntentError::WrongProtocol)?;
+                require!(
+                    original_reserve.key() == expected_lend_reserve(&ctx.accounts.lending_market_registry, &ctx.accounts.intent_account, &LendingProtocol::PortFinance)?,
+                    IntentError::LendingMarketNotRegistered
+                );
+                let original_reserve = port_finance::load_reserve(original_reserve)?;
+                require!(
+                    original_reserve.liquidity.mint_pubkey == ctx.accounts.intent_account.from_mint,
+                    IntentError::InvalidAmount
+                );
+                let original_apy = port_finance::calculate_port_apy(&original_reserve)?;
+                require!(
+                    original_apy < ctx.accounts.intent_account.min_apy.unwrap_or(0),
+                    IntentError::WrongProtocol
+                );
+                Some((LendingProtocol::PortFinance, original_apy))
+            }
+            _ => {
+                msg!("❌ execute_lend_intent_solend called on an intent routed to {:?}", ctx.accounts.intent_account.selected_lending_protocol);
+                return err!(IntentError::WrongProtocol);
+            }
+        };
+        
+        let solend_reserve = ctx.accounts.solend_reserve.as_ref().ok_or(IntentError::MissingExecutionAccounts)?;
         require!(
-            matches!(ctx.accounts.intent_account.selected_lending_protocol, Some(LendingProtocol::Solend)), 
-            IntentError::WrongProtocol
+            solend_reserve.key() == expected_lend_reserve(&ctx.accounts.lending_market_registry, &ctx.accounts.intent_account, &LendingProtocol::Solend)?,
+            IntentError::LendingMarketNotRegistered
+        );
+        ctx.accounts.intent_account.pool_id = Some(solend_reserve.key());
+        
+        // Deposit only into the reserve's own liquidity supply, never an account the keeper picks
+        let destination = ctx.accounts.solend_destination_liquidity.as_ref().ok_or(IntentError::MissingExecutionAccounts)?;
+        let (reserve_mint, reserve_supply) = reserve_liquidity(solend_reserve, &solend::SOLEND_PROGRAM_ID)
+            .map_err(|_| IntentError::InvalidLendReserve)?;
+        require!(
+            reserve_mint == ctx.accounts.intent_account.from_mint && reserve_supply == destination.key(),
+            IntentError::InvalidLendReserve
         );
         
         msg!("🏦 Executing Solend lending...");
         
-        let protocol_fee = ctx.accounts.intent_account.protocol_fee;
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
         let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
         
-        // Collect protocol fee
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.treasury_fee_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-        token::transfer(cpi_ctx, protocol_fee)?;
-        
         // Execute Solend lending with real integration
         let lend_params = solend::SolendLendParams {
-            reserve: ctx.accounts.solend_reserve.as_ref().unwrap().key(),
+            reserve: solend_reserve.key(),
             lending_market: ctx.accounts.solend_lending_market.as_ref().unwrap().key(),
             amount: net_amount,
             expected_apy: ctx.accounts.intent_account.min_apy.unwrap_or(0),
         };
         
+        let reserve_data = solend::load_reserve(solend_reserve)?;
         let actual_apy = solend::execute_solend_lend(&ctx.accounts.intent_account, lend_params, reserve_data)?;
         
-        // Transfer tokens to Solend reserve
-        let solend_cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.solend_destination_liquidity.as_ref().unwrap().to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let solend_cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), solend_cpi_accounts);
-        token::transfer(solend_cpi_ctx, net_amount)?;
+        if let Some((old_protocol, old_apy)) = reselected_from {
+            // Hysteresis: only switch for a meaningful APY gain, not noise between reserves
+            require!(
+                actual_apy >= old_apy.saturating_add(ctx.accounts.protocol_state.min_apy_spread_bps),
+                IntentError::ApySpreadTooSmall
+            );
+            ctx.accounts.intent_account.selected_lending_protocol = Some(LendingProtocol::Solend);
+            emit!(ProtocolReselected {
+                intent_id: ctx.accounts.intent_account.key(),
+                old_protocol,
+                new_protocol: LendingProtocol::Solend,
+                old_apy,
+                new_apy: actual_apy,
+            });
+        }
         
-        // Update intent status
+        // Flip to Executed and update counters before any token movement, so a
+        // re-entrant execute during the CPIs fails the Pending check
         ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
         ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
         ctx.accounts.intent_account.execution_apy = Some(actual_apy);
         
         // Update counters
         ctx.accounts.user_account.active_intents -= 1;
-        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
         ctx.accounts.protocol_state.total_intents_executed += 1;
         ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
         
+        // Transfer tokens to Solend reserve
+        OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state).transfer(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.solend_destination_liquidity.as_ref().unwrap().to_account_info(),
+            net_amount,
+        )?;
+        
+        // Collect the protocol fee only once the deposit has gone through, so a
+        // failed deposit never leaves the fee charged without a position
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
         emit!(LendIntentExecuted {
             intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
             user: ctx.accounts.user.key(),
             mint: ctx.accounts.intent_account.from_mint,
             amount: net_amount,
             apy: actual_apy,
+            net_apy: net_effective_apy(
+                actual_apy,
+                ctx.accounts.intent_account.fee_bps,
+                ctx.accounts.intent_account.amount,
+            ),
             protocol: LendingProtocol::Solend,
             protocol_fee,
         });
@@ -455,67 +1461,155 @@ pub mod intentfi {
     }
 
     /// Execute a lending intent through Port Finance
-    pub fn execute_lend_intent_port(
-        ctx: Context<ExecuteLendIntentPort>,
-        reserve_data: port_finance::PortReserve,
+    pub fn execute_lend_intent_port<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteLendIntentPort<'info>>,
     ) -> Result<()> {
         require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
-        require!(Clock::get()?.unix_timestamp < ctx.accounts.intent_account.expires_at, IntentError::IntentExpired);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                Some(&ctx.accounts.lending_cap),
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        
+        // A Solend intent may fall back to Port Finance once Solend's registered reserve can no
+        // longer meet min APY
+        let reselected_from = match ctx.accounts.intent_account.selected_lending_protocol {
+            Some(LendingProtocol::PortFinance) => None,
+            Some(LendingProtocol::Solend) => {
+                let original_reserve = ctx.accounts.solend_reserve.as_ref().ok_or(IntentError::WrongProtocol)?;
+                require!(
+                    original_reserve.key() == expected_lend_reserve(&ctx.accounts.lending_market_registry, &ctx.accounts.intent_account, &LendingProtocol::Solend)?,
+                    IntentError::LendingMarketNotRegistered
+                );
+                let original_reserve = solend::load_reserve(original_reserve)?;
+                require!(
+                    original_reserve.liquidity.mint_pubkey == ctx.accounts.intent_account.from_mint,
+                    IntentError::InvalidAmount
+                );
+                let original_apy = solend::calculate_lending_apy(&original_reserve)?;
+                require!(
+                    original_apy < ctx.accounts.intent_account.min_apy.unwrap_or(0),
+                    IntentError::WrongProtocol
+                );
+                Some((LendingProtocol::Solend, original_apy))
+            }
+            _ => {
+                msg!("❌ execute_lend_intent_port called on an intent routed to {:?}", ctx.accounts.intent_account.selected_lending_protocol);
+                return err!(IntentError::WrongProtocol);
+            }
+        };
+        
+        let port_reserve = ctx.accounts.port_reserve.as_ref().ok_or(IntentError::MissingExecutionAccounts)?;
         require!(
-            matches!(ctx.accounts.intent_account.selected_lending_protocol, Some(LendingProtocol::PortFinance)), 
-            IntentError::WrongProtocol
+            port_reserve.key() == expected_lend_reserve(&ctx.accounts.lending_market_registry, &ctx.accounts.intent_account, &LendingProtocol::PortFinance)?,
+            IntentError::LendingMarketNotRegistered
+        );
+        ctx.accounts.intent_account.pool_id = Some(port_reserve.key());
+        
+        // Deposit only into the reserve's own liquidity supply, never an account the keeper picks
+        let destination = ctx.accounts.port_destination_liquidity.as_ref().ok_or(IntentError::MissingExecutionAccounts)?;
+        let (reserve_mint, reserve_supply) = reserve_liquidity(port_reserve, &port_finance::PORT_FINANCE_PROGRAM_ID)
+            .map_err(|_| IntentError::InvalidLendReserve)?;
+        require!(
+            reserve_mint == ctx.accounts.intent_account.from_mint && reserve_supply == destination.key(),
+            IntentError::InvalidLendReserve
         );
         
         msg!("🏦 Executing Port Finance lending...");
         
-        let protocol_fee = ctx.accounts.intent_account.protocol_fee;
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
         let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
         
-        // Collect protocol fee
-        let cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.treasury_fee_account.to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), cpi_accounts);
-        token::transfer(cpi_ctx, protocol_fee)?;
-        
         // Execute Port Finance lending
         let lend_params = port_finance::PortLendParams {
-            reserve: ctx.accounts.port_reserve.as_ref().unwrap().key(),
+            reserve: port_reserve.key(),
             staking_pool: ctx.accounts.port_staking_pool.as_ref().unwrap().key(),
             amount: net_amount,
             expected_apy: ctx.accounts.intent_account.min_apy.unwrap_or(0),
         };
         
+        let reserve_data = port_finance::load_reserve(port_reserve)?;
         let actual_apy = port_finance::execute_port_lend(&ctx.accounts.intent_account, lend_params, reserve_data)?;
         
-        // Transfer tokens to Port Finance reserve
-        let port_cpi_accounts = Transfer {
-            from: ctx.accounts.user_token_account.to_account_info(),
-            to: ctx.accounts.port_reserve.as_ref().unwrap().to_account_info(),
-            authority: ctx.accounts.user.to_account_info(),
-        };
-        let port_cpi_ctx = CpiContext::new(ctx.accounts.token_program.to_account_info(), port_cpi_accounts);
-        token::transfer(port_cpi_ctx, net_amount)?;
+        if let Some((old_protocol, old_apy)) = reselected_from {
+            // Hysteresis: only switch for a meaningful APY gain, not noise between reserves
+            require!(
+                actual_apy >= old_apy.saturating_add(ctx.accounts.protocol_state.min_apy_spread_bps),
+                IntentError::ApySpreadTooSmall
+            );
+            ctx.accounts.intent_account.selected_lending_protocol = Some(LendingProtocol::PortFinance);
+            emit!(ProtocolReselected {
+                intent_id: ctx.accounts.intent_account.key(),
+                old_protocol,
+                new_protocol: LendingProtocol::PortFinance,
+                old_apy,
+                new_apy: actual_apy,
+            });
+        }
         
-        // Update intent status
+        // Flip to Executed and update counters before any token movement, so a
+        // re-entrant execute during the CPIs fails the Pending check
         ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
         ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
         ctx.accounts.intent_account.execution_apy = Some(actual_apy);
         
         // Update counters
         ctx.accounts.user_account.active_intents -= 1;
-        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        record_volume(&mut ctx.accounts.user_account, net_amount, protocol_fee)?;
         ctx.accounts.protocol_state.total_intents_executed += 1;
         ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
         
+        // Transfer tokens into the Port Finance reserve's liquidity supply
+        OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state).transfer(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.port_destination_liquidity.as_ref().unwrap().to_account_info(),
+            net_amount,
+        )?;
+        
+        // Collect the protocol fee only once the deposit has gone through, so a
+        // failed deposit never leaves the fee charged without a position
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
         emit!(LendIntentExecuted {
             intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
             user: ctx.accounts.user.key(),
             mint: ctx.accounts.intent_account.from_mint,
             amount: net_amount,
             apy: actual_apy,
+            net_apy: net_effective_apy(
+                actual_apy,
+                ctx.accounts.intent_account.fee_bps,
+                ctx.accounts.intent_account.amount,
+            ),
             protocol: LendingProtocol::PortFinance,
             protocol_fee,
         });
@@ -524,10 +1618,10 @@ pub mod intentfi {
         Ok(())
     }
 
-    /// Create a buy intent with price conditions
-    pub fn create_buy_intent(
-        ctx: Context<CreateBuyIntent>,
-        params: BuyIntentParams,
+    /// Create an intent to repay a borrow position on Solend or Port Finance
+    pub fn create_repay_intent(
+        ctx: Context<CreateRepayIntent>,
+        params: RepayIntentParams,
     ) -> Result<()> {
         let user_account = &mut ctx.accounts.user_account;
         let intent_account = &mut ctx.accounts.intent_account;
@@ -535,599 +1629,10082 @@ pub mod intentfi {
         
         require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
         require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
-        require!(params.usdc_amount > 0, IntentError::InvalidAmount);
-        
-        let protocol_fee = (params.usdc_amount as u128)
-            .checked_mul(PROTOCOL_FEE_BPS as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
+        require!(params.amount > 0, IntentError::InvalidAmount);
+        require!(
+            matches!(params.protocol, LendingProtocol::Solend | LendingProtocol::PortFinance),
+            IntentError::WrongProtocol
+        );
         
-        // Rugproof check if enabled
-        if params.rugproof_check {
-            let rugproof_score = perform_rugproof_check(&params.mint)?;
-            require!(rugproof_score >= MIN_RUGPROOF_SCORE, IntentError::RugproofCheckFailed);
-        }
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::Repay);
+        let protocol_fee = calculate_protocol_fee(params.amount, fee_bps, protocol_state.min_fee_tokens)?;
         
-        intent_account.authority = ctx.accounts.authority.key();
-        intent_account.intent_type = IntentType::Buy;
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::Repay;
         intent_account.status = IntentStatus::Pending;
-        intent_account.from_mint = params.usdc_mint; // Passed in params
-        intent_account.to_mint = params.mint;
-        intent_account.amount = params.usdc_amount;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.mint;
+        intent_account.to_mint = params.mint; // Debt is repaid in the borrowed token
+        intent_account.amount = params.amount;
         intent_account.protocol_fee = protocol_fee;
         intent_account.max_slippage = 0;
         intent_account.min_apy = None;
-        intent_account.target_price = params.target_price;
-        intent_account.max_price_impact = Some(params.max_price_impact);
+        intent_account.target_price = None;
+        intent_account.max_price_impact = None;
         intent_account.execution_price = None;
         intent_account.execution_apy = None;
-        intent_account.rugproof_enabled = params.rugproof_check;
-        intent_account.selected_swap_protocol = SwapProtocol::Jupiter; // Default for buy intents
-        intent_account.selected_lending_protocol = None;
+        intent_account.rugproof_enabled = false;
+        intent_account.selected_swap_protocol = SwapProtocol::Jupiter; // Default value
+        intent_account.selected_lending_protocol = Some(params.protocol.clone());
         intent_account.created_at = Clock::get()?.unix_timestamp;
         intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
         intent_account.executed_at = None;
         intent_account.cancelled_at = None;
+        intent_account.pool_id = Some(params.obligation); // Borrow position being repaid
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = PriceSource::Pyth;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = None;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = false;
         intent_account.bump = ctx.bumps.intent_account;
         
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
         user_account.active_intents += 1;
         user_account.total_intents_created += 1;
         protocol_state.total_intents_created += 1;
         
         msg!(
-            "💳 Buy intent created: ${} for {} (Fee: ${})",
-            params.usdc_amount,
+            "💳 Repay intent created: {} tokens of {} debt via {:?} (Fee: {})",
+            params.amount,
             params.mint,
+            params.protocol,
             protocol_fee
         );
         
         Ok(())
     }
 
-    /// Cancel an active intent
-    pub fn cancel_intent(ctx: Context<CancelIntent>) -> Result<()> {
-        let intent_account = &mut ctx.accounts.intent_account;
-        let user_account = &mut ctx.accounts.user_account;
+    /// Execute a repay intent against the selected lending protocol.
+    /// Pass the reserve data matching the intent's protocol.
+    pub fn execute_repay_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteRepayIntent<'info>>,
+    ) -> Result<()> {
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if ctx.accounts.intent_account.intent_type != IntentType::Repay {
+            msg!("❌ execute_repay_intent called on a {:?} intent", ctx.accounts.intent_account.intent_type);
+            return err!(IntentError::WrongProtocol);
+        }
         
-        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
-        require!(intent_account.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
+        msg!("💳 Executing debt repayment...");
         
-        intent_account.status = IntentStatus::Cancelled;
-        intent_account.cancelled_at = Some(Clock::get()?.unix_timestamp);
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let net_amount = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        let obligation = ctx.accounts.obligation.key();
         
-        user_account.active_intents -= 1;
+        let protocol = ctx.accounts.intent_account.selected_lending_protocol.clone();
+        let with_clock = match protocol {
+            Some(LendingProtocol::Solend) => {
+                require!(ctx.accounts.lending_program.key() == solend::SOLEND_PROGRAM_ID, IntentError::WrongProtocol);
+                false
+            }
+            Some(LendingProtocol::PortFinance) => {
+                require!(ctx.accounts.lending_program.key() == port_finance::PORT_FINANCE_PROGRAM_ID, IntentError::WrongProtocol);
+                true
+            }
+            _ => {
+                msg!("❌ execute_repay_intent called on an intent routed to {:?}", protocol);
+                return err!(IntentError::WrongProtocol);
+            }
+        };
         
-        msg!("❌ Intent cancelled: {}", intent_account.key());
+        // Only the owner's own obligation, repaid into the reserve's liquidity supply
+        let lending_program = ctx.accounts.lending_program.key();
+        require!(
+            obligation_owner(&ctx.accounts.obligation, &lending_program)? == ctx.accounts.user.key(),
+            IntentError::InvalidObligation
+        );
+        let (reserve_mint, reserve_supply) = reserve_liquidity(&ctx.accounts.repay_reserve, &lending_program)?;
+        require!(
+            reserve_mint == ctx.accounts.intent_account.from_mint
+                && reserve_supply == ctx.accounts.repay_reserve_liquidity.key(),
+            IntentError::InvalidRepayReserve
+        );
+        
+        // The protocol caps the repayment at the outstanding debt, so measure what it took
+        let owner_authority = OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state);
+        let balance_before = ctx.accounts.user_token_account.amount;
+        let repay = RepayObligationLiquidity {
+            source_liquidity: ctx.accounts.user_token_account.key(),
+            destination_liquidity: ctx.accounts.repay_reserve_liquidity.key(),
+            repay_reserve: ctx.accounts.repay_reserve.key(),
+            obligation,
+            lending_market: ctx.accounts.lending_market.key(),
+            transfer_authority: owner_authority.authority().key(),
+        };
+        owner_authority.invoke(
+            &repay.instruction(lending_program, net_amount, with_clock),
+            &[
+                ctx.accounts.user_token_account.to_account_info(),
+                ctx.accounts.repay_reserve_liquidity.to_account_info(),
+                ctx.accounts.repay_reserve.to_account_info(),
+                ctx.accounts.obligation.to_account_info(),
+                ctx.accounts.lending_market.to_account_info(),
+                owner_authority.authority(),
+                ctx.accounts.clock.to_account_info(),
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.lending_program.to_account_info(),
+            ],
+        )?;
+        ctx.accounts.user_token_account.reload()?;
+        let amount_repaid = balance_before
+            .checked_sub(ctx.accounts.user_token_account.amount)
+            .ok_or(IntentError::InvalidAmount)?;
+        require!(amount_repaid > 0, IntentError::InvalidAmount);
+        
+        // Charge the fee only on the debt actually reduced
+        let protocol_fee = if protocol_fee == 0 {
+            0
+        } else {
+            calculate_protocol_fee(amount_repaid, ctx.accounts.intent_account.fee_bps, ctx.accounts.protocol_state.min_fee_tokens)?
+                .min(protocol_fee)
+        };
+        ctx.accounts.intent_account.protocol_fee = protocol_fee;
+        
+        // Collect the protocol fee only once the repayment has gone through
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_account.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
+        // Update intent status
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        ctx.accounts.user_account.total_volume += amount_repaid + protocol_fee;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(DebtRepaid {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user: ctx.accounts.user.key(),
+            mint: ctx.accounts.intent_account.from_mint,
+            obligation,
+            protocol: protocol.unwrap(),
+            amount_repaid,
+            protocol_fee,
+        });
+        
+        msg!("✅ Repaid {} tokens of debt on obligation {} (Fee: {})", amount_repaid, obligation, protocol_fee);
         Ok(())
     }
 
-    /// Emergency pause protocol (admin only)
-    pub fn pause_protocol(ctx: Context<PauseProtocol>) -> Result<()> {
+    /// Create an intent to rebalance a portfolio to target weights.
+    /// Pass the user's token account for each weighted mint, in order, as remaining accounts.
+    pub fn create_rebalance_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateRebalanceIntent<'info>>,
+        params: RebalanceIntentParams,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let intent_account = &mut ctx.accounts.intent_account;
+        let rebalance_weights = &mut ctx.accounts.rebalance_weights;
         let protocol_state = &mut ctx.accounts.protocol_state;
-        require!(protocol_state.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
         
-        protocol_state.is_paused = true;
-        msg!("⏸️ Protocol paused by admin");
+        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(params.max_slippage <= MAX_SLIPPAGE_BPS, IntentError::SlippageTooHigh);
+        validate_rebalance_weights(&params.weights)?;
+        require!(
+            ctx.remaining_accounts.len() >= params.weights.len(),
+            IntentError::MissingExecutionAccounts
+        );
+        
+        // Snapshot the holdings the weights are applied to
+        let mut targets = Vec::with_capacity(params.weights.len());
+        for (weight, token_account) in params.weights.iter().zip(ctx.remaining_accounts) {
+            targets.push(RebalanceTarget {
+                mint: weight.mint,
+                target_bps: weight.target_bps,
+                balance: rebalance_token_balance(token_account, &user_account.authority, &weight.mint)?,
+            });
+        }
+        
+        // The fee is charged on the traded notional, which is only known at execution
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::Rebalance);
+        
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::Rebalance;
+        intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.weights[0].mint; // Fee is charged in the first asset
+        intent_account.to_mint = params.weights[0].mint;
+        intent_account.amount = 0;
+        intent_account.protocol_fee = 0;
+        intent_account.max_slippage = params.max_slippage;
+        intent_account.min_apy = None;
+        intent_account.target_price = None;
+        intent_account.max_price_impact = params.max_price_impact;
+        intent_account.execution_price = None;
+        intent_account.execution_apy = None;
+        intent_account.rugproof_enabled = false;
+        intent_account.selected_swap_protocol = SwapProtocol::Jupiter; // Legs are routed through Jupiter
+        intent_account.selected_lending_protocol = None;
+        intent_account.created_at = Clock::get()?.unix_timestamp;
+        intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
+        intent_account.executed_at = None;
+        intent_account.cancelled_at = None;
+        intent_account.pool_id = None;
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = params.price_source;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = params.max_route_fee;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = false;
+        intent_account.bump = ctx.bumps.intent_account;
+        
+        rebalance_weights.intent = intent_account.key();
+        rebalance_weights.targets = targets;
+        rebalance_weights.bump = ctx.bumps.rebalance_weights;
+        
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
+        user_account.active_intents += 1;
+        user_account.total_intents_created += 1;
+        protocol_state.total_intents_created += 1;
+        
+        msg!(
+            "⚖️ Rebalance intent created across {} assets (Fee: {} bps of traded notional)",
+            params.weights.len(),
+            fee_bps
+        );
+        
         Ok(())
     }
 
-    /// Unpause protocol (admin only)
-    pub fn unpause_protocol(ctx: Context<UnpauseProtocol>) -> Result<()> {
-        let protocol_state = &mut ctx.accounts.protocol_state;
-        require!(protocol_state.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
-        
-        protocol_state.is_paused = false;
+    /// Execute a rebalance intent, trading overweight assets into underweight ones
+    /// through Jupiter. Remaining accounts hold, for each target in order, the user's
+    /// token account, the mint, its price feed PDA and the feed's oracle, followed by any
+    /// fee split beneficiaries. Pass one route per leg, in the order `plan_rebalance_legs` returns.
+    pub fn execute_rebalance_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteRebalanceIntent<'info>>,
+        leg_swap_data: Vec<jupiter::JupiterSwapData>,
+    ) -> Result<()> {
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if ctx.accounts.intent_account.intent_type != IntentType::Rebalance {
+            msg!("❌ execute_rebalance_intent called on a {:?} intent", ctx.accounts.intent_account.intent_type);
+            return err!(IntentError::WrongProtocol);
+        }
+        require!(!ctx.accounts.protocol_state.is_paused, IntentError::ProtocolPaused);
+        
+        msg!("⚖️ Executing portfolio rebalance...");
+        
+        let user = ctx.accounts.user.key();
+        let targets = ctx.accounts.rebalance_weights.targets.clone();
+        let asset_account_count = targets.len() * 4;
+        require!(
+            ctx.remaining_accounts.len() >= asset_account_count,
+            IntentError::MissingExecutionAccounts
+        );
+        let (asset_accounts, beneficiary_accounts) = ctx.remaining_accounts.split_at(asset_account_count);
+        
+        // Value each live holding in the oracle's 6-decimal USDC scale
+        let mut balances = Vec::with_capacity(targets.len());
+        let mut prices = Vec::with_capacity(targets.len());
+        let mut units = Vec::with_capacity(targets.len());
+        let mut values = Vec::with_capacity(targets.len());
+        let now = Clock::get()?.unix_timestamp;
+        for (target, accounts) in targets.iter().zip(asset_accounts.chunks(4)) {
+            let balance = rebalance_token_balance(&accounts[0], &user, &target.mint)?;
+            require!(
+                accounts[1].key() == target.mint && accounts[1].owner == &token::ID,
+                IntentError::SourceMintMismatch
+            );
+            let decimals = Mint::try_deserialize(&mut &accounts[1].data.borrow()[..])?.decimals;
+            let price = rebalance_asset_price(&accounts[2], &accounts[3], &target.mint, now)?;
+            require!(price > 0, IntentError::InvalidOracle);
+            
+            let unit = 10u128.pow(decimals as u32);
+            let value = u64::try_from(balance as u128 * price as u128 / unit)
+                .map_err(|_| IntentError::RebalanceBalanceMismatch)?;
+            values.push(value);
+            balances.push(balance);
+            prices.push(price as u128);
+            units.push(unit);
+        }
+        
+        // Size each leg in the sold asset, dropping legs too small to trade a single unit
+        let target_bps: Vec<u16> = targets.iter().map(|t| t.target_bps).collect();
+        let legs: Vec<(usize, usize, u64, u64)> = plan_rebalance_legs(&values, &target_bps)
+            .into_iter()
+            .map(|(sell, buy, notional)| (sell, buy, notional, (notional as u128 * units[sell] / prices[sell]) as u64))
+            .filter(|(_, _, _, amount_in)| *amount_in > 0)
+            .collect();
+        require!(!legs.is_empty(), IntentError::NothingToRebalance);
+        require!(leg_swap_data.len() == legs.len(), IntentError::RebalanceRouteMismatch);
+        
+        // One fee on the total traded notional, expressed in the first asset
+        let total_notional = legs
+            .iter()
+            .try_fold(0u64, |total, (_, _, notional, _)| total.checked_add(*notional))
+            .ok_or(IntentError::RebalanceBalanceMismatch)?;
+        let notional_in_fee_mint = (total_notional as u128 * units[0] / prices[0]) as u64;
+        ctx.accounts.intent_account.protocol_fee = calculate_protocol_fee(
+            notional_in_fee_mint,
+            ctx.accounts.intent_account.fee_bps,
+            ctx.accounts.protocol_state.min_fee_tokens,
+        )?;
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        
+        let max_slippage = ctx.accounts.intent_account.max_slippage;
+        let mut executed_legs = Vec::with_capacity(legs.len());
+        for ((sell, buy, notional, amount_in), swap_data) in legs.into_iter().zip(leg_swap_data) {
+            // The route must trade the planned amount and quote within slippage of the oracle
+            let expected_out = notional as u128 * units[buy] / prices[buy];
+            let min_out = (expected_out * (10000 - max_slippage as u128) / 10000) as u64;
+            require!(swap_data.in_amount == amount_in, IntentError::RebalanceRouteMismatch);
+            require!(swap_data.quoted_out_amount >= min_out, IntentError::SlippageExceeded);
+            
+            let swap_params = jupiter::JupiterSwapParams {
+                from_mint: targets[sell].mint,
+                to_mint: targets[buy].mint,
+                amount: amount_in,
+                slippage_bps: max_slippage,
+                platform_fee_bps: 0, // Collected once for the whole rebalance
+                max_route_fee: ctx.accounts.intent_account.max_route_fee,
+                max_price_impact: ctx.accounts.intent_account.max_price_impact,
+            };
+            let amount_out = jupiter::execute_jupiter_swap_simple(
+                &ctx.accounts.user.to_account_info(),
+                &asset_accounts[sell * 4],
+                &asset_accounts[buy * 4],
+                &ctx.accounts.jupiter_program.to_account_info(),
+                &ctx.accounts.token_program.to_account_info(),
+                swap_params,
+                swap_data,
+            )?;
+            
+            apply_rebalance_leg(&mut balances, sell, buy, amount_in, amount_out)?;
+            executed_legs.push(RebalanceLeg {
+                from_mint: targets[sell].mint,
+                to_mint: targets[buy].mint,
+                amount_in,
+                amount_out,
+                notional,
+            });
+        }
+        
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &asset_accounts[0],
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            beneficiary_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        balances[0] = balances[0].saturating_sub(protocol_fee);
+        
+        // Record the post-trade holdings
+        for (target, balance) in ctx.accounts.rebalance_weights.targets.iter_mut().zip(&balances) {
+            target.balance = *balance;
+        }
+        
+        // Update intent status
+        ctx.accounts.intent_account.amount = notional_in_fee_mint;
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        ctx.accounts.user_account.total_volume += notional_in_fee_mint;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        let leg_count = executed_legs.len();
+        emit!(RebalanceExecuted {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user,
+            legs: executed_legs,
+            total_notional,
+            protocol_fee,
+        });
+        
+        msg!("✅ Rebalanced in {} legs, {} USDC notional traded (Fee: {})", leg_count, total_notional, protocol_fee);
+        Ok(())
+    }
+
+    /// Create a buy intent with price conditions
+    ///
+    /// With `execute_immediately`, a buy whose target price is already met is executed
+    /// through Jupiter in the same instruction, using the optional execution accounts.
+    pub fn create_buy_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, CreateBuyIntent<'info>>,
+        params: BuyIntentParams,
+        jupiter_swap_data: Option<jupiter::JupiterSwapData>,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let intent_account = &mut ctx.accounts.intent_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        
+        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(params.usdc_amount > 0, IntentError::InvalidAmount);
+        // Omitted slippage falls back to the user's default, same as swap intents
+        let max_slippage = params.max_slippage.unwrap_or(user_account.default_slippage_bps);
+        require!(max_slippage <= MAX_SLIPPAGE_BPS, IntentError::SlippageTooHigh);
+        
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::Buy);
+        let protocol_fee = calculate_protocol_fee(params.usdc_amount, fee_bps, protocol_state.min_fee_tokens)?;
+        
+        // A price check deadline only makes sense for conditional buys, and must fall
+        // before the general expiry (which always applies regardless of the deadline).
+        // It gets the same minimum lifetime as expires_at, so an intent can't be created
+        // already about to lapse just to be expired for the bounty.
+        if let Some(deadline) = params.price_check_deadline {
+            let now = Clock::get()?.unix_timestamp;
+            require!(params.target_price.is_some(), IntentError::InvalidPriceCheckDeadline);
+            require!(
+                deadline >= now + MIN_INTENT_LIFETIME && deadline <= now + INTENT_EXPIRY_SECONDS,
+                IntentError::InvalidPriceCheckDeadline
+            );
+        }
+        
+        // Rugproof check if enabled
+        if params.rugproof_check {
+            let rugproof_score = cached_rugproof_score(&params.mint, ctx.accounts.rugproof_cache.as_deref())?;
+            require!(
+                rugproof_score >= user_account.rugproof_min_score.max(MIN_RUGPROOF_SCORE),
+                IntentError::RugproofCheckFailed
+            );
+        }
+        
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::Buy;
+        intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.usdc_mint; // Passed in params
+        intent_account.to_mint = params.mint;
+        intent_account.amount = params.usdc_amount;
+        intent_account.protocol_fee = protocol_fee;
+        intent_account.max_slippage = max_slippage;
+        intent_account.min_apy = None;
+        intent_account.target_price = params.target_price;
+        intent_account.max_price_impact = Some(params.max_price_impact);
+        intent_account.execution_price = None;
+        intent_account.execution_apy = None;
+        intent_account.rugproof_enabled = params.rugproof_check;
+        intent_account.selected_swap_protocol = SwapProtocol::Jupiter; // Default for buy intents
+        intent_account.selected_lending_protocol = None;
+        intent_account.created_at = Clock::get()?.unix_timestamp;
+        intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
+        intent_account.executed_at = None;
+        intent_account.cancelled_at = None;
+        intent_account.pool_id = None;
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = params.price_check_deadline;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = params.price_source.clone();
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = None;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = false;
+        intent_account.bump = ctx.bumps.intent_account;
+        
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
+        user_account.active_intents += 1;
+        user_account.total_intents_created += 1;
+        protocol_state.total_intents_created += 1;
+        
+        msg!(
+            "💳 Buy intent created: ${} for {} (Fee: ${})",
+            params.usdc_amount,
+            params.mint,
+            protocol_fee
+        );
+        
+        if !params.execute_immediately {
+            return Ok(());
+        }
+        
+        // Execute inline only when the buy condition already holds
+        let target_price = params.target_price.ok_or(IntentError::ConditionNotMet)?;
+        // Prefer the intent's oracle over the caller-supplied price when one is passed
+        let current_price = match ctx.accounts.price_oracle.as_ref() {
+            Some(price_oracle) => oracle::read_price(&intent_account.price_source, price_oracle)?,
+            None => params.current_price.ok_or(IntentError::ConditionNotMet)?,
+        };
+        require!(buy_condition_met(intent_account, current_price), IntentError::ConditionNotMet);
+        require!(params.not_before.is_none(), IntentError::IntentNotYetActive);
+        // Delegates can create intents but cannot move the owner's tokens
+        require!(intent_account.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
+        
+        let (
+            Some(user_source_token),
+            Some(user_destination_token),
+            Some(treasury_fee_account),
+            Some(fee_ledger),
+            Some(jupiter_program),
+            Some(token_program),
+            Some(jupiter_swap_data),
+        ) = (
+            ctx.accounts.user_source_token.as_ref(),
+            ctx.accounts.user_destination_token.as_ref(),
+            ctx.accounts.treasury_fee_account.as_ref(),
+            ctx.accounts.fee_ledger.as_ref(),
+            ctx.accounts.jupiter_program.as_ref(),
+            ctx.accounts.token_program.as_ref(),
+            jupiter_swap_data,
+        ) else {
+            return err!(IntentError::MissingExecutionAccounts);
+        };
+        require!(user_source_token.mint == intent_account.from_mint, IntentError::SourceMintMismatch);
+        require!(user_destination_token.mint == intent_account.to_mint, IntentError::DestinationMintMismatch);
+        require!(treasury_fee_account.owner == protocol_state.treasury_authority, IntentError::InvalidTreasuryAccount);
+        require!(treasury_fee_account.mint == intent_account.from_mint, IntentError::FeeMintMismatch);
+        require!(
+            treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint),
+            IntentError::NonCanonicalTreasury
+        );
+        require!(
+            fee_ledger.key() == Pubkey::find_program_address(&[b"fee_ledger", intent_account.from_mint.as_ref()], ctx.program_id).0,
+            IntentError::InvalidFeeLedger
+        );
+        
+        begin_intent_execution(intent_account, ctx.accounts.authority.key(), ctx.program_id)?;
+        
+        // Same guard as execute_swap_intent_jupiter: the route quote is caller-supplied,
+        // so it can't feed the breaker, but a tripped pair stays halted
+        check_circuit_breaker(&ctx.accounts.circuit_breaker.to_account_info(), intent_account, None)?;
+        
+        let protocol_fee = apply_fee_exemption(intent_account, protocol_state);
+        let net_amount = intent_account.amount.checked_sub(protocol_fee).unwrap();
+        
+        collect_protocol_fee(
+            &token_program.to_account_info(),
+            &user_source_token.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.authority.to_account_info(), protocol_state),
+            &treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            intent_account.key(),
+            protocol_fee,
+        )?;
+        
+        let swap_params = jupiter_swap_params(intent_account, net_amount);
+        
+        let route_labels = jupiter::route_labels(&jupiter_swap_data);
+        
+        let estimated_output = jupiter::execute_jupiter_swap_simple(
+            &ctx.accounts.authority.to_account_info(),
+            &user_source_token.to_account_info(),
+            &user_destination_token.to_account_info(),
+            &jupiter_program.to_account_info(),
+            &token_program.to_account_info(),
+            swap_params,
+            jupiter_swap_data,
+        )?;
+        
+        intent_account.status = IntentStatus::Executed;
+        intent_account.is_executing = false;
+        release_keeper_tip(
+            &intent_account.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        record_fill(intent_account, net_amount, estimated_output)?;
+        
+        user_account.active_intents -= 1;
+        user_account.total_volume += intent_account.amount;
+        protocol_state.total_intents_executed += 1;
+        protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(SwapIntentExecuted {
+            intent_id: intent_account.key(),
+            client_order_id: intent_account.client_order_id,
+            keeper_tip_lamports: intent_account.keeper_tip_lamports,
+            user: ctx.accounts.authority.key(),
+            protocol: SwapProtocol::Jupiter,
+            from_mint: intent_account.from_mint,
+            to_mint: intent_account.to_mint,
+            amount_in: net_amount,
+            amount_out: estimated_output,
+            protocol_fee,
+            route_labels,
+        });
+        
+        msg!(
+            "✅ Buy condition already met ({} <= {}), executed: {} → {} tokens",
+            current_price,
+            target_price,
+            net_amount,
+            estimated_output
+        );
+        
+        Ok(())
+    }
+
+    /// Create an intent to provide liquidity to an AMM pool
+    pub fn create_add_liquidity_intent(
+        ctx: Context<CreateAddLiquidityIntent>,
+        params: AddLiquidityIntentParams,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let intent_account = &mut ctx.accounts.intent_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        
+        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(params.amount_a > 0 && params.amount_b > 0, IntentError::InvalidAmount);
+        require!(params.mint_a != params.mint_b, IntentError::InvalidAmount);
+        require!(params.max_slippage <= MAX_SLIPPAGE_BPS, IntentError::SlippageTooHigh);
+        
+        // Fee is charged on the notional value of side A
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::AddLiquidity);
+        let protocol_fee = calculate_protocol_fee(params.amount_a, fee_bps, protocol_state.min_fee_tokens)?;
+        
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::AddLiquidity;
+        intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.mint_a;
+        intent_account.to_mint = params.mint_b;
+        intent_account.amount = params.amount_a;
+        intent_account.protocol_fee = protocol_fee;
+        intent_account.max_slippage = params.max_slippage;
+        intent_account.min_apy = None;
+        intent_account.target_price = None;
+        intent_account.max_price_impact = None;
+        intent_account.execution_price = None;
+        intent_account.execution_apy = None;
+        intent_account.rugproof_enabled = false;
+        intent_account.selected_swap_protocol = SwapProtocol::Raydium;
+        intent_account.selected_lending_protocol = None;
+        intent_account.created_at = Clock::get()?.unix_timestamp;
+        intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
+        intent_account.executed_at = None;
+        intent_account.cancelled_at = None;
+        intent_account.pool_id = Some(params.pool_id);
+        intent_account.secondary_amount = params.amount_b;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = 0;
+        intent_account.min_amount_b_out = 0;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = PriceSource::Pyth;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = None;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = false;
+        intent_account.bump = ctx.bumps.intent_account;
+        
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
+        user_account.active_intents += 1;
+        user_account.total_intents_created += 1;
+        protocol_state.total_intents_created += 1;
+        
+        msg!(
+            "💧 Add liquidity intent created: {} {} + {} {} into pool {} (Fee: {})",
+            params.amount_a,
+            params.mint_a,
+            params.amount_b,
+            params.mint_b,
+            params.pool_id,
+            protocol_fee
+        );
+        
+        Ok(())
+    }
+
+    /// Execute an add liquidity intent by depositing both sides into the Raydium pool
+    pub fn execute_add_liquidity_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteAddLiquidityIntent<'info>>,
+        pool_info: raydium::RaydiumPoolInfo,
+    ) -> Result<()> {
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if ctx.accounts.intent_account.intent_type != IntentType::AddLiquidity {
+            msg!("❌ execute_add_liquidity_intent called on a {:?} intent", ctx.accounts.intent_account.intent_type);
+            return err!(IntentError::WrongProtocol);
+        }
+        require!(
+            ctx.accounts.intent_account.pool_id == Some(ctx.accounts.raydium_pool.key()),
+            IntentError::InvalidPool
+        );
+        require!(ctx.accounts.lp_mint.key() == pool_info.lp_mint_address, IntentError::InvalidPool);
+        
+        // Map intent sides onto the pool's coin/pc sides
+        let mint_a = ctx.accounts.intent_account.from_mint;
+        let mint_b = ctx.accounts.intent_account.to_mint;
+        let a_is_coin = if mint_a == pool_info.coin_mint_address && mint_b == pool_info.pc_mint_address {
+            true
+        } else if mint_a == pool_info.pc_mint_address && mint_b == pool_info.coin_mint_address {
+            false
+        } else {
+            return err!(IntentError::InvalidPool);
+        };
+        let (pool_token_a, pool_token_b) = if a_is_coin {
+            (pool_info.pool_coin_token_account, pool_info.pool_pc_token_account)
+        } else {
+            (pool_info.pool_pc_token_account, pool_info.pool_coin_token_account)
+        };
+        require!(ctx.accounts.pool_token_a.key() == pool_token_a, IntentError::InvalidPool);
+        require!(ctx.accounts.pool_token_b.key() == pool_token_b, IntentError::InvalidPool);
+        require!(
+            ctx.accounts.pool_token_a.mint == mint_a && ctx.accounts.pool_token_b.mint == mint_b,
+            IntentError::InvalidPool
+        );
+        require!(
+            ctx.accounts.user_token_a.mint == mint_a && ctx.accounts.user_token_b.mint == mint_b,
+            IntentError::SourceMintMismatch
+        );
+        raydium::verify_pool_vaults(
+            &ctx.accounts.raydium_pool.to_account_info(),
+            &pool_info.pool_coin_token_account,
+            &pool_info.pool_pc_token_account,
+        )?;
+        
+        // Caller-supplied reserves may be a stale snapshot; price off the live vault balances
+        let reserve_a = ctx.accounts.pool_token_a.amount;
+        let reserve_b = ctx.accounts.pool_token_b.amount;
+        
+        msg!("💧 Executing Raydium liquidity deposit...");
+        
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let net_amount_a = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        let amount_b = ctx.accounts.intent_account.secondary_amount;
+        
+        // Enforce slippage on the deposit ratio against current pool reserves
+        raydium::check_deposit_ratio(
+            net_amount_a,
+            amount_b,
+            reserve_a,
+            reserve_b,
+            ctx.accounts.intent_account.max_slippage,
+        )?;
+        
+        // The pool must mint at least the pro-rata LP estimate, less the intent's slippage
+        let expected_lp = raydium::calculate_lp_tokens(
+            net_amount_a,
+            amount_b,
+            reserve_a,
+            reserve_b,
+            ctx.accounts.lp_mint.supply,
+        )?;
+        let min_lp_tokens = (expected_lp as u128 * (10000 - ctx.accounts.intent_account.max_slippage as u128) / 10000) as u64;
+        
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_token_a.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
+        // Deposit through Raydium sized off side A, then measure what actually moved
+        ctx.accounts.user_token_a.reload()?;
+        let (a_before, b_before) = (ctx.accounts.user_token_a.amount, ctx.accounts.user_token_b.amount);
+        let lp_before = ctx.accounts.user_lp_account.amount;
+        
+        let owner_authority = OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state);
+        let (user_coin, user_pc, pool_coin, pool_pc) = if a_is_coin {
+            (&ctx.accounts.user_token_a, &ctx.accounts.user_token_b, &ctx.accounts.pool_token_a, &ctx.accounts.pool_token_b)
+        } else {
+            (&ctx.accounts.user_token_b, &ctx.accounts.user_token_a, &ctx.accounts.pool_token_b, &ctx.accounts.pool_token_a)
+        };
+        let (max_coin_amount, max_pc_amount, base_side) = if a_is_coin {
+            (net_amount_a, amount_b, 0)
+        } else {
+            (amount_b, net_amount_a, 1)
+        };
+        let deposit = raydium::RaydiumDeposit {
+            amm: ctx.accounts.raydium_pool.key(),
+            amm_authority: ctx.accounts.amm_authority.key(),
+            amm_open_orders: ctx.accounts.amm_open_orders.key(),
+            amm_target_orders: ctx.accounts.amm_target_orders.key(),
+            lp_mint: ctx.accounts.lp_mint.key(),
+            pool_coin_token_account: pool_coin.key(),
+            pool_pc_token_account: pool_pc.key(),
+            serum_market: ctx.accounts.serum_market.key(),
+            user_coin_token_account: user_coin.key(),
+            user_pc_token_account: user_pc.key(),
+            user_lp_token_account: ctx.accounts.user_lp_account.key(),
+            user_owner: owner_authority.authority().key(),
+            serum_event_queue: ctx.accounts.serum_event_queue.key(),
+        };
+        owner_authority.invoke(
+            &deposit.instruction(max_coin_amount, max_pc_amount, base_side),
+            &[
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.raydium_pool.to_account_info(),
+                ctx.accounts.amm_authority.to_account_info(),
+                ctx.accounts.amm_open_orders.to_account_info(),
+                ctx.accounts.amm_target_orders.to_account_info(),
+                ctx.accounts.lp_mint.to_account_info(),
+                pool_coin.to_account_info(),
+                pool_pc.to_account_info(),
+                ctx.accounts.serum_market.to_account_info(),
+                user_coin.to_account_info(),
+                user_pc.to_account_info(),
+                ctx.accounts.user_lp_account.to_account_info(),
+                owner_authority.authority(),
+                ctx.accounts.serum_event_queue.to_account_info(),
+                ctx.accounts.raydium_program.to_account_info(),
+            ],
+        )?;
+        
+        ctx.accounts.user_token_a.reload()?;
+        ctx.accounts.user_token_b.reload()?;
+        ctx.accounts.user_lp_account.reload()?;
+        let deposited_a = a_before.checked_sub(ctx.accounts.user_token_a.amount).ok_or(IntentError::InvalidAmount)?;
+        let deposited_b = b_before.checked_sub(ctx.accounts.user_token_b.amount).ok_or(IntentError::InvalidAmount)?;
+        let lp_tokens = ctx.accounts.user_lp_account.amount.checked_sub(lp_before).ok_or(IntentError::InvalidAmount)?;
+        require!(lp_tokens > 0 && lp_tokens >= min_lp_tokens, IntentError::SlippageExceeded);
+        
+        // Update intent status
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        ctx.accounts.intent_account.lp_tokens = Some(lp_tokens);
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(LiquidityAdded {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user: ctx.accounts.user.key(),
+            pool_id: ctx.accounts.raydium_pool.key(),
+            mint_a,
+            mint_b,
+            amount_a: deposited_a,
+            amount_b: deposited_b,
+            lp_tokens,
+            protocol_fee,
+        });
+        
+        msg!("✅ Liquidity added: {} + {} → {} LP tokens (Fee: {})", deposited_a, deposited_b, lp_tokens, protocol_fee);
+        Ok(())
+    }
+
+    /// Create an intent to withdraw liquidity from an AMM pool
+    pub fn create_remove_liquidity_intent(
+        ctx: Context<CreateRemoveLiquidityIntent>,
+        params: RemoveLiquidityIntentParams,
+    ) -> Result<()> {
+        let user_account = &mut ctx.accounts.user_account;
+        let intent_account = &mut ctx.accounts.intent_account;
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        
+        require!(user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        require!(!protocol_state.is_paused, IntentError::ProtocolPaused);
+        require!(params.lp_amount > 0, IntentError::InvalidAmount);
+        require!(params.mint_a != params.mint_b, IntentError::InvalidAmount);
+        
+        // Fee is charged in LP tokens on the amount withdrawn
+        let fee_bps = type_fee_bps(protocol_state, &IntentType::RemoveLiquidity);
+        let protocol_fee = calculate_protocol_fee(params.lp_amount, fee_bps, protocol_state.min_fee_tokens)?;
+        
+        intent_account.authority = user_account.authority;
+        intent_account.intent_type = IntentType::RemoveLiquidity;
+        intent_account.status = IntentStatus::Pending;
+        intent_account.client_order_id = params.client_order_id;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, None, IntentStatus::Pending)?;
+        intent_account.from_mint = params.mint_a;
+        intent_account.to_mint = params.mint_b;
+        intent_account.amount = params.lp_amount;
+        intent_account.protocol_fee = protocol_fee;
+        intent_account.max_slippage = 0;
+        intent_account.min_apy = None;
+        intent_account.target_price = None;
+        intent_account.max_price_impact = None;
+        intent_account.execution_price = None;
+        intent_account.execution_apy = None;
+        intent_account.rugproof_enabled = false;
+        intent_account.selected_swap_protocol = SwapProtocol::Raydium;
+        intent_account.selected_lending_protocol = None;
+        intent_account.created_at = Clock::get()?.unix_timestamp;
+        intent_account.expires_at = Clock::get()?.unix_timestamp + INTENT_EXPIRY_SECONDS;
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
+        require!(
+            !matches!(params.not_before, Some(not_before) if not_before >= intent_account.expires_at),
+            IntentError::InvalidNotBefore
+        );
+        intent_account.not_before = params.not_before;
+        intent_account.executed_at = None;
+        intent_account.cancelled_at = None;
+        intent_account.pool_id = Some(params.pool_id);
+        intent_account.secondary_amount = 0;
+        intent_account.lp_tokens = None;
+        intent_account.min_amount_a_out = params.min_amount_a_out;
+        intent_account.min_amount_b_out = params.min_amount_b_out;
+        intent_account.is_executing = false;
+        intent_account.price_check_deadline = None;
+        intent_account.cumulative_in = 0;
+        intent_account.cumulative_out = 0;
+        intent_account.price_source = PriceSource::Pyth;
+        intent_account.keeper_tip_lamports = params.keeper_tip_lamports;
+        intent_account.max_route_fee = None;
+        intent_account.exact_output = false;
+        intent_account.max_input = 0;
+        intent_account.execution_attempts = 0;
+        intent_account.fee_bps = fee_bps;
+        intent_account.allowed_executor = None;
+        intent_account.min_fill_amount = 0;
+        intent_account.lending_cap_reserved = false;
+        intent_account.auto_lend = false;
+        intent_account.bump = ctx.bumps.intent_account;
+        
+        // Prepay the keeper tip into the intent PDA
+        fund_keeper_tip(
+            &ctx.accounts.system_program.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            &intent_account.to_account_info(),
+            params.keeper_tip_lamports,
+        )?;
+        
+        user_account.active_intents += 1;
+        user_account.total_intents_created += 1;
+        protocol_state.total_intents_created += 1;
+        
+        msg!(
+            "💧 Remove liquidity intent created: {} LP from pool {} (min out: {} / {}, Fee: {})",
+            params.lp_amount,
+            params.pool_id,
+            params.min_amount_a_out,
+            params.min_amount_b_out,
+            protocol_fee
+        );
+        
+        Ok(())
+    }
+
+    /// Execute a remove liquidity intent by burning LP tokens for the underlying
+    pub fn execute_remove_liquidity_intent<'info>(
+        ctx: Context<'_, '_, '_, 'info, ExecuteRemoveLiquidityIntent<'info>>,
+        pool_info: raydium::RaydiumPoolInfo,
+    ) -> Result<()> {
+        require!(ctx.accounts.intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        begin_intent_execution(&mut ctx.accounts.intent_account, ctx.accounts.keeper.key(), ctx.program_id)?;
+        if Clock::get()?.unix_timestamp >= ctx.accounts.intent_account.expires_at {
+            // Clean up the stale intent instead of failing with no state change
+            return expire_on_execution(
+                &mut ctx.accounts.intent_account,
+                &mut ctx.accounts.user_account,
+                &ctx.accounts.user.to_account_info(),
+                ctx.accounts.keeper.key(),
+                None,
+            );
+        }
+        if let Some(not_before) = ctx.accounts.intent_account.not_before {
+            require!(Clock::get()?.unix_timestamp >= not_before, IntentError::IntentNotYetActive);
+        }
+        if ctx.accounts.intent_account.intent_type != IntentType::RemoveLiquidity {
+            msg!("❌ execute_remove_liquidity_intent called on a {:?} intent", ctx.accounts.intent_account.intent_type);
+            return err!(IntentError::WrongProtocol);
+        }
+        require!(
+            ctx.accounts.intent_account.pool_id == Some(ctx.accounts.raydium_pool.key()),
+            IntentError::InvalidPool
+        );
+        require!(ctx.accounts.lp_mint.key() == pool_info.lp_mint_address, IntentError::InvalidPool);
+        
+        raydium::verify_pool_vaults(
+            &ctx.accounts.raydium_pool.to_account_info(),
+            &ctx.accounts.pool_coin_vault.key(),
+            &ctx.accounts.pool_pc_vault.key(),
+        )?;
+        
+        // Map intent sides onto the pool's coin/pc sides
+        let mint_a = ctx.accounts.intent_account.from_mint;
+        let mint_b = ctx.accounts.intent_account.to_mint;
+        let (coin_mint, pc_mint) = (ctx.accounts.pool_coin_vault.mint, ctx.accounts.pool_pc_vault.mint);
+        let a_is_coin = if mint_a == coin_mint && mint_b == pc_mint {
+            true
+        } else if mint_a == pc_mint && mint_b == coin_mint {
+            false
+        } else {
+            return err!(IntentError::InvalidPool);
+        };
+        require!(
+            ctx.accounts.user_token_a.mint == mint_a && ctx.accounts.user_token_b.mint == mint_b,
+            IntentError::DestinationMintMismatch
+        );
+        
+        msg!("💧 Executing Raydium liquidity withdrawal...");
+        
+        let protocol_fee = apply_fee_exemption(&mut ctx.accounts.intent_account, &ctx.accounts.protocol_state);
+        let lp_to_burn = ctx.accounts.intent_account.amount.checked_sub(protocol_fee).unwrap();
+        
+        collect_protocol_fee(
+            &ctx.accounts.token_program.to_account_info(),
+            &ctx.accounts.user_lp_account.to_account_info(),
+            &OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state),
+            &ctx.accounts.treasury_fee_account.to_account_info(),
+            &ctx.accounts.fee_split_config.to_account_info(),
+            &ctx.accounts.fee_ledger.to_account_info(),
+            ctx.remaining_accounts,
+            ctx.accounts.intent_account.key(),
+            protocol_fee,
+        )?;
+        
+        // Withdraw through Raydium, then hold the user's floors against what actually arrived
+        let (a_before, b_before) = (ctx.accounts.user_token_a.amount, ctx.accounts.user_token_b.amount);
+        
+        let owner_authority = OwnerAuthority::new(ctx.accounts.user.to_account_info(), &ctx.accounts.protocol_state);
+        let (user_coin, user_pc) = if a_is_coin {
+            (&ctx.accounts.user_token_a, &ctx.accounts.user_token_b)
+        } else {
+            (&ctx.accounts.user_token_b, &ctx.accounts.user_token_a)
+        };
+        let withdraw = raydium::RaydiumWithdraw {
+            amm: ctx.accounts.raydium_pool.key(),
+            amm_authority: ctx.accounts.amm_authority.key(),
+            amm_open_orders: ctx.accounts.amm_open_orders.key(),
+            amm_target_orders: ctx.accounts.amm_target_orders.key(),
+            lp_mint: ctx.accounts.lp_mint.key(),
+            pool_coin_token_account: ctx.accounts.pool_coin_vault.key(),
+            pool_pc_token_account: ctx.accounts.pool_pc_vault.key(),
+            serum_program: ctx.accounts.serum_program.key(),
+            serum_market: ctx.accounts.serum_market.key(),
+            serum_coin_vault: ctx.accounts.serum_coin_vault.key(),
+            serum_pc_vault: ctx.accounts.serum_pc_vault.key(),
+            serum_vault_signer: ctx.accounts.serum_vault_signer.key(),
+            user_lp_token_account: ctx.accounts.user_lp_account.key(),
+            user_coin_token_account: user_coin.key(),
+            user_pc_token_account: user_pc.key(),
+            user_owner: owner_authority.authority().key(),
+            serum_event_queue: ctx.accounts.serum_event_queue.key(),
+            serum_bids: ctx.accounts.serum_bids.key(),
+            serum_asks: ctx.accounts.serum_asks.key(),
+        };
+        owner_authority.invoke(
+            &withdraw.instruction(lp_to_burn),
+            &[
+                ctx.accounts.token_program.to_account_info(),
+                ctx.accounts.raydium_pool.to_account_info(),
+                ctx.accounts.amm_authority.to_account_info(),
+                ctx.accounts.amm_open_orders.to_account_info(),
+                ctx.accounts.amm_target_orders.to_account_info(),
+                ctx.accounts.lp_mint.to_account_info(),
+                ctx.accounts.pool_coin_vault.to_account_info(),
+                ctx.accounts.pool_pc_vault.to_account_info(),
+                ctx.accounts.serum_program.to_account_info(),
+                ctx.accounts.serum_market.to_account_info(),
+                ctx.accounts.serum_coin_vault.to_account_info(),
+                ctx.accounts.serum_pc_vault.to_account_info(),
+                ctx.accounts.serum_vault_signer.to_account_info(),
+                ctx.accounts.user_lp_account.to_account_info(),
+                user_coin.to_account_info(),
+                user_pc.to_account_info(),
+                owner_authority.authority(),
+                ctx.accounts.serum_event_queue.to_account_info(),
+                ctx.accounts.serum_bids.to_account_info(),
+                ctx.accounts.serum_asks.to_account_info(),
+                ctx.accounts.raydium_program.to_account_info(),
+            ],
+        )?;
+        
+        ctx.accounts.user_token_a.reload()?;
+        ctx.accounts.user_token_b.reload()?;
+        let amount_a_out = ctx.accounts.user_token_a.amount.checked_sub(a_before).ok_or(IntentError::InvalidAmount)?;
+        let amount_b_out = ctx.accounts.user_token_b.amount.checked_sub(b_before).ok_or(IntentError::InvalidAmount)?;
+        require!(amount_a_out >= ctx.accounts.intent_account.min_amount_a_out, IntentError::SlippageExceeded);
+        require!(amount_b_out >= ctx.accounts.intent_account.min_amount_b_out, IntentError::SlippageExceeded);
+        
+        // Update intent status
+        ctx.accounts.intent_account.status = IntentStatus::Executed;
+        ctx.accounts.intent_account.is_executing = false;
+        release_keeper_tip(
+            &ctx.accounts.intent_account.to_account_info(),
+            &ctx.accounts.keeper.to_account_info(),
+            ctx.accounts.intent_account.keeper_tip_lamports,
+        )?;
+        emit_intent_state_changed(ctx.accounts.intent_account.key(), ctx.accounts.intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Executed)?;
+        ctx.accounts.intent_account.executed_at = Some(Clock::get()?.unix_timestamp);
+        ctx.accounts.intent_account.lp_tokens = Some(lp_to_burn);
+        
+        // Update counters
+        ctx.accounts.user_account.active_intents -= 1;
+        ctx.accounts.user_account.total_volume += ctx.accounts.intent_account.amount;
+        ctx.accounts.protocol_state.total_intents_executed += 1;
+        ctx.accounts.protocol_state.total_fees_collected += protocol_fee;
+        
+        emit!(LiquidityRemoved {
+            intent_id: ctx.accounts.intent_account.key(),
+            client_order_id: ctx.accounts.intent_account.client_order_id,
+            keeper_tip_lamports: ctx.accounts.intent_account.keeper_tip_lamports,
+            user: ctx.accounts.user.key(),
+            pool_id: ctx.accounts.raydium_pool.key(),
+            mint_a,
+            mint_b,
+            lp_tokens_burned: lp_to_burn,
+            amount_a: amount_a_out,
+            amount_b: amount_b_out,
+            protocol_fee,
+        });
+        
+        msg!("✅ Liquidity removed: {} LP → {} + {} tokens (Fee: {})", lp_to_burn, amount_a_out, amount_b_out, protocol_fee);
+        Ok(())
+    }
+
+    /// Cancel an active intent
+    ///
+    /// Deliberately not gated on `is_paused`: users must always be able to exit their
+    /// pending intents, especially during an incident pause.
+    pub fn cancel_intent(ctx: Context<CancelIntent>) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(intent_account.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
+        
+        // Protocol fees are only transferred at execution, so a pending intent has never
+        // been charged and cancelling has nothing to refund
+        intent_account.status = IntentStatus::Cancelled;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Cancelled)?;
+        intent_account.cancelled_at = Some(Clock::get()?.unix_timestamp);
+        
+        user_account.active_intents -= 1;
+        
+        if intent_account.intent_type == IntentType::Lend {
+            release_lending_cap(&ctx.accounts.lending_cap, intent_account)?;
+        }
+        
+        // Refund the unused keeper tip
+        release_keeper_tip(
+            &intent_account.to_account_info(),
+            &ctx.accounts.authority.to_account_info(),
+            intent_account.keeper_tip_lamports,
+        )?;
+        
+        msg!("❌ Intent cancelled: {}", intent_account.key());
+        Ok(())
+    }
+
+    /// Close a finished intent, reclaiming its rent, after folding it into the owner's
+    /// IntentHistory so a tamper-evident summary outlives the intent account. Like
+    /// cancel_intent, this stays available while the protocol is paused.
+    pub fn close_intent(ctx: Context<CloseIntent>) -> Result<()> {
+        let intent_account = &ctx.accounts.intent_account;
+        let intent_history = &mut ctx.accounts.intent_history;
+        
+        require!(!intent_account.is_executing, IntentError::Reentrancy);
+        match intent_account.status {
+            IntentStatus::Executed => intent_history.executed_count += 1,
+            IntentStatus::Cancelled => intent_history.cancelled_count += 1,
+            IntentStatus::Expired => intent_history.expired_count += 1,
+            IntentStatus::Failed => intent_history.failed_count += 1,
+            IntentStatus::Pending => return err!(IntentError::IntentStillPending),
+        }
+        
+        // Chain the intent's final state onto the previous digest
+        let status = intent_account.status.clone() as u8;
+        let finished_at = intent_account.executed_at.or(intent_account.cancelled_at).unwrap_or(0);
+        intent_history.history_hash = hashv(&[
+            &intent_history.history_hash,
+            intent_account.key().as_ref(),
+            &[status],
+            &intent_account.amount.to_le_bytes(),
+            &intent_account.cumulative_out.to_le_bytes(),
+            &finished_at.to_le_bytes(),
+        ])
+        .to_bytes();
+        intent_history.authority = intent_account.authority;
+        intent_history.bump = ctx.bumps.intent_history;
+        
+        emit!(IntentCompacted {
+            intent_id: intent_account.key(),
+            authority: intent_account.authority,
+            status: intent_account.status.clone(),
+            history_hash: intent_history.history_hash,
+        });
+        
+        msg!("🗜️ Intent {} compacted into history and closed", intent_account.key());
+        Ok(())
+    }
+
+    /// Force-cancel a pending intent the owner can no longer cancel (admin only, incident use)
+    pub fn admin_cancel_intent(ctx: Context<AdminCancelIntent>, reason_code: u16) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let intent_account = &mut ctx.accounts.intent_account;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        
+        intent_account.status = IntentStatus::Cancelled;
+        // A stuck intent may still hold the execution lock
+        intent_account.is_executing = false;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Cancelled)?;
+        intent_account.cancelled_at = Some(Clock::get()?.unix_timestamp);
+        
+        user_account.active_intents = user_account.active_intents.saturating_sub(1);
+        
+        if intent_account.intent_type == IntentType::Lend {
+            release_lending_cap(&ctx.accounts.lending_cap, intent_account)?;
+        }
+        
+        // Refund the unused keeper tip to the intent's owner
+        release_keeper_tip(
+            &intent_account.to_account_info(),
+            &ctx.accounts.intent_authority.to_account_info(),
+            intent_account.keeper_tip_lamports,
+        )?;
+        
+        emit!(AdminCancelled {
+            intent_id: intent_account.key(),
+            intent_authority: intent_account.authority,
+            admin: ctx.accounts.authority.key(),
+            reason_code,
+        });
+        
+        msg!("🛑 Intent {} force-cancelled by admin (reason: {})", intent_account.key(), reason_code);
+        Ok(())
+    }
+
+    /// Expire a stale intent, paying the calling keeper a bounty from the bounty vault
+    ///
+    /// An intent is stale once `expires_at` passes. Buy intents with a `price_check_deadline`
+    /// become stale earlier, as soon as that deadline passes without execution.
+    pub fn expire_intent(ctx: Context<ExpireIntent>) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(intent_is_stale(intent_account, Clock::get()?.unix_timestamp), IntentError::IntentNotExpired);
+        
+        intent_account.status = IntentStatus::Expired;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Expired)?;
+        user_account.active_intents -= 1;
+        
+        if intent_account.intent_type == IntentType::Lend {
+            release_lending_cap(&ctx.accounts.lending_cap, intent_account)?;
+        }
+        
+        // Refund the unused keeper tip to the intent's owner
+        release_keeper_tip(
+            &intent_account.to_account_info(),
+            &ctx.accounts.intent_authority.to_account_info(),
+            intent_account.keeper_tip_lamports,
+        )?;
+        
+        let bounty_lamports = payable_expiry_bounty(
+            ctx.accounts.protocol_state.expiry_bounty_lamports,
+            ctx.accounts.bounty_vault.lamports(),
+            Rent::get()?.minimum_balance(0),
+            ctx.accounts.keeper.key() == intent_account.authority,
+        );
+        
+        if bounty_lamports > 0 {
+            let seeds = &[b"bounty_vault".as_ref(), &[ctx.bumps.bounty_vault]];
+            let signer = &[&seeds[..]];
+            let cpi_ctx = CpiContext::new_with_signer(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.bounty_vault.to_account_info(),
+                    to: ctx.accounts.keeper.to_account_info(),
+                },
+                signer,
+            );
+            system_program::transfer(cpi_ctx, bounty_lamports)?;
+        }
+        
+        emit!(IntentExpired {
+            intent_id: intent_account.key(),
+            authority: intent_account.authority,
+            keeper: ctx.accounts.keeper.key(),
+            bounty_lamports,
+        });
+        
+        msg!("⌛ Intent expired: {} (keeper bounty: {} lamports)", intent_account.key(), bounty_lamports);
+        Ok(())
+    }
+
+    /// Expire an intent left pending long past its expiry and sweep its keeper tip to the
+    /// treasury. Permissionless; before the grace period ends, expire_intent refunds the owner.
+    pub fn sweep_expired_tip(ctx: Context<SweepExpiredTip>) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        let user_account = &mut ctx.accounts.user_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(!intent_account.is_executing, IntentError::Reentrancy);
+        require!(
+            Clock::get()?.unix_timestamp >= intent_account.expires_at.saturating_add(TIP_SWEEP_GRACE_PERIOD),
+            IntentError::TipSweepTooEarly
+        );
+        
+        intent_account.status = IntentStatus::Expired;
+        emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Expired)?;
+        user_account.active_intents -= 1;
+        
+        if intent_account.intent_type == IntentType::Lend {
+            release_lending_cap(&ctx.accounts.lending_cap, intent_account)?;
+        }
+        
+        let tip_lamports = intent_account.keeper_tip_lamports;
+        release_keeper_tip(
+            &intent_account.to_account_info(),
+            &ctx.accounts.treasury.to_account_info(),
+            tip_lamports,
+        )?;
+        
+        emit!(TipSwept {
+            intent_id: intent_account.key(),
+            authority: intent_account.authority,
+            treasury: ctx.accounts.treasury.key(),
+            tip_lamports,
+        });
+        
+        msg!("🧹 Swept {} lamport tip from stale intent {} to treasury", tip_lamports, intent_account.key());
+        Ok(())
+    }
+
+    /// Warn that a pending intent is about to go stale. Permissionless and read-only:
+    /// emits IntentExpiringSoon inside the protocol's warning window, and nothing otherwise.
+    pub fn emit_expiring_soon(ctx: Context<EmitExpiringSoon>) -> Result<()> {
+        let intent_account = &ctx.accounts.intent_account;
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        
+        // Same staleness point as expire_intent, a lapsed price check deadline comes first
+        let expires_at = intent_account.price_check_deadline
+            .map_or(intent_account.expires_at, |deadline| deadline.min(intent_account.expires_at));
+        let remaining_seconds = expires_at.saturating_sub(Clock::get()?.unix_timestamp);
+        
+        // Already-stale intents are for expire_intent
+        if remaining_seconds <= 0 || remaining_seconds > ctx.accounts.protocol_state.expiry_warning_window {
+            return Ok(());
+        }
+        
+        emit!(IntentExpiringSoon {
+            intent_id: intent_account.key(),
+            authority: intent_account.authority,
+            client_order_id: intent_account.client_order_id,
+            expires_at,
+            remaining_seconds,
+            // What a keeper will earn for expiring it once it lapses
+            bounty_lamports: ctx.accounts.protocol_state.expiry_bounty_lamports,
+        });
+        
+        msg!("⏰ Intent {} expires in {}s", intent_account.key(), remaining_seconds);
+        Ok(())
+    }
+
+    /// Cancel every pending intent passed in remaining accounts
+    ///
+    /// Remaining accounts are the user's intent PDAs, followed by the lending cap PDA
+    /// of each lend intent that reserved against one. Intents that are no longer pending are skipped.
+    /// Works while paused, same as cancel_intent.
+    pub fn cancel_all_intents<'info>(
+        ctx: Context<'_, '_, 'info, 'info, CancelAllIntents<'info>>,
+    ) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let user_account = &mut ctx.accounts.user_account;
+        let now = Clock::get()?.unix_timestamp;
+        
+        let mut intents_seen: usize = 0;
+        let mut cancelled_count: u32 = 0;
+        
+        for info in ctx.remaining_accounts.iter() {
+            // Anything that isn't an intent is a lending cap, consumed below
+            let Ok(mut intent_account) = Account::<IntentAccount>::try_from(info) else {
+                continue;
+            };
+            
+            intents_seen += 1;
+            require!(intents_seen <= MAX_BULK_CANCELS, IntentError::TooManyBulkCancels);
+            require!(intent_account.authority == authority, IntentError::Unauthorized);
+            
+            if intent_account.status != IntentStatus::Pending {
+                continue;
+            }
+            
+            if intent_account.lending_cap_reserved {
+                let (lending_cap_key, _) = Pubkey::find_program_address(
+                    &[b"lending_cap", intent_account.from_mint.as_ref()],
+                    ctx.program_id,
+                );
+                let lending_cap = ctx.remaining_accounts
+                    .iter()
+                    .find(|account| account.key() == lending_cap_key)
+                    .ok_or(IntentError::LendingCapAccountMissing)?;
+                release_lending_cap(lending_cap, &mut intent_account)?;
+            }
+            
+            intent_account.status = IntentStatus::Cancelled;
+            emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Cancelled)?;
+            intent_account.cancelled_at = Some(now);
+            intent_account.exit(ctx.program_id)?;
+            
+            release_keeper_tip(
+                &intent_account.to_account_info(),
+                &ctx.accounts.authority.to_account_info(),
+                intent_account.keeper_tip_lamports,
+            )?;
+            
+            user_account.active_intents -= 1;
+            cancelled_count += 1;
+        }
+        
+        emit!(BulkCancelled {
+            user: authority,
+            cancelled_count,
+        });
+        
+        msg!("❌ {} intents cancelled for {}", cancelled_count, authority);
+        Ok(())
+    }
+
+    /// Move a pending intent to another user's wallet
+    pub fn transfer_intent(ctx: Context<TransferIntent>) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        let old_user_account = &mut ctx.accounts.old_user_account;
+        let new_user_account = &mut ctx.accounts.new_user_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(intent_account.authority == ctx.accounts.authority.key(), IntentError::Unauthorized);
+        require!(
+            new_user_account.authority != old_user_account.authority,
+            IntentError::InvalidIntentTransfer
+        );
+        require!(new_user_account.active_intents < MAX_INTENTS_PER_USER, IntentError::TooManyActiveIntents);
+        
+        let old_authority = intent_account.authority;
+        intent_account.authority = new_user_account.authority;
+        
+        old_user_account.active_intents -= 1;
+        new_user_account.active_intents += 1;
+        
+        emit!(IntentTransferred {
+            intent_id: intent_account.key(),
+            old_authority,
+            new_authority: new_user_account.authority,
+        });
+        
+        msg!("🔁 Intent {} transferred: {} → {}", intent_account.key(), old_authority, new_user_account.authority);
+        Ok(())
+    }
+
+    /// Move a pending conditional intent's trigger price without recreating it.
+    /// Buy intents fill once the price is at or below the target, so any positive
+    /// target is valid; the new trigger gets a fresh set of execution attempts.
+    pub fn update_target_price(ctx: Context<UpdateTargetPrice>, new_target: u64) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(!intent_account.is_executing, IntentError::Reentrancy);
+        let old_target = match (&intent_account.intent_type, intent_account.target_price) {
+            (IntentType::Buy, Some(target_price)) => target_price,
+            _ => return err!(IntentError::NotConditionalIntent),
+        };
+        require!(new_target > 0 && new_target != old_target, IntentError::InvalidTargetPrice);
+        
+        intent_account.target_price = Some(new_target);
+        intent_account.execution_attempts = 0;
+        
+        emit!(TargetPriceUpdated {
+            intent_id: intent_account.key(),
+            client_order_id: intent_account.client_order_id,
+            old_target,
+            new_target,
+        });
+        
+        msg!("🎯 Target price for {} moved: {} → {}", intent_account.key(), old_target, new_target);
+        Ok(())
+    }
+
+    /// Restrict (or reopen) who may execute a pending intent
+    pub fn set_allowed_executor(ctx: Context<SetAllowedExecutor>, allowed_executor: Option<Pubkey>) -> Result<()> {
+        let intent_account = &mut ctx.accounts.intent_account;
+        
+        require!(intent_account.status == IntentStatus::Pending, IntentError::IntentNotPending);
+        require!(!intent_account.is_executing, IntentError::Reentrancy);
+        
+        intent_account.allowed_executor = allowed_executor;
+        
+        emit!(AllowedExecutorUpdated {
+            intent_id: intent_account.key(),
+            client_order_id: intent_account.client_order_id,
+            allowed_executor,
+        });
+        
+        match allowed_executor {
+            Some(executor) => msg!("🔐 Intent {} executable only by {} or its owner", intent_account.key(), executor),
+            None => msg!("🔓 Intent {} executable by any executor", intent_account.key()),
+        }
+        Ok(())
+    }
+
+    /// Emergency pause protocol (admin only)
+    pub fn pause_protocol(ctx: Context<PauseProtocol>) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.is_paused = true;
+        msg!("⏸️ Protocol paused by admin");
+        Ok(())
+    }
+
+    /// Unpause protocol (admin only)
+    pub fn unpause_protocol(ctx: Context<UnpauseProtocol>) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.is_paused = false;
         msg!("▶️ Protocol unpaused by admin");
         Ok(())
     }
-}
-
-// Account Structs
-#[account]
-pub struct ProtocolState {
-    pub authority: Pubkey,
-    pub treasury_authority: Pubkey,
-    pub protocol_fee_bps: u16,
-    pub total_fees_collected: u64,
-    pub total_intents_created: u64,
-    pub total_intents_executed: u64,
-    pub is_paused: bool,
-    pub bump: u8,
-}
-
-#[account]
-pub struct UserAccount {
-    pub authority: Pubkey,
-    pub active_intents: u8,
-    pub total_intents_created: u64,
-    pub total_volume: u64,
-    pub rugproof_enabled: bool,
-    pub bump: u8,
-}
-
-#[account]
-pub struct IntentAccount {
-    pub authority: Pubkey,
-    pub intent_type: IntentType,
-    pub status: IntentStatus,
-    pub from_mint: Pubkey,
-    pub to_mint: Pubkey,
-    pub amount: u64,
-    pub protocol_fee: u64,
-    pub max_slippage: u16,
-    pub min_apy: Option<u16>,
-    pub target_price: Option<u64>,
-    pub max_price_impact: Option<u16>,
-    pub execution_price: Option<u64>,
-    pub execution_apy: Option<u16>,
-    pub rugproof_enabled: bool,
-    pub selected_swap_protocol: SwapProtocol, // For swap intents
-    pub selected_lending_protocol: Option<LendingProtocol>, // For lending intents
-    pub created_at: i64,
-    pub expires_at: i64,
-    pub executed_at: Option<i64>,
-    pub cancelled_at: Option<i64>,
-    pub bump: u8,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum IntentType {
-    Swap,
-    Lend,
-    Buy,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
-pub enum IntentStatus {
-    Pending,
-    Executed,
-    Cancelled,
-    Expired,
-}
-
-// Parameter Structs
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct SwapIntentParams {
-    pub from_mint: Pubkey,
-    pub to_mint: Pubkey,
-    pub amount: u64,
-    pub max_slippage: u16,
-    pub rugproof_enabled: bool,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct LendIntentParams {
-    pub mint: Pubkey,
-    pub amount: u64,
-    pub min_apy: u16,
-}
-
-#[derive(AnchorSerialize, AnchorDeserialize)]
-pub struct BuyIntentParams {
-    pub mint: Pubkey,
-    pub usdc_mint: Pubkey,
-    pub usdc_amount: u64,
-    pub target_price: Option<u64>,
-    pub max_price_impact: u16,
-    pub rugproof_check: bool,
-}
-
-// Context Structs
-#[derive(Accounts)]
-pub struct InitializeProtocol<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+
+    /// Update the minimum pool reserves required for direct AMM swaps (admin only)
+    pub fn set_min_pool_liquidity(
+        ctx: Context<UpdateProtocolConfig>,
+        min_pool_liquidity: u64,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.min_pool_liquidity = min_pool_liquidity;
+        msg!("🌊 Minimum pool liquidity set to {}", min_pool_liquidity);
+        Ok(())
+    }
+
+    /// Update the absolute protocol fee floor (admin only)
+    pub fn set_min_fee_tokens(
+        ctx: Context<UpdateProtocolConfig>,
+        min_fee_tokens: u64,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.min_fee_tokens = min_fee_tokens;
+        msg!("💰 Protocol fee floor set to {} tokens", min_fee_tokens);
+        Ok(())
+    }
+
+    /// Update the keeper bounty paid for expiring stale intents (admin only)
+    pub fn set_expiry_bounty(
+        ctx: Context<UpdateProtocolConfig>,
+        expiry_bounty_lamports: u64,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.expiry_bounty_lamports = expiry_bounty_lamports;
+        msg!("⌛ Expiry bounty set to {} lamports", expiry_bounty_lamports);
+        Ok(())
+    }
+
+    /// Top up the vault that pays keeper bounties for expiring stale intents
+    pub fn fund_bounty_vault(ctx: Context<FundBountyVault>, lamports: u64) -> Result<()> {
+        require!(lamports > 0, IntentError::InvalidAmount);
+        
+        let cpi_ctx = CpiContext::new(
+            ctx.accounts.system_program.to_account_info(),
+            system_program::Transfer {
+                from: ctx.accounts.funder.to_account_info(),
+                to: ctx.accounts.bounty_vault.to_account_info(),
+            },
+        );
+        system_program::transfer(cpi_ctx, lamports)?;
+        
+        emit!(BountyVaultFunded {
+            funder: ctx.accounts.funder.key(),
+            lamports,
+            vault_balance: ctx.accounts.bounty_vault.lamports(),
+        });
+        
+        msg!("⌛ Bounty vault funded with {} lamports", lamports);
+        Ok(())
+    }
+
+    /// Update the highest min APY a lend intent may request (admin only)
+    pub fn set_max_realistic_apy(
+        ctx: Context<UpdateProtocolConfig>,
+        max_realistic_apy: u16,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(max_realistic_apy > 0 && max_realistic_apy <= 10000, IntentError::InvalidAPY);
+        
+        protocol_state.max_realistic_apy = max_realistic_apy;
+        msg!("📈 Max realistic APY set to {} bps", max_realistic_apy);
+        Ok(())
+    }
+
+    /// Update the APY gain required before a lend intent switches protocols (admin only)
+    pub fn set_min_apy_spread(
+        ctx: Context<UpdateProtocolConfig>,
+        min_apy_spread_bps: u16,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(min_apy_spread_bps <= 10000, IntentError::InvalidAPY);
+        
+        protocol_state.min_apy_spread_bps = min_apy_spread_bps;
+        msg!("↔️ Min APY spread for reselection set to {} bps", min_apy_spread_bps);
+        Ok(())
+    }
+
+    /// Enable or disable flash-loan-funded swap execution (admin only)
+    pub fn set_flash_loans_enabled(
+        ctx: Context<UpdateProtocolConfig>,
+        enabled: bool,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        protocol_state.flash_loans_enabled = enabled;
+        msg!("⚡ Flash loan swaps {}", if enabled { "enabled" } else { "disabled" });
+        Ok(())
+    }
+
+    /// Update how long before expiry intents can be flagged as expiring soon (admin only)
+    pub fn set_expiry_warning_window(
+        ctx: Context<UpdateProtocolConfig>,
+        expiry_warning_window: i64,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(
+            expiry_warning_window > 0 && expiry_warning_window <= INTENT_EXPIRY_SECONDS,
+            IntentError::InvalidExpiryWarningWindow
+        );
+        
+        protocol_state.expiry_warning_window = expiry_warning_window;
+        msg!("⏰ Expiry warning window set to {}s", expiry_warning_window);
+        Ok(())
+    }
+
+    /// Update how many unmet-condition executions an intent gets before failing (admin only)
+    pub fn set_max_execution_attempts(
+        ctx: Context<UpdateProtocolConfig>,
+        max_execution_attempts: u8,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(max_execution_attempts > 0, IntentError::InvalidAmount);
+        
+        protocol_state.max_execution_attempts = max_execution_attempts;
+        msg!("🔁 Max execution attempts set to {}", max_execution_attempts);
+        Ok(())
+    }
+
+    /// Set the protocol fee charged on one intent type (admin only)
+    pub fn set_type_fee(
+        ctx: Context<UpdateProtocolConfig>,
+        intent_type: IntentType,
+        fee_bps: u16,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(fee_bps <= MAX_TYPE_FEE_BPS, IntentError::InvalidTypeFee);
+        
+        let old_fee_bps = type_fee_bps(protocol_state, &intent_type);
+        protocol_state.fee_bps_by_type[intent_type.clone() as usize] = fee_bps;
+        
+        emit!(TypeFeeUpdated {
+            intent_type: intent_type.clone(),
+            old_fee_bps,
+            new_fee_bps: fee_bps,
+        });
+        
+        msg!("💸 {:?} fee set to {} bps", intent_type, fee_bps);
+        Ok(())
+    }
+
+    /// Emit derived protocol metrics for dashboards
+    pub fn emit_protocol_metrics(ctx: Context<EmitProtocolMetrics>) -> Result<()> {
+        let protocol_state = &ctx.accounts.protocol_state;
+        
+        // Rates are zero until the first intent is created / executed
+        let execution_rate_bps = (protocol_state.total_intents_executed as u128)
+            .checked_mul(10000)
+            .unwrap()
+            .checked_div(protocol_state.total_intents_created as u128)
+            .unwrap_or(0) as u16;
+        let average_fee_per_intent = protocol_state.total_fees_collected
+            .checked_div(protocol_state.total_intents_executed)
+            .unwrap_or(0);
+        let unexecuted_intents = protocol_state.total_intents_created
+            .saturating_sub(protocol_state.total_intents_executed);
+        
+        emit!(ProtocolMetrics {
+            total_intents_created: protocol_state.total_intents_created,
+            total_intents_executed: protocol_state.total_intents_executed,
+            unexecuted_intents,
+            execution_rate_bps,
+            total_fees_collected: protocol_state.total_fees_collected,
+            average_fee_per_intent,
+            is_paused: protocol_state.is_paused,
+        });
+        
+        msg!(
+            "📊 Protocol metrics: {}/{} executed ({} bps), avg fee {}",
+            protocol_state.total_intents_executed,
+            protocol_state.total_intents_created,
+            execution_rate_bps,
+            average_fee_per_intent
+        );
+        Ok(())
+    }
+
+    /// Preflight an intent without creating it: emits the protocol that would be chosen,
+    /// the fee, the expected output or APY, the rugproof score and whether creation
+    /// would pass validation. Swap and buy output estimates need the pair's pool vaults
+    /// in remaining accounts. Never mutates state.
+    pub fn simulate_intent(ctx: Context<SimulateIntent>, params: SimulateIntentParams) -> Result<()> {
+        let protocol_state = &ctx.accounts.protocol_state;
+        // Without a user account (first intent) there is always capacity
+        let has_capacity = !matches!(
+            ctx.accounts.user_account.as_ref(),
+            Some(user_account) if user_account.active_intents >= MAX_INTENTS_PER_USER
+        );
+        let min_rugproof_score = ctx.accounts.user_account
+            .as_ref()
+            .map_or(MIN_RUGPROOF_SCORE, |user_account| user_account.rugproof_min_score.max(MIN_RUGPROOF_SCORE));
+        let default_slippage_bps = ctx.accounts.user_account
+            .as_ref()
+            .map_or(DEFAULT_SLIPPAGE_BPS, |user_account| user_account.default_slippage_bps);
+        
+        let mut would_pass = has_capacity && !protocol_state.is_paused;
+        let mut swap_protocol = None;
+        let mut lending_protocol = None;
+        let mut estimated_output = None;
+        let mut apy = None;
+        let mut rugproof_score = None;
+        
+        let (intent_type, amount) = match &params {
+            SimulateIntentParams::Swap(swap) => {
+                let max_slippage = swap.max_slippage.unwrap_or(default_slippage_bps);
+                would_pass &= max_slippage <= MAX_SLIPPAGE_BPS;
+                would_pass &= !ProtocolRouter::is_stable_pair(&swap.from_mint, &swap.to_mint)
+                    || max_slippage <= MAX_STABLE_PAIR_SLIPPAGE_BPS;
+                if swap.has_liquidity {
+                    would_pass &= find_pool_reserves(
+                        ctx.remaining_accounts,
+                        &swap.from_mint,
+                        &swap.to_mint,
+                        protocol_state.min_pool_liquidity,
+                    ).is_some();
+                }
+                if swap.auto_lend {
+                    would_pass &= swap.min_apy.unwrap_or(0) <= protocol_state.max_realistic_apy.min(10000);
+                }
+                let protocol = if swap.auto_lend {
+                    SwapProtocol::Jupiter
+                } else {
+                    ProtocolRouter::choose_best_protocol(&swap.from_mint, &swap.to_mint, swap.amount)
+                };
+                swap_protocol = Some(protocol);
+                if swap.rugproof_enabled {
+                    let score = perform_rugproof_check(&swap.to_mint)?;
+                    would_pass &= score >= min_rugproof_score;
+                    rugproof_score = Some(score);
+                }
+                (IntentType::Swap, swap.amount)
+            }
+            SimulateIntentParams::Lend(lend) => {
+                would_pass &= lend.min_apy > 0 && lend.min_apy <= protocol_state.max_realistic_apy.min(10000);
+                let (protocol, best_apy) = LendingRouter::get_best_apy_for_token(&lend.mint)?;
+                would_pass &= best_apy >= lend.min_apy;
+                lending_protocol = Some(protocol);
+                apy = Some(best_apy);
+                (IntentType::Lend, lend.amount)
+            }
+            SimulateIntentParams::Buy(buy) => {
+                swap_protocol = Some(SwapProtocol::Jupiter);
+                if buy.rugproof_check {
+                    let score = perform_rugproof_check(&buy.mint)?;
+                    would_pass &= score >= min_rugproof_score;
+                    rugproof_score = Some(score);
+                }
+                (IntentType::Buy, buy.usdc_amount)
+            }
+        };
+        
+        // A fee at or above the amount is rejected at creation
+        let fee_bps = type_fee_bps(protocol_state, &intent_type);
+        let protocol_fee = match calculate_protocol_fee(amount, fee_bps, protocol_state.min_fee_tokens) {
+            Ok(fee) => fee,
+            Err(_) => {
+                would_pass = false;
+                0
+            }
+        };
+        would_pass &= amount > 0;
+        
+        // Constant-product estimate against the passed pool, after the protocol fee
+        let pair = match &params {
+            SimulateIntentParams::Swap(swap) => Some((swap.from_mint, swap.to_mint)),
+            SimulateIntentParams::Buy(buy) => Some((buy.usdc_mint, buy.mint)),
+            SimulateIntentParams::Lend(_) => None,
+        };
+        if let Some((from_mint, to_mint)) = pair {
+            if let Some((reserve_in, reserve_out)) = find_pool_reserves(ctx.remaining_accounts, &from_mint, &to_mint, 0) {
+                estimated_output = Some(raydium::calculate_raydium_output(
+                    amount.saturating_sub(protocol_fee),
+                    reserve_in,
+                    reserve_out,
+                    25,    // Raydium fee: 0.25%
+                    10000,
+                )?);
+            }
+        }
+        
+        emit!(SimulationResult {
+            intent_type,
+            swap_protocol,
+            lending_protocol,
+            protocol_fee,
+            estimated_output,
+            apy,
+            rugproof_score,
+            would_pass,
+        });
+        
+        msg!("🧪 Simulated intent: fee {}, would pass: {}", protocol_fee, would_pass);
+        Ok(())
+    }
+
+    /// Emit how many more intents a user can open before hitting the active-intent limit
+    pub fn view_capacity(ctx: Context<ViewCapacity>) -> Result<()> {
+        let user_account = &ctx.accounts.user_account;
+        let remaining = MAX_INTENTS_PER_USER.saturating_sub(user_account.active_intents);
+        
+        emit!(UserCapacity {
+            user: user_account.authority,
+            active_intents: user_account.active_intents,
+            max_intents: MAX_INTENTS_PER_USER,
+            remaining,
+        });
+        
+        msg!("📦 Intent capacity: {}/{} active, {} remaining", user_account.active_intents, MAX_INTENTS_PER_USER, remaining);
+        Ok(())
+    }
+
+    /// Exempt an account's intents from protocol fees (admin only)
+    pub fn add_fee_exempt(
+        ctx: Context<UpdateProtocolConfig>,
+        account: Pubkey,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        require!(!protocol_state.fee_exempt.contains(&account), IntentError::AlreadyFeeExempt);
+        require!(protocol_state.fee_exempt.len() < MAX_FEE_EXEMPT, IntentError::TooManyFeeExempt);
+        
+        protocol_state.fee_exempt.push(account);
+        
+        emit!(FeeExemptionUpdated {
+            account,
+            exempt: true,
+        });
+        
+        msg!("🎟️ Fee exemption granted to {}", account);
+        Ok(())
+    }
+
+    /// Register or replace a protocol's lending market for a token (admin only)
+    pub fn register_lending_market(
+        ctx: Context<RegisterLendingMarket>,
+        market: LendingMarket,
+    ) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let registry = &mut ctx.accounts.lending_market_registry;
+        registry.bump = ctx.bumps.lending_market_registry;
+        
+        // One market per (protocol, token), re-registering updates the reserve and market
+        match registry
+            .markets
+            .iter_mut()
+            .find(|existing| existing.protocol == market.protocol && existing.mint == market.mint)
+        {
+            Some(existing) => *existing = market.clone(),
+            None => {
+                require!(registry.markets.len() < MAX_LENDING_MARKETS, IntentError::TooManyLendingMarkets);
+                registry.markets.push(market.clone());
+            }
+        }
+        
+        emit!(LendingMarketRegistered {
+            protocol: market.protocol.clone(),
+            mint: market.mint,
+            reserve: market.reserve,
+            market: market.market,
+        });
+        
+        msg!("🏦 Registered {:?} market for {}: reserve {}", market.protocol, market.mint, market.reserve);
+        Ok(())
+    }
+
+    /// Register or replace the oracle that prices a mint in rebalances (admin only)
+    pub fn set_price_feed(ctx: Context<SetPriceFeed>, price_source: PriceSource) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        // Refuse accounts that don't parse as a live price for the chosen source
+        oracle::read_price_checked(&price_source, &ctx.accounts.oracle, Clock::get()?.unix_timestamp)?;
+        
+        let price_feed = &mut ctx.accounts.price_feed;
+        price_feed.mint = ctx.accounts.mint.key();
+        price_feed.price_source = price_source.clone();
+        price_feed.oracle = ctx.accounts.oracle.key();
+        price_feed.bump = ctx.bumps.price_feed;
+        
+        emit!(PriceFeedSet {
+            mint: price_feed.mint,
+            price_source,
+            oracle: price_feed.oracle,
+        });
+        
+        msg!("🔮 Price feed for {} set to {:?} oracle {}", price_feed.mint, price_feed.price_source, price_feed.oracle);
+        Ok(())
+    }
+
+    /// Remove an account's protocol fee exemption (admin only)
+    pub fn remove_fee_exempt(
+        ctx: Context<UpdateProtocolConfig>,
+        account: Pubkey,
+    ) -> Result<()> {
+        let protocol_state = &mut ctx.accounts.protocol_state;
+        require_authority(protocol_state, &ctx.accounts.authority.key())?;
+        
+        let index = protocol_state.fee_exempt
+            .iter()
+            .position(|exempt| *exempt == account)
+            .ok_or(IntentError::NotFeeExempt)?;
+        protocol_state.fee_exempt.remove(index);
+        
+        emit!(FeeExemptionUpdated {
+            account,
+            exempt: false,
+        });
+        
+        msg!("🎟️ Fee exemption revoked for {}", account);
+        Ok(())
+    }
+
+    /// Initialize the protocol fee split between beneficiaries (admin only)
+    pub fn initialize_fee_split(
+        ctx: Context<InitializeFeeSplit>,
+        beneficiaries: Vec<FeeBeneficiary>,
+    ) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        validate_fee_split(&beneficiaries)?;
+        
+        let fee_split_config = &mut ctx.accounts.fee_split_config;
+        fee_split_config.authority = ctx.accounts.authority.key();
+        fee_split_config.beneficiaries = beneficiaries;
+        fee_split_config.bump = ctx.bumps.fee_split_config;
+        
+        msg!("💸 Fee split configured across {} beneficiaries", fee_split_config.beneficiaries.len());
+        Ok(())
+    }
+
+    /// Update the protocol fee split beneficiaries (admin only)
+    pub fn update_fee_split(
+        ctx: Context<UpdateFeeSplit>,
+        beneficiaries: Vec<FeeBeneficiary>,
+    ) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        validate_fee_split(&beneficiaries)?;
+        
+        let fee_split_config = &mut ctx.accounts.fee_split_config;
+        fee_split_config.beneficiaries = beneficiaries;
+        
+        msg!("💸 Fee split updated across {} beneficiaries", fee_split_config.beneficiaries.len());
+        Ok(())
+    }
+
+    /// Initialize a deposit cap for lending intents on a mint (admin only)
+    pub fn initialize_lending_cap(ctx: Context<InitializeLendingCap>, max_total: u64) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let lending_cap = &mut ctx.accounts.lending_cap;
+        lending_cap.mint = ctx.accounts.mint.key();
+        lending_cap.max_total = max_total;
+        lending_cap.current_total = 0;
+        lending_cap.bump = ctx.bumps.lending_cap;
+        
+        msg!("🧢 Lending cap for {} set to {}", lending_cap.mint, max_total);
+        Ok(())
+    }
+
+    /// Update the deposit cap for lending intents on a mint (admin only)
+    pub fn update_lending_cap(ctx: Context<UpdateLendingCap>, max_total: u64) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let lending_cap = &mut ctx.accounts.lending_cap;
+        lending_cap.max_total = max_total;
+        
+        msg!("🧢 Lending cap for {} updated to {} ({} reserved)", lending_cap.mint, max_total, lending_cap.current_total);
+        Ok(())
+    }
+
+    /// Create a price-deviation circuit breaker for a swap pair (admin only)
+    pub fn initialize_circuit_breaker(ctx: Context<InitializeCircuitBreaker>, max_deviation_bps: u16) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        require!(max_deviation_bps > 0 && max_deviation_bps <= 10000, IntentError::InvalidDeviationThreshold);
+        
+        let circuit_breaker = &mut ctx.accounts.circuit_breaker;
+        circuit_breaker.from_mint = ctx.accounts.from_mint.key();
+        circuit_breaker.to_mint = ctx.accounts.to_mint.key();
+        circuit_breaker.max_deviation_bps = max_deviation_bps;
+        circuit_breaker.last_price = 0;
+        circuit_breaker.tripped = false;
+        circuit_breaker.bump = ctx.bumps.circuit_breaker;
+        
+        msg!("🚨 Circuit breaker for {} → {} set to {} bps", circuit_breaker.from_mint, circuit_breaker.to_mint, max_deviation_bps);
+        Ok(())
+    }
+
+    /// Update a pair's deviation threshold, clear its reference price and reopen it if
+    /// tripped (admin only). The next execution on the pair records a fresh reference.
+    pub fn update_circuit_breaker(ctx: Context<UpdateCircuitBreaker>, max_deviation_bps: u16) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        require!(max_deviation_bps > 0 && max_deviation_bps <= 10000, IntentError::InvalidDeviationThreshold);
+        
+        let circuit_breaker = &mut ctx.accounts.circuit_breaker;
+        circuit_breaker.max_deviation_bps = max_deviation_bps;
+        circuit_breaker.last_price = 0;
+        circuit_breaker.tripped = false;
+        
+        msg!("🚨 Circuit breaker for {} → {} updated to {} bps", circuit_breaker.from_mint, circuit_breaker.to_mint, max_deviation_bps);
+        Ok(())
+    }
+
+    /// Recompute a token's rugproof score from its mint, metadata and optional pool and
+    /// cache it for the create paths. Anyone can call it, it only refreshes data.
+    pub fn refresh_rugproof_score(ctx: Context<RefreshRugproofScore>) -> Result<()> {
+        let mint_key = ctx.accounts.mint.key();
+        let score = rugproof::score_token(
+            &mint_key,
+            &ctx.accounts.mint,
+            &ctx.accounts.metadata,
+            ctx.accounts.liquidity_pool.as_deref(),
+        )?;
+        
+        let now = Clock::get()?.unix_timestamp;
+        let rugproof_cache = &mut ctx.accounts.rugproof_cache;
+        // A zero timestamp means the cache was just created
+        let old_score = (rugproof_cache.updated_at != 0).then_some(rugproof_cache.score);
+        rugproof_cache.mint = mint_key;
+        rugproof_cache.score = score;
+        rugproof_cache.updated_at = now;
+        rugproof_cache.bump = ctx.bumps.rugproof_cache;
+        
+        emit!(RugproofScoreRefreshed {
+            mint: mint_key,
+            old_score,
+            score,
+            updated_at: now,
+            refreshed_by: ctx.accounts.payer.key(),
+        });
+        
+        msg!("🛡️ Rugproof score for {} refreshed: {}", mint_key, score);
+        Ok(())
+    }
+
+    /// Start tracking the fees paid into one mint's treasury fee account, from its
+    /// current balance (admin only)
+    pub fn initialize_fee_ledger(ctx: Context<InitializeFeeLedger>) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let fee_ledger = &mut ctx.accounts.fee_ledger;
+        fee_ledger.mint = ctx.accounts.treasury_fee_account.mint;
+        fee_ledger.opening_balance = ctx.accounts.treasury_fee_account.amount;
+        fee_ledger.fees_collected = 0;
+        fee_ledger.bump = ctx.bumps.fee_ledger;
+        
+        msg!("🧾 Fee ledger for {} opened at {}", fee_ledger.mint, fee_ledger.opening_balance);
+        Ok(())
+    }
+
+    /// Compare a treasury fee account's balance with what its mint's fee ledger recorded
+    /// and report any drift (admin only). Never corrects the ledger, so accounting bugs
+    /// stay visible.
+    pub fn reconcile_treasury(ctx: Context<ReconcileTreasury>) -> Result<()> {
+        require_authority(&ctx.accounts.protocol_state, &ctx.accounts.authority.key())?;
+        
+        let fee_ledger = &ctx.accounts.fee_ledger;
+        let tracked_fees = fee_ledger.opening_balance.saturating_add(fee_ledger.fees_collected);
+        let treasury_balance = ctx.accounts.treasury_fee_account.amount;
+        let discrepancy = fee_ledger.discrepancy(treasury_balance);
+        
+        emit!(TreasuryReconciliation {
+            treasury_fee_account: ctx.accounts.treasury_fee_account.key(),
+            mint: ctx.accounts.treasury_fee_account.mint,
+            tracked_fees,
+            treasury_balance,
+            discrepancy,
+            timestamp: Clock::get()?.unix_timestamp,
+        });
+        
+        if discrepancy == 0 {
+            msg!("🧾 Treasury reconciled: {} tracked, {} held", tracked_fees, treasury_balance);
+        } else {
+            msg!("⚠️ Treasury drift of {}: {} tracked, {} held", discrepancy, tracked_fees, treasury_balance);
+        }
+        Ok(())
+    }
+
+    /// Rewrite a protocol state created before the current layout, filling the newer
+    /// settings with initialize_protocol's defaults (admin only)
+    pub fn migrate_protocol_state(ctx: Context<MigrateProtocolState>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        migrate_account(
+            &ctx.accounts.protocol_state,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            |old: ProtocolStateV0| {
+                require!(old.authority == authority, IntentError::Unauthorized);
+                Ok(ProtocolState {
+                    authority: old.authority,
+                    treasury_authority: old.treasury_authority,
+                    protocol_fee_bps: old.protocol_fee_bps,
+                    total_fees_collected: old.total_fees_collected,
+                    total_intents_created: old.total_intents_created,
+                    total_intents_executed: old.total_intents_executed,
+                    is_paused: old.is_paused,
+                    min_pool_liquidity: DEFAULT_MIN_POOL_LIQUIDITY,
+                    min_fee_tokens: 0,
+                    expiry_bounty_lamports: 0,
+                    fee_exempt: Vec::new(),
+                    max_realistic_apy: DEFAULT_MAX_REALISTIC_APY,
+                    expiry_warning_window: DEFAULT_EXPIRY_WARNING_WINDOW,
+                    max_execution_attempts: DEFAULT_MAX_EXECUTION_ATTEMPTS,
+                    fee_bps_by_type: [old.protocol_fee_bps; INTENT_TYPE_COUNT],
+                    min_apy_spread_bps: DEFAULT_MIN_APY_SPREAD_BPS,
+                    flash_loans_enabled: false,
+                    config_version: CONFIG_VERSION,
+                    bump: old.bump,
+                })
+            },
+        )?;
+        
+        msg!("🛠️ Protocol state migrated to config version {}", CONFIG_VERSION);
+        Ok(())
+    }
+
+    /// Rewrite the caller's user account from the original layout, with initialize_user's
+    /// defaults for the newer settings
+    pub fn migrate_user_account(ctx: Context<MigrateUserAccount>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        migrate_account(
+            &ctx.accounts.user_account,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            |old: UserAccountV0| {
+                require!(old.authority == authority, IntentError::Unauthorized);
+                Ok(UserAccount {
+                    authority: old.authority,
+                    active_intents: old.active_intents,
+                    total_intents_created: old.total_intents_created,
+                    total_volume: old.total_volume,
+                    rugproof_enabled: old.rugproof_enabled,
+                    delegate: None,
+                    rugproof_min_score: MIN_RUGPROOF_SCORE,
+                    default_slippage_bps: DEFAULT_SLIPPAGE_BPS,
+                    bump: old.bump,
+                })
+            },
+        )?;
+        
+        msg!("🛠️ User account migrated for: {}", authority);
+        Ok(())
+    }
+
+    /// Rewrite one of the caller's intents from the original layout so it can still be
+    /// executed, cancelled or closed. Newer options start unset, as if never requested
+    pub fn migrate_intent(ctx: Context<MigrateIntent>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        migrate_account(
+            &ctx.accounts.intent_account,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            |old: IntentAccountV0| {
+                require!(old.authority == authority, IntentError::Unauthorized);
+                Ok(IntentAccount {
+                    authority: old.authority,
+                    intent_type: old.intent_type,
+                    status: old.status,
+                    from_mint: old.from_mint,
+                    to_mint: old.to_mint,
+                    amount: old.amount,
+                    protocol_fee: old.protocol_fee,
+                    max_slippage: old.max_slippage,
+                    min_apy: old.min_apy,
+                    target_price: old.target_price,
+                    max_price_impact: old.max_price_impact,
+                    execution_price: old.execution_price,
+                    execution_apy: old.execution_apy,
+                    rugproof_enabled: old.rugproof_enabled,
+                    selected_swap_protocol: old.selected_swap_protocol,
+                    selected_lending_protocol: old.selected_lending_protocol,
+                    created_at: old.created_at,
+                    expires_at: old.expires_at,
+                    executed_at: old.executed_at,
+                    cancelled_at: old.cancelled_at,
+                    pool_id: None,
+                    secondary_amount: 0,
+                    lp_tokens: None,
+                    min_amount_a_out: 0,
+                    min_amount_b_out: 0,
+                    is_executing: false,
+                    price_check_deadline: None,
+                    not_before: None,
+                    client_order_id: None,
+                    keeper_tip_lamports: 0,
+                    cumulative_in: 0,
+                    cumulative_out: 0,
+                    price_source: PriceSource::Pyth,
+                    max_route_fee: None,
+                    exact_output: false,
+                    max_input: 0,
+                    execution_attempts: 0,
+                    fee_bps: PROTOCOL_FEE_BPS, // The only rate the original layout ever charged
+                    allowed_executor: None,
+                    min_fill_amount: 0,
+                    lending_cap_reserved: false,
+                    auto_lend: false,
+                    bump: old.bump,
+                })
+            },
+        )?;
+        
+        msg!("🛠️ Intent {} migrated", ctx.accounts.intent_account.key());
+        Ok(())
+    }
+}
+
+// Account Structs
+#[account]
+#[derive(InitSpace)]
+pub struct ProtocolState {
+    pub authority: Pubkey,
+    pub treasury_authority: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub total_fees_collected: u64,
+    pub total_intents_created: u64,
+    pub total_intents_executed: u64,
+    pub is_paused: bool, // Blocks creates and executes only; cancel and close stay open
+    pub min_pool_liquidity: u64,
+    pub min_fee_tokens: u64, // Absolute fee floor so dust intents still pay
+    pub expiry_bounty_lamports: u64, // Paid to keepers who expire stale intents
+    #[max_len(MAX_FEE_EXEMPT)]
+    pub fee_exempt: Vec<Pubkey>, // Up to MAX_FEE_EXEMPT accounts executing fee-free
+    pub max_realistic_apy: u16, // Lend intents asking for more could never execute
+    pub expiry_warning_window: i64, // Seconds before expiry that emit_expiring_soon fires
+    pub max_execution_attempts: u8, // Condition misses allowed before an intent is marked Failed
+    pub fee_bps_by_type: [u16; INTENT_TYPE_COUNT], // Indexed by IntentType
+    pub min_apy_spread_bps: u16, // APY gain required before reselecting a lending protocol
+    pub flash_loans_enabled: bool, // Gates execute_swap_with_flash_loan
+    pub config_version: u8, // CONFIG_VERSION the account was written with
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct UserAccount {
+    pub authority: Pubkey,
+    pub active_intents: u8,
+    pub total_intents_created: u64,
+    pub total_volume: u64,
+    pub rugproof_enabled: bool,
+    pub delegate: Option<Pubkey>, // Smart wallet / multisig allowed to create intents for this user
+    pub rugproof_min_score: u8, // Personal threshold, never looser than MIN_RUGPROOF_SCORE
+    pub default_slippage_bps: u16, // Used by swap intents created without a slippage
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct IntentAccount {
+    pub authority: Pubkey,
+    pub intent_type: IntentType,
+    pub status: IntentStatus,
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+    pub max_slippage: u16,
+    pub min_apy: Option<u16>,
+    pub target_price: Option<u64>, // USDC per token at oracle::ORACLE_PRICE_DECIMALS
+    pub max_price_impact: Option<u16>,
+    pub execution_price: Option<u64>,
+    pub execution_apy: Option<u16>,
+    pub rugproof_enabled: bool,
+    pub selected_swap_protocol: SwapProtocol, // For swap intents
+    pub selected_lending_protocol: Option<LendingProtocol>, // For lending intents
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub executed_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub pool_id: Option<Pubkey>, // For liquidity intents
+    pub secondary_amount: u64, // Side B amount for liquidity intents
+    pub lp_tokens: Option<u64>,
+    pub min_amount_a_out: u64, // Withdrawal floors for remove liquidity intents
+    pub min_amount_b_out: u64,
+    pub is_executing: bool, // Reentrancy lock held for the duration of an execute handler
+    pub price_check_deadline: Option<i64>, // Target price abandoned after this, never later than expires_at
+    pub not_before: Option<i64>, // Scheduled intents cannot execute before this
+    pub client_order_id: Option<[u8; 16]>, // Integrator-supplied id for off-chain correlation
+    pub keeper_tip_lamports: u64, // Prepaid into this PDA, paid to the executor or refunded
+    pub cumulative_in: u64, // Input swapped across all fills
+    pub cumulative_out: u64, // Output received across all fills
+    pub price_source: PriceSource, // Oracle used to check target_price
+    pub max_route_fee: Option<u64>, // Cap on AMM fees summed across Jupiter route steps
+    pub exact_output: bool, // amount is the output to receive rather than the input to spend
+    pub max_input: u64, // Input budget including fee for exact-output swaps
+    pub execution_attempts: u8, // Execute calls that found the intent's condition unmet
+    pub fee_bps: u16, // Rate the protocol fee was charged at
+    pub allowed_executor: Option<Pubkey>, // Only this keeper (or the owner) may execute, None allows any
+    pub min_fill_amount: u64, // Smallest partial fill accepted short of the final remainder
+    pub lending_cap_reserved: bool, // amount is held against the mint's LendingCap
+    pub auto_lend: bool, // Swap output is deposited into a registered reserve, floored by min_apy
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct RebalanceTarget {
+    pub mint: Pubkey,
+    pub target_bps: u16,
+    pub balance: u64, // Holding at creation, then after the last execution
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RebalanceWeights {
+    pub intent: Pubkey,
+    #[max_len(MAX_REBALANCE_ASSETS)]
+    pub targets: Vec<RebalanceTarget>, // Up to MAX_REBALANCE_ASSETS, bps sum to 10000
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeSplitConfig {
+    pub authority: Pubkey,
+    #[max_len(MAX_FEE_BENEFICIARIES)]
+    pub beneficiaries: Vec<FeeBeneficiary>, // Up to MAX_FEE_BENEFICIARIES, bps sum to 10000
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct FeeLedger {
+    pub mint: Pubkey,
+    pub opening_balance: u64, // Treasury fee account balance when tracking started
+    pub fees_collected: u64, // Fees paid into the treasury fee account since then
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LendingCap {
+    pub mint: Pubkey,
+    pub max_total: u64,
+    pub current_total: u64, // Amount reserved by pending and executed lend intents
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CircuitBreaker {
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub max_deviation_bps: u16,
+    pub last_price: u64, // Reference price scaled by PRICE_SCALE, 0 until first execution
+    pub tripped: bool, // Pair halted until an admin resets the breaker
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct PriceFeed {
+    pub mint: Pubkey,
+    pub price_source: PriceSource,
+    pub oracle: Pubkey, // Pyth price account or Switchboard aggregator for the mint
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct IntentHistory {
+    pub authority: Pubkey,
+    pub executed_count: u64,
+    pub cancelled_count: u64,
+    pub expired_count: u64,
+    pub failed_count: u64,
+    pub history_hash: [u8; 32], // Rolling hash over every closed intent's final state
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct RugproofCache {
+    pub mint: Pubkey,
+    pub score: u8,
+    pub updated_at: i64,
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct LendingMarketRegistry {
+    #[max_len(MAX_LENDING_MARKETS)]
+    pub markets: Vec<LendingMarket>, // Up to MAX_LENDING_MARKETS
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct LendingMarket {
+    pub protocol: LendingProtocol,
+    pub mint: Pubkey,
+    pub reserve: Pubkey,
+    pub market: Pubkey, // Solend lending market or Port staking pool
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct FeeBeneficiary {
+    pub token_account: Pubkey, // Fee token account receiving this share
+    pub bps: u16,
+}
+
+// Original account layouts, read only by the migrate_* instructions
+#[derive(AnchorDeserialize)]
+pub struct ProtocolStateV0 {
+    pub authority: Pubkey,
+    pub treasury_authority: Pubkey,
+    pub protocol_fee_bps: u16,
+    pub total_fees_collected: u64,
+    pub total_intents_created: u64,
+    pub total_intents_executed: u64,
+    pub is_paused: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorDeserialize)]
+pub struct UserAccountV0 {
+    pub authority: Pubkey,
+    pub active_intents: u8,
+    pub total_intents_created: u64,
+    pub total_volume: u64,
+    pub rugproof_enabled: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorDeserialize)]
+pub struct IntentAccountV0 {
+    pub authority: Pubkey,
+    pub intent_type: IntentType,
+    pub status: IntentStatus,
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount: u64,
+    pub protocol_fee: u64,
+    pub max_slippage: u16,
+    pub min_apy: Option<u16>,
+    pub target_price: Option<u64>,
+    pub max_price_impact: Option<u16>,
+    pub execution_price: Option<u64>,
+    pub execution_apy: Option<u16>,
+    pub rugproof_enabled: bool,
+    pub selected_swap_protocol: SwapProtocol,
+    pub selected_lending_protocol: Option<LendingProtocol>,
+    pub created_at: i64,
+    pub expires_at: i64,
+    pub executed_at: Option<i64>,
+    pub cancelled_at: Option<i64>,
+    pub bump: u8,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq, InitSpace)]
+pub enum IntentType {
+    Swap,
+    Lend,
+    Buy,
+    AddLiquidity,
+    RemoveLiquidity,
+    Repay,
+    Rebalance,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
+pub enum IntentStatus {
+    Pending,
+    Executed,
+    Cancelled,
+    Expired,
+    Failed, // Hit the protocol's max execution attempts without meeting its condition
+}
+
+// Parameter Structs
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct SwapIntentParams {
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount: u64,
+    pub max_slippage: Option<u16>, // None uses the user's default_slippage_bps
+    pub rugproof_enabled: bool,
+    pub has_liquidity: bool, // Require pool vaults for the pair in remaining accounts
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+    pub max_route_fee: Option<u64>, // Reject Jupiter routes whose summed AMM fees exceed this
+    pub exact_output: bool, // amount is the exact to_mint output wanted, spending up to max_input
+    pub max_input: u64, // Budget of from_mint including the protocol fee, exact-output only
+    pub min_fill_amount: u64, // Smallest partial fill accepted, 0 for no minimum
+    pub auto_lend: bool, // Deposit the output into the to_mint's registered lending reserve
+    pub min_apy: Option<u16>, // Lend leg floor for auto_lend, execution reverts below it
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub enum SimulateIntentParams {
+    Swap(SwapIntentParams),
+    Lend(LendIntentParams),
+    Buy(BuyIntentParams),
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RepayIntentParams {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub protocol: LendingProtocol,
+    pub obligation: Pubkey, // Borrow position to repay
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RebalanceWeight {
+    pub mint: Pubkey,
+    pub target_bps: u16, // Share of portfolio value, weights sum to 10000
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RebalanceIntentParams {
+    pub weights: Vec<RebalanceWeight>, // Up to MAX_REBALANCE_ASSETS, first mint pays the fee
+    pub max_slippage: u16, // Per leg, against the oracle-implied output
+    pub price_source: PriceSource, // Oracle used to value the holdings
+    pub max_route_fee: Option<u64>, // Reject leg routes whose summed AMM fees exceed this
+    pub max_price_impact: Option<u16>, // Reject leg routes moving the price more than this, in bps
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct LendIntentParams {
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub min_apy: u16,
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct BuyIntentParams {
+    pub mint: Pubkey,
+    pub usdc_mint: Pubkey,
+    pub usdc_amount: u64,
+    pub target_price: Option<u64>, // USDC per token at oracle::ORACLE_PRICE_DECIMALS
+    pub max_price_impact: u16,
+    pub max_slippage: Option<u16>, // Slippage from the route quote, None uses the user's default
+    pub rugproof_check: bool,
+    pub price_check_deadline: Option<i64>, // Expire early if target_price isn't hit by then
+    pub execute_immediately: bool, // Execute in the create call if current_price already meets target_price
+    pub current_price: Option<u64>, // Same scale as target_price, ignored when a price oracle account is passed
+    pub price_source: PriceSource, // Oracle covering the token, Pyth or Switchboard
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct AddLiquidityIntentParams {
+    pub pool_id: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub max_slippage: u16, // Max deviation of deposit ratio from pool ratio
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize)]
+pub struct RemoveLiquidityIntentParams {
+    pub pool_id: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub lp_amount: u64,
+    pub min_amount_a_out: u64,
+    pub min_amount_b_out: u64,
+    pub not_before: Option<i64>, // Earliest execution time, None executes immediately
+    pub client_order_id: Option<[u8; 16]>, // Echoed in intent events for off-chain correlation
+    pub keeper_tip_lamports: u64, // Paid to whoever executes the intent, 0 for none
+}
+
+// Context Structs
+#[derive(Accounts)]
+pub struct InitializeProtocol<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProtocolState::INIT_SPACE,
+        seeds = [b"protocol_state"],
+        bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeUser<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + UserAccount::INIT_SPACE,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetDelegate<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct ManageExecutionDelegation<'info> {
+    pub user: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct SetRugproofThreshold<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SetDefaultSlippage<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CreateSwapIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    /// Cached rugproof score for the output mint, skips recomputing when fresh
+    #[account(
+        seeds = [b"rugproof_cache", rugproof_cache.mint.as_ref()],
+        bump = rugproof_cache.bump
+    )]
+    pub rugproof_cache: Option<Account<'info, RugproofCache>>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSwapIntentJupiter<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_source_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_source_token.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_source_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_destination_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_destination_token.mint == intent_account.to_mint @ IntentError::DestinationMintMismatch
+    )]
+    pub user_destination_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Per-pair circuit breaker PDA, may be uninitialized for pairs without one
+    #[account(
+        mut,
+        seeds = [b"circuit_breaker", intent_account.from_mint.as_ref(), intent_account.to_mint.as_ref()],
+        bump
+    )]
+    pub circuit_breaker: UncheckedAccount<'info>,
+    
+    /// CHECK: Jupiter program
+    #[account(address = jupiter::JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+    
+    /// CHECK: Pyth or Switchboard price account for buy intents, owner is validated against the intent's price source
+    pub price_oracle: Option<UncheckedAccount<'info>>,
+    
+    // Raydium pool the intent was routed to, only passed to fall back to Jupiter
+    /// CHECK: Owner and vaults are verified in the handler
+    pub primary_pool: Option<UncheckedAccount<'info>>,
+    
+    pub primary_pool_coin_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub primary_pool_pc_vault: Option<Account<'info, TokenAccount>>,
+    
+    // Lend leg of auto-lend intents, the output goes into the to_mint's registered reserve
+    #[account(
+        seeds = [b"lending_market_registry"],
+        bump = lending_market_registry.bump
+    )]
+    pub lending_market_registry: Option<Account<'info, LendingMarketRegistry>>,
+    
+    /// CHECK: Solend or Port Finance reserve, checked against the registry in the handler
+    pub auto_lend_reserve: Option<UncheckedAccount<'info>>,
+    
+    /// Reserve liquidity supply the output is deposited into, checked against the reserve
+    #[account(mut)]
+    pub auto_lend_destination: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+
+
+#[derive(Accounts)]
+pub struct ExecuteSwapWithFlashLoan<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    /// Receives the borrowed liquidity and repays it with the flash fee
+    #[account(
+        mut,
+        constraint = user_source_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_source_token.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_source_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_destination_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_destination_token.mint == intent_account.to_mint @ IntentError::DestinationMintMismatch
+    )]
+    pub user_destination_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Per-pair circuit breaker PDA, may be uninitialized for pairs without one
+    #[account(
+        mut,
+        seeds = [b"circuit_breaker", intent_account.from_mint.as_ref(), intent_account.to_mint.as_ref()],
+        bump
+    )]
+    pub circuit_breaker: UncheckedAccount<'info>,
+    
+    /// Registered lending markets, the loan must come from the input token's Solend reserve
+    #[account(
+        seeds = [b"lending_market_registry"],
+        bump = lending_market_registry.bump
+    )]
+    pub lending_market_registry: Account<'info, LendingMarketRegistry>,
+    
+    /// CHECK: Solend reserve lending the input token, checked against the registry in the handler
+    #[account(mut)]
+    pub flash_reserve: UncheckedAccount<'info>,
+    
+    /// Reserve liquidity supply the loan is borrowed from and repaid to, checked against the reserve
+    #[account(mut)]
+    pub flash_reserve_liquidity: Account<'info, TokenAccount>,
+    
+    /// CHECK: Reserve's fee receiver, validated by Solend
+    #[account(mut)]
+    pub flash_fee_receiver: UncheckedAccount<'info>,
+    
+    /// CHECK: Host fee receiver, validated by Solend
+    #[account(mut)]
+    pub host_fee_receiver: UncheckedAccount<'info>,
+    
+    /// CHECK: Lending market of the reserve, validated by Solend
+    pub lending_market: UncheckedAccount<'info>,
+    
+    /// CHECK: Lending market authority PDA, validated by Solend
+    pub lending_market_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: Instructions sysvar, read by Solend to pair the borrow with its repay
+    #[account(address = anchor_lang::solana_program::sysvar::instructions::ID)]
+    pub instructions: UncheckedAccount<'info>,
+    
+    /// CHECK: Solend program
+    #[account(address = solend::SOLEND_PROGRAM_ID)]
+    pub solend_program: UncheckedAccount<'info>,
+    
+    /// CHECK: Jupiter program
+    #[account(address = jupiter::JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteSwapIntentRaydium<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_source_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_source_token.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_source_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_destination_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_destination_token.mint == intent_account.to_mint @ IntentError::DestinationMintMismatch
+    )]
+    pub user_destination_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Per-pair circuit breaker PDA, may be uninitialized for pairs without one
+    #[account(
+        mut,
+        seeds = [b"circuit_breaker", intent_account.from_mint.as_ref(), intent_account.to_mint.as_ref()],
+        bump
+    )]
+    pub circuit_breaker: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium pool account, vaults are verified against its state in the handler
+    #[account(owner = raydium::RAYDIUM_AMM_PROGRAM_ID @ IntentError::InvalidPool)]
+    pub raydium_pool: UncheckedAccount<'info>,
+    
+    pub pool_coin_vault: Account<'info, TokenAccount>,
+    
+    pub pool_pc_vault: Account<'info, TokenAccount>,
+    
+    /// CHECK: Raydium program
+    #[account(address = raydium::RAYDIUM_AMM_PROGRAM_ID)]
+    pub raydium_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+
+
+#[derive(Accounts)]
+pub struct ExecuteSwapIntentMeteora<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_source_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_source_token.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_source_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_destination_token.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_destination_token.mint == intent_account.to_mint @ IntentError::DestinationMintMismatch
+    )]
+    pub user_destination_token: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Per-pair circuit breaker PDA, may be uninitialized for pairs without one
+    #[account(
+        mut,
+        seeds = [b"circuit_breaker", intent_account.from_mint.as_ref(), intent_account.to_mint.as_ref()],
+        bump
+    )]
+    pub circuit_breaker: UncheckedAccount<'info>,
+    
+    /// CHECK: Meteora DLMM pool (LbPair) account, parsed by meteora::read_pool_info
+    #[account(owner = meteora::METEORA_DLMM_PROGRAM_ID @ IntentError::InvalidPool)]
+    pub meteora_pool: UncheckedAccount<'info>,
+    
+    /// CHECK: BinArray holding the pool's active bin, checked against the pool in meteora::read_pool_info
+    #[account(owner = meteora::METEORA_DLMM_PROGRAM_ID @ IntentError::InvalidPool)]
+    pub meteora_bin_array: UncheckedAccount<'info>,
+    
+    /// CHECK: Meteora DLMM program
+    #[account(address = meteora::METEORA_DLMM_PROGRAM_ID)]
+    pub meteora_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+
+
+#[derive(Accounts)]
+#[instruction(params: LendIntentParams)]
+pub struct CreateLendIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    /// CHECK: Lending cap PDA for the mint, left uninitialized when the mint is uncapped
+    #[account(
+        mut,
+        seeds = [b"lending_cap", params.mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+    
+    /// Registered lending markets, lend intents only route to these
+    #[account(
+        seeds = [b"lending_market_registry"],
+        bump = lending_market_registry.bump
+    )]
+    pub lending_market_registry: Account<'info, LendingMarketRegistry>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRepayIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRepayIntent<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_token_account.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Borrow obligation recorded on the intent, program and owner checked in the handler
+    #[account(
+        mut,
+        constraint = intent_account.pool_id == Some(obligation.key()) @ IntentError::InvalidObligation
+    )]
+    pub obligation: UncheckedAccount<'info>,
+    
+    /// CHECK: Reserve the debt is repaid to, program and liquidity checked in the handler
+    #[account(mut)]
+    pub repay_reserve: UncheckedAccount<'info>,
+    
+    /// Reserve liquidity supply the debt is repaid into, checked against the reserve
+    #[account(
+        mut,
+        constraint = repay_reserve_liquidity.mint == intent_account.from_mint @ IntentError::DestinationMintMismatch
+    )]
+    pub repay_reserve_liquidity: Account<'info, TokenAccount>,
+    
+    /// CHECK: Lending market of the reserve and obligation, validated by the lending program
+    pub lending_market: UncheckedAccount<'info>,
+    
+    /// CHECK: Solend or Port Finance program, matched against the intent's protocol in the handler
+    pub lending_program: UncheckedAccount<'info>,
+    
+    pub clock: Sysvar<'info, Clock>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRebalanceIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + RebalanceWeights::INIT_SPACE,
+        seeds = [b"rebalance", intent_account.key().as_ref()],
+        bump
+    )]
+    pub rebalance_weights: Account<'info, RebalanceWeights>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRebalanceIntent<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"rebalance", intent_account.key().as_ref()],
+        bump = rebalance_weights.bump
+    )]
+    pub rebalance_weights: Account<'info, RebalanceWeights>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Jupiter program
+    #[account(address = jupiter::JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteLendIntentSolend<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, released if the intent has expired
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+    
+    /// Registered lending markets, a fallback must deposit into one of these
+    #[account(
+        seeds = [b"lending_market_registry"],
+        bump = lending_market_registry.bump
+    )]
+    pub lending_market_registry: Account<'info, LendingMarketRegistry>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_token_account.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    // Solend-specific accounts
+    /// CHECK: Solend reserve account
+    pub solend_reserve: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Solend lending market
+    pub solend_lending_market: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Solend reserve's liquidity supply, checked against the reserve's own data
+    pub solend_destination_liquidity: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Solend collateral mint
+    pub solend_collateral_mint: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: User's collateral token account
+    pub user_collateral_account: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Solend program
+    #[account(address = solend::SOLEND_PROGRAM_ID)]
+    pub solend_program: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Port Finance reserve a Port intent was routed to, read for its APY when falling back
+    pub port_reserve: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteLendIntentPort<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, released if the intent has expired
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+    
+    /// Registered lending markets, a fallback must deposit into one of these
+    #[account(
+        seeds = [b"lending_market_registry"],
+        bump = lending_market_registry.bump
+    )]
+    pub lending_market_registry: Account<'info, LendingMarketRegistry>,
+    
+    #[account(
+        mut,
+        constraint = user_token_account.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_token_account.mint == intent_account.from_mint @ IntentError::SourceMintMismatch
+    )]
+    pub user_token_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    // Port Finance-specific accounts
+    /// CHECK: Port Finance reserve
+    pub port_reserve: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Port Finance reserve's liquidity supply, checked against the reserve's own data
+    pub port_destination_liquidity: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Port Finance staking pool
+    pub port_staking_pool: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Port Finance LP token account
+    pub port_lp_account: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Port Finance program
+    #[account(address = port_finance::PORT_FINANCE_PROGRAM_ID)]
+    pub port_program: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Solend reserve a Solend intent was routed to, read for its APY when falling back
+    pub solend_reserve: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: BuyIntentParams)]
+pub struct CreateBuyIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    // Execution accounts, only required with `execute_immediately`
+    #[account(mut)]
+    pub user_source_token: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub user_destination_token: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub treasury_fee_account: Option<Account<'info, TokenAccount>>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the intent's USDC mint, only used with `execute_immediately`
+    #[account(mut)]
+    pub fee_ledger: Option<UncheckedAccount<'info>>,
+    
+    /// CHECK: Per-pair circuit breaker PDA, may be uninitialized for pairs without one
+    #[account(
+        seeds = [b"circuit_breaker", params.usdc_mint.as_ref(), params.mint.as_ref()],
+        bump
+    )]
+    pub circuit_breaker: UncheckedAccount<'info>,
+    
+    /// CHECK: Jupiter program
+    #[account(address = jupiter::JUPITER_PROGRAM_ID)]
+    pub jupiter_program: Option<UncheckedAccount<'info>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
+    
+    /// CHECK: Pyth or Switchboard price account, owner is validated against the intent's price source
+    pub price_oracle: Option<UncheckedAccount<'info>>,
+    
+    /// Cached rugproof score for the bought mint, skips recomputing when fresh
+    #[account(
+        seeds = [b"rugproof_cache", rugproof_cache.mint.as_ref()],
+        bump = rugproof_cache.bump
+    )]
+    pub rugproof_cache: Option<Account<'info, RugproofCache>>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateAddLiquidityIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteAddLiquidityIntent<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_token_a.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_token_a: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_token_b.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_token_b: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub pool_token_a: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub pool_token_b: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = user_lp_account.owner == user.key() @ IntentError::Unauthorized,
+        constraint = user_lp_account.mint == lp_mint.key() @ IntentError::DestinationMintMismatch
+    )]
+    pub user_lp_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == intent_account.from_mint @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium pool account
+    #[account(mut, owner = raydium::RAYDIUM_AMM_PROGRAM_ID @ IntentError::InvalidPool)]
+    pub raydium_pool: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium AMM authority PDA, checked by Raydium
+    pub amm_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: The pool's open orders, checked by Raydium against the pool state
+    pub amm_open_orders: UncheckedAccount<'info>,
+    
+    /// CHECK: The pool's target orders, checked by Raydium against the pool state
+    #[account(mut)]
+    pub amm_target_orders: UncheckedAccount<'info>,
+    
+    /// CHECK: Market backing the pool, checked by Raydium against the pool state
+    pub serum_market: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's event queue, checked by Raydium
+    pub serum_event_queue: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium program
+    #[account(address = raydium::RAYDIUM_AMM_PROGRAM_ID)]
+    pub raydium_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CreateRemoveLiquidityIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user_account.authority.as_ref()],
+        bump = user_account.bump,
+        constraint = user_account.authority == authority.key()
+            || user_account.delegate == Some(authority.key()) @ IntentError::Unauthorized
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + IntentAccount::INIT_SPACE,
+        seeds = [b"intent", user_account.authority.as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
+        bump
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteRemoveLiquidityIntent<'info> {
+    /// Keeper executing the intent, paid the intent's keeper tip
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    /// Owner of the intent. Signs to execute their own intent; otherwise the keeper moves
+    /// its tokens through the protocol PDA's `delegate_for_execution` allowance
+    #[account(mut)]
+    pub user: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == user.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_lp_account.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_lp_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_token_a.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_token_a: Account<'info, TokenAccount>,
+    
+    #[account(
+        mut,
+        constraint = user_token_b.owner == user.key() @ IntentError::Unauthorized
+    )]
+    pub user_token_b: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub lp_mint: Account<'info, Mint>,
+    
+    #[account(
+        mut,
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.mint == lp_mint.key() @ IntentError::FeeMintMismatch,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Fee split config PDA, left uninitialized while every fee goes to the treasury
+    #[account(
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: UncheckedAccount<'info>,
+    
+    /// CHECK: Fee ledger PDA for the fee mint, left uninitialized for mints nobody reconciles
+    #[account(
+        mut,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: UncheckedAccount<'info>,
+    
+    #[account(mut)]
+    pub pool_coin_vault: Account<'info, TokenAccount>,
+    
+    #[account(mut)]
+    pub pool_pc_vault: Account<'info, TokenAccount>,
+    
+    /// CHECK: Raydium pool account
+    #[account(mut, owner = raydium::RAYDIUM_AMM_PROGRAM_ID @ IntentError::InvalidPool)]
+    pub raydium_pool: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium AMM authority PDA, checked by Raydium
+    pub amm_authority: UncheckedAccount<'info>,
+    
+    /// CHECK: The pool's open orders, checked by Raydium against the pool state
+    #[account(mut)]
+    pub amm_open_orders: UncheckedAccount<'info>,
+    
+    /// CHECK: The pool's target orders, checked by Raydium against the pool state
+    #[account(mut)]
+    pub amm_target_orders: UncheckedAccount<'info>,
+    
+    /// CHECK: Market program the pool settles through, checked by Raydium
+    pub serum_program: UncheckedAccount<'info>,
+    
+    /// CHECK: Market backing the pool, checked by Raydium against the pool state
+    #[account(mut)]
+    pub serum_market: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's coin vault, checked by the market program
+    #[account(mut)]
+    pub serum_coin_vault: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's pc vault, checked by the market program
+    #[account(mut)]
+    pub serum_pc_vault: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's vault signer PDA, checked by the market program
+    pub serum_vault_signer: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's event queue, checked by Raydium
+    #[account(mut)]
+    pub serum_event_queue: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's bids, checked by Raydium
+    #[account(mut)]
+    pub serum_bids: UncheckedAccount<'info>,
+    
+    /// CHECK: The market's asks, checked by Raydium
+    #[account(mut)]
+    pub serum_asks: UncheckedAccount<'info>,
+    
+    /// CHECK: Raydium program
+    #[account(address = raydium::RAYDIUM_AMM_PROGRAM_ID)]
+    pub raydium_program: UncheckedAccount<'info>,
+    
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == authority.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(mut)]
+    pub user_account: Account<'info, UserAccount>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, left uninitialized when the mint is uncapped
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct CloseIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        close = authority,
+        constraint = intent_account.authority == authority.key() @ IntentError::Unauthorized
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + IntentHistory::INIT_SPACE,
+        seeds = [b"intent_history", authority.key().as_ref()],
+        bump
+    )]
+    pub intent_history: Account<'info, IntentHistory>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AdminCancelIntent<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(mut)]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    /// Owner of the intent, receives the keeper tip refund
+    #[account(
+        mut,
+        address = intent_account.authority @ IntentError::Unauthorized
+    )]
+    pub intent_authority: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", intent_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, left uninitialized when the mint is uncapped
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ExpireIntent<'info> {
+    #[account(mut)]
+    pub keeper: Signer<'info>,
+    
+    #[account(mut)]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    /// Owner of the intent, receives the keeper tip refund
+    #[account(
+        mut,
+        address = intent_account.authority @ IntentError::Unauthorized
+    )]
+    pub intent_authority: SystemAccount<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", intent_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    /// Lamport vault funding keeper bounties
+    #[account(
+        mut,
+        seeds = [b"bounty_vault"],
+        bump
+    )]
+    pub bounty_vault: SystemAccount<'info>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, left uninitialized when the mint is uncapped
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct FundBountyVault<'info> {
+    #[account(mut)]
+    pub funder: Signer<'info>,
+    
+    /// Lamport vault funding keeper bounties
+    #[account(
+        mut,
+        seeds = [b"bounty_vault"],
+        bump
+    )]
+    pub bounty_vault: SystemAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CancelAllIntents<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateTargetPrice<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == authority.key() @ IntentError::Unauthorized
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+}
+
+#[derive(Accounts)]
+pub struct SweepExpiredTip<'info> {
+    pub keeper: Signer<'info>,
+    
+    #[account(mut)]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", intent_account.authority.as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    /// Treasury authority, receives the swept tip
+    #[account(
+        mut,
+        address = protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount
+    )]
+    pub treasury: SystemAccount<'info>,
+    
+    /// CHECK: Lending cap PDA for the intent's mint, left uninitialized when the mint is uncapped
+    #[account(
+        mut,
+        seeds = [b"lending_cap", intent_account.from_mint.as_ref()],
+        bump
+    )]
+    pub lending_cap: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct SetAllowedExecutor<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == authority.key() @ IntentError::Unauthorized
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+}
+
+#[derive(Accounts)]
+pub struct TransferIntent<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        constraint = intent_account.authority == authority.key()
+    )]
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump = old_user_account.bump
+    )]
+    pub old_user_account: Account<'info, UserAccount>,
+    
+    #[account(
+        mut,
+        seeds = [b"user_account", new_user_account.authority.as_ref()],
+        bump = new_user_account.bump
+    )]
+    pub new_user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct PauseProtocol<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct UnpauseProtocol<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateProtocolConfig<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct EmitProtocolMetrics<'info> {
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SimulateIntent<'info> {
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    /// Creating user's account, checked for intent capacity and rugproof threshold
+    pub user_account: Option<Account<'info, UserAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct ViewCapacity<'info> {
+    /// CHECK: Wallet whose intent capacity is queried
+    pub user: UncheckedAccount<'info>,
+    
+    #[account(
+        seeds = [b"user_account", user.key().as_ref()],
+        bump = user_account.bump
+    )]
+    pub user_account: Account<'info, UserAccount>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeSplit<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeSplitConfig::INIT_SPACE,
+        seeds = [b"fee_split_config"],
+        bump
+    )]
+    pub fee_split_config: Account<'info, FeeSplitConfig>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateFeeSplit<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"fee_split_config"],
+        bump = fee_split_config.bump
+    )]
+    pub fee_split_config: Account<'info, FeeSplitConfig>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeLendingCap<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + LendingCap::INIT_SPACE,
+        seeds = [b"lending_cap", mint.key().as_ref()],
+        bump
+    )]
+    pub lending_cap: Account<'info, LendingCap>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLendingCap<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"lending_cap", lending_cap.mint.as_ref()],
+        bump = lending_cap.bump
+    )]
+    pub lending_cap: Account<'info, LendingCap>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeCircuitBreaker<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    pub from_mint: Account<'info, Mint>,
+    
+    pub to_mint: Account<'info, Mint>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + CircuitBreaker::INIT_SPACE,
+        seeds = [b"circuit_breaker", from_mint.key().as_ref(), to_mint.key().as_ref()],
+        bump
+    )]
+    pub circuit_breaker: Account<'info, CircuitBreaker>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateCircuitBreaker<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        mut,
+        seeds = [b"circuit_breaker", circuit_breaker.from_mint.as_ref(), circuit_breaker.to_mint.as_ref()],
+        bump = circuit_breaker.bump
+    )]
+    pub circuit_breaker: Account<'info, CircuitBreaker>,
+}
+
+#[derive(Accounts)]
+pub struct RefreshRugproofScore<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    /// CHECK: Metaplex metadata PDA for the mint, address checked during scoring
+    pub metadata: UncheckedAccount<'info>,
+    
+    /// CHECK: Pool token account holding the mint, owner and mint checked during scoring
+    pub liquidity_pool: Option<UncheckedAccount<'info>>,
+    
+    #[account(
+        init_if_needed,
+        payer = payer,
+        space = 8 + RugproofCache::INIT_SPACE,
+        seeds = [b"rugproof_cache", mint.key().as_ref()],
+        bump
+    )]
+    pub rugproof_cache: Account<'info, RugproofCache>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct EmitExpiringSoon<'info> {
+    pub intent_account: Account<'info, IntentAccount>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+}
+
+#[derive(Accounts)]
+pub struct SetPriceFeed<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    pub mint: Account<'info, Mint>,
+    
+    /// CHECK: Pyth or Switchboard account for the mint, owner and layout checked by reading it
+    pub oracle: UncheckedAccount<'info>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + PriceFeed::INIT_SPACE,
+        seeds = [b"price_feed", mint.key().as_ref()],
+        bump
+    )]
+    pub price_feed: Account<'info, PriceFeed>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RegisterLendingMarket<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + LendingMarketRegistry::INIT_SPACE,
+        seeds = [b"lending_market_registry"],
+        bump
+    )]
+    pub lending_market_registry: Account<'info, LendingMarketRegistry>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ReconcileTreasury<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump = fee_ledger.bump
+    )]
+    pub fee_ledger: Account<'info, FeeLedger>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateProtocolState<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Still in the original layout, so it can't load as ProtocolState; migrate_account checks the discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"protocol_state"],
+        bump
+    )]
+    pub protocol_state: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateUserAccount<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Still in the original layout, so it can't load as UserAccount; migrate_account checks the discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"user_account", authority.key().as_ref()],
+        bump
+    )]
+    pub user_account: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateIntent<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Still in the original layout, so it can't load as IntentAccount; migrate_account checks the discriminator and owner
+    #[account(mut, owner = crate::ID)]
+    pub intent_account: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeFeeLedger<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    #[account(
+        seeds = [b"protocol_state"],
+        bump = protocol_state.bump
+    )]
+    pub protocol_state: Account<'info, ProtocolState>,
+    
+    #[account(
+        constraint = treasury_fee_account.owner == protocol_state.treasury_authority @ IntentError::InvalidTreasuryAccount,
+        constraint = treasury_fee_account.key() == treasury_fee_ata(&protocol_state.treasury_authority, &treasury_fee_account.mint) @ IntentError::NonCanonicalTreasury
+    )]
+    pub treasury_fee_account: Account<'info, TokenAccount>,
+    
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + FeeLedger::INIT_SPACE,
+        seeds = [b"fee_ledger", treasury_fee_account.mint.as_ref()],
+        bump
+    )]
+    pub fee_ledger: Account<'info, FeeLedger>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+// Events
+#[event]
+pub struct SwapIntentExecuted {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub protocol: SwapProtocol,
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub protocol_fee: u64,
+    pub route_labels: Vec<String>, // Jupiter venues used, empty for direct swaps
+}
+
+#[event]
+pub struct FlashSwapExecuted {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount_in: u64, // The user's own input, after the protocol fee
+    pub borrowed: u64,
+    pub repaid: u64, // Borrowed amount plus the flash fee
+    pub flash_fee: u64,
+    pub amount_out: u64,
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct LendIntentExecuted {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub amount: u64,
+    pub apy: u16,
+    pub net_apy: u16, // APY on the full principal after the protocol fee
+    pub protocol: LendingProtocol,
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct DebtRepaid {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub mint: Pubkey,
+    pub obligation: Pubkey,
+    pub protocol: LendingProtocol,
+    pub amount_repaid: u64,
+    pub protocol_fee: u64,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub struct RebalanceLeg {
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub amount_in: u64,
+    pub amount_out: u64,
+    pub notional: u64, // USDC value traded, 6 decimals
+}
+
+#[event]
+pub struct RebalanceExecuted {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub legs: Vec<RebalanceLeg>,
+    pub total_notional: u64, // USDC value across all legs, 6 decimals
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct LiquidityAdded {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub pool_id: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub lp_tokens: u64,
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct LiquidityRemoved {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub keeper_tip_lamports: u64,
+    pub user: Pubkey,
+    pub pool_id: Pubkey,
+    pub mint_a: Pubkey,
+    pub mint_b: Pubkey,
+    pub lp_tokens_burned: u64,
+    pub amount_a: u64,
+    pub amount_b: u64,
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct IntentExpired {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub keeper: Pubkey,
+    pub bounty_lamports: u64,
+}
+
+#[event]
+pub struct BountyVaultFunded {
+    pub funder: Pubkey,
+    pub lamports: u64,
+    pub vault_balance: u64,
+}
+
+#[event]
+pub struct ProtocolMetrics {
+    pub total_intents_created: u64,
+    pub total_intents_executed: u64,
+    pub unexecuted_intents: u64, // Pending, cancelled and expired
+    pub execution_rate_bps: u16,
+    pub total_fees_collected: u64,
+    pub average_fee_per_intent: u64, // Per executed intent
+    pub is_paused: bool,
+}
+
+#[event]
+pub struct SimulationResult {
+    pub intent_type: IntentType,
+    pub swap_protocol: Option<SwapProtocol>,
+    pub lending_protocol: Option<LendingProtocol>,
+    pub protocol_fee: u64,
+    pub estimated_output: Option<u64>, // Swaps and buys, when pool vaults were passed
+    pub apy: Option<u16>, // Lends
+    pub rugproof_score: Option<u8>,
+    pub would_pass: bool,
+}
+
+#[event]
+pub struct UserCapacity {
+    pub user: Pubkey,
+    pub active_intents: u8,
+    pub max_intents: u8,
+    pub remaining: u8,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub intent_id: Pubkey,
+    pub from_mint: Pubkey,
+    pub to_mint: Pubkey,
+    pub reference_price: u64,
+    pub observed_price: u64,
+    pub deviation_bps: u64,
+    pub max_deviation_bps: u16,
+}
+
+#[event]
+pub struct AdminCancelled {
+    pub intent_id: Pubkey,
+    pub intent_authority: Pubkey,
+    pub admin: Pubkey,
+    pub reason_code: u16,
+}
+
+#[event]
+pub struct FeeExemptionUpdated {
+    pub account: Pubkey,
+    pub exempt: bool,
+}
+
+#[event]
+pub struct ExecutionDelegationUpdated {
+    pub user: Pubkey,
+    pub token_account: Pubkey,
+    pub amount: u64, // 0 when revoked
+}
+
+#[event]
+pub struct TypeFeeUpdated {
+    pub intent_type: IntentType,
+    pub old_fee_bps: u16,
+    pub new_fee_bps: u16,
+}
+
+#[event]
+pub struct FeeWaived {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub waived_fee: u64,
+}
+
+#[event]
+pub struct BulkCancelled {
+    pub user: Pubkey,
+    pub cancelled_count: u32,
+}
+
+#[event]
+pub struct IntentCompacted {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub status: IntentStatus,
+    pub history_hash: [u8; 32],
+}
+
+#[event]
+pub struct TipSwept {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub treasury: Pubkey,
+    pub tip_lamports: u64,
+}
+
+#[event]
+pub struct AllowedExecutorUpdated {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub allowed_executor: Option<Pubkey>,
+}
+
+#[event]
+pub struct TargetPriceUpdated {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub old_target: u64,
+    pub new_target: u64,
+}
+
+#[event]
+pub struct IntentTransferred {
+    pub intent_id: Pubkey,
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct RugproofThresholdUpdated {
+    pub user: Pubkey,
+    pub old_min_score: u8,
+    pub new_min_score: u8,
+}
+
+#[event]
+pub struct DefaultSlippageUpdated {
+    pub user: Pubkey,
+    pub old_slippage_bps: u16,
+    pub new_slippage_bps: u16,
+}
+
+#[event]
+pub struct PriceFeedSet {
+    pub mint: Pubkey,
+    pub price_source: PriceSource,
+    pub oracle: Pubkey,
+}
+
+#[event]
+pub struct LendingMarketRegistered {
+    pub protocol: LendingProtocol,
+    pub mint: Pubkey,
+    pub reserve: Pubkey,
+    pub market: Pubkey,
+}
+
+#[event]
+pub struct TreasuryReconciliation {
+    pub treasury_fee_account: Pubkey,
+    pub mint: Pubkey,
+    pub tracked_fees: u64, // Opening balance plus fees the mint's ledger recorded
+    pub treasury_balance: u64,
+    pub discrepancy: i128, // treasury_balance - tracked_fees
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct RugproofScoreRefreshed {
+    pub mint: Pubkey,
+    pub old_score: Option<u8>, // None on the first refresh
+    pub score: u8,
+    pub updated_at: i64,
+    pub refreshed_by: Pubkey,
+}
+
+#[event]
+pub struct VenueFallback {
+    pub intent_id: Pubkey,
+    pub old_protocol: SwapProtocol,
+    pub new_protocol: SwapProtocol,
+    pub primary_output: u64, // What the drained primary pool would have returned
+    pub fallback_output: u64,
+}
+
+#[event]
+pub struct ProtocolReselected {
+    pub intent_id: Pubkey,
+    pub old_protocol: LendingProtocol,
+    pub new_protocol: LendingProtocol,
+    pub old_apy: u16,
+    pub new_apy: u16,
+}
+
+#[event]
+pub struct IntentFailed {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub execution_attempts: u8,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct IntentExpiringSoon {
+    pub intent_id: Pubkey,
+    pub authority: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub expires_at: i64, // Earlier of expires_at and the price check deadline
+    pub remaining_seconds: i64,
+    pub bounty_lamports: u64,
+}
+
+#[event]
+pub struct IntentStateChanged {
+    pub intent_id: Pubkey,
+    pub client_order_id: Option<[u8; 16]>,
+    pub old_status: Option<IntentStatus>, // None on creation
+    pub new_status: IntentStatus,
+    pub timestamp: i64,
+}
+
+#[event]
+pub struct FeeDistributed {
+    pub intent_id: Pubkey,
+    pub total_fee: u64,
+    pub beneficiaries: Vec<Pubkey>,
+    pub amounts: Vec<u64>,
+    pub treasury_dust: u64, // Rounding remainder sent to the treasury
+}
+
+// Error Codes
+#[error_code]
+pub enum IntentError {
+    #[msg("Too many active intents")]
+    TooManyActiveIntents,
+    #[msg("Protocol is paused")]
+    ProtocolPaused,
+    #[msg("Invalid amount")]
+    InvalidAmount,
+    #[msg("Slippage too high")]
+    SlippageTooHigh,
+    #[msg("Rugproof check failed")]
+    RugproofCheckFailed,
+    #[msg("Intent is not pending")]
+    IntentNotPending,
+    #[msg("Intent has expired")]
+    IntentExpired,
+    #[msg("Slippage exceeded")]
+    SlippageExceeded,
+    #[msg("Invalid APY")]
+    InvalidAPY,
+    #[msg("APY too low")]
+    APYTooLow,
+    #[msg("Unauthorized")]
+    Unauthorized,
+    #[msg("Wrong protocol selected")]
+    WrongProtocol,
+    #[msg("Invalid fee split configuration")]
+    InvalidFeeSplit,
+    #[msg("Fee beneficiary account mismatch")]
+    FeeBeneficiaryMismatch,
+    #[msg("Insufficient pool liquidity")]
+    InsufficientPoolLiquidity,
+    #[msg("Amount is below the protocol fee floor")]
+    AmountBelowFeeFloor,
+    #[msg("Pool does not match intent")]
+    InvalidPool,
+    #[msg("Invalid intent transfer")]
+    InvalidIntentTransfer,
+    #[msg("Intent has not expired yet")]
+    IntentNotExpired,
+    #[msg("Treasury fee account is not owned by the protocol treasury")]
+    InvalidTreasuryAccount,
+    #[msg("Source token account mint does not match intent")]
+    SourceMintMismatch,
+    #[msg("Destination token account mint does not match intent")]
+    DestinationMintMismatch,
+    #[msg("Intent is already being executed")]
+    Reentrancy,
+    #[msg("Lending cap reached for this mint")]
+    LendingCapReached,
+    #[msg("Rugproof threshold must be between MIN_RUGPROOF_SCORE and 100")]
+    InvalidRugproofThreshold,
+    #[msg("Price check deadline requires a target price and must fall between the minimum intent lifetime and expiry")]
+    InvalidPriceCheckDeadline,
+    #[msg("Jupiter route has too many steps")]
+    RouteTooLong,
+    #[msg("Jupiter route AMM fees exceed the intent's maximum")]
+    RouteFeeTooHigh,
+    #[msg("Intent is scheduled for a later time")]
+    IntentNotYetActive,
+    #[msg("Scheduled start must fall before the intent expires")]
+    InvalidNotBefore,
+    #[msg("Too many intents for one bulk cancel")]
+    TooManyBulkCancels,
+    #[msg("Lending cap account missing for lend intent")]
+    LendingCapAccountMissing,
+    #[msg("Lending cap release exceeds the amount reserved")]
+    LendingCapUnderflow,
+    #[msg("Fee ledger account is not the PDA for the fee mint")]
+    InvalidFeeLedger,
+    #[msg("Account is already fee exempt")]
+    AlreadyFeeExempt,
+    #[msg("Account is not fee exempt")]
+    NotFeeExempt,
+    #[msg("Fee exemption list is full")]
+    TooManyFeeExempt,
+    #[msg("Treasury fee account mint does not match the fee token")]
+    FeeMintMismatch,
+    #[msg("Intent condition is not met")]
+    ConditionNotMet,
+    #[msg("Execution accounts are required to execute immediately")]
+    MissingExecutionAccounts,
+    #[msg("Deviation threshold must be between 1 and 10000 bps")]
+    InvalidDeviationThreshold,
+    #[msg("Circuit breaker tripped for this pair, awaiting an admin reset")]
+    PairHalted,
+    #[msg("Obligation does not match the intent's borrow position")]
+    InvalidObligation,
+    #[msg("Repay reserve does not lend the intent's token into the given supply")]
+    InvalidRepayReserve,
+    #[msg("No pool with liquidity for this pair was provided")]
+    NoLiquidity,
+    #[msg("Oracle account does not belong to the intent's price source")]
+    InvalidOracle,
+    #[msg("Oracle price is missing or invalid")]
+    InvalidOraclePrice,
+    #[msg("Oracle price is older than the allowed staleness window")]
+    StaleOraclePrice,
+    #[msg("Oracle confidence interval is too wide to trade on")]
+    OracleConfidenceTooWide,
+    #[msg("Price feed or oracle is not the one registered for this mint")]
+    InvalidPriceFeed,
+    #[msg("Rebalance leg moved more than the tracked balance")]
+    RebalanceBalanceMismatch,
+    #[msg("Metadata account is not the mint's metadata PDA")]
+    InvalidMetadata,
+    #[msg("Minimum APY exceeds what any reserve realistically pays")]
+    UnrealisticApy,
+    #[msg("No lending market registered for this token")]
+    LendingMarketNotRegistered,
+    #[msg("Lending market registry is full")]
+    TooManyLendingMarkets,
+    #[msg("Exact output needs more input than the intent's max_input")]
+    ExactOutputBudgetExceeded,
+    #[msg("Exact-output swaps are not supported on this path")]
+    ExactOutputUnsupported,
+    #[msg("Treasury fee account is not the treasury's associated token account for the fee mint")]
+    NonCanonicalTreasury,
+    #[msg("Expiry warning window must be positive and no longer than the intent lifetime")]
+    InvalidExpiryWarningWindow,
+    #[msg("Slippage too high for a stable pair")]
+    SlippageTooHighForStablePair,
+    #[msg("wSOL account must be a native token account owned by the user")]
+    InvalidWsolAccount,
+    #[msg("Intent has no target price to update")]
+    NotConditionalIntent,
+    #[msg("Target price must be positive and differ from the current target")]
+    InvalidTargetPrice,
+    #[msg("Per-type fee exceeds the maximum")]
+    InvalidTypeFee,
+    #[msg("Leverage would open the position too close to liquidation")]
+    LeverageTooRisky,
+    #[msg("Signer is not the intent's designated executor")]
+    ExecutorNotAllowed,
+    #[msg("Intent owner must sign to wrap SOL for this execution")]
+    OwnerSignatureRequired,
+    #[msg("Fill is below the intent's minimum fill size")]
+    FillTooSmall,
+    #[msg("Tip sweep grace period has not elapsed")]
+    TipSweepTooEarly,
+    #[msg("Trade would move the price more than the intent allows")]
+    PriceImpactTooHigh,
+    #[msg("Pending intents cannot be closed")]
+    IntentStillPending,
+    #[msg("Alternative protocol's APY gain is below the minimum spread")]
+    ApySpreadTooSmall,
+    #[msg("Expiry leaves the intent less than the minimum lifetime")]
+    InvalidExpiry,
+    #[msg("Rebalance weights must name distinct mints and sum to exactly 10000 bps")]
+    InvalidRebalanceWeights,
+    #[msg("Routes do not match the planned rebalance legs")]
+    RebalanceRouteMismatch,
+    #[msg("Portfolio is already at its target weights")]
+    NothingToRebalance,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Lending reserve is not the lending protocol's reserve for the intent's token")]
+    InvalidLendReserve,
+    #[msg("Flash loans are disabled")]
+    FlashLoansDisabled,
+    #[msg("Flash loan was not repaid to the reserve")]
+    FlashLoanNotRepaid,
+}
+
+/// Rewrite an account still in an older layout as `New`, growing it to `New::INIT_SPACE`
+/// with the payer topping up rent. Accounts already at full size are left alone
+fn migrate_account<'info, Old: AnchorDeserialize, New: AccountSerialize + anchor_lang::Discriminator + Space>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    upgrade: impl FnOnce(Old) -> Result<New>,
+) -> Result<()> {
+    let new_len = 8 + New::INIT_SPACE;
+    let old = {
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == New::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        require!(data.len() < new_len, IntentError::AlreadyMigrated);
+        Old::deserialize(&mut &data[8..])?
+    };
+    let migrated = upgrade(old)?;
+    
+    let rent_shortfall = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if rent_shortfall > 0 {
+        let cpi_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(cpi_ctx, rent_shortfall)?;
+    }
+    account.realloc(new_len, true)?;
+    migrated.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])
+}
+
+/// A partial fill must meet the intent's minimum unless it takes the whole remainder
+pub fn check_fill_amount(fill_amount: u64, remaining_amount: u64, min_fill_amount: u64) -> Result<()> {
+    require!(fill_amount > 0 && fill_amount <= remaining_amount, IntentError::InvalidAmount);
+    require!(
+        fill_amount >= min_fill_amount || fill_amount == remaining_amount,
+        IntentError::FillTooSmall
+    );
+    Ok(())
+}
+
+/// Shared admin gate: the signer must be the protocol authority
+fn require_authority(protocol_state: &ProtocolState, signer: &Pubkey) -> Result<()> {
+    require!(protocol_state.authority == *signer, IntentError::Unauthorized);
+    Ok(())
+}
+
+/// Fee rate configured for an intent type
+fn type_fee_bps(protocol_state: &ProtocolState, intent_type: &IntentType) -> u16 {
+    protocol_state.fee_bps_by_type[intent_type.clone() as usize]
+}
+
+/// Protocol fee to charge at execution; zeroes it on the intent when its authority is exempt
+fn apply_fee_exemption(intent_account: &mut Account<IntentAccount>, protocol_state: &ProtocolState) -> u64 {
+    if !protocol_state.fee_exempt.contains(&intent_account.authority) {
+        return intent_account.protocol_fee;
+    }
+    
+    emit!(FeeWaived {
+        intent_id: intent_account.key(),
+        authority: intent_account.authority,
+        waived_fee: intent_account.protocol_fee,
+    });
+    
+    intent_account.protocol_fee = 0;
+    0
+}
+
+impl LendingCap {
+    fn reserve(&mut self, amount: u64) -> Result<()> {
+        let new_total = self.current_total
+            .checked_add(amount)
+            .ok_or(IntentError::LendingCapReached)?;
+        require!(new_total <= self.max_total, IntentError::LendingCapReached);
+        self.current_total = new_total;
+        Ok(())
+    }
+    
+    fn release(&mut self, amount: u64) -> Result<()> {
+        self.current_total = self.current_total
+            .checked_sub(amount)
+            .ok_or(IntentError::LendingCapUnderflow)?;
+        Ok(())
+    }
+}
+
+/// Reserve a lend intent must deposit into on `protocol`: the registered reserve chosen
+/// at creation, or, when it falls back to the other protocol, that protocol's registered
+/// reserve for the mint
+fn expected_lend_reserve(
+    registry: &LendingMarketRegistry,
+    intent_account: &IntentAccount,
+    protocol: &LendingProtocol,
+) -> Result<Pubkey> {
+    if intent_account.selected_lending_protocol.as_ref() == Some(protocol) {
+        if let Some(reserve) = intent_account.pool_id {
+            return Ok(reserve);
+        }
+    }
+    registry.markets
+        .iter()
+        .find(|market| market.protocol == *protocol && market.mint == intent_account.from_mint)
+        .map(|market| market.reserve)
+        .ok_or_else(|| error!(IntentError::LendingMarketNotRegistered))
+}
+
+/// Deposit an auto-lend swap's output into the registered reserve of its output token, on
+/// whichever lending protocol owns the reserve passed, returning that protocol and its APY
+#[allow(clippy::too_many_arguments)]
+fn auto_lend_swap_output<'info>(
+    intent_account: &IntentAccount,
+    registry: Option<&LendingMarketRegistry>,
+    reserve: Option<&UncheckedAccount<'info>>,
+    destination: Option<&Account<'info, TokenAccount>>,
+    authority: &OwnerAuthority<'info>,
+    token_program: &AccountInfo<'info>,
+    source: &AccountInfo<'info>,
+    amount: u64,
+) -> Result<(LendingProtocol, u16)> {
+    let (Some(registry), Some(reserve), Some(destination)) = (registry, reserve, destination) else {
+        msg!("❌ Auto-lend intent needs the lending registry, a reserve and its liquidity supply");
+        return err!(IntentError::MissingExecutionAccounts);
+    };
+    let (protocol, mint, supply, apy) = if reserve.owner == &solend::SOLEND_PROGRAM_ID {
+        let reserve_data = solend::load_reserve(reserve)?;
+        let apy = solend::calculate_lending_apy(&reserve_data)?;
+        (LendingProtocol::Solend, reserve_data.liquidity.mint_pubkey, reserve_data.liquidity.supply_pubkey, apy)
+    } else {
+        let reserve_data = port_finance::load_reserve(reserve)?;
+        let apy = port_finance::calculate_port_apy(&reserve_data)?;
+        (LendingProtocol::PortFinance, reserve_data.liquidity.mint_pubkey, reserve_data.liquidity.supply_pubkey, apy)
+    };
+    require!(
+        registry.markets.iter().any(|market| {
+            market.protocol == protocol && market.mint == intent_account.to_mint && market.reserve == reserve.key()
+        }),
+        IntentError::LendingMarketNotRegistered
+    );
+    // Deposit only into the reserve's own liquidity supply, never an account the keeper picks
+    require!(
+        mint == intent_account.to_mint && supply == destination.key(),
+        IntentError::InvalidLendReserve
+    );
+    require!(apy >= intent_account.min_apy.unwrap_or(0), IntentError::APYTooLow);
+    
+    authority.transfer(token_program, source, &destination.to_account_info(), amount)?;
+    msg!("🏦 Auto-lent {} tokens via {:?} at {}% APY", amount, protocol, apy);
+    Ok((protocol, apy))
+}
+
+/// Refuse intents that would be born expired, or live less than MIN_INTENT_LIFETIME
+fn check_intent_lifetime(created_at: i64, expires_at: i64) -> Result<()> {
+    require!(
+        expires_at.checked_sub(created_at).is_some_and(|lifetime| lifetime > MIN_INTENT_LIFETIME),
+        IntentError::InvalidExpiry
+    );
+    Ok(())
+}
+
+/// Whether `expire_intent` may retire the intent: `expires_at` has passed, or a buy
+/// intent's price check deadline lapsed without execution
+fn intent_is_stale(intent_account: &IntentAccount, now: i64) -> bool {
+    now >= intent_account.expires_at
+        || intent_account.price_check_deadline.is_some_and(|deadline| now >= deadline)
+}
+
+/// Bounty a keeper earns for expiring an intent. It is capped by what the vault holds
+/// above its rent-exempt minimum, and owners expiring their own intents get nothing,
+/// or they could farm the vault with short-lived intents.
+fn payable_expiry_bounty(bounty_lamports: u64, vault_lamports: u64, vault_rent_minimum: u64, self_expiry: bool) -> u64 {
+    if self_expiry {
+        return 0;
+    }
+    bounty_lamports.min(vault_lamports.saturating_sub(vault_rent_minimum))
+}
+
+/// Reserve `amount` against a mint's lending cap. Returns whether a reservation was
+/// made; uninitialized caps are unlimited and reserve nothing.
+fn reserve_lending_cap(lending_cap_info: &AccountInfo, amount: u64) -> Result<bool> {
+    if lending_cap_info.data_is_empty() {
+        return Ok(false);
+    }
+    require!(lending_cap_info.owner == &crate::ID, IntentError::Unauthorized);
+    
+    let mut data = lending_cap_info.try_borrow_mut_data()?;
+    let mut lending_cap = LendingCap::try_deserialize(&mut &data[..])?;
+    lending_cap.reserve(amount)?;
+    lending_cap.try_serialize(&mut &mut data[..])?;
+    Ok(true)
+}
+
+/// Release the intent's reservation back to its mint's lending cap. Intents created
+/// before the cap existed never reserved anything, so they leave it untouched.
+fn release_lending_cap(lending_cap_info: &AccountInfo, intent_account: &mut IntentAccount) -> Result<()> {
+    if !intent_account.lending_cap_reserved {
+        return Ok(());
+    }
+    require!(lending_cap_info.owner == &crate::ID, IntentError::Unauthorized);
+    
+    let mut data = lending_cap_info.try_borrow_mut_data()?;
+    let mut lending_cap = LendingCap::try_deserialize(&mut &data[..])?;
+    lending_cap.release(intent_account.amount)?;
+    lending_cap.try_serialize(&mut &mut data[..])?;
+    intent_account.lending_cap_reserved = false;
+    Ok(())
+}
+
+/// Whether a buy intent's target price is met at `current_price`; intents without a
+/// target always are
+fn buy_condition_met(intent_account: &IntentAccount, current_price: u64) -> bool {
+    !matches!(intent_account.target_price, Some(target_price) if current_price > target_price)
+}
+
+/// Jupiter route limits for an intent, shared by the keeper path and inline buys
+fn jupiter_swap_params(intent_account: &IntentAccount, amount: u64) -> jupiter::JupiterSwapParams {
+    jupiter::JupiterSwapParams {
+        from_mint: intent_account.from_mint,
+        to_mint: intent_account.to_mint,
+        amount,
+        slippage_bps: intent_account.max_slippage,
+        platform_fee_bps: 0, // We already collected our fee
+        max_route_fee: intent_account.max_route_fee,
+        max_price_impact: intent_account.max_price_impact,
+    }
+}
+
+impl CircuitBreaker {
+    /// Compare an observed price (scaled by PRICE_SCALE) against the reference. Trips the
+    /// pair and returns the deviation when it exceeds the threshold, keeping the old
+    /// reference; otherwise rolls the reference forward.
+    fn observe(&mut self, observed_price: u64) -> Option<u64> {
+        if self.last_price > 0 {
+            let deviation_bps = ((observed_price.abs_diff(self.last_price) as u128) * 10000 / self.last_price as u128)
+                .min(u64::MAX as u128) as u64;
+            if deviation_bps > self.max_deviation_bps as u64 {
+                self.tripped = true;
+                return Some(deviation_bps);
+            }
+        }
+        self.last_price = observed_price;
+        None
+    }
+}
+
+/// Compare an execution's pre-trade price against the pair's reference price.
+/// `pool_price` is the (amount in, amount out) read from the venue's own accounts;
+/// caller-supplied quotes pass None and only get checked against a tripped pair.
+/// Trips the pair (releasing the intent untouched) when the deviation exceeds the
+/// threshold, otherwise rolls the reference forward. Pairs without an initialized
+/// breaker are never tripped.
+fn check_circuit_breaker(
+    circuit_breaker_info: &AccountInfo,
+    intent_account: &mut Account<IntentAccount>,
+    pool_price: Option<(u64, u64)>,
+) -> Result<bool> {
+    if circuit_breaker_info.data_is_empty() {
+        return Ok(false);
+    }
+    require!(circuit_breaker_info.owner == &crate::ID, IntentError::Unauthorized);
+    
+    let mut data = circuit_breaker_info.try_borrow_mut_data()?;
+    let mut circuit_breaker = CircuitBreaker::try_deserialize(&mut &data[..])?;
+    require!(!circuit_breaker.tripped, IntentError::PairHalted);
+    
+    let Some((amount_in, amount_out)) = pool_price.filter(|(amount_in, _)| *amount_in > 0) else {
+        return Ok(false);
+    };
+    let observed_price = ((amount_out as u128) * PRICE_SCALE / amount_in as u128)
+        .min(u64::MAX as u128) as u64;
+    let reference_price = circuit_breaker.last_price;
+    let tripped = circuit_breaker.observe(observed_price);
+    circuit_breaker.try_serialize(&mut &mut data[..])?;
+    
+    let Some(deviation_bps) = tripped else {
+        return Ok(false);
+    };
+    intent_account.is_executing = false;
+    emit!(CircuitBreakerTripped {
+        intent_id: intent_account.key(),
+        from_mint: circuit_breaker.from_mint,
+        to_mint: circuit_breaker.to_mint,
+        reference_price,
+        observed_price,
+        deviation_bps,
+        max_deviation_bps: circuit_breaker.max_deviation_bps,
+    });
+    msg!("🚨 Circuit breaker tripped: price deviated {} bps (max {}), pair halted", deviation_bps, circuit_breaker.max_deviation_bps);
+    Ok(true)
+}
+
+/// The pair's reference price from its circuit breaker, scaled by PRICE_SCALE. None for
+/// pairs without a breaker or whose breaker hasn't seen an execution yet
+fn pair_reference_price(circuit_breaker_info: &AccountInfo) -> Result<Option<u64>> {
+    if circuit_breaker_info.data_is_empty() {
+        return Ok(None);
+    }
+    require!(circuit_breaker_info.owner == &crate::ID, IntentError::Unauthorized);
+    let circuit_breaker = CircuitBreaker::try_deserialize(&mut &circuit_breaker_info.try_borrow_data()?[..])?;
+    Ok(Some(circuit_breaker.last_price).filter(|price| *price > 0))
+}
+
+/// Record an execute call whose condition wasn't met. Returns Ok so the count persists;
+/// at the protocol's max attempts the intent is marked Failed, freeing the user's slot
+/// and refunding the keeper tip.
+fn record_failed_attempt(
+    intent_account: &mut Account<IntentAccount>,
+    user_account: &mut Account<UserAccount>,
+    protocol_state: &ProtocolState,
+    user: &AccountInfo,
+) -> Result<()> {
+    intent_account.is_executing = false;
+    intent_account.execution_attempts = intent_account.execution_attempts.saturating_add(1);
+    msg!(
+        "🔁 Condition not met for {}: attempt {} of {}",
+        intent_account.key(),
+        intent_account.execution_attempts,
+        protocol_state.max_execution_attempts
+    );
+    if intent_account.execution_attempts < protocol_state.max_execution_attempts {
+        return Ok(());
+    }
+    
+    intent_account.status = IntentStatus::Failed;
+    emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Failed)?;
+    user_account.active_intents -= 1;
+    
+    // The intent never executed, so its tip goes back to the owner rather than the keeper
+    release_keeper_tip(&intent_account.to_account_info(), user, intent_account.keeper_tip_lamports)?;
+    
+    emit!(IntentFailed {
+        intent_id: intent_account.key(),
+        authority: intent_account.authority,
+        execution_attempts: intent_account.execution_attempts,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    
+    msg!("❌ Intent failed after {} attempts: {}", intent_account.execution_attempts, intent_account.key());
+    Ok(())
+}
+
+/// Expire an intent found stale at execution time: free the user's slot, refund the
+/// keeper tip and, for lend intents, release the lending cap reservation
+fn expire_on_execution(
+    intent_account: &mut Account<IntentAccount>,
+    user_account: &mut Account<UserAccount>,
+    user: &AccountInfo,
+    keeper: Pubkey,
+    lending_cap_info: Option<&AccountInfo>,
+) -> Result<()> {
+    intent_account.status = IntentStatus::Expired;
+    intent_account.is_executing = false;
+    emit_intent_state_changed(intent_account.key(), intent_account.client_order_id, Some(IntentStatus::Pending), IntentStatus::Expired)?;
+    user_account.active_intents -= 1;
+    
+    if let Some(lending_cap_info) = lending_cap_info {
+        release_lending_cap(lending_cap_info, intent_account)?;
+    }
+    
+    // The intent never executed, so its tip goes back to the owner rather than the keeper
+    release_keeper_tip(&intent_account.to_account_info(), user, intent_account.keeper_tip_lamports)?;
+    
+    emit!(IntentExpired {
+        intent_id: intent_account.key(),
+        authority: intent_account.authority,
+        keeper,
+        bounty_lamports: 0,
+    });
+    
+    msg!("⌛ Intent expired on execution attempt: {}", intent_account.key());
+    Ok(())
+}
+
+/// Find two token vaults in `accounts` under the same pool authority, one per side of
+/// the pair, each holding at least `min_reserve`. Returns (reserve_in, reserve_out).
+fn find_pool_reserves(accounts: &[AccountInfo], from_mint: &Pubkey, to_mint: &Pubkey, min_reserve: u64) -> Option<(u64, u64)> {
+    let vaults: Vec<TokenAccount> = accounts
+        .iter()
+        .filter(|info| info.owner == &token::ID)
+        .filter_map(|info| TokenAccount::try_deserialize(&mut &info.data.borrow()[..]).ok())
+        .filter(|vault| vault.amount >= min_reserve)
+        .collect();
+    
+    vaults.iter().filter(|from_vault| from_vault.mint == *from_mint).find_map(|from_vault| {
+        vaults
+            .iter()
+            .find(|to_vault| to_vault.mint == *to_mint && to_vault.owner == from_vault.owner)
+            .map(|to_vault| (from_vault.amount, to_vault.amount))
+    })
+}
+
+/// Count an execution's full input, fee included, toward the user's volume
+fn record_volume(user_account: &mut UserAccount, net_amount: u64, protocol_fee: u64) -> Result<()> {
+    user_account.total_volume = net_amount
+        .checked_add(protocol_fee)
+        .and_then(|volume| user_account.total_volume.checked_add(volume))
+        .ok_or(IntentError::InvalidAmount)?;
+    Ok(())
+}
+
+/// Add a fill to an intent's running totals and set `execution_price` to the
+/// volume-weighted average across all fills, scaled by PRICE_SCALE
+fn record_fill(intent_account: &mut IntentAccount, amount_in: u64, amount_out: u64) -> Result<()> {
+    intent_account.cumulative_in = intent_account.cumulative_in
+        .checked_add(amount_in)
+        .ok_or(IntentError::InvalidAmount)?;
+    intent_account.cumulative_out = intent_account.cumulative_out
+        .checked_add(amount_out)
+        .ok_or(IntentError::InvalidAmount)?;
+    
+    intent_account.execution_price = (intent_account.cumulative_out as u128)
+        .checked_mul(PRICE_SCALE)
+        .unwrap()
+        .checked_div(intent_account.cumulative_in as u128)
+        .map(|price| price.min(u64::MAX as u128) as u64);
+    Ok(())
+}
+
+/// Take the execution lock on an intent and persist it before any CPI,
+/// so a re-entrant call on the same intent sees the flag and aborts
+fn begin_intent_execution(intent_account: &mut Account<IntentAccount>, executor: Pubkey, program_id: &Pubkey) -> Result<()> {
+    require!(!intent_account.is_executing, IntentError::Reentrancy);
+    require!(executor_allowed(intent_account, &executor), IntentError::ExecutorNotAllowed);
+    intent_account.is_executing = true;
+    intent_account.exit(program_id)
+}
+
+/// The owner can always execute their own intent; keepers only when no allowed
+/// executor is set or they are the allowed one
+fn executor_allowed(intent_account: &IntentAccount, executor: &Pubkey) -> bool {
+    *executor == intent_account.authority
+        || intent_account.allowed_executor.is_none()
+        || intent_account.allowed_executor == Some(*executor)
+}
+
+/// Move a keeper tip from the intent creator into the intent PDA
+fn fund_keeper_tip<'info>(
+    system_program: &AccountInfo<'info>,
+    payer: &AccountInfo<'info>,
+    intent_account: &AccountInfo<'info>,
+    tip_lamports: u64,
+) -> Result<()> {
+    if tip_lamports == 0 {
+        return Ok(());
+    }
+    let cpi_ctx = CpiContext::new(
+        system_program.clone(),
+        system_program::Transfer {
+            from: payer.clone(),
+            to: intent_account.clone(),
+        },
+    );
+    system_program::transfer(cpi_ctx, tip_lamports)
+}
+
+/// Pay a prepaid keeper tip out of the intent PDA (to the executor, or back as a refund)
+fn release_keeper_tip(intent_account: &AccountInfo, to: &AccountInfo, tip_lamports: u64) -> Result<()> {
+    if tip_lamports == 0 {
+        return Ok(());
+    }
+    **intent_account.try_borrow_mut_lamports()? = intent_account
+        .lamports()
+        .checked_sub(tip_lamports)
+        .ok_or(IntentError::InvalidAmount)?;
+    **to.try_borrow_mut_lamports()? = to.lamports().checked_add(tip_lamports).unwrap();
+    Ok(())
+}
+
+/// Canonical treasury account for fees in `mint`: the treasury authority's associated
+/// token account, so fees in every token land somewhere deterministic
+pub fn treasury_fee_ata(treasury_authority: &Pubkey, mint: &Pubkey) -> Pubkey {
+    get_associated_token_address(treasury_authority, mint)
+}
+
+/// Fee and net input for an exact-output swap whose venue needs `required_input`.
+/// Rejects the fill when input plus fee exceeds max_input; unused budget never leaves
+/// the user's token account, so nothing needs refunding.
+fn exact_output_amounts(
+    intent_account: &mut IntentAccount,
+    protocol_state: &ProtocolState,
+    required_input: u64,
+) -> Result<(u64, u64)> {
+    // apply_fee_exemption has already zeroed the fee for exempt authorities
+    let protocol_fee = if intent_account.protocol_fee == 0 {
+        0
+    } else {
+        calculate_protocol_fee(required_input, intent_account.fee_bps, protocol_state.min_fee_tokens)?
+    };
+    let total_input = required_input.checked_add(protocol_fee).ok_or(IntentError::InvalidAmount)?;
+    require!(total_input <= intent_account.max_input, IntentError::ExactOutputBudgetExceeded);
+    
+    intent_account.protocol_fee = protocol_fee;
+    msg!("🎯 Exact output {}: spending {} of {} budget", intent_account.amount, total_input, intent_account.max_input);
+    Ok((protocol_fee, required_input))
+}
+
+/// Rugproof score for `mint`, served from a fresh RugproofCache when one is passed
+fn cached_rugproof_score(mint: &Pubkey, cache: Option<&RugproofCache>) -> Result<u8> {
+    if let Some(cache) = cache {
+        let age = Clock::get()?.unix_timestamp.saturating_sub(cache.updated_at);
+        if cache.mint == *mint && age <= RUGPROOF_CACHE_TTL {
+            msg!("🛡️ Cached rugproof score for {}: {} ({}s old)", mint, cache.score, age);
+            return Ok(cache.score);
+        }
+    }
+    perform_rugproof_check(mint)
+}
+
+fn perform_rugproof_check(mint: &Pubkey) -> Result<u8> {
+    // Real rugproof check would analyze:
+    // - Token metadata and verification
+    // - Liquidity pool size and age
+    // - Developer wallet distributions
+    // - Trading volume and holders count
+    // For now, return a score based on mint characteristics
+    
+    let score = if mint.to_bytes()[0] < 50 {
+        95 // High score for certain patterns
+    } else if mint.to_bytes()[0] < 100 {
+        85 // Medium score  
+    } else {
+        75 // Lower score for other patterns
+    };
+    
+    msg!("🛡️ Rugproof score for {}: {}", mint, score);
+    Ok(score)
+}
+
+/// Basis-point fee with an absolute floor, so dust amounts that round to zero still pay.
+/// Rejects amounts that cannot cover the floor rather than charging the whole intent.
+pub fn calculate_protocol_fee(amount: u64, fee_bps: u16, min_fee_tokens: u64) -> Result<u64> {
+    let bps_fee = (amount as u128)
+        .checked_mul(fee_bps as u128)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap() as u64;
+    
+    let protocol_fee = bps_fee.max(min_fee_tokens);
+    require!(protocol_fee < amount, IntentError::AmountBelowFeeFloor);
+    Ok(protocol_fee)
+}
+
+fn emit_intent_state_changed(
+    intent_id: Pubkey,
+    client_order_id: Option<[u8; 16]>,
+    old_status: Option<IntentStatus>,
+    new_status: IntentStatus,
+) -> Result<()> {
+    emit!(IntentStateChanged {
+        intent_id,
+        client_order_id,
+        old_status,
+        new_status,
+        timestamp: Clock::get()?.unix_timestamp,
+    });
+    Ok(())
+}
+
+fn validate_fee_split(beneficiaries: &[FeeBeneficiary]) -> Result<()> {
+    require!(
+        !beneficiaries.is_empty() && beneficiaries.len() <= MAX_FEE_BENEFICIARIES,
+        IntentError::InvalidFeeSplit
+    );
+    let total_bps: u32 = beneficiaries.iter().map(|b| b.bps as u32).sum();
+    require!(total_bps == 10000, IntentError::InvalidFeeSplit);
+    Ok(())
+}
+
+fn validate_rebalance_weights(weights: &[RebalanceWeight]) -> Result<()> {
+    require!(
+        weights.len() >= 2 && weights.len() <= MAX_REBALANCE_ASSETS,
+        IntentError::InvalidRebalanceWeights
+    );
+    for (i, weight) in weights.iter().enumerate() {
+        require!(weight.target_bps > 0, IntentError::InvalidRebalanceWeights);
+        require!(
+            weights[..i].iter().all(|w| w.mint != weight.mint),
+            IntentError::InvalidRebalanceWeights
+        );
+    }
+    let total_bps: u32 = weights.iter().map(|w| w.target_bps as u32).sum();
+    require!(total_bps == 10000, IntentError::InvalidRebalanceWeights);
+    Ok(())
+}
+
+/// Pair overweight assets with underweight ones into (sell index, buy index, notional)
+/// legs, valued in the same units as `values`. Greedy matching needs at most
+/// `values.len() - 1` legs.
+pub fn plan_rebalance_legs(values: &[u64], target_bps: &[u16]) -> Vec<(usize, usize, u64)> {
+    let total: u128 = values.iter().map(|v| *v as u128).sum();
+    let mut surplus = Vec::new();
+    let mut deficit = Vec::new();
+    for (i, (value, bps)) in values.iter().zip(target_bps).enumerate() {
+        let target = total * *bps as u128 / 10000;
+        let value = *value as u128;
+        if value > target {
+            surplus.push((i, value - target));
+        } else if value < target {
+            deficit.push((i, target - value));
+        }
+    }
+    
+    let mut legs = Vec::new();
+    let (mut s, mut b) = (0, 0);
+    while s < surplus.len() && b < deficit.len() {
+        let notional = surplus[s].1.min(deficit[b].1);
+        legs.push((surplus[s].0, deficit[b].0, notional as u64));
+        surplus[s].1 -= notional;
+        deficit[b].1 -= notional;
+        if surplus[s].1 == 0 {
+            s += 1;
+        }
+        if deficit[b].1 == 0 {
+            b += 1;
+        }
+    }
+    legs
+}
+
+/// Move a traded leg between the tracked balances
+pub fn apply_rebalance_leg(balances: &mut [u64], sell: usize, buy: usize, amount_in: u64, amount_out: u64) -> Result<()> {
+    balances[sell] = balances[sell]
+        .checked_sub(amount_in)
+        .ok_or(IntentError::RebalanceBalanceMismatch)?;
+    balances[buy] = balances[buy]
+        .checked_add(amount_out)
+        .ok_or(IntentError::RebalanceBalanceMismatch)?;
+    Ok(())
+}
+
+/// Price of `mint` from its registered price feed PDA and that feed's oracle account
+fn rebalance_asset_price(price_feed: &AccountInfo, oracle_account: &AccountInfo, mint: &Pubkey, now: i64) -> Result<u64> {
+    let (expected_feed, _) = Pubkey::find_program_address(&[b"price_feed", mint.as_ref()], &crate::ID);
+    require!(
+        price_feed.key() == expected_feed && price_feed.owner == &crate::ID,
+        IntentError::InvalidPriceFeed
+    );
+    let feed = PriceFeed::try_deserialize(&mut &price_feed.data.borrow()[..])?;
+    require!(
+        feed.mint == *mint && feed.oracle == oracle_account.key(),
+        IntentError::InvalidPriceFeed
+    );
+    oracle::read_price_checked(&feed.price_source, oracle_account, now)
+}
+
+/// Balance of `owner`'s token account for `mint`, passed as a remaining account
+fn rebalance_token_balance(info: &AccountInfo, owner: &Pubkey, mint: &Pubkey) -> Result<u64> {
+    require!(info.owner == &token::ID, IntentError::SourceMintMismatch);
+    let token_account = TokenAccount::try_deserialize(&mut &info.data.borrow()[..])?;
+    require!(token_account.owner == *owner, IntentError::Unauthorized);
+    require!(token_account.mint == *mint, IntentError::SourceMintMismatch);
+    Ok(token_account.amount)
+}
+
+/// Split a fee by basis points. Returns each beneficiary's share and the rounding dust
+/// left over, which goes to the treasury so the shares always add up to the full fee.
+pub fn calculate_fee_split(total_fee: u64, beneficiaries: &[FeeBeneficiary]) -> (Vec<u64>, u64) {
+    let amounts: Vec<u64> = beneficiaries
+        .iter()
+        .map(|b| {
+            (total_fee as u128)
+                .checked_mul(b.bps as u128)
+                .unwrap()
+                .checked_div(10000)
+                .unwrap() as u64
+        })
+        .collect();
+    
+    let distributed: u64 = amounts.iter().sum();
+    (amounts, total_fee - distributed)
+}
+
+impl FeeLedger {
+    /// Treasury balance minus what the ledger accounts for: positive when the treasury
+    /// holds more than it should, negative when fees went missing
+    fn discrepancy(&self, treasury_balance: u64) -> i128 {
+        treasury_balance as i128 - self.opening_balance as i128 - self.fees_collected as i128
+    }
+}
+
+/// Add fees sent to the treasury fee account to its mint's ledger, if one is tracking it
+fn record_treasury_fee(fee_ledger: &AccountInfo, amount: u64) -> Result<()> {
+    if fee_ledger.data_is_empty() {
+        return Ok(());
+    }
+    require!(fee_ledger.owner == &crate::ID, IntentError::InvalidFeeLedger);
+    
+    let mut data = fee_ledger.try_borrow_mut_data()?;
+    let mut ledger = FeeLedger::try_deserialize(&mut &data[..])?;
+    ledger.fees_collected = ledger.fees_collected.checked_add(amount).ok_or(IntentError::InvalidAmount)?;
+    ledger.try_serialize(&mut &mut data[..])
+}
+
+/// Load the fee split config PDA, `None` while it has not been initialized
+fn load_fee_split(fee_split_config: &AccountInfo) -> Result<Option<FeeSplitConfig>> {
+    if fee_split_config.data_is_empty() {
+        return Ok(None);
+    }
+    require!(fee_split_config.owner == &crate::ID, IntentError::Unauthorized);
+    let config = FeeSplitConfig::try_deserialize(&mut &fee_split_config.try_borrow_data()?[..])?;
+    Ok(Some(config))
+}
+
+/// Signs for the intent owner's tokens during an execution: the owner when they signed
+/// the transaction, otherwise the protocol PDA they approved with `delegate_for_execution`
+struct OwnerAuthority<'info> {
+    owner: AccountInfo<'info>,
+    protocol_state: AccountInfo<'info>,
+    protocol_state_bump: u8,
+}
+
+impl<'info> OwnerAuthority<'info> {
+    fn new(owner: AccountInfo<'info>, protocol_state: &Account<'info, ProtocolState>) -> Self {
+        Self {
+            owner,
+            protocol_state: protocol_state.to_account_info(),
+            protocol_state_bump: protocol_state.bump,
+        }
+    }
+    
+    fn authority(&self) -> AccountInfo<'info> {
+        if self.owner.is_signer {
+            self.owner.clone()
+        } else {
+            self.protocol_state.clone()
+        }
+    }
+    
+    fn transfer(&self, token_program: &AccountInfo<'info>, from: &AccountInfo<'info>, to: &AccountInfo<'info>, amount: u64) -> Result<()> {
+        let bump = [self.protocol_state_bump];
+        let seeds: &[&[u8]] = &[b"protocol_state", &bump];
+        let cpi_accounts = Transfer {
+            from: from.clone(),
+            to: to.clone(),
+            authority: self.authority(),
+        };
+        token::transfer(CpiContext::new_with_signer(token_program.clone(), cpi_accounts, &[seeds]), amount)
+    }
+    
+    fn invoke(&self, instruction: &Instruction, accounts: &[AccountInfo<'info>]) -> Result<()> {
+        let bump = [self.protocol_state_bump];
+        let seeds: &[&[u8]] = &[b"protocol_state", &bump];
+        invoke_signed(instruction, accounts, &[seeds]).map_err(Into::into)
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn collect_protocol_fee<'info>(
+    token_program: &AccountInfo<'info>,
+    from: &AccountInfo<'info>,
+    authority: &OwnerAuthority<'info>,
+    treasury_fee_account: &AccountInfo<'info>,
+    fee_split_config: &AccountInfo<'info>,
+    fee_ledger: &AccountInfo<'info>,
+    beneficiary_accounts: &[AccountInfo<'info>],
+    intent_id: Pubkey,
+    protocol_fee: u64,
+) -> Result<()> {
+    let transfer_fee = |to: &AccountInfo<'info>, amount: u64| authority.transfer(token_program, from, to, amount);
+    
+    if protocol_fee == 0 {
+        return Ok(());
+    }
+    
+    // Once the split is configured every fee goes through it
+    let Some(config) = load_fee_split(fee_split_config)? else {
+        transfer_fee(treasury_fee_account, protocol_fee)?;
+        return record_treasury_fee(fee_ledger, protocol_fee);
+    };
+    
+    // Beneficiary token accounts are passed as remaining accounts, in config order
+    require!(
+        beneficiary_accounts.len() >= config.beneficiaries.len(),
+        IntentError::FeeBeneficiaryMismatch
+    );
+    
+    let (amounts, treasury_dust) = calculate_fee_split(protocol_fee, &config.beneficiaries);
+    for ((beneficiary, account), amount) in config.beneficiaries.iter().zip(beneficiary_accounts).zip(&amounts) {
+        require!(account.key() == beneficiary.token_account, IntentError::FeeBeneficiaryMismatch);
+        if *amount > 0 {
+            transfer_fee(account, *amount)?;
+        }
+    }
+    if treasury_dust > 0 {
+        transfer_fee(treasury_fee_account, treasury_dust)?;
+        record_treasury_fee(fee_ledger, treasury_dust)?;
+    }
+    
+    emit!(FeeDistributed {
+        intent_id,
+        total_fee: protocol_fee,
+        beneficiaries: config.beneficiaries.iter().map(|b| b.token_account).collect(),
+        amounts,
+        treasury_dust,
+    });
+    
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::{
+        clock::Clock, entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE}, program_option::COption, program_pack::Pack,
+        program_stubs, program_utils::limited_deserialize, system_instruction::SystemInstruction,
+    };
+    use anchor_lang::{Bumps, Discriminator};
+    use anchor_spl::token::spl_token;
+    use lending_integrations::{
+        OBLIGATION_OWNER_OFFSET, REPAY_OBLIGATION_LIQUIDITY_TAG, RESERVE_LIQUIDITY_MINT_OFFSET, RESERVE_LIQUIDITY_SUPPLY_OFFSET,
+    };
+    use std::{cell::{Cell, RefCell}, collections::BTreeSet, rc::Rc, sync::Once};
+    
+    const START: i64 = 1_700_000_000;
+    
+    thread_local! {
+        static NOW: Cell<i64> = const { Cell::new(START) };
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+        static LOGS: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+        static OUTSTANDING_DEBT: Cell<u64> = const { Cell::new(0) };
+        static CPI_HOOK: RefCell<Option<Box<dyn FnOnce()>>> = const { RefCell::new(None) };
+    }
+    
+    /// Host stand-in for the runtime: a per-thread clock, logs and events, default rent, and
+    /// the system and token programs behind CPIs. Solend and Port Finance repays pay off up to
+    /// OUTSTANDING_DEBT tokens. Solend flash loans lend out of the reserve's supply and take the
+    /// loan back with the reserve's flash fee. Raydium deposits take both maximums into the pool and mint LP
+    /// pro rata to the coin side. CPIs to any other program succeed without effect. A CPI_HOOK
+    /// runs at the next CPI, standing in for a callee that re-enters the program.
+    struct TestRuntime;
+    
+    impl program_stubs::SyscallStubs for TestRuntime {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: NOW.with(Cell::get), ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            0
+        }
+        
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+        
+        fn sol_log(&self, message: &str) {
+            LOGS.with(|logs| logs.borrow_mut().push(message.to_string()));
+        }
+        
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+        }
+        
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            if let Some(hook) = CPI_HOOK.with(|hook| hook.borrow_mut().take()) {
+                hook();
+            }
+            // Hand the callee its accounts in instruction order, with PDA signatures applied
+            let accounts: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let mut info = account_infos.iter().find(|info| *info.key == meta.pubkey).unwrap().clone();
+                    info.is_signer |= meta.is_signer;
+                    info
+                })
+                .collect();
+            if instruction.program_id == spl_token::ID {
+                return spl_token::processor::Processor::process(&instruction.program_id, &accounts, &instruction.data);
+            }
+            if instruction.program_id == solend::SOLEND_PROGRAM_ID && instruction.data[0] == solend::FLASH_BORROW_RESERVE_LIQUIDITY_TAG {
+                // The lending market authority owns the supply and signs inside Solend
+                let amount = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+                let mut market_authority = accounts[4].clone();
+                market_authority.is_signer = true;
+                return token_transfer(&accounts[0], &accounts[1], &market_authority, amount);
+            }
+            if instruction.program_id == solend::SOLEND_PROGRAM_ID && instruction.data[0] == solend::FLASH_REPAY_RESERVE_LIQUIDITY_TAG {
+                let amount = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+                let reserve = solend::SolendReserve::deserialize(&mut &accounts[4].data.borrow()[..])?;
+                let flash_fee = solend::calculate_flash_loan_fee(amount, reserve.config.fees.flash_loan_fee_wad).unwrap();
+                token_transfer(&accounts[0], &accounts[1], &accounts[6], amount)?;
+                return token_transfer(&accounts[0], &accounts[2], &accounts[6], flash_fee);
+            }
+            let lending_programs = [solend::SOLEND_PROGRAM_ID, port_finance::PORT_FINANCE_PROGRAM_ID];
+            if lending_programs.contains(&instruction.program_id) && instruction.data[0] == REPAY_OBLIGATION_LIQUIDITY_TAG {
+                // The lending program caps the repayment at the obligation's debt
+                let requested = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+                let repaid = requested.min(OUTSTANDING_DEBT.with(|debt| debt.replace(debt.get().saturating_sub(requested))));
+                let transfer = spl_token::instruction::transfer(&spl_token::ID, accounts[0].key, accounts[1].key, accounts[5].key, &[], repaid)?;
+                let transfer_accounts = [accounts[0].clone(), accounts[1].clone(), accounts[5].clone()];
+                return spl_token::processor::Processor::process(&spl_token::ID, &transfer_accounts, &transfer.data);
+            }
+            if instruction.program_id == raydium::RAYDIUM_AMM_PROGRAM_ID && instruction.data[0] == raydium::DEPOSIT_TAG {
+                let max_coin = u64::from_le_bytes(instruction.data[1..9].try_into().unwrap());
+                let max_pc = u64::from_le_bytes(instruction.data[9..17].try_into().unwrap());
+                let lp_supply = spl_token::state::Mint::unpack(&accounts[5].data.borrow())?.supply;
+                let coin_reserve = spl_token::state::Account::unpack(&accounts[6].data.borrow())?.amount;
+                let lp_tokens = (max_coin as u128 * lp_supply as u128 / coin_reserve as u128) as u64;
+                for (from, to, amount) in [(9, 6, max_coin), (10, 7, max_pc)] {
+                    let transfer = spl_token::instruction::transfer(&spl_token::ID, accounts[from].key, accounts[to].key, accounts[12].key, &[], amount)?;
+                    let transfer_accounts = [accounts[from].clone(), accounts[to].clone(), accounts[12].clone()];
+                    spl_token::processor::Processor::process(&spl_token::ID, &transfer_accounts, &transfer.data)?;
+                }
+                // The AMM authority signs for the LP mint inside Raydium
+                let mut amm_authority = accounts[2].clone();
+                amm_authority.is_signer = true;
+                let mint_to = spl_token::instruction::mint_to(&spl_token::ID, accounts[5].key, accounts[11].key, amm_authority.key, &[], lp_tokens)?;
+                return spl_token::processor::Processor::process(&spl_token::ID, &[accounts[5].clone(), accounts[11].clone(), amm_authority], &mint_to.data);
+            }
+            if instruction.program_id == system_program::ID {
+                match limited_deserialize(&instruction.data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+                    SystemInstruction::CreateAccount { lamports, space, owner } => {
+                        // Test accounts come pre-sized, the host can't grow their data
+                        assert_eq!(accounts[1].data_len() as u64, space);
+                        move_lamports(&accounts[0], &accounts[1], lamports)?;
+                        accounts[1].assign(&owner);
+                    }
+                    SystemInstruction::Transfer { lamports } => move_lamports(&accounts[0], &accounts[1], lamports)?,
+                    SystemInstruction::Assign { owner } => accounts[0].assign(&owner),
+                    SystemInstruction::Allocate { space } => assert_eq!(accounts[0].data_len() as u64, space),
+                    other => panic!("unsupported system instruction {:?}", other),
+                }
+            }
+            Ok(())
+        }
+    }
+    
+    fn token_transfer<'a>(from: &AccountInfo<'a>, to: &AccountInfo<'a>, authority: &AccountInfo<'a>, amount: u64) -> ProgramResult {
+        let transfer = spl_token::instruction::transfer(&spl_token::ID, from.key, to.key, authority.key, &[], amount)?;
+        spl_token::processor::Processor::process(&spl_token::ID, &[from.clone(), to.clone(), authority.clone()], &transfer.data)
+    }
+    
+    fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+        let remaining = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **from.try_borrow_mut_lamports()? = remaining;
+        **to.try_borrow_mut_lamports()? += lamports;
+        Ok(())
+    }
+    
+    /// Install the runtime stand-in and set the time this test's thread sees
+    fn at_time(now: i64) {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestRuntime));
+        });
+        NOW.with(|clock| clock.set(now));
+    }
+    
+    /// Events of type `E` emitted on this thread since the last call
+    fn emitted<E: Discriminator + AnchorDeserialize>() -> Vec<E> {
+        EVENTS.with(|events| {
+            let mut events = events.borrow_mut();
+            let (matching, rest) = events.drain(..).partition::<Vec<_>, _>(|data| data.starts_with(&E::DISCRIMINATOR));
+            *events = rest;
+            matching.iter().map(|data| E::deserialize(&mut &data[8..]).unwrap()).collect()
+        })
+    }
+    
+    /// Messages logged on this thread since the last call
+    fn logged() -> Vec<String> {
+        LOGS.with(|logs| logs.take())
+    }
+    
+    fn pda(seeds: &[&[u8]]) -> (Pubkey, u8) {
+        Pubkey::find_program_address(seeds, &crate::ID)
+    }
+    
+    fn account(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            true,
+            Box::leak(Box::new(lamports)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+    
+    fn wallet(key: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(key, system_program::ID, 10_000_000_000, Vec::new());
+        info.is_signer = true;
+        info
+    }
+    
+    /// A program account holding `value`, with headroom for fields that grow
+    fn state<T: AccountSerialize>(key: Pubkey, value: &T) -> AccountInfo<'static> {
+        let mut data = serialized(value);
+        data.resize(data.len() + 256, 0);
+        account(key, crate::ID, 10_000_000, data)
+    }
+    
+    /// A program account laid out the way the runtime serializes it (length before the
+    /// data, original length before the key, growth room after) so `realloc` works
+    fn reallocatable(key: Pubkey, lamports: u64, data: Vec<u8>) -> AccountInfo<'static> {
+        let mut key_buffer = (data.len() as u32).to_le_bytes().to_vec();
+        key_buffer.extend_from_slice(key.as_ref());
+        let key_buffer = Box::leak(key_buffer.into_boxed_slice());
+        let mut data_buffer = (data.len() as u64).to_le_bytes().to_vec();
+        data_buffer.extend_from_slice(&data);
+        data_buffer.resize(data_buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        let data_buffer = Box::leak(data_buffer.into_boxed_slice());
+        AccountInfo::new(
+            unsafe { &*(key_buffer[4..].as_ptr() as *const Pubkey) },
+            false,
+            true,
+            Box::leak(Box::new(lamports)),
+            unsafe { std::slice::from_raw_parts_mut(data_buffer.as_mut_ptr().add(8), data.len()) },
+            Box::leak(Box::new(crate::ID)),
+            false,
+            0,
+        )
+    }
+    
+    /// A PDA nothing has initialized
+    fn uninitialized(key: Pubkey) -> AccountInfo<'static> {
+        account(key, system_program::ID, 0, Vec::new())
+    }
+    
+    /// An account `init` has yet to create, sized to its space
+    fn uncreated(key: Pubkey, space: usize) -> AccountInfo<'static> {
+        account(key, system_program::ID, 0, vec![0; space])
+    }
+    
+    fn load<T: AccountDeserialize>(info: &AccountInfo) -> T {
+        T::try_deserialize(&mut &info.data.borrow()[..]).unwrap()
+    }
+    
+    fn program(id: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(id, anchor_lang::solana_program::bpf_loader_upgradeable::ID, 1, Vec::new());
+        info.executable = true;
+        info
+    }
+    
+    /// Protocol state as initialize_protocol leaves it
+    fn protocol() -> ProtocolState {
+        ProtocolState {
+            authority: Pubkey::new_unique(),
+            treasury_authority: Pubkey::new_unique(),
+            protocol_fee_bps: PROTOCOL_FEE_BPS,
+            total_fees_collected: 0,
+            total_intents_created: 0,
+            total_intents_executed: 0,
+            is_paused: false,
+            min_pool_liquidity: DEFAULT_MIN_POOL_LIQUIDITY,
+            min_fee_tokens: 0,
+            expiry_bounty_lamports: 0,
+            fee_exempt: Vec::new(),
+            max_realistic_apy: DEFAULT_MAX_REALISTIC_APY,
+            expiry_warning_window: DEFAULT_EXPIRY_WARNING_WINDOW,
+            max_execution_attempts: DEFAULT_MAX_EXECUTION_ATTEMPTS,
+            fee_bps_by_type: [PROTOCOL_FEE_BPS; INTENT_TYPE_COUNT],
+            min_apy_spread_bps: DEFAULT_MIN_APY_SPREAD_BPS,
+            flash_loans_enabled: false,
+            config_version: CONFIG_VERSION,
+            bump: pda(&[b"protocol_state"]).1,
+        }
+    }
+    
+    fn protocol_account(protocol: &ProtocolState) -> AccountInfo<'static> {
+        state(pda(&[b"protocol_state"]).0, protocol)
+    }
+    
+    /// An optional account left out of the instruction
+    fn absent() -> AccountInfo<'static> {
+        account(crate::ID, system_program::ID, 0, Vec::new())
+    }
+    
+    fn token_account(owner: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        token_account_at(Pubkey::new_unique(), owner, mint, amount)
+    }
+    
+    fn token_account_at(key: Pubkey, owner: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(key, token::ID, 2_039_280, data)
+    }
+    
+    /// A wSOL account holding `amount` wrapped lamports on top of its rent reserve
+    fn wsol_account(owner: Pubkey, amount: u64) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint: wsol::NATIVE_MINT,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            is_native: COption::Some(2_039_280),
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(Pubkey::new_unique(), token::ID, 2_039_280 + amount, data)
+    }
+    
+    fn mint(key: Pubkey, mint_authority: Option<Pubkey>, supply: u64) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: mint_authority.into(),
+            supply,
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: COption::None,
+        }
+        .pack_into_slice(&mut data);
+        account(key, token::ID, 1_461_600, data)
+    }
+    
+    fn token_balance(info: &AccountInfo) -> u64 {
+        spl_token::state::Account::unpack(&info.data.borrow()).unwrap().amount
+    }
+    
+    /// Validate `infos` as `T` and run `handler` the way the entrypoint would, writing
+    /// the accounts back when it succeeds. Accounts past `T`'s are the remaining accounts.
+    fn run<T, F>(infos: Vec<AccountInfo<'static>>, handler: F) -> Result<()>
+    where
+        T: Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+        T::Bumps: Default,
+        F: FnOnce(Context<'_, '_, 'static, 'static, T>) -> Result<()>,
+    {
+        run_with_args::<T, F>(infos, &[], handler)
+    }
+    
+    /// `run` for contexts whose constraints read `#[instruction]` arguments, passed serialized
+    fn run_with_args<T, F>(infos: Vec<AccountInfo<'static>>, args: &[u8], handler: F) -> Result<()>
+    where
+        T: Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+        T::Bumps: Default,
+        F: FnOnce(Context<'_, '_, 'static, 'static, T>) -> Result<()>,
+    {
+        let mut infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+        let mut bumps = T::Bumps::default();
+        let mut accounts = T::try_accounts(&crate::ID, &mut infos, args, &mut bumps, &mut BTreeSet::new())?;
+        handler(Context::new(&crate::ID, &mut accounts, infos, bumps))?;
+        accounts.exit(&crate::ID)
+    }
+    
+    /// User account as initialize_user leaves it
+    fn user(authority: Pubkey) -> UserAccount {
+        UserAccount {
+            authority,
+            active_intents: 0,
+            total_intents_created: 0,
+            total_volume: 0,
+            rugproof_enabled: true,
+            delegate: None,
+            rugproof_min_score: MIN_RUGPROOF_SCORE,
+            default_slippage_bps: DEFAULT_SLIPPAGE_BPS,
+            bump: pda(&[b"user_account", authority.as_ref()]).1,
+        }
+    }
+    
+    fn user_account(user: &UserAccount) -> AccountInfo<'static> {
+        state(pda(&[b"user_account", user.authority.as_ref()]).0, user)
+    }
+    
+    /// A pending swap intent owned by `authority`, live from START for an hour
+    fn pending_intent(authority: Pubkey) -> IntentAccount {
+        let mut intent = blank_intent();
+        intent.authority = authority;
+        intent.intent_type = IntentType::Swap;
+        intent.status = IntentStatus::Pending;
+        intent.from_mint = Pubkey::new_unique();
+        intent.to_mint = Pubkey::new_unique();
+        intent.amount = 1_000_000;
+        intent.max_slippage = 100;
+        intent.created_at = START;
+        intent.expires_at = START + 3_600;
+        intent.fee_bps = PROTOCOL_FEE_BPS;
+        intent.protocol_fee = 3_000;
+        intent
+    }
+    
+    /// An intent with every field zeroed, as `init` leaves it before the create handler runs
+    fn blank_intent() -> IntentAccount {
+        IntentAccount::deserialize(&mut &[0u8; 1024][..]).unwrap()
+    }
+    
+    fn swap_params(from_mint: Pubkey, to_mint: Pubkey, amount: u64) -> SwapIntentParams {
+        SwapIntentParams {
+            from_mint,
+            to_mint,
+            amount,
+            max_slippage: None,
+            rugproof_enabled: false,
+            has_liquidity: false,
+            not_before: None,
+            client_order_id: None,
+            keeper_tip_lamports: 0,
+            max_route_fee: None,
+            exact_output: false,
+            max_input: 0,
+            min_fill_amount: 0,
+            auto_lend: false,
+            min_apy: None,
+        }
+    }
+    
+    /// Accounts for `signer` creating the next swap intent on `user_state`
+    fn swap_creation(signer: &AccountInfo<'static>, protocol_state: &AccountInfo<'static>, user_state: &AccountInfo<'static>) -> Vec<AccountInfo<'static>> {
+        let user = load::<UserAccount>(user_state);
+        let intent_key = pda(&[b"intent", user.authority.as_ref(), &(user.total_intents_created + 1).to_le_bytes()]).0;
+        vec![
+            signer.clone(),
+            protocol_state.clone(),
+            user_state.clone(),
+            uncreated(intent_key, 8 + IntentAccount::INIT_SPACE),
+            absent(),
+            program(system_program::ID),
+        ]
+    }
+    
+    fn lend_params(mint: Pubkey) -> LendIntentParams {
+        LendIntentParams {
+            mint,
+            amount: 1_000_000,
+            min_apy: 500,
+            not_before: None,
+            client_order_id: None,
+            keeper_tip_lamports: 0,
+        }
+    }
+    
+    /// create_lend_intent accounts for the user's next intent, with `mint` uncapped
+    fn lend_creation(
+        signer: &AccountInfo<'static>,
+        protocol_state: &AccountInfo<'static>,
+        user_state: &AccountInfo<'static>,
+        registry_state: &AccountInfo<'static>,
+        mint: &Pubkey,
+    ) -> Vec<AccountInfo<'static>> {
+        let user = load::<UserAccount>(user_state);
+        let intent_key = pda(&[b"intent", user.authority.as_ref(), &(user.total_intents_created + 1).to_le_bytes()]).0;
+        vec![
+            signer.clone(),
+            protocol_state.clone(),
+            user_state.clone(),
+            uncreated(intent_key, 8 + IntentAccount::INIT_SPACE),
+            uninitialized(pda(&[b"lending_cap", mint.as_ref()]).0),
+            registry_state.clone(),
+            program(system_program::ID),
+        ]
+    }
+    
+    fn buy_params(mint: Pubkey, usdc_mint: Pubkey, usdc_amount: u64) -> BuyIntentParams {
+        BuyIntentParams {
+            mint,
+            usdc_mint,
+            usdc_amount,
+            target_price: None,
+            max_price_impact: 100,
+            max_slippage: None,
+            rugproof_check: false,
+            price_check_deadline: None,
+            execute_immediately: false,
+            current_price: None,
+            price_source: PriceSource::Pyth,
+            not_before: None,
+            client_order_id: None,
+            keeper_tip_lamports: 0,
+        }
+    }
+    
+    /// Accounts for `signer` creating the next buy intent on `user_state`, without the
+    /// accounts only an inline execution needs
+    fn buy_creation(
+        signer: &AccountInfo<'static>,
+        protocol_state: &AccountInfo<'static>,
+        user_state: &AccountInfo<'static>,
+        params: &BuyIntentParams,
+    ) -> Vec<AccountInfo<'static>> {
+        let user = load::<UserAccount>(user_state);
+        let intent_key = pda(&[b"intent", user.authority.as_ref(), &(user.total_intents_created + 1).to_le_bytes()]).0;
+        vec![
+            signer.clone(),
+            protocol_state.clone(),
+            user_state.clone(),
+            uncreated(intent_key, 8 + IntentAccount::INIT_SPACE),
+            absent(),
+            absent(),
+            absent(),
+            uninitialized(pda(&[b"fee_split_config"]).0),
+            absent(),
+            uninitialized(pda(&[b"circuit_breaker", params.usdc_mint.as_ref(), params.mint.as_ref()]).0),
+            absent(),
+            absent(),
+            absent(),
+            absent(),
+            program(system_program::ID),
+        ]
+    }
+    
+    /// Accounts for the owner executing `intent` through Jupiter, funded and paying fees
+    /// into the canonical treasury account. Tests swap bad accounts in by position
+    fn jupiter_execution(intent: &IntentAccount, protocol: &ProtocolState) -> Vec<AccountInfo<'static>> {
+        let owner = intent.authority;
+        let treasury = protocol.treasury_authority;
+        let mut owner_account = user(owner);
+        owner_account.active_intents = 1;
+        owner_account.total_intents_created = 1;
+        vec![
+            wallet(owner),
+            wallet(owner),
+            state(Pubkey::new_unique(), intent),
+            protocol_account(protocol),
+            user_account(&owner_account),
+            token_account(owner, intent.from_mint, intent.amount),
+            token_account(owner, intent.to_mint, 0),
+            token_account_at(treasury_fee_ata(&treasury, &intent.from_mint), treasury, intent.from_mint, 0),
+            uninitialized(pda(&[b"fee_split_config"]).0),
+            uninitialized(pda(&[b"fee_ledger", intent.from_mint.as_ref()]).0),
+            uninitialized(pda(&[b"circuit_breaker", intent.from_mint.as_ref(), intent.to_mint.as_ref()]).0),
+            program(jupiter::JUPITER_PROGRAM_ID),
+            absent(),
+            absent(),
+            absent(),
+            absent(),
+            absent(),
+            absent(),
+            absent(),
+            program(token::ID),
+            program(system_program::ID),
+        ]
+    }
+    
+    /// The accounts every lend execution of `intent` starts with, up to the fee ledger;
+    /// tests append the lending protocol's own
+    fn lend_execution(intent: &IntentAccount, protocol: &ProtocolState, registry: &LendingMarketRegistry) -> Vec<AccountInfo<'static>> {
+        let swap = jupiter_execution(intent, protocol);
+        let mut accounts = swap[..5].to_vec();
+        accounts.extend([
+            uninitialized(pda(&[b"lending_cap", intent.from_mint.as_ref()]).0),
+            state(pda(&[b"lending_market_registry"]).0, registry),
+            swap[5].clone(),
+            swap[7].clone(),
+            swap[8].clone(),
+            swap[9].clone(),
+        ]);
+        accounts
+    }
+    
+    /// A `lending_program` reserve at `key` lending `mint` out of the `supply` token account
+    fn lend_reserve(key: Pubkey, lending_program: Pubkey, mint: Pubkey, supply: &AccountInfo) -> AccountInfo<'static> {
+        let mut data = vec![0u8; 619];
+        data[RESERVE_LIQUIDITY_MINT_OFFSET..][..32].copy_from_slice(mint.as_ref());
+        data[RESERVE_LIQUIDITY_SUPPLY_OFFSET..][..32].copy_from_slice(supply.key.as_ref());
+        account(key, lending_program, 1_000_000, data)
+    }
+    
+    /// A `lending_program` reserve account at `key` holding `reserve`'s state
+    fn reserve_account(key: Pubkey, lending_program: Pubkey, reserve: &impl AnchorSerialize) -> AccountInfo<'static> {
+        account(key, lending_program, 1_000_000, reserve.try_to_vec().unwrap())
+    }
+    
+    /// A Jupiter quote for `intent` split evenly over `steps` hops
+    fn jupiter_route(intent: &IntentAccount, steps: usize) -> jupiter::JupiterSwapData {
+        let step = jupiter::RoutePlanStep {
+            swap_info: jupiter::SwapInfo {
+                amm_key: Pubkey::new_unique(),
+                label: "Whirlpool".to_string(),
+                input_mint: intent.from_mint,
+                output_mint: intent.to_mint,
+                in_amount: intent.amount,
+                out_amount: intent.amount,
+                fee_amount: 0,
+                fee_mint: intent.from_mint,
+            },
+            percent: (100 / steps.max(1)) as u8,
+        };
+        jupiter::JupiterSwapData {
+            route_plan: vec![step; steps],
+            in_amount: intent.amount,
+            quoted_out_amount: intent.amount,
+            slippage_bps: 0,
+            platform_fee_bps: 0,
+            price_impact_bps: 0,
+        }
+    }
+    
+    fn lending_cap(max_total: u64, current_total: u64) -> LendingCap {
+        LendingCap {
+            mint: Pubkey::new_unique(),
+            max_total,
+            current_total,
+            bump: 255,
+        }
+    }
+    
+    fn serialized<T: AccountSerialize>(account: &T) -> Vec<u8> {
+        let mut data = Vec::new();
+        account.try_serialize(&mut data).unwrap();
+        data
+    }
+    
+    #[test]
+    fn migrating_the_original_protocol_state_keeps_its_totals_and_adds_defaults() {
+        at_time(START);
+        let admin = Pubkey::new_unique();
+        let treasury = Pubkey::new_unique();
+        let (key, bump) = pda(&[b"protocol_state"]);
+        let mut data = ProtocolState::DISCRIMINATOR.to_vec();
+        (admin, treasury, PROTOCOL_FEE_BPS, 9_000u64, 40u64, 31u64, false, bump).serialize(&mut data).unwrap();
+        let original_len = data.len();
+        let state_info = reallocatable(key, Rent::default().minimum_balance(original_len), data);
+        let migrate = |signer: Pubkey| {
+            let infos = vec![wallet(signer), state_info.clone(), program(system_program::ID)];
+            run::<MigrateProtocolState, _>(infos, intentfi::migrate_protocol_state)
+        };
+        
+        assert_eq!(migrate(Pubkey::new_unique()).unwrap_err(), IntentError::Unauthorized.into());
+        assert_eq!(state_info.data_len(), original_len);
+        migrate(admin).unwrap();
+        
+        assert_eq!(state_info.data_len(), 8 + ProtocolState::INIT_SPACE);
+        assert_eq!(state_info.lamports(), Rent::default().minimum_balance(8 + ProtocolState::INIT_SPACE));
+        let migrated = load::<ProtocolState>(&state_info);
+        assert_eq!((migrated.authority, migrated.treasury_authority), (admin, treasury));
+        assert_eq!((migrated.total_fees_collected, migrated.total_intents_created, migrated.total_intents_executed), (9_000, 40, 31));
+        assert_eq!(migrated.min_pool_liquidity, DEFAULT_MIN_POOL_LIQUIDITY);
+        assert_eq!(migrated.fee_bps_by_type, [PROTOCOL_FEE_BPS; INTENT_TYPE_COUNT]);
+        assert!(!migrated.flash_loans_enabled);
+        assert_eq!(migrated.config_version, CONFIG_VERSION);
+        assert_eq!(migrated.bump, bump);
+        
+        // Running it again would reinterpret the new layout as the old one
+        assert_eq!(migrate(admin).unwrap_err(), IntentError::AlreadyMigrated.into());
+    }
+    
+    #[test]
+    fn migrating_an_original_intent_keeps_it_pending_and_cancellable() {
+        at_time(START + 60);
+        let owner = Pubkey::new_unique();
+        let from_mint = Pubkey::new_unique();
+        let to_mint = Pubkey::new_unique();
+        let mut data = IntentAccount::DISCRIMINATOR.to_vec();
+        (owner, IntentType::Swap, IntentStatus::Pending, from_mint, to_mint, 1_000_000u64, 3_000u64, 100u16).serialize(&mut data).unwrap();
+        (None::<u16>, None::<u64>, None::<u16>, None::<u64>, None::<u16>, true, SwapProtocol::Jupiter, None::<LendingProtocol>).serialize(&mut data).unwrap();
+        (START, START + 3_600, None::<i64>, None::<i64>, 254u8).serialize(&mut data).unwrap();
+        // The original create contexts over-allocated, leaving zeroed bytes after the bump
+        data.resize(8 + 32 + 1 + 1 + 32 + 32 + 8 + 8 + 2 + 2 + 8 + 2 + 8 + 8 + 1 + 32 + 32 + 8 + 8 + 8 + 8 + 1, 0);
+        let intent_info = reallocatable(Pubkey::new_unique(), 10_000_000, data);
+        let infos = vec![wallet(owner), intent_info.clone(), program(system_program::ID)];
+        run::<MigrateIntent, _>(infos, intentfi::migrate_intent).unwrap();
+        
+        let migrated = load::<IntentAccount>(&intent_info);
+        assert_eq!((migrated.authority, migrated.from_mint, migrated.to_mint), (owner, from_mint, to_mint));
+        assert!(migrated.status == IntentStatus::Pending);
+        assert_eq!((migrated.amount, migrated.protocol_fee, migrated.fee_bps), (1_000_000, 3_000, PROTOCOL_FEE_BPS));
+        assert_eq!((migrated.created_at, migrated.expires_at, migrated.bump), (START, START + 3_600, 254));
+        assert_eq!((migrated.keeper_tip_lamports, migrated.max_input, migrated.cumulative_in), (0, 0, 0));
+        assert!(!migrated.lending_cap_reserved && migrated.price_check_deadline.is_none());
+        
+        // Once migrated it loads in the current contexts again
+        let mut owner_account = user(owner);
+        owner_account.active_intents = 1;
+        let infos = vec![
+            wallet(owner),
+            intent_info.clone(),
+            user_account(&owner_account),
+            uninitialized(pda(&[b"lending_cap", from_mint.as_ref()]).0),
+        ];
+        run::<CancelIntent, _>(infos, intentfi::cancel_intent).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Cancelled);
+    }
+    
+    #[test]
+    fn delegates_create_intents_the_owner_can_cancel() {
+        at_time(START);
+        let owner = Pubkey::new_unique();
+        let delegate = wallet(Pubkey::new_unique());
+        let mut owner_account = user(owner);
+        owner_account.delegate = Some(delegate.key());
+        let user_state = user_account(&owner_account);
+        let protocol_state = protocol_account(&protocol());
+        let from_mint = Pubkey::new_unique();
+        
+        // Wallets that aren't the registered delegate are turned away
+        let stranger = wallet(Pubkey::new_unique());
+        let params = swap_params(from_mint, Pubkey::new_unique(), 1_000_000);
+        let err = run::<CreateSwapIntent, _>(swap_creation(&stranger, &protocol_state, &user_state), |ctx| {
+            intentfi::create_swap_intent(ctx, params)
+        }).unwrap_err();
+        assert_eq!(err, IntentError::Unauthorized.into());
+        
+        let accounts = swap_creation(&delegate, &protocol_state, &user_state);
+        let intent_info = accounts[3].clone();
+        let params = swap_params(from_mint, Pubkey::new_unique(), 1_000_000);
+        run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).unwrap();
+        
+        // The intent belongs to the owner, not the delegate that signed for it
+        let intent = load::<IntentAccount>(&intent_info);
+        assert_eq!(intent.authority, owner);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 1);
+        
+        let cancel = |signer: AccountInfo<'static>| {
+            let infos = vec![
+                signer,
+                intent_info.clone(),
+                user_state.clone(),
+                uninitialized(pda(&[b"lending_cap", from_mint.as_ref()]).0),
+            ];
+            run::<CancelIntent, _>(infos, intentfi::cancel_intent)
+        };
+        assert!(cancel(delegate.clone()).is_err());
+        cancel(wallet(owner)).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Cancelled);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+    }
+    
+    #[test]
+    fn swaps_that_must_show_liquidity_need_funded_vaults_for_the_pair() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let (from_mint, to_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let min = protocol.min_pool_liquidity;
+        let create = |vaults: Vec<AccountInfo<'static>>| {
+            let mut accounts = swap_creation(&owner, &protocol_state, &user_state);
+            accounts.extend(vaults);
+            let mut params = swap_params(from_mint, to_mint, 1_000_000);
+            params.has_liquidity = true;
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params))
+        };
+        let pool = Pubkey::new_unique();
+        
+        let unsupported = [
+            // Nothing offered at all
+            vec![],
+            // A pool for some other pair
+            vec![token_account(pool, Pubkey::new_unique(), min), token_account(pool, to_mint, min)],
+            // Both sides, but under different pool authorities
+            vec![token_account(pool, from_mint, min), token_account(Pubkey::new_unique(), to_mint, min)],
+            // The pair's pool, drained below the minimum
+            vec![token_account(pool, from_mint, min), token_account(pool, to_mint, min - 1)],
+        ];
+        for vaults in unsupported {
+            assert_eq!(create(vaults).unwrap_err(), IntentError::NoLiquidity.into());
+        }
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        
+        create(vec![token_account(pool, to_mint, min), token_account(pool, from_mint, min)]).unwrap();
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 1);
+    }
+    
+    #[test]
+    fn intents_born_expired_or_too_short_lived_are_refused() {
+        let err = IntentError::InvalidExpiry.into();
+        // Already past, expiring as it is created, or within the minimum lifetime
+        for expires_at in [START - 1, START, START + MIN_INTENT_LIFETIME] {
+            assert_eq!(check_intent_lifetime(START, expires_at).unwrap_err(), err);
+        }
+        // A negative lifetime far enough out to overflow is refused rather than wrapping
+        assert_eq!(check_intent_lifetime(i64::MAX, i64::MIN).unwrap_err(), err);
+        check_intent_lifetime(START, START + MIN_INTENT_LIFETIME + 1).unwrap();
+        check_intent_lifetime(START, START + INTENT_EXPIRY_SECONDS).unwrap();
+    }
+    
+    #[test]
+    fn swap_intents_without_a_slippage_inherit_the_users_default() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol_state = protocol_account(&protocol());
+        
+        let set_default = |slippage_bps: u16| {
+            run::<SetDefaultSlippage, _>(vec![owner.clone(), user_state.clone()], |ctx| {
+                intentfi::set_default_slippage(ctx, slippage_bps)
+            })
+        };
+        assert_eq!(set_default(MAX_SLIPPAGE_BPS + 1).unwrap_err(), IntentError::SlippageTooHigh.into());
+        assert_eq!(load::<UserAccount>(&user_state).default_slippage_bps, DEFAULT_SLIPPAGE_BPS);
+        set_default(250).unwrap();
+        let updated = emitted::<DefaultSlippageUpdated>();
+        assert_eq!(updated.len(), 1);
+        assert_eq!((updated[0].old_slippage_bps, updated[0].new_slippage_bps), (DEFAULT_SLIPPAGE_BPS, 250));
+        
+        let create = |max_slippage: Option<u16>| {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let intent = accounts[3].clone();
+            let mut params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+            params.max_slippage = max_slippage;
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params))
+                .map(|_| load::<IntentAccount>(&intent).max_slippage)
+        };
+        // Omitted slippage picks up the default, an explicit one still wins
+        assert_eq!(create(None).unwrap(), 250);
+        assert_eq!(create(Some(75)).unwrap(), 75);
+        assert_eq!(create(Some(MAX_SLIPPAGE_BPS + 1)).unwrap_err(), IntentError::SlippageTooHigh.into());
+        
+        set_default(10).unwrap();
+        assert_eq!(create(None).unwrap(), 10);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 3);
+    }
+    
+    #[test]
+    fn a_stricter_personal_rugproof_threshold_blocks_tokens_the_default_allows() {
+        at_time(START);
+        let protocol_state = protocol_account(&protocol());
+        let cautious = wallet(Pubkey::new_unique());
+        let cautious_state = user_account(&user(cautious.key()));
+        let relaxed = wallet(Pubkey::new_unique());
+        let relaxed_state = user_account(&user(relaxed.key()));
+        // Scores 75 and 95 respectively in perform_rugproof_check
+        let risky_mint = Pubkey::new_from_array([200; 32]);
+        let safe_mint = Pubkey::new_from_array([10; 32]);
+        
+        let set_threshold = |min_score: u8| {
+            run::<SetRugproofThreshold, _>(vec![cautious.clone(), cautious_state.clone()], |ctx| {
+                intentfi::set_rugproof_threshold(ctx, min_score)
+            })
+        };
+        assert_eq!(set_threshold(MIN_RUGPROOF_SCORE - 1).unwrap_err(), IntentError::InvalidRugproofThreshold.into());
+        set_threshold(90).unwrap();
+        assert_eq!(load::<UserAccount>(&cautious_state).rugproof_min_score, 90);
+        
+        let create = |signer: &AccountInfo<'static>, user_state: &AccountInfo<'static>, to_mint: Pubkey| {
+            let mut params = swap_params(Pubkey::new_unique(), to_mint, 1_000_000);
+            params.rugproof_enabled = true;
+            run::<CreateSwapIntent, _>(swap_creation(signer, &protocol_state, user_state), |ctx| {
+                intentfi::create_swap_intent(ctx, params)
+            })
+        };
+        let err = create(&cautious, &cautious_state, risky_mint).unwrap_err();
+        assert_eq!(err, IntentError::RugproofCheckFailed.into());
+        create(&cautious, &cautious_state, safe_mint).unwrap();
+        create(&relaxed, &relaxed_state, risky_mint).unwrap();
+        assert_eq!(load::<UserAccount>(&cautious_state).active_intents, 1);
+    }
+    
+    #[test]
+    fn simulations_report_swap_lend_and_buy_outcomes_without_touching_state() {
+        at_time(START);
+        let protocol_state = protocol_account(&protocol());
+        let mut cautious = user(Pubkey::new_unique());
+        cautious.rugproof_min_score = 90;
+        let user_state = user_account(&cautious);
+        let before = [protocol_state.data.borrow().to_vec(), user_state.data.borrow().to_vec()];
+        let simulate = |params: SimulateIntentParams, remaining: Vec<AccountInfo<'static>>| {
+            let mut infos = vec![protocol_state.clone(), user_state.clone()];
+            infos.extend(remaining);
+            run::<SimulateIntent, _>(infos, |ctx| intentfi::simulate_intent(ctx, params)).unwrap();
+            let results = emitted::<SimulationResult>();
+            assert_eq!(results.len(), 1);
+            results.into_iter().next().unwrap()
+        };
+        
+        // Swaps quote against the pool vaults passed along
+        let (from_mint, to_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let pool = Pubkey::new_unique();
+        let (reserve_in, reserve_out) = (50_000_000_000, 100_000_000_000);
+        let vaults = vec![token_account(pool, from_mint, reserve_in), token_account(pool, to_mint, reserve_out)];
+        let mut swap = swap_params(from_mint, to_mint, 1_000_000);
+        swap.has_liquidity = true;
+        let result = simulate(SimulateIntentParams::Swap(swap), vaults);
+        assert!(result.intent_type == IntentType::Swap && result.would_pass);
+        assert_eq!(result.protocol_fee, 3_000);
+        let expected_out = raydium::calculate_raydium_output(997_000, reserve_in, reserve_out, 25, 10000).unwrap();
+        assert_eq!(result.estimated_output, Some(expected_out));
+        assert_eq!((result.apy, result.rugproof_score), (None, None));
+        
+        // Lends report the routed protocol's APY and fail a min APY it can't meet
+        let lend = |min_apy: u16| LendIntentParams {
+            mint: Pubkey::new_unique(),
+            amount: 1_000_000,
+            min_apy,
+            not_before: None,
+            client_order_id: None,
+            keeper_tip_lamports: 0,
+        };
+        let result = simulate(SimulateIntentParams::Lend(lend(500)), Vec::new());
+        assert!(result.intent_type == IntentType::Lend && result.would_pass);
+        assert_eq!((result.lending_protocol, result.apy), (Some(LendingProtocol::Solend), Some(650)));
+        assert!(!simulate(SimulateIntentParams::Lend(lend(700)), Vec::new()).would_pass);
+        
+        // Buys check the token against the user's own rugproof threshold
+        let usdc_mint = Pubkey::new_unique();
+        let mut safe_buy = buy_params(Pubkey::new_from_array([10; 32]), usdc_mint, 1_000_000);
+        safe_buy.rugproof_check = true;
+        let result = simulate(SimulateIntentParams::Buy(safe_buy), Vec::new());
+        assert!(result.intent_type == IntentType::Buy && result.would_pass);
+        assert_eq!((result.rugproof_score, result.estimated_output), (Some(95), None));
+        let mut risky_buy = buy_params(Pubkey::new_from_array([200; 32]), usdc_mint, 1_000_000);
+        risky_buy.rugproof_check = true;
+        let result = simulate(SimulateIntentParams::Buy(risky_buy), Vec::new());
+        assert_eq!((result.rugproof_score, result.would_pass), (Some(75), false));
+        
+        assert_eq!([protocol_state.data.borrow().to_vec(), user_state.data.borrow().to_vec()], before);
+    }
+    
+    #[test]
+    fn view_capacity_counts_down_to_the_create_that_fails() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let mut owner_account = user(owner.key());
+        owner_account.active_intents = MAX_INTENTS_PER_USER - 1;
+        owner_account.total_intents_created = MAX_INTENTS_PER_USER as u64 - 1;
+        let user_state = user_account(&owner_account);
+        let protocol_state = protocol_account(&protocol());
+        let capacity = || {
+            run::<ViewCapacity, _>(vec![owner.clone(), user_state.clone()], intentfi::view_capacity).unwrap();
+            let events = emitted::<UserCapacity>();
+            assert_eq!(events.len(), 1);
+            assert_eq!((events[0].user, events[0].max_intents), (owner.key(), MAX_INTENTS_PER_USER));
+            (events[0].active_intents, events[0].remaining)
+        };
+        let create = || {
+            let params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+            run::<CreateSwapIntent, _>(swap_creation(&owner, &protocol_state, &user_state), |ctx| {
+                intentfi::create_swap_intent(ctx, params)
+            })
+        };
+        
+        assert_eq!(capacity(), (MAX_INTENTS_PER_USER - 1, 1));
+        create().unwrap();
+        assert_eq!(capacity(), (MAX_INTENTS_PER_USER, 0));
+        // Nothing remaining means the next create is refused
+        assert_eq!(create().unwrap_err(), IntentError::TooManyActiveIntents.into());
+        assert_eq!(capacity(), (MAX_INTENTS_PER_USER, 0));
+    }
+    
+    #[test]
+    fn transfers_to_a_full_user_are_rejected_and_counters_stay_put() {
+        at_time(START + 60);
+        let owner = Pubkey::new_unique();
+        let recipient = Pubkey::new_unique();
+        let intent_info = state(Pubkey::new_unique(), &pending_intent(owner));
+        let mut old_account = user(owner);
+        old_account.active_intents = 1;
+        let old_state = user_account(&old_account);
+        let mut new_account = user(recipient);
+        new_account.active_intents = MAX_INTENTS_PER_USER;
+        let new_state = user_account(&new_account);
+        let transfer = || {
+            let infos = vec![wallet(owner), intent_info.clone(), old_state.clone(), new_state.clone()];
+            run::<TransferIntent, _>(infos, intentfi::transfer_intent)
+        };
+        
+        assert_eq!(transfer().unwrap_err(), IntentError::TooManyActiveIntents.into());
+        assert_eq!(load::<IntentAccount>(&intent_info).authority, owner);
+        assert_eq!(load::<UserAccount>(&old_state).active_intents, 1);
+        assert_eq!(load::<UserAccount>(&new_state).active_intents, MAX_INTENTS_PER_USER);
+        
+        // One slot freed up is enough
+        new_account.active_intents = MAX_INTENTS_PER_USER - 1;
+        new_account.serialize(&mut &mut new_state.data.borrow_mut()[8..]).unwrap();
+        transfer().unwrap();
+        assert_eq!(load::<IntentAccount>(&intent_info).authority, recipient);
+        assert_eq!(load::<UserAccount>(&old_state).active_intents, 0);
+        assert_eq!(load::<UserAccount>(&new_state).active_intents, MAX_INTENTS_PER_USER);
+    }
+    
+    #[test]
+    fn cancelling_a_pending_intent_moves_no_tokens_and_refunds_only_the_tip() {
+        at_time(START + 60);
+        let owner = Pubkey::new_unique();
+        let mut intent = pending_intent(owner);
+        intent.keeper_tip_lamports = 5_000;
+        let mut owner_account = user(owner);
+        owner_account.active_intents = 1;
+        let intent_info = state(Pubkey::new_unique(), &intent);
+        let owner_info = wallet(owner);
+        let rent_held = intent_info.lamports() - 5_000;
+        
+        // No token program or token account is part of a cancel, so no fee can move
+        let infos = vec![
+            owner_info.clone(),
+            intent_info.clone(),
+            user_account(&owner_account),
+            uninitialized(pda(&[b"lending_cap", intent.from_mint.as_ref()]).0),
+        ];
+        assert!(infos.iter().all(|info| info.owner != &token::ID));
+        let user_info = infos[2].clone();
+        run::<CancelIntent, _>(infos, intentfi::cancel_intent).unwrap();
+        
+        let cancelled = load::<IntentAccount>(&intent_info);
+        assert!(cancelled.status == IntentStatus::Cancelled);
+        assert_eq!(cancelled.cancelled_at, Some(START + 60));
+        assert_eq!(load::<UserAccount>(&user_info).active_intents, 0);
+        // The owner gets the unused tip back; only the intent's rent stays put until close
+        assert_eq!(owner_info.lamports(), 10_000_000_000 + 5_000);
+        assert_eq!(intent_info.lamports(), rent_held);
+    }
+    
+    #[test]
+    fn only_the_protocol_authority_can_force_cancel_a_stuck_intent() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        // Stuck holding the execution lock, so the owner's own cancel keeps failing
+        intent.is_executing = true;
+        intent.keeper_tip_lamports = 5_000;
+        let intent_info = state(Pubkey::new_unique(), &intent);
+        let owner = wallet(intent.authority);
+        let mut owner_account = user(intent.authority);
+        owner_account.active_intents = 1;
+        let user_state = user_account(&owner_account);
+        let admin_cancel = |signer: Pubkey| {
+            let infos = vec![
+                wallet(signer),
+                protocol_state.clone(),
+                intent_info.clone(),
+                owner.clone(),
+                user_state.clone(),
+                uninitialized(pda(&[b"lending_cap", intent.from_mint.as_ref()]).0),
+            ];
+            run::<AdminCancelIntent, _>(infos, |ctx| intentfi::admin_cancel_intent(ctx, 7))
+        };
+        
+        for outsider in [Pubkey::new_unique(), intent.authority, protocol.treasury_authority] {
+            assert_eq!(admin_cancel(outsider).unwrap_err(), IntentError::Unauthorized.into());
+        }
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+        
+        admin_cancel(protocol.authority).unwrap();
+        let cancelled = load::<IntentAccount>(&intent_info);
+        assert!(cancelled.status == IntentStatus::Cancelled && !cancelled.is_executing);
+        assert_eq!(cancelled.cancelled_at, Some(START + 60));
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(owner.lamports(), 10_000_000_000 + 5_000);
+        let events = emitted::<AdminCancelled>();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].admin, events[0].reason_code), (protocol.authority, 7));
+        
+        // Already terminal, nothing left to force
+        assert_eq!(admin_cancel(protocol.authority).unwrap_err(), IntentError::IntentNotPending.into());
+    }
+    
+    #[test]
+    fn cancel_all_clears_pending_intents_and_skips_finished_ones() {
+        at_time(START + 60);
+        let owner = Pubkey::new_unique();
+        let mut owner_account = user(owner);
+        owner_account.active_intents = 3;
+        let user_state = user_account(&owner_account);
+        let mut tipped = pending_intent(owner);
+        tipped.keeper_tip_lamports = 5_000;
+        let mut executed = pending_intent(owner);
+        executed.status = IntentStatus::Executed;
+        let intents = [&tipped, &pending_intent(owner), &pending_intent(owner), &executed]
+            .map(|intent| state(Pubkey::new_unique(), intent));
+        
+        let owner_info = wallet(owner);
+        let cancel_all = |intents: &[AccountInfo<'static>]| {
+            let mut infos = vec![owner_info.clone(), user_state.clone()];
+            infos.extend_from_slice(intents);
+            run::<CancelAllIntents, _>(infos, intentfi::cancel_all_intents)
+        };
+        
+        // Someone else's intent anywhere in the batch aborts the whole call. The harness
+        // doesn't roll back failed calls, so these batches use intents of their own
+        let strangers = [owner, Pubkey::new_unique()].map(|authority| state(Pubkey::new_unique(), &pending_intent(authority)));
+        assert_eq!(cancel_all(&strangers).unwrap_err(), IntentError::Unauthorized.into());
+        let too_many = vec![state(Pubkey::new_unique(), &executed); MAX_BULK_CANCELS + 1];
+        assert_eq!(cancel_all(&too_many).unwrap_err(), IntentError::TooManyBulkCancels.into());
+        
+        cancel_all(&intents).unwrap();
+        let statuses = intents.iter().map(|info| load::<IntentAccount>(info).status).collect::<Vec<_>>();
+        assert!(statuses[..3].iter().all(|status| *status == IntentStatus::Cancelled));
+        assert!(statuses[3] == IntentStatus::Executed);
+        assert_eq!(load::<IntentAccount>(&intents[0]).cancelled_at, Some(START + 60));
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(owner_info.lamports(), 10_000_000_000 + 5_000);
+    }
+    
+    #[test]
+    fn keeper_tips_go_to_the_executing_keeper_or_back_to_the_owner_on_expiry() {
+        at_time(START + 60);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.keeper_tip_lamports = 5_000;
+        let protocol = protocol();
+        let protocol_key = pda(&[b"protocol_state"]).0;
+        
+        // A third-party keeper moves the owner's tokens through the protocol PDA's allowance
+        let keeper = wallet(Pubkey::new_unique());
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[0] = keeper.clone();
+        accounts[1].is_signer = false;
+        let mut source = spl_token::state::Account::unpack(&accounts[5].data.borrow()).unwrap();
+        source.delegate = COption::Some(protocol_key);
+        source.delegated_amount = intent.amount;
+        source.pack_into_slice(&mut accounts[5].data.borrow_mut());
+        let intent_info = accounts[2].clone();
+        let rent_held = intent_info.lamports() - 5_000;
+        let route = jupiter_route(&intent, 1);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert_eq!(keeper.lamports(), 10_000_000_000 + 5_000);
+        assert_eq!(intent_info.lamports(), rent_held);
+        assert_eq!(emitted::<SwapIntentExecuted>()[0].keeper_tip_lamports, 5_000);
+        
+        // Nobody executed an expired intent, so its owner gets the tip back
+        at_time(intent.expires_at);
+        let mut owner_account = user(intent.authority);
+        owner_account.active_intents = 1;
+        let owner = wallet(intent.authority);
+        let infos = vec![
+            owner.clone(),
+            state(Pubkey::new_unique(), &intent),
+            owner.clone(),
+            user_account(&owner_account),
+            protocol_account(&protocol),
+            account(pda(&[b"bounty_vault"]).0, system_program::ID, 0, Vec::new()),
+            uninitialized(pda(&[b"lending_cap", intent.from_mint.as_ref()]).0),
+            program(system_program::ID),
+        ];
+        run::<ExpireIntent, _>(infos, intentfi::expire_intent).unwrap();
+        assert_eq!(owner.lamports(), 10_000_000_000 + 5_000);
+    }
+    
+    #[test]
+    fn stale_tips_go_to_the_owner_on_expiry_or_to_treasury_after_the_grace_period() {
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let treasury = account(protocol.treasury_authority, system_program::ID, 1_000_000, Vec::new());
+        let tipped_intent = || {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            intent.keeper_tip_lamports = 5_000;
+            let mut owner_account = user(intent.authority);
+            owner_account.active_intents = 1;
+            (state(Pubkey::new_unique(), &intent), user_account(&owner_account), intent)
+        };
+        let sweep = |intent_info: &AccountInfo<'static>, user_state: &AccountInfo<'static>, treasury: &AccountInfo<'static>| {
+            let from_mint = load::<IntentAccount>(intent_info).from_mint;
+            let infos = vec![
+                wallet(Pubkey::new_unique()),
+                intent_info.clone(),
+                user_state.clone(),
+                protocol_state.clone(),
+                treasury.clone(),
+                uninitialized(pda(&[b"lending_cap", from_mint.as_ref()]).0),
+            ];
+            run::<SweepExpiredTip, _>(infos, intentfi::sweep_expired_tip)
+        };
+        let expire = |intent_info: &AccountInfo<'static>, user_state: &AccountInfo<'static>, owner: &AccountInfo<'static>| {
+            let from_mint = load::<IntentAccount>(intent_info).from_mint;
+            let infos = vec![
+                owner.clone(),
+                intent_info.clone(),
+                owner.clone(),
+                user_state.clone(),
+                protocol_state.clone(),
+                account(pda(&[b"bounty_vault"]).0, system_program::ID, 0, Vec::new()),
+                uninitialized(pda(&[b"lending_cap", from_mint.as_ref()]).0),
+                program(system_program::ID),
+            ];
+            run::<ExpireIntent, _>(infos, intentfi::expire_intent)
+        };
+        
+        // An owner who expires their own intent gets the tip back, leaving nothing to sweep
+        let (intent_info, user_state, intent) = tipped_intent();
+        let owner = wallet(intent.authority);
+        at_time(intent.expires_at);
+        expire(&intent_info, &user_state, &owner).unwrap();
+        assert_eq!(owner.lamports(), 10_000_000_000 + 5_000);
+        at_time(intent.expires_at + TIP_SWEEP_GRACE_PERIOD);
+        assert_eq!(sweep(&intent_info, &user_state, &treasury).unwrap_err(), IntentError::IntentNotPending.into());
+        assert_eq!(treasury.lamports(), 1_000_000);
+        
+        // Left pending through the grace period, anyone can sweep the tip to the treasury
+        let (intent_info, user_state, intent) = tipped_intent();
+        at_time(intent.expires_at + TIP_SWEEP_GRACE_PERIOD - 1);
+        assert_eq!(sweep(&intent_info, &user_state, &treasury).unwrap_err(), IntentError::TipSweepTooEarly.into());
+        at_time(intent.expires_at + TIP_SWEEP_GRACE_PERIOD);
+        let elsewhere = wallet(Pubkey::new_unique());
+        assert_eq!(sweep(&intent_info, &user_state, &elsewhere).unwrap_err(), IntentError::InvalidTreasuryAccount.into());
+        sweep(&intent_info, &user_state, &treasury).unwrap();
+        assert_eq!(treasury.lamports(), 1_000_000 + 5_000);
+        assert_eq!(intent_info.lamports(), 10_000_000 - 5_000);
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Expired);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        let swept = emitted::<TipSwept>();
+        assert_eq!((swept[0].intent_id, swept[0].treasury, swept[0].tip_lamports), (intent_info.key(), protocol.treasury_authority, 5_000));
+        // The owner can't claim the swept tip a second time
+        let owner = wallet(intent.authority);
+        assert_eq!(expire(&intent_info, &user_state, &owner).unwrap_err(), IntentError::IntentNotPending.into());
+        assert_eq!(owner.lamports(), 10_000_000_000);
+    }
+    
+    #[test]
+    fn closing_finished_intents_folds_each_into_the_owners_history() {
+        at_time(START + 7_200);
+        let owner = wallet(Pubkey::new_unique());
+        let history_state = uncreated(pda(&[b"intent_history", owner.key.as_ref()]).0, 8 + IntentHistory::INIT_SPACE);
+        let finished = |status: IntentStatus| {
+            let mut intent = pending_intent(owner.key());
+            intent.status = status;
+            match intent.status {
+                IntentStatus::Executed => (intent.executed_at, intent.cumulative_out) = (Some(START + 60), 990_000),
+                IntentStatus::Cancelled => intent.cancelled_at = Some(START + 120),
+                _ => {}
+            }
+            let intent_info = reallocatable(Pubkey::new_unique(), 10_000_000, serialized(&intent));
+            (intent_info, intent)
+        };
+        let close = |signer: &AccountInfo<'static>, intent_info: &AccountInfo<'static>| {
+            let infos = vec![signer.clone(), intent_info.clone(), history_state.clone(), program(system_program::ID)];
+            run::<CloseIntent, _>(infos, intentfi::close_intent)
+        };
+        
+        // Other users can't close the owner's intents
+        let (executed_info, executed) = finished(IntentStatus::Executed);
+        let stranger = wallet(Pubkey::new_unique());
+        let stranger_history = uncreated(pda(&[b"intent_history", stranger.key.as_ref()]).0, 8 + IntentHistory::INIT_SPACE);
+        let infos = vec![stranger, executed_info.clone(), stranger_history, program(system_program::ID)];
+        assert_eq!(run::<CloseIntent, _>(infos, intentfi::close_intent).unwrap_err(), IntentError::Unauthorized.into());
+        
+        let mut expected_hash = [0u8; 32];
+        let owner_lamports = owner.lamports();
+        let closing = [
+            (executed_info, executed),
+            finished(IntentStatus::Cancelled),
+            finished(IntentStatus::Expired),
+            finished(IntentStatus::Cancelled),
+        ];
+        for (intent_info, intent) in closing {
+            close(&owner, &intent_info).unwrap();
+            let finished_at = intent.executed_at.or(intent.cancelled_at).unwrap_or(0);
+            expected_hash = hashv(&[
+                &expected_hash,
+                intent_info.key.as_ref(),
+                &[intent.status.clone() as u8],
+                &intent.amount.to_le_bytes(),
+                &intent.cumulative_out.to_le_bytes(),
+                &finished_at.to_le_bytes(),
+            ])
+            .to_bytes();
+            assert_eq!(emitted::<IntentCompacted>()[0].history_hash, expected_hash);
+            assert_eq!((intent_info.lamports(), intent_info.owner), (0, &system_program::ID));
+        }
+        
+        // Pending intents stay open and out of the history
+        let (pending_info, _) = finished(IntentStatus::Pending);
+        assert_eq!(close(&owner, &pending_info).unwrap_err(), IntentError::IntentStillPending.into());
+        
+        let history = load::<IntentHistory>(&history_state);
+        assert_eq!(history.authority, owner.key());
+        let counts = (history.executed_count, history.cancelled_count, history.expired_count, history.failed_count);
+        assert_eq!(counts, (1, 2, 1, 0));
+        assert_eq!(history.history_hash, expected_hash);
+        // Every closed intent's rent went back to its owner, less what the history account cost
+        let history_rent = history_state.lamports();
+        assert_eq!(owner.lamports(), owner_lamports + 4 * 10_000_000 - history_rent);
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 32 + 2 + 8 + 8 + 8 + 1 + 1,
-        seeds = [b"protocol_state"],
-        bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn owners_still_cancel_and_close_intents_while_the_protocol_is_paused() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        run::<PauseProtocol, _>(vec![wallet(protocol.authority), protocol_state.clone()], intentfi::pause_protocol).unwrap();
+        let paused = load::<ProtocolState>(&protocol_state);
+        assert!(paused.is_paused);
+        
+        let owner = wallet(Pubkey::new_unique());
+        let mut owner_account = user(owner.key());
+        owner_account.active_intents = 3;
+        let user_state = user_account(&owner_account);
+        // New intents and executions are halted
+        let params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+        let err = run::<CreateSwapIntent, _>(swap_creation(&owner, &protocol_state, &user_state), |ctx| intentfi::create_swap_intent(ctx, params)).unwrap_err();
+        assert_eq!(err, IntentError::ProtocolPaused.into());
+        let intent = pending_intent(owner.key());
+        let route = jupiter_route(&intent, 1);
+        let accounts = jupiter_execution(&intent, &paused);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap_err();
+        assert_eq!(err, IntentError::ProtocolPaused.into());
+        
+        // But the owner can still walk away from what's pending and reclaim the rent
+        let mut intent_data = serialized(&intent);
+        intent_data.resize(intent_data.len() + 256, 0);
+        let intent_info = reallocatable(Pubkey::new_unique(), 10_000_000, intent_data);
+        let lending_cap = uninitialized(pda(&[b"lending_cap", intent.from_mint.as_ref()]).0);
+        let infos = vec![owner.clone(), intent_info.clone(), user_state.clone(), lending_cap];
+        run::<CancelIntent, _>(infos, intentfi::cancel_intent).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Cancelled);
+        let others = [pending_intent(owner.key()), pending_intent(owner.key())].map(|intent| state(Pubkey::new_unique(), &intent));
+        let mut infos = vec![owner.clone(), user_state.clone()];
+        infos.extend_from_slice(&others);
+        run::<CancelAllIntents, _>(infos, intentfi::cancel_all_intents).unwrap();
+        assert!(others.iter().all(|info| load::<IntentAccount>(info).status == IntentStatus::Cancelled));
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        
+        let history_state = uncreated(pda(&[b"intent_history", owner.key.as_ref()]).0, 8 + IntentHistory::INIT_SPACE);
+        let infos = vec![owner.clone(), intent_info.clone(), history_state.clone(), program(system_program::ID)];
+        run::<CloseIntent, _>(infos, intentfi::close_intent).unwrap();
+        assert_eq!(intent_info.lamports(), 0);
+        assert_eq!(load::<IntentHistory>(&history_state).cancelled_count, 1);
+        assert!(load::<ProtocolState>(&protocol_state).is_paused);
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct InitializeUser<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn executing_a_stale_intent_expires_it_and_frees_the_slot() {
+        at_time(START + 3_600);
+        let protocol = protocol();
+        
+        let intent = pending_intent(Pubkey::new_unique());
+        let accounts = jupiter_execution(&intent, &protocol);
+        let (intent_info, user_state, source) = (accounts[2].clone(), accounts[4].clone(), accounts[5].clone());
+        let route = jupiter_route(&intent, 1);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Expired);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(token_balance(&source), intent.amount);
+        let expired = emitted::<IntentExpired>();
+        assert_eq!(expired.len(), 1);
+        assert_eq!((expired[0].intent_id, expired[0].bounty_lamports), (intent_info.key(), 0));
+        
+        // Lend intents also hand back their share of the mint's lending cap
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.intent_type = IntentType::Lend;
+        intent.to_mint = intent.from_mint;
+        intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+        intent.lending_cap_reserved = true;
+        let registry = LendingMarketRegistry { markets: Vec::new(), bump: pda(&[b"lending_market_registry"]).1 };
+        let mut accounts = lend_execution(&intent, &protocol, &registry);
+        let mut cap = lending_cap(10 * intent.amount, intent.amount);
+        cap.mint = intent.from_mint;
+        accounts[5] = state(accounts[5].key(), &cap);
+        let (intent_info, user_state, cap_info) = (accounts[2].clone(), accounts[4].clone(), accounts[5].clone());
+        accounts.extend([absent(), absent(), absent(), absent(), absent(), absent(), absent(), program(token::ID)]);
+        run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend).unwrap();
+        let lend = load::<IntentAccount>(&intent_info);
+        assert!(lend.status == IntentStatus::Expired && !lend.lending_cap_reserved);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(load::<LendingCap>(&cap_info).current_total, 0);
+        assert_eq!(emitted::<IntentExpired>().len(), 1);
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 8 + 8 + 1 + 1,
-        seeds = [b"user_account", authority.key().as_ref()],
-        bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn expiry_bounty_is_paid_to_other_keepers_above_the_vault_rent() {
+        assert_eq!(payable_expiry_bounty(5_000, 1_000_000, 890_880, false), 5_000);
+        // Never drains the vault below rent exemption
+        assert_eq!(payable_expiry_bounty(500_000, 1_000_000, 890_880, false), 109_120);
+        assert_eq!(payable_expiry_bounty(5_000, 890_880, 890_880, false), 0);
+        assert_eq!(payable_expiry_bounty(5_000, 0, 890_880, false), 0);
+        // Owners expiring their own intents earn nothing
+        assert_eq!(payable_expiry_bounty(5_000, 1_000_000, 890_880, true), 0);
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct CreateSwapIntent<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn only_stale_intents_can_be_expired_for_a_bounty() {
+        let mut intent = blank_intent();
+        intent.expires_at = 1_000;
+        assert!(!intent_is_stale(&intent, 999));
+        assert!(intent_is_stale(&intent, 1_000));
+        
+        // A lapsed price check deadline makes a buy stale before expires_at
+        intent.price_check_deadline = Some(500);
+        assert!(!intent_is_stale(&intent, 499));
+        assert!(intent_is_stale(&intent, 500));
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn buys_expire_early_once_their_price_check_deadline_lapses() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let mut protocol = protocol();
+        protocol.expiry_bounty_lamports = 5_000;
+        let protocol_state = protocol_account(&protocol);
+        let (mint, usdc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        
+        let create = |target_price: Option<u64>, price_check_deadline: Option<i64>| {
+            let mut params = buy_params(mint, usdc_mint, 1_000_000);
+            params.target_price = target_price;
+            params.price_check_deadline = price_check_deadline;
+            let infos = buy_creation(&owner, &protocol_state, &user_state, &params);
+            let intent_info = infos[3].clone();
+            let args = params.try_to_vec().unwrap();
+            run_with_args::<CreateBuyIntent, _>(infos, &args, |ctx| intentfi::create_buy_intent(ctx, params, None))
+                .map(|()| intent_info)
+        };
+        
+        // Deadlines need a target price to abandon and the minimum lifetime
+        let deadline = START + 2 * 3600;
+        assert_eq!(create(None, Some(deadline)).unwrap_err(), IntentError::InvalidPriceCheckDeadline.into());
+        let err = create(Some(1_000), Some(START + MIN_INTENT_LIFETIME - 1)).unwrap_err();
+        assert_eq!(err, IntentError::InvalidPriceCheckDeadline.into());
+        let err = create(Some(1_000), Some(START + INTENT_EXPIRY_SECONDS + 1)).unwrap_err();
+        assert_eq!(err, IntentError::InvalidPriceCheckDeadline.into());
+        let intent_info = create(Some(1_000), Some(deadline)).unwrap();
+        
+        let keeper = wallet(Pubkey::new_unique());
+        let bounty_vault = account(pda(&[b"bounty_vault"]).0, system_program::ID, 10_000_000, Vec::new());
+        let expire = || {
+            let infos = vec![
+                keeper.clone(),
+                intent_info.clone(),
+                wallet(owner.key()),
+                user_state.clone(),
+                protocol_state.clone(),
+                bounty_vault.clone(),
+                uninitialized(pda(&[b"lending_cap", usdc_mint.as_ref()]).0),
+                program(system_program::ID),
+            ];
+            run::<ExpireIntent, _>(infos, intentfi::expire_intent)
+        };
+        at_time(deadline - 1);
+        assert_eq!(expire().unwrap_err(), IntentError::IntentNotExpired.into());
+        
+        // Well before expires_at, the lapsed deadline alone retires the intent
+        at_time(deadline);
+        expire().unwrap();
+        let intent = load::<IntentAccount>(&intent_info);
+        assert!(intent.status == IntentStatus::Expired);
+        assert!(deadline < intent.expires_at);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(keeper.lamports(), 10_000_000_000 + 5_000);
+    }
     
-    #[account(
-        mut,
-        seeds = [b"user_account", authority.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn buys_whose_target_is_already_met_execute_in_the_create_call() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let (mint, usdc_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let treasury = protocol.treasury_authority;
+        
+        let create = |current_price: u64| {
+            let mut params = buy_params(mint, usdc_mint, 1_000_000);
+            params.target_price = Some(2_000);
+            params.current_price = Some(current_price);
+            params.execute_immediately = true;
+            let mut infos = buy_creation(&owner, &protocol_state, &user_state, &params);
+            infos[4] = token_account(owner.key(), usdc_mint, 1_000_000);
+            infos[5] = token_account(owner.key(), mint, 0);
+            infos[6] = token_account_at(treasury_fee_ata(&treasury, &usdc_mint), treasury, usdc_mint, 0);
+            infos[8] = uninitialized(pda(&[b"fee_ledger", usdc_mint.as_ref()]).0);
+            infos[10] = program(jupiter::JUPITER_PROGRAM_ID);
+            infos[11] = program(token::ID);
+            let (intent_info, treasury_info) = (infos[3].clone(), infos[6].clone());
+            let mut quoted = blank_intent();
+            (quoted.from_mint, quoted.to_mint, quoted.amount) = (usdc_mint, mint, params.usdc_amount);
+            let route = jupiter_route(&quoted, 1);
+            let args = params.try_to_vec().unwrap();
+            run_with_args::<CreateBuyIntent, _>(infos, &args, |ctx| intentfi::create_buy_intent(ctx, params, Some(route)))
+                .map(|()| (load::<IntentAccount>(&intent_info), token_balance(&treasury_info)))
+        };
+        
+        // Above the target the buy can't be created to execute inline
+        assert_eq!(create(2_001).err(), Some(IntentError::ConditionNotMet.into()));
+        // Logs of the refused call would be discarded with it on chain
+        emitted::<IntentStateChanged>();
+        
+        let Ok((intent, treasury_balance)) = create(1_950) else { panic!("a met condition executes inline") };
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!(treasury_balance, intent.protocol_fee);
+        let transitions = emitted::<IntentStateChanged>()
+            .iter()
+            .map(|event| (event.old_status.clone(), event.new_status.clone()))
+            .collect::<Vec<_>>();
+        assert!(transitions == [(None, IntentStatus::Pending), (Some(IntentStatus::Pending), IntentStatus::Executed)]);
+        assert_eq!(emitted::<SwapIntentExecuted>().len(), 1);
+        let user = load::<UserAccount>(&user_state);
+        assert_eq!((user.active_intents, user.total_intents_created), (0, 1));
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 1 + 32 + 32 + 8 + 8 + 2 + 2 + 8 + 2 + 8 + 8 + 1 + 32 + 32 + 8 + 8 + 8 + 8 + 1, // Updated space for both protocol selections
-        seeds = [b"intent", authority.key().as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
-        bump
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn fees_cannot_be_redirected_to_a_keeper_owned_treasury_account() {
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        run::<ExecuteSwapIntentJupiter, _>(jupiter_execution(&intent, &protocol), |_| Ok(())).unwrap();
+        
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[7] = token_account(Pubkey::new_unique(), intent.from_mint, 0);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, IntentError::InvalidTreasuryAccount.into());
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct ExecuteSwapIntentJupiter<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn only_the_real_jupiter_program_is_accepted() {
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        run::<ExecuteSwapIntentJupiter, _>(jupiter_execution(&intent, &protocol), |_| Ok(())).unwrap();
+        
+        // The placeholder id this program used to expect
+        let placeholder = Pubkey::new_from_array([
+            1, 16, 106, 110, 107, 91, 33, 106, 72, 198, 34, 156, 221, 205, 112, 238,
+            188, 163, 65, 120, 50, 91, 190, 150, 162, 145, 196, 146, 158, 246, 87, 78,
+        ]);
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[11] = program(placeholder);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, ErrorCode::ConstraintAddress.into());
+    }
     
-    #[account(
-        mut,
-        constraint = intent_account.authority == user.key()
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn swap_token_accounts_must_hold_the_intents_mints() {
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[6] = token_account(intent.authority, Pubkey::new_unique(), 0);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, IntentError::DestinationMintMismatch.into());
+        
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[5] = token_account(intent.authority, intent.to_mint, intent.amount);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, IntentError::SourceMintMismatch.into());
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn treasury_accounts_for_another_mint_fail_with_a_fee_mint_mismatch() {
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        let treasury = protocol.treasury_authority;
+        // The treasury's own canonical account, just for the output token
+        let wrong_mint_account = || {
+            token_account_at(treasury_fee_ata(&treasury, &intent.to_mint), treasury, intent.to_mint, 0)
+        };
+        
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[7] = wrong_mint_account();
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, IntentError::FeeMintMismatch.into());
+        
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[7] = wrong_mint_account();
+        accounts.truncate(11);
+        accounts.extend([
+            account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, Vec::new()),
+            token_account(Pubkey::new_unique(), intent.from_mint, 0),
+            token_account(Pubkey::new_unique(), intent.to_mint, 0),
+            program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+            program(token::ID),
+            program(system_program::ID),
+        ]);
+        let err = run::<ExecuteSwapIntentRaydium, _>(accounts, |_| Ok(())).unwrap_err();
+        assert_eq!(err, IntentError::FeeMintMismatch.into());
+    }
     
-    #[account(
-        mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn a_reentrant_execution_of_the_same_intent_aborts() {
+        at_time(START + 60);
+        let intent = pending_intent(Pubkey::new_unique());
+        let accounts = jupiter_execution(&intent, &protocol());
+        let intent_info = accounts[2].clone();
+        
+        // The outer execution takes the lock and persists it before its CPI
+        let outer_info: &'static AccountInfo<'static> = Box::leak(Box::new(intent_info.clone()));
+        let mut outer = Account::<IntentAccount>::try_from(outer_info).unwrap();
+        begin_intent_execution(&mut outer, intent.authority, &crate::ID).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).is_executing);
+        
+        // A callee re-entering with the same intent sees the lock
+        let swap = jupiter_route(&intent, 0);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, swap)).unwrap_err();
+        assert_eq!(err, IntentError::Reentrancy.into());
+    }
     
-    #[account(mut)]
-    pub user_source_token: Account<'info, TokenAccount>,
+    #[test]
+    fn a_lend_execution_reentered_during_its_deposit_aborts_the_inner_call() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mint = Pubkey::new_unique();
+        let reserve = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket { protocol: LendingProtocol::Solend, mint, reserve, market: Pubkey::new_unique() }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.intent_type = IntentType::Lend;
+        (intent.from_mint, intent.to_mint) = (mint, mint);
+        intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+        intent.pool_id = Some(reserve);
+        let destination = token_account(Pubkey::new_unique(), mint, 0);
+        let mut accounts = lend_execution(&intent, &protocol, &registry);
+        accounts.extend([
+            lend_reserve(reserve, solend::SOLEND_PROGRAM_ID, mint, &destination),
+            account(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000, Vec::new()),
+            destination.clone(),
+            absent(),
+            absent(),
+            program(solend::SOLEND_PROGRAM_ID),
+            absent(),
+            program(token::ID),
+        ]);
+        let (intent_info, user_state) = (accounts[2].clone(), accounts[4].clone());
+        
+        // The deposit's CPI calls back into the same execution before it returns
+        let inner_result = Rc::new(RefCell::new(None));
+        let (inner_accounts, inner_slot) = (accounts.clone(), inner_result.clone());
+        CPI_HOOK.with(|hook| {
+            *hook.borrow_mut() = Some(Box::new(move || {
+                let result = run::<ExecuteLendIntentSolend, _>(inner_accounts, intentfi::execute_lend_intent_solend);
+                *inner_slot.borrow_mut() = Some(result);
+            }))
+        });
+        run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend).unwrap();
+        
+        let inner_result = inner_result.borrow_mut().take().expect("the deposit made a CPI");
+        assert_eq!(inner_result.unwrap_err(), IntentError::Reentrancy.into());
+        // Deposited, counted and charged once
+        let executed = load::<IntentAccount>(&intent_info);
+        assert!(executed.status == IntentStatus::Executed && !executed.is_executing);
+        assert_eq!(token_balance(&destination), intent.amount - intent.protocol_fee);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        assert_eq!(emitted::<LendIntentExecuted>().len(), 1);
+    }
+    
+    #[test]
+    fn refreshing_a_rugproof_score_caches_it_and_reports_the_previous_one() {
+        at_time(START);
+        let payer = Pubkey::new_unique();
+        let mint_key = Pubkey::new_unique();
+        let mint = mint(mint_key, Some(Pubkey::new_unique()), 1_000_000);
+        let (metadata_key, _) = Pubkey::find_program_address(
+            &[b"metadata", rugproof::TOKEN_METADATA_PROGRAM_ID.as_ref(), mint_key.as_ref()],
+            &rugproof::TOKEN_METADATA_PROGRAM_ID,
+        );
+        let cache_key = pda(&[b"rugproof_cache", mint_key.as_ref()]).0;
+        let refresh = |metadata: Pubkey, pool: AccountInfo<'static>, cache: &AccountInfo<'static>| {
+            let infos = vec![
+                wallet(payer),
+                mint.clone(),
+                uninitialized(metadata),
+                pool,
+                cache.clone(),
+                program(system_program::ID),
+            ];
+            run::<RefreshRugproofScore, _>(infos, intentfi::refresh_rugproof_score)
+        };
+        
+        // Metadata must be the mint's Metaplex PDA
+        let cache = uncreated(cache_key, 8 + RugproofCache::INIT_SPACE);
+        let err = refresh(Pubkey::new_unique(), absent(), &cache).unwrap_err();
+        assert_eq!(err, IntentError::InvalidMetadata.into());
+        
+        // Frozen-free but still mintable, with no metadata or pool: 40 + 15
+        let cache = uncreated(cache_key, 8 + RugproofCache::INIT_SPACE);
+        refresh(metadata_key, absent(), &cache).unwrap();
+        let cached = load::<RugproofCache>(&cache);
+        assert_eq!((cached.mint, cached.score, cached.updated_at), (mint_key, 55, START));
+        let refreshed = emitted::<RugproofScoreRefreshed>();
+        assert_eq!(refreshed.len(), 1);
+        assert_eq!((refreshed[0].old_score, refreshed[0].score), (None, 55));
+        assert_eq!(refreshed[0].refreshed_by, payer);
+        
+        // A pool holding 10% of the supply adds its bonus, and the refresh reports the old score
+        at_time(START + 600);
+        refresh(metadata_key, token_account(Pubkey::new_unique(), mint_key, 100_000), &cache).unwrap();
+        let cached = load::<RugproofCache>(&cache);
+        assert_eq!((cached.score, cached.updated_at), (65, START + 600));
+        let refreshed = emitted::<RugproofScoreRefreshed>();
+        assert_eq!((refreshed[0].old_score, refreshed[0].score, refreshed[0].updated_at), (Some(55), 65, START + 600));
+    }
+    
+    #[test]
+    fn jupiter_routes_paying_more_amm_fees_than_the_intent_allows_are_refused() {
+        at_time(START + 60);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.max_route_fee = Some(2_000);
+        let protocol = protocol();
+        let route_with_fees = |fee_amount: u64| {
+            let mut route = jupiter_route(&intent, 2);
+            route.route_plan.iter_mut().for_each(|step| step.swap_info.fee_amount = fee_amount);
+            route
+        };
+        
+        // Two hops at 1,500 each sum past the cap even though each is under it
+        let accounts = jupiter_execution(&intent, &protocol);
+        let intent_info = accounts[2].clone();
+        let route = route_with_fees(1_500);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap_err();
+        assert_eq!(err, IntentError::RouteFeeTooHigh.into());
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+        
+        // Fees summing to exactly the cap go through
+        let accounts = jupiter_execution(&intent, &protocol);
+        let intent_info = accounts[2].clone();
+        let route = route_with_fees(1_000);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed);
+    }
+    
+    #[test]
+    fn reconciling_a_treasury_reports_its_mints_drift_to_the_authority() {
+        at_time(START);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let reconcile = |signer: Pubkey, mint: Pubkey, ledger: &FeeLedger, held: u64| {
+            let (ledger_key, bump) = pda(&[b"fee_ledger", mint.as_ref()]);
+            let infos = vec![
+                wallet(signer),
+                protocol_state.clone(),
+                token_account_at(treasury_fee_ata(&protocol.treasury_authority, &mint), protocol.treasury_authority, mint, held),
+                state(ledger_key, &FeeLedger { bump, ..ledger.clone() }),
+            ];
+            run::<ReconcileTreasury, _>(infos, intentfi::reconcile_treasury)
+        };
+        
+        // 50 of the 500 recorded fees never arrived, as with a fee-on-transfer mint
+        let usdc = Pubkey::new_unique();
+        let usdc_ledger = FeeLedger { mint: usdc, opening_balance: 1_000, fees_collected: 500, bump: 0 };
+        let err = reconcile(Pubkey::new_unique(), usdc, &usdc_ledger, 1_450).unwrap_err();
+        assert_eq!(err, IntentError::Unauthorized.into());
+        reconcile(protocol.authority, usdc, &usdc_ledger, 1_450).unwrap();
+        let reports = emitted::<TreasuryReconciliation>();
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].mint, usdc);
+        assert_eq!((reports[0].tracked_fees, reports[0].treasury_balance, reports[0].discrepancy), (1_500, 1_450, -50));
+        
+        // Each mint is checked against its own ledger, not the protocol-wide counter
+        let sol = Pubkey::new_unique();
+        let sol_ledger = FeeLedger { mint: sol, opening_balance: 0, fees_collected: 7_000, bump: 0 };
+        reconcile(protocol.authority, sol, &sol_ledger, 7_000).unwrap();
+        let reports = emitted::<TreasuryReconciliation>();
+        assert_eq!((reports[0].mint, reports[0].tracked_fees, reports[0].discrepancy), (sol, 7_000, 0));
+    }
+    
+    #[test]
+    fn lend_intents_may_ask_for_up_to_the_max_realistic_apy() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let mint = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket {
+                protocol: LendingProtocol::Solend,
+                mint,
+                reserve: Pubkey::new_unique(),
+                market: Pubkey::new_unique(),
+            }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let registry_state = state(pda(&[b"lending_market_registry"]).0, &registry);
+        let create = |min_apy: u16| {
+            let infos = lend_creation(&owner, &protocol_state, &user_state, &registry_state, &mint);
+            let params = LendIntentParams { min_apy, ..lend_params(mint) };
+            let args = params.try_to_vec().unwrap();
+            run_with_args::<CreateLendIntent, _>(infos, &args, |ctx| intentfi::create_lend_intent(ctx, params))
+        };
+        
+        let err = create(DEFAULT_MAX_REALISTIC_APY + 1).unwrap_err();
+        assert_eq!(err, IntentError::UnrealisticApy.into());
+        create(DEFAULT_MAX_REALISTIC_APY).unwrap();
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 1);
+        
+        // The authority can tighten the bound, and intents at the old one are then refused
+        run::<UpdateProtocolConfig, _>(vec![wallet(protocol.authority), protocol_state.clone()], |ctx| {
+            intentfi::set_max_realistic_apy(ctx, 3_000)
+        })
+        .unwrap();
+        let err = create(3_001).unwrap_err();
+        assert_eq!(err, IntentError::UnrealisticApy.into());
+        create(3_000).unwrap();
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 2);
+    }
+    
+    #[test]
+    fn lend_intents_route_to_markets_the_authority_registered() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let registry_key = pda(&[b"lending_market_registry"]).0;
+        let mint = Pubkey::new_unique();
+        let market = LendingMarket {
+            protocol: LendingProtocol::Solend,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let register = |signer: Pubkey, registry_state: &AccountInfo<'static>| {
+            let infos = vec![wallet(signer), protocol_state.clone(), registry_state.clone(), program(system_program::ID)];
+            run::<RegisterLendingMarket, _>(infos, |ctx| intentfi::register_lending_market(ctx, market.clone()))
+        };
+        
+        let err = register(Pubkey::new_unique(), &uncreated(registry_key, 8 + LendingMarketRegistry::INIT_SPACE)).unwrap_err();
+        assert_eq!(err, IntentError::Unauthorized.into());
+        let registry_state = uncreated(registry_key, 8 + LendingMarketRegistry::INIT_SPACE);
+        register(protocol.authority, &registry_state).unwrap();
+        assert_eq!(load::<LendingMarketRegistry>(&registry_state).markets.len(), 1);
+        assert_eq!(emitted::<LendingMarketRegistered>()[0].reserve, market.reserve);
+        
+        // The intent is pinned to the registered reserve for execution to check
+        let infos = lend_creation(&owner, &protocol_state, &user_state, &registry_state, &mint);
+        let intent_info = infos[3].clone();
+        let params = lend_params(mint);
+        let args = params.try_to_vec().unwrap();
+        run_with_args::<CreateLendIntent, _>(infos, &args, |ctx| intentfi::create_lend_intent(ctx, params)).unwrap();
+        let intent = load::<IntentAccount>(&intent_info);
+        assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::Solend));
+        assert_eq!(intent.pool_id, Some(market.reserve));
+        
+        // Mints nobody registered have nowhere to lend
+        let unlisted = Pubkey::new_unique();
+        let infos = lend_creation(&owner, &protocol_state, &user_state, &registry_state, &unlisted);
+        let params = lend_params(unlisted);
+        let args = params.try_to_vec().unwrap();
+        let err = run_with_args::<CreateLendIntent, _>(infos, &args, |ctx| intentfi::create_lend_intent(ctx, params)).unwrap_err();
+        assert_eq!(err, IntentError::LendingMarketNotRegistered.into());
+    }
+    
+    #[test]
+    fn exact_output_swaps_spend_the_quoted_input_within_max_input() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let exact_output = |max_input: u64| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            (intent.exact_output, intent.amount, intent.max_input) = (true, 950_000, max_input);
+            intent
+        };
+        // Getting 950,000 out takes 1,000,000 in, plus the 3,000 fee on it
+        let route_for = |intent: &IntentAccount| {
+            let mut route = jupiter_route(intent, 1);
+            (route.in_amount, route.quoted_out_amount) = (1_000_000, 950_000);
+            route
+        };
+        
+        let intent = exact_output(1_002_999);
+        let accounts = jupiter_execution(&intent, &protocol);
+        let (intent_info, treasury_info) = (accounts[2].clone(), accounts[7].clone());
+        let route = route_for(&intent);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap_err();
+        assert_eq!(err, IntentError::ExactOutputBudgetExceeded.into());
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+        assert_eq!(token_balance(&treasury_info), 0);
+        
+        let intent = exact_output(1_003_000);
+        let accounts = jupiter_execution(&intent, &protocol);
+        let (intent_info, treasury_info) = (accounts[2].clone(), accounts[7].clone());
+        let route = route_for(&intent);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        let executed = load::<IntentAccount>(&intent_info);
+        assert!(executed.status == IntentStatus::Executed);
+        assert_eq!(executed.protocol_fee, 3_000);
+        assert_eq!(token_balance(&treasury_info), 3_000);
+        let swaps = emitted::<SwapIntentExecuted>();
+        assert_eq!((swaps[0].amount_in, swaps[0].protocol_fee), (1_000_000, 3_000));
+    }
+    
+    #[test]
+    fn raydium_intents_fall_back_to_jupiter_only_once_their_pool_cannot_fill() {
+        at_time(START + 60);
+        let protocol = protocol();
+        // Executes a Raydium intent through Jupiter on a pair last priced at `reference_price`,
+        // proving its pool holds `reserve_out`
+        let execute = |reference_price: Option<u64>, reserve_out: Option<u64>, quoted_out_amount: u64| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            intent.selected_swap_protocol = SwapProtocol::Raydium;
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            if let Some(last_price) = reference_price {
+                let (key, bump) = pda(&[b"circuit_breaker", intent.from_mint.as_ref(), intent.to_mint.as_ref()]);
+                accounts[10] = state(key, &CircuitBreaker {
+                    from_mint: intent.from_mint,
+                    to_mint: intent.to_mint,
+                    max_deviation_bps: 500,
+                    last_price,
+                    tripped: false,
+                    bump,
+                });
+            }
+            if let Some(reserve_out) = reserve_out {
+                let coin_vault = token_account(Pubkey::new_unique(), intent.from_mint, 1_000_000_000);
+                let pc_vault = token_account(Pubkey::new_unique(), intent.to_mint, reserve_out);
+                let mut pool_data = vec![0u8; raydium::AMM_PC_VAULT_OFFSET + 32];
+                pool_data[raydium::AMM_COIN_VAULT_OFFSET..][..32].copy_from_slice(coin_vault.key.as_ref());
+                pool_data[raydium::AMM_PC_VAULT_OFFSET..][..32].copy_from_slice(pc_vault.key.as_ref());
+                accounts[13] = account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, pool_data);
+                (accounts[14], accounts[15]) = (coin_vault, pc_vault);
+            }
+            let intent_info = accounts[2].clone();
+            let mut route = jupiter_route(&intent, 1);
+            (route.in_amount, route.quoted_out_amount) = (997_000, quoted_out_amount);
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route))
+                .map(|()| load::<IntentAccount>(&intent_info))
+        };
+        // The pair trades at 0.95, which Jupiter's simulated route fills at
+        let price = PRICE_SCALE as u64 * 95 / 100;
+        
+        // Without the pool to show it's drained, or while it can still fill, the intent stays on Raydium
+        assert_eq!(execute(Some(price), None, 947_150).err(), Some(IntentError::WrongProtocol.into()));
+        assert_eq!(execute(Some(price), Some(1_000_000_000), 947_150).err(), Some(IntentError::WrongProtocol.into()));
+        // An inflated route quote doesn't make a healthy pool look short
+        assert_eq!(execute(Some(price), Some(1_000_000_000), u64::MAX / 2).err(), Some(IntentError::WrongProtocol.into()));
+        // Without a reference price nothing proves what the pool should give
+        assert_eq!(execute(None, Some(10_000), 947_150).err(), Some(IntentError::WrongProtocol.into()));
+        // A fallback filling under the floor would be no better than the drained pool
+        assert_eq!(execute(Some(PRICE_SCALE as u64), Some(10_000), 947_150).err(), Some(IntentError::SlippageExceeded.into()));
+        assert!(emitted::<VenueFallback>().is_empty());
+        
+        // A drained pool gives far less than the slippage floor, so Jupiter fills it
+        let filled = execute(Some(price), Some(10_000), 947_150).unwrap();
+        assert!(filled.status == IntentStatus::Executed);
+        assert!(matches!(filled.selected_swap_protocol, SwapProtocol::Jupiter));
+        let fallbacks = emitted::<VenueFallback>();
+        assert_eq!(fallbacks.len(), 1);
+        assert!(matches!(
+            (&fallbacks[0].old_protocol, &fallbacks[0].new_protocol),
+            (SwapProtocol::Raydium, SwapProtocol::Jupiter)
+        ));
+        assert!(fallbacks[0].primary_output < 10_000);
+        assert_eq!(fallbacks[0].fallback_output, 947_150);
+    }
+    
+    #[test]
+    fn fees_land_in_the_treasury_ata_for_each_fee_mint() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let treasury = protocol.treasury_authority;
+        let execute = |intent: &IntentAccount, treasury_fee_account: Option<AccountInfo<'static>>| {
+            let mut accounts = jupiter_execution(intent, &protocol);
+            if let Some(treasury_fee_account) = treasury_fee_account {
+                accounts[7] = treasury_fee_account;
+            }
+            let treasury_info = accounts[7].clone();
+            let route = jupiter_route(intent, 1);
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route))
+                .map(|()| treasury_info)
+        };
+        let usdc_intent = pending_intent(Pubkey::new_unique());
+        let sol_intent = pending_intent(Pubkey::new_unique());
+        
+        // A treasury-owned account for the right mint still has to be the treasury's ATA
+        let stray = token_account(treasury, usdc_intent.from_mint, 0);
+        assert_eq!(execute(&usdc_intent, Some(stray)).err(), Some(IntentError::NonCanonicalTreasury.into()));
+        let other_mint_ata = token_account_at(treasury_fee_ata(&treasury, &sol_intent.from_mint), treasury, sol_intent.from_mint, 0);
+        assert_eq!(execute(&usdc_intent, Some(other_mint_ata)).err(), Some(IntentError::FeeMintMismatch.into()));
+        
+        let usdc_treasury = execute(&usdc_intent, None).unwrap();
+        let sol_treasury = execute(&sol_intent, None).unwrap();
+        assert_eq!(*usdc_treasury.key, treasury_fee_ata(&treasury, &usdc_intent.from_mint));
+        assert_eq!(*sol_treasury.key, treasury_fee_ata(&treasury, &sol_intent.from_mint));
+        assert_ne!(usdc_treasury.key, sol_treasury.key);
+        assert_eq!(token_balance(&usdc_treasury), usdc_intent.protocol_fee);
+        assert_eq!(token_balance(&sol_treasury), sol_intent.protocol_fee);
+    }
     
-    #[account(mut)]
-    pub user_destination_token: Account<'info, TokenAccount>,
+    #[test]
+    fn expiring_soon_warnings_fire_only_inside_the_window() {
+        let mut protocol = protocol();
+        (protocol.expiry_warning_window, protocol.expiry_bounty_lamports) = (600, 5_000);
+        let protocol_state = protocol_account(&protocol);
+        let intent = pending_intent(Pubkey::new_unique());
+        let intent_info = state(Pubkey::new_unique(), &intent);
+        let before = intent_info.data.borrow().to_vec();
+        let warn = |intent_info: &AccountInfo<'static>| {
+            run::<EmitExpiringSoon, _>(vec![intent_info.clone(), protocol_state.clone()], intentfi::emit_expiring_soon).unwrap();
+            emitted::<IntentExpiringSoon>()
+        };
+        
+        // A fresh intent, and one already past expiry, stay silent
+        at_time(START);
+        assert!(warn(&intent_info).is_empty());
+        at_time(intent.expires_at);
+        assert!(warn(&intent_info).is_empty());
+        
+        at_time(intent.expires_at - 600);
+        let warnings = warn(&intent_info);
+        assert_eq!(warnings.len(), 1);
+        assert_eq!((warnings[0].expires_at, warnings[0].remaining_seconds), (intent.expires_at, 600));
+        assert_eq!((warnings[0].authority, warnings[0].bounty_lamports), (intent.authority, 5_000));
+        assert_eq!(intent_info.data.borrow().to_vec(), before);
+        
+        // An earlier price check deadline is when the intent goes stale
+        let mut deadlined = intent.clone();
+        deadlined.price_check_deadline = Some(START + 1_200);
+        at_time(START + 1_000);
+        let warnings = warn(&state(Pubkey::new_unique(), &deadlined));
+        assert_eq!((warnings[0].expires_at, warnings[0].remaining_seconds), (START + 1_200, 200));
+    }
     
-    #[account(mut)]
-    pub treasury_fee_account: Account<'info, TokenAccount>,
+    #[test]
+    fn stable_pair_swaps_cap_slippage_tighter_than_other_pairs() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol_state = protocol_account(&protocol());
+        let (sol, usdc, usdt) = (Pubkey::new_from_array([0; 32]), Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let create = |from_mint: Pubkey, to_mint: Pubkey, max_slippage: u16| {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let params = SwapIntentParams { max_slippage: Some(max_slippage), ..swap_params(from_mint, to_mint, 1_000_000) };
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params))
+        };
+        
+        assert_eq!(create(usdc, usdt, 1_000).unwrap_err(), IntentError::SlippageTooHighForStablePair.into());
+        assert_eq!(create(usdt, usdc, MAX_STABLE_PAIR_SLIPPAGE_BPS + 1).unwrap_err(), IntentError::SlippageTooHighForStablePair.into());
+        create(usdc, usdt, MAX_STABLE_PAIR_SLIPPAGE_BPS).unwrap();
+        // Volatile pairs keep the general limit
+        create(usdc, sol, 1_000).unwrap();
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 2);
+    }
     
-    /// CHECK: Jupiter program
-    #[account(address = jupiter::JUPITER_PROGRAM_ID)]
-    pub jupiter_program: UncheckedAccount<'info>,
+    #[test]
+    fn buys_whose_target_is_never_met_fail_after_the_max_attempts() {
+        at_time(START + 60);
+        let mut protocol = protocol();
+        protocol.max_execution_attempts = 3;
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.intent_type = IntentType::Buy;
+        intent.target_price = Some(100_000_000);
+        intent.keeper_tip_lamports = 5_000;
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts[0] = wallet(Pubkey::new_unique());
+        // 150.00 USDC on Pyth, above the 100.00 target
+        let mut oracle_data = vec![0u8; 240];
+        oracle_data[..4].copy_from_slice(&oracle::PYTH_MAGIC.to_le_bytes());
+        oracle_data[oracle::PYTH_EXPO_OFFSET..][..4].copy_from_slice(&(-8i32).to_le_bytes());
+        oracle_data[oracle::PYTH_TIMESTAMP_OFFSET..][..8].copy_from_slice(&(START + 55).to_le_bytes());
+        oracle_data[oracle::PYTH_AGG_PRICE_OFFSET..][..8].copy_from_slice(&15_000_000_000i64.to_le_bytes());
+        oracle_data[oracle::PYTH_AGG_STATUS_OFFSET..][..4].copy_from_slice(&1u32.to_le_bytes());
+        accounts[12] = account(Pubkey::new_unique(), oracle::PYTH_ORACLE_PROGRAM_ID, 1_000_000, oracle_data);
+        let (keeper, owner, intent_info, user_state) = (accounts[0].clone(), accounts[1].clone(), accounts[2].clone(), accounts[4].clone());
+        let (keeper_lamports, owner_lamports) = (keeper.lamports(), owner.lamports());
+        let execute = || {
+            let route = jupiter_route(&intent, 1);
+            run::<ExecuteSwapIntentJupiter, _>(accounts.clone(), |ctx| intentfi::execute_swap_intent_jupiter(ctx, route))
+        };
+        
+        // Misses are recorded rather than reverted, so keepers can't retry forever
+        for attempt in 1..3 {
+            execute().unwrap();
+            let missed = load::<IntentAccount>(&intent_info);
+            assert!(missed.status == IntentStatus::Pending && !missed.is_executing);
+            assert_eq!(missed.execution_attempts, attempt);
+        }
+        assert!(emitted::<IntentFailed>().is_empty());
+        
+        execute().unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Failed);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+        let failed = emitted::<IntentFailed>();
+        assert_eq!((failed.len(), failed[0].execution_attempts), (1, 3));
+        // The tip goes back to the owner, the keeper never executed it
+        assert_eq!((keeper.lamports(), owner.lamports()), (keeper_lamports, owner_lamports + 5_000));
+        assert_eq!(execute().unwrap_err(), IntentError::IntentNotPending.into());
+    }
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
-
-
-
-#[derive(Accounts)]
-pub struct ExecuteSwapIntentRaydium<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn sol_legs_wrap_the_owners_lamports_and_unwrap_back_to_sol() {
+        at_time(START + 60);
+        let protocol = protocol();
+        // The keeper is a separate wallet so the owner's lamports are tracked on their own
+        let execute = |intent: &IntentAccount, source: AccountInfo<'static>, destination: AccountInfo<'static>, owner_signs: bool| {
+            let mut accounts = jupiter_execution(intent, &protocol);
+            accounts[0] = wallet(Pubkey::new_unique());
+            accounts[1].is_signer = owner_signs;
+            (accounts[5], accounts[6]) = (source, destination);
+            let route = jupiter_route(intent, 1);
+            let infos = (accounts[1].clone(), accounts[2].clone(), accounts[7].clone());
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).map(|()| infos)
+        };
+        
+        // SOL -> USDC: the owner's lamports top up an empty wSOL account to cover the swap and fee
+        let mut sol_to_usdc = pending_intent(Pubkey::new_unique());
+        sol_to_usdc.from_mint = wsol::NATIVE_MINT;
+        let owner = sol_to_usdc.authority;
+        
+        // Keepers can't wrap lamports for the owner
+        let err = execute(&sol_to_usdc, wsol_account(owner, 0), token_account(owner, sol_to_usdc.to_mint, 0), false).err();
+        assert_eq!(err, Some(IntentError::OwnerSignatureRequired.into()));
+        
+        let source = wsol_account(owner, 0);
+        let (user, intent_info, treasury) =
+            execute(&sol_to_usdc, source.clone(), token_account(owner, sol_to_usdc.to_mint, 0), true).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed);
+        assert_eq!(token_balance(&treasury), sol_to_usdc.protocol_fee);
+        // The wSOL account is closed, handing what the host's no-op swap left in it back as SOL
+        assert_eq!((source.lamports(), *source.owner), (0, system_program::ID));
+        assert_eq!(user.lamports(), 10_000_000_000 + 2_039_280 - sol_to_usdc.protocol_fee);
+        
+        // USDC -> SOL: the wSOL destination is closed into the owner's lamports
+        let mut usdc_to_sol = pending_intent(Pubkey::new_unique());
+        usdc_to_sol.to_mint = wsol::NATIVE_MINT;
+        let owner = usdc_to_sol.authority;
+        let destination = wsol_account(owner, 0);
+        let (user, intent_info, _) =
+            execute(&usdc_to_sol, token_account(owner, usdc_to_sol.from_mint, usdc_to_sol.amount), destination.clone(), true).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed);
+        assert_eq!((destination.lamports(), *destination.owner), (0, system_program::ID));
+        assert_eq!(user.lamports(), 10_000_000_000 + 2_039_280);
+    }
     
-    #[account(
-        mut,
-        constraint = intent_account.authority == user.key()
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn owners_move_a_pending_buys_target_up_and_down() {
+        at_time(START + 60);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.intent_type = IntentType::Buy;
+        intent.target_price = Some(100_000_000);
+        intent.execution_attempts = 2;
+        let intent_info = state(Pubkey::new_unique(), &intent);
+        let update = |signer: Pubkey, intent_info: &AccountInfo<'static>, new_target: u64| {
+            run::<UpdateTargetPrice, _>(vec![wallet(signer), intent_info.clone()], |ctx| {
+                intentfi::update_target_price(ctx, new_target)
+            })
+        };
+        
+        assert_eq!(update(Pubkey::new_unique(), &intent_info, 120_000_000).unwrap_err(), IntentError::Unauthorized.into());
+        assert_eq!(update(intent.authority, &intent_info, 100_000_000).unwrap_err(), IntentError::InvalidTargetPrice.into());
+        
+        // A new target gets a fresh set of execution attempts
+        update(intent.authority, &intent_info, 120_000_000).unwrap();
+        let raised = load::<IntentAccount>(&intent_info);
+        assert_eq!((raised.target_price, raised.execution_attempts), (Some(120_000_000), 0));
+        update(intent.authority, &intent_info, 90_000_000).unwrap();
+        assert_eq!(load::<IntentAccount>(&intent_info).target_price, Some(90_000_000));
+        let updates = emitted::<TargetPriceUpdated>();
+        assert_eq!(updates.len(), 2);
+        assert_eq!((updates[0].old_target, updates[0].new_target), (100_000_000, 120_000_000));
+        assert_eq!((updates[1].old_target, updates[1].new_target), (120_000_000, 90_000_000));
+        
+        // Plain swaps have no target to move
+        let swap = pending_intent(Pubkey::new_unique());
+        let err = update(swap.authority, &state(Pubkey::new_unique(), &swap), 90_000_000).unwrap_err();
+        assert_eq!(err, IntentError::NotConditionalIntent.into());
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn liquidity_deposits_price_off_live_vaults_not_the_callers_reserves() {
+        at_time(START + 60);
+        let protocol = protocol();
+        // Deposits 997,000 coin and 1,994,000 pc into a pool whose vaults hold 1,000,000,000
+        // coin and `live_pc_reserve` pc, while `pool_info` claims `snapshot_pc_reserve` pc
+        let deposit = |live_pc_reserve: u64, snapshot_pc_reserve: u64| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            let (coin, pc, pool, amm_authority) = (intent.from_mint, intent.to_mint, Pubkey::new_unique(), Pubkey::new_unique());
+            intent.intent_type = IntentType::AddLiquidity;
+            intent.pool_id = Some(pool);
+            intent.secondary_amount = 1_994_000;
+            let owner = intent.authority;
+            let swap = jupiter_execution(&intent, &protocol);
+            let coin_vault = token_account(Pubkey::new_unique(), coin, 1_000_000_000);
+            let pc_vault = token_account(Pubkey::new_unique(), pc, live_pc_reserve);
+            let lp_mint = mint(Pubkey::new_unique(), Some(amm_authority), 1_000_000_000);
+            let mut pool_data = vec![0u8; raydium::AMM_PC_VAULT_OFFSET + 32];
+            pool_data[raydium::AMM_COIN_VAULT_OFFSET..][..32].copy_from_slice(coin_vault.key.as_ref());
+            pool_data[raydium::AMM_PC_VAULT_OFFSET..][..32].copy_from_slice(pc_vault.key.as_ref());
+            let mut pool_info = raydium::RaydiumPoolInfo::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            (pool_info.pool_coin_token_account, pool_info.pool_pc_token_account) = (coin_vault.key(), pc_vault.key());
+            (pool_info.coin_mint_address, pool_info.pc_mint_address, pool_info.lp_mint_address) = (coin, pc, lp_mint.key());
+            (pool_info.pool_coin_amount, pool_info.pool_pc_amount) = (1_000_000_000, snapshot_pc_reserve);
+            
+            let mut accounts = swap[..5].to_vec();
+            accounts.extend([
+                token_account(owner, coin, intent.amount),
+                token_account(owner, pc, intent.secondary_amount),
+                coin_vault,
+                pc_vault,
+                lp_mint.clone(),
+                token_account(owner, lp_mint.key(), 0),
+            ]);
+            accounts.extend(swap[7..10].iter().cloned());
+            accounts.extend([
+                account(pool, raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, pool_data),
+                account(amm_authority, system_program::ID, 0, Vec::new()),
+                account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 0, Vec::new()),
+                account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 0, Vec::new()),
+                account(Pubkey::new_unique(), Pubkey::new_unique(), 0, Vec::new()),
+                account(Pubkey::new_unique(), Pubkey::new_unique(), 0, Vec::new()),
+                program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+                program(token::ID),
+                program(system_program::ID),
+            ]);
+            let (intent_info, user_lp) = (accounts[2].clone(), accounts[10].clone());
+            run::<ExecuteAddLiquidityIntent, _>(accounts, |ctx| intentfi::execute_add_liquidity_intent(ctx, pool_info))
+                .map(|()| (load::<IntentAccount>(&intent_info), token_balance(&user_lp)))
+        };
+        
+        // A snapshot matching the deposit doesn't hide that the pool has since moved to 1:3
+        assert_eq!(deposit(3_000_000_000, 2_000_000_000).err(), Some(IntentError::SlippageExceeded.into()));
+        
+        // Nor does a stale 1:3 snapshot block a deposit at the live 1:2 ratio, LP is pro rata to the vaults
+        let (added, lp_tokens) = deposit(2_000_000_000, 3_000_000_000).unwrap();
+        assert!(added.status == IntentStatus::Executed);
+        assert_eq!((added.lp_tokens, lp_tokens), (Some(997_000), 997_000));
+        let events = emitted::<LiquidityAdded>();
+        assert_eq!((events[0].amount_a, events[0].amount_b), (997_000, 1_994_000));
+    }
     
-    #[account(
-        mut,
-        seeds = [b"user_account", user.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn swaps_and_lends_of_equal_size_pay_their_own_type_fee() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let mint = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket {
+                protocol: LendingProtocol::Solend,
+                mint,
+                reserve: Pubkey::new_unique(),
+                market: Pubkey::new_unique(),
+            }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let registry_state = state(pda(&[b"lending_market_registry"]).0, &registry);
+        let set_type_fee = |signer: Pubkey, intent_type: IntentType, fee_bps: u16| {
+            run::<UpdateProtocolConfig, _>(vec![wallet(signer), protocol_state.clone()], |ctx| {
+                intentfi::set_type_fee(ctx, intent_type, fee_bps)
+            })
+        };
+        
+        assert_eq!(set_type_fee(Pubkey::new_unique(), IntentType::Lend, 10).unwrap_err(), IntentError::Unauthorized.into());
+        assert_eq!(set_type_fee(protocol.authority, IntentType::Lend, MAX_TYPE_FEE_BPS + 1).unwrap_err(), IntentError::InvalidTypeFee.into());
+        set_type_fee(protocol.authority, IntentType::Lend, 10).unwrap();
+        let updates = emitted::<TypeFeeUpdated>();
+        assert_eq!((updates[0].old_fee_bps, updates[0].new_fee_bps), (PROTOCOL_FEE_BPS, 10));
+        
+        let accounts = swap_creation(&owner, &protocol_state, &user_state);
+        let swap_info = accounts[3].clone();
+        let params = swap_params(mint, Pubkey::new_unique(), 1_000_000);
+        run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).unwrap();
+        let infos = lend_creation(&owner, &protocol_state, &user_state, &registry_state, &mint);
+        let lend_info = infos[3].clone();
+        let params = lend_params(mint);
+        let args = params.try_to_vec().unwrap();
+        run_with_args::<CreateLendIntent, _>(infos, &args, |ctx| intentfi::create_lend_intent(ctx, params)).unwrap();
+        
+        let (swap, lend) = (load::<IntentAccount>(&swap_info), load::<IntentAccount>(&lend_info));
+        assert_eq!((swap.amount, lend.amount), (1_000_000, 1_000_000));
+        assert_eq!((swap.fee_bps, swap.protocol_fee), (PROTOCOL_FEE_BPS, 3_000));
+        assert_eq!((lend.fee_bps, lend.protocol_fee), (10, 1_000));
+    }
     
-    #[account(mut)]
-    pub user_source_token: Account<'info, TokenAccount>,
+    #[test]
+    fn partial_fills_below_the_minimum_are_refused_unless_they_finish_the_intent() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol_state = protocol_account(&protocol());
+        let create = |min_fill_amount: u64| {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let intent_info = accounts[3].clone();
+            let mut params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+            params.min_fill_amount = min_fill_amount;
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).map(|()| load::<IntentAccount>(&intent_info))
+        };
+        
+        // A minimum above the whole intent could never be met
+        assert_eq!(create(1_000_001).err(), Some(IntentError::InvalidAmount.into()));
+        let intent = create(100_000).ok().unwrap();
+        assert_eq!(intent.min_fill_amount, 100_000);
+        
+        // Dust fills are turned away while more than the minimum remains
+        assert_eq!(check_fill_amount(99_999, intent.amount, intent.min_fill_amount).unwrap_err(), IntentError::FillTooSmall.into());
+        check_fill_amount(100_000, intent.amount, intent.min_fill_amount).unwrap();
+        check_fill_amount(950_000, intent.amount, intent.min_fill_amount).unwrap();
+        // The last 50,000 are below the minimum but close the intent out
+        check_fill_amount(50_000, 50_000, intent.min_fill_amount).unwrap();
+        assert_eq!(check_fill_amount(40_000, 50_000, intent.min_fill_amount).unwrap_err(), IntentError::FillTooSmall.into());
+        // Fills can't be empty or exceed what's left
+        assert_eq!(check_fill_amount(0, 50_000, 0).unwrap_err(), IntentError::InvalidAmount.into());
+        assert_eq!(check_fill_amount(50_001, 50_000, 0).unwrap_err(), IntentError::InvalidAmount.into());
+    }
     
-    #[account(mut)]
-    pub user_destination_token: Account<'info, TokenAccount>,
+    #[test]
+    fn keepers_execute_through_a_delegation_until_it_is_revoked() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let owner = Pubkey::new_unique();
+        let from_mint = Pubkey::new_unique();
+        let source = token_account(owner, from_mint, 2_000_000);
+        let manage = |delegate: bool| {
+            let infos = vec![wallet(owner), source.clone(), protocol_account(&protocol), program(token::ID)];
+            match delegate {
+                true => run::<ManageExecutionDelegation, _>(infos, |ctx| intentfi::delegate_for_execution(ctx, 1_000_000)),
+                false => run::<ManageExecutionDelegation, _>(infos, intentfi::revoke_delegation),
+            }
+        };
+        // A keeper run: the owner doesn't sign, so fees move on the protocol PDA's allowance
+        let execute_as_keeper = || {
+            let mut intent = pending_intent(owner);
+            intent.from_mint = from_mint;
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            accounts[0] = wallet(Pubkey::new_unique());
+            accounts[1].is_signer = false;
+            accounts[5] = source.clone();
+            let (intent_info, treasury) = (accounts[2].clone(), accounts[7].clone());
+            let route = jupiter_route(&intent, 1);
+            let result = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route));
+            (result, load::<IntentAccount>(&intent_info), token_balance(&treasury))
+        };
+        
+        manage(true).unwrap();
+        let delegated = spl_token::state::Account::unpack(&source.data.borrow()).unwrap();
+        assert_eq!((delegated.delegate, delegated.delegated_amount), (COption::Some(pda(&[b"protocol_state"]).0), 1_000_000));
+        let (result, intent, treasury_balance) = execute_as_keeper();
+        result.unwrap();
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!(treasury_balance, intent.protocol_fee);
+        assert_eq!(spl_token::state::Account::unpack(&source.data.borrow()).unwrap().delegated_amount, 1_000_000 - intent.protocol_fee);
+        
+        manage(false).unwrap();
+        assert_eq!(spl_token::state::Account::unpack(&source.data.borrow()).unwrap().delegate, COption::None);
+        let (result, intent, treasury_balance) = execute_as_keeper();
+        assert!(result.is_err());
+        assert!(intent.status == IntentStatus::Pending);
+        assert_eq!(treasury_balance, 0);
+    }
     
-    #[account(mut)]
-    pub treasury_fee_account: Account<'info, TokenAccount>,
+    #[test]
+    fn jupiter_routes_longer_than_max_route_steps_are_refused() {
+        at_time(START + 60);
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        
+        let accounts = jupiter_execution(&intent, &protocol);
+        let intent_info = accounts[2].clone();
+        let route = jupiter_route(&intent, jupiter::MAX_ROUTE_STEPS + 1);
+        let err = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap_err();
+        assert_eq!(err, IntentError::RouteTooLong.into());
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+        
+        let accounts = jupiter_execution(&intent, &protocol);
+        let intent_info = accounts[2].clone();
+        let route = jupiter_route(&intent, jupiter::MAX_ROUTE_STEPS);
+        let treasury_info = accounts[7].clone();
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed);
+        assert_eq!(token_balance(&treasury_info), intent.protocol_fee);
+    }
     
-    /// CHECK: Raydium pool account
-    pub raydium_pool: UncheckedAccount<'info>,
+    #[test]
+    fn jupiter_executions_record_the_venues_their_route_took() {
+        at_time(START + 60);
+        let intent = pending_intent(Pubkey::new_unique());
+        let protocol = protocol();
+        let execute = |labels: &[&str]| {
+            let mut route = jupiter_route(&intent, labels.len());
+            for (step, label) in route.route_plan.iter_mut().zip(labels) {
+                step.swap_info.label = label.to_string();
+            }
+            run::<ExecuteSwapIntentJupiter, _>(jupiter_execution(&intent, &protocol), |ctx| {
+                intentfi::execute_swap_intent_jupiter(ctx, route)
+            })
+            .unwrap();
+            let executed = emitted::<SwapIntentExecuted>();
+            assert_eq!(executed.len(), 1);
+            assert!(matches!(executed[0].protocol, SwapProtocol::Jupiter));
+            executed[0].route_labels.clone()
+        };
+        
+        assert_eq!(execute(&["Whirlpool", "Raydium", "Meteora"]), ["Whirlpool", "Raydium", "Meteora"]);
+        // Longer routes keep only the first MAX_ROUTE_LABELS venues
+        let long_route = ["Whirlpool", "Raydium", "Meteora", "Phoenix", "Lifinity", "Orca"];
+        assert_eq!(execute(&long_route), long_route[..jupiter::MAX_ROUTE_LABELS]);
+    }
     
-    /// CHECK: Raydium program
-    #[account(address = raydium::RAYDIUM_AMM_PROGRAM_ID)]
-    pub raydium_program: UncheckedAccount<'info>,
+    #[test]
+    fn scheduled_intents_execute_only_from_not_before() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol_state = protocol_account(&protocol());
+        let create = |not_before: Option<i64>| {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let intent_info = accounts[3].clone();
+            let mut params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+            params.not_before = not_before;
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).map(|()| intent_info)
+        };
+        
+        // A start time at or past the expiry could never execute
+        let err = create(Some(START + INTENT_EXPIRY_SECONDS)).unwrap_err();
+        assert_eq!(err, IntentError::InvalidNotBefore.into());
+        let unlock = START + 3_600;
+        let mut intent = load::<IntentAccount>(&create(Some(unlock)).unwrap());
+        assert_eq!(intent.not_before, Some(unlock));
+        intent.selected_swap_protocol = SwapProtocol::Jupiter;
+        
+        let execute = || {
+            let accounts = jupiter_execution(&intent, &protocol());
+            let intent_info = accounts[2].clone();
+            let route = jupiter_route(&intent, 1);
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route))
+                .map(|()| load::<IntentAccount>(&intent_info).status)
+        };
+        at_time(unlock - 1);
+        assert_eq!(execute().err(), Some(IntentError::IntentNotYetActive.into()));
+        at_time(unlock);
+        assert!(execute().is_ok_and(|status| status == IntentStatus::Executed));
+    }
     
-    pub token_program: Program<'info, Token>,
-    pub system_program: Program<'info, System>,
-}
-
-
-
-#[derive(Accounts)]
-pub struct CreateLendIntent<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn every_intent_transition_emits_one_timestamped_state_change() {
+        at_time(START);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let transitions = || {
+            emitted::<IntentStateChanged>()
+                .into_iter()
+                .map(|event| (event.intent_id, event.old_status, event.new_status, event.timestamp))
+                .collect::<Vec<_>>()
+        };
+        
+        // Creation starts the timeline with no previous status
+        let create = || {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let intent_info = accounts[3].clone();
+            let params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).unwrap();
+            intent_info
+        };
+        let (cancelled, executed, expired) = (create(), create(), create());
+        let created = transitions();
+        assert_eq!(created.len(), 3);
+        for (event, intent) in created.iter().zip([&cancelled, &executed, &expired]) {
+            assert!(*event == (intent.key(), None, IntentStatus::Pending, START));
+        }
+        
+        at_time(START + 60);
+        let lending_cap = |intent: &AccountInfo<'static>| uninitialized(pda(&[b"lending_cap", load::<IntentAccount>(intent).from_mint.as_ref()]).0);
+        let infos = vec![owner.clone(), cancelled.clone(), user_state.clone(), lending_cap(&cancelled)];
+        run::<CancelIntent, _>(infos, intentfi::cancel_intent).unwrap();
+        assert!(transitions() == [(cancelled.key(), Some(IntentStatus::Pending), IntentStatus::Cancelled, START + 60)]);
+        
+        at_time(START + 120);
+        let mut accounts = jupiter_execution(&load::<IntentAccount>(&executed), &protocol);
+        accounts[2] = executed.clone();
+        let route = jupiter_route(&load::<IntentAccount>(&executed), 1);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert!(transitions() == [(executed.key(), Some(IntentStatus::Pending), IntentStatus::Executed, START + 120)]);
+        
+        let expires_at = load::<IntentAccount>(&expired).expires_at;
+        at_time(expires_at);
+        let infos = vec![
+            owner.clone(),
+            expired.clone(),
+            owner.clone(),
+            user_state.clone(),
+            protocol_state.clone(),
+            account(pda(&[b"bounty_vault"]).0, system_program::ID, 0, Vec::new()),
+            lending_cap(&expired),
+            program(system_program::ID),
+        ];
+        run::<ExpireIntent, _>(infos, intentfi::expire_intent).unwrap();
+        assert!(transitions() == [(expired.key(), Some(IntentStatus::Pending), IntentStatus::Expired, expires_at)]);
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn client_order_ids_round_trip_through_creation_and_execution_events() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let order_id = *b"order-0000000042";
+        
+        let accounts = swap_creation(&owner, &protocol_account(&protocol()), &user_state);
+        let intent_info = accounts[3].clone();
+        let mut params = swap_params(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000);
+        params.client_order_id = Some(order_id);
+        run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).unwrap();
+        let created = emitted::<IntentStateChanged>();
+        assert_eq!(created.len(), 1);
+        assert_eq!((created[0].intent_id, created[0].client_order_id), (intent_info.key(), Some(order_id)));
+        
+        at_time(START + 60);
+        let mut intent = load::<IntentAccount>(&intent_info);
+        assert_eq!(intent.client_order_id, Some(order_id));
+        intent.selected_swap_protocol = SwapProtocol::Jupiter;
+        let route = jupiter_route(&intent, 1);
+        run::<ExecuteSwapIntentJupiter, _>(jupiter_execution(&intent, &protocol()), |ctx| {
+            intentfi::execute_swap_intent_jupiter(ctx, route)
+        }).unwrap();
+        let executed = emitted::<IntentStateChanged>();
+        assert!(executed[0].new_status == IntentStatus::Executed);
+        assert_eq!(executed[0].client_order_id, Some(order_id));
+        assert_eq!(emitted::<SwapIntentExecuted>()[0].client_order_id, Some(order_id));
+    }
     
-    #[account(
-        mut,
-        seeds = [b"user_account", authority.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn protocol_metrics_derive_rates_and_report_zero_before_any_intent() {
+        at_time(START);
+        let metrics = |protocol: &ProtocolState| {
+            run::<EmitProtocolMetrics, _>(vec![protocol_account(protocol)], intentfi::emit_protocol_metrics).unwrap();
+            emitted::<ProtocolMetrics>().pop().unwrap()
+        };
+        
+        let mut protocol = protocol();
+        let fresh = metrics(&protocol);
+        assert_eq!((fresh.execution_rate_bps, fresh.average_fee_per_intent, fresh.unexecuted_intents), (0, 0, 0));
+        
+        protocol.total_intents_created = 8;
+        protocol.total_intents_executed = 6;
+        protocol.total_fees_collected = 18_000;
+        let busy = metrics(&protocol);
+        assert_eq!((busy.total_intents_created, busy.total_intents_executed), (8, 6));
+        assert_eq!(busy.execution_rate_bps, 7_500);
+        assert_eq!(busy.average_fee_per_intent, 3_000);
+        assert_eq!(busy.unexecuted_intents, 2);
+        assert!(!busy.is_paused);
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 1 + 32 + 32 + 8 + 8 + 2 + 2 + 8 + 2 + 8 + 8 + 1 + 32 + 32 + 8 + 8 + 8 + 8 + 1,
-        seeds = [b"intent", authority.key().as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
-        bump
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn fee_exempt_accounts_execute_without_paying_the_protocol_fee() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let market_maker = Pubkey::new_unique();
+        let update = |signer: Pubkey, exempt: bool| {
+            run::<UpdateProtocolConfig, _>(vec![wallet(signer), protocol_state.clone()], |ctx| {
+                if exempt {
+                    intentfi::add_fee_exempt(ctx, market_maker)
+                } else {
+                    intentfi::remove_fee_exempt(ctx, market_maker)
+                }
+            })
+        };
+        assert_eq!(update(market_maker, true).unwrap_err(), IntentError::Unauthorized.into());
+        update(protocol.authority, true).unwrap();
+        assert_eq!(update(protocol.authority, true).unwrap_err(), IntentError::AlreadyFeeExempt.into());
+        
+        let execute = |intent: &IntentAccount| {
+            let accounts = jupiter_execution(intent, &load::<ProtocolState>(&protocol_state));
+            let (intent_info, treasury_info) = (accounts[2].clone(), accounts[7].clone());
+            let route = jupiter_route(intent, 1);
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+            (load::<IntentAccount>(&intent_info), token_balance(&treasury_info))
+        };
+        let (exempt, treasury_balance) = execute(&pending_intent(market_maker));
+        assert_eq!((exempt.protocol_fee, treasury_balance), (0, 0));
+        assert_eq!(exempt.cumulative_in, exempt.amount);
+        
+        // Everyone else still pays, as does the market maker once revoked
+        let (charged, treasury_balance) = execute(&pending_intent(Pubkey::new_unique()));
+        assert_eq!(treasury_balance, charged.protocol_fee);
+        assert!(charged.protocol_fee > 0);
+        update(protocol.authority, false).unwrap();
+        assert_eq!(update(protocol.authority, false).unwrap_err(), IntentError::NotFeeExempt.into());
+        let (revoked, treasury_balance) = execute(&pending_intent(market_maker));
+        assert_eq!(treasury_balance, revoked.protocol_fee);
+        assert!(revoked.protocol_fee > 0);
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct ExecuteLendIntentSolend<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn wrong_protocol_failures_log_what_the_intent_was_routed_to() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let intent = pending_intent(Pubkey::new_unique());
+        
+        // A Jupiter intent sent to the Raydium handler
+        let mut accounts = jupiter_execution(&intent, &protocol);
+        accounts.truncate(11);
+        accounts.extend([
+            account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, Vec::new()),
+            token_account(Pubkey::new_unique(), intent.from_mint, 0),
+            token_account(Pubkey::new_unique(), intent.to_mint, 0),
+            program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+            program(token::ID),
+            program(system_program::ID),
+        ]);
+        logged();
+        let pool_info = raydium::RaydiumPoolInfo::deserialize(&mut &[0u8; 1024][..]).unwrap();
+        let result = run::<ExecuteSwapIntentRaydium, _>(accounts, |ctx| intentfi::execute_swap_intent_raydium(ctx, pool_info));
+        assert_eq!(result.unwrap_err(), IntentError::WrongProtocol.into());
+        assert!(logged().iter().any(|log| log == "❌ execute_swap_intent_raydium called on an intent routed to Jupiter"));
+        
+        // The same swap intent sent to the Solend lend handler
+        let registry = LendingMarketRegistry { markets: Vec::new(), bump: pda(&[b"lending_market_registry"]).1 };
+        let mut accounts = lend_execution(&intent, &protocol, &registry);
+        accounts.extend([absent(), absent(), absent(), absent(), absent(), absent(), absent(), program(token::ID)]);
+        let result = run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend);
+        assert_eq!(result.unwrap_err(), IntentError::WrongProtocol.into());
+        assert!(logged().iter().any(|log| log == "❌ execute_lend_intent_solend called on an intent routed to None"));
+    }
     
-    #[account(
-        mut,
-        constraint = intent_account.authority == user.key()
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn buys_refuse_trades_moving_the_price_past_their_impact_cap() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mut intent = pending_intent(Pubkey::new_unique());
+        (intent.intent_type, intent.selected_swap_protocol) = (IntentType::Buy, SwapProtocol::Raydium);
+        intent.max_price_impact = Some(100);
+        // Buys 997,000 tokens' worth into a pool `reserve_in` deep on the input side
+        let buy_from_pool = |reserve_in: u64| {
+            let coin_vault = token_account(Pubkey::new_unique(), intent.from_mint, reserve_in);
+            let pc_vault = token_account(Pubkey::new_unique(), intent.to_mint, 2 * reserve_in);
+            let mut pool_data = vec![0u8; raydium::AMM_PC_VAULT_OFFSET + 32];
+            pool_data[raydium::AMM_COIN_VAULT_OFFSET..][..32].copy_from_slice(coin_vault.key.as_ref());
+            pool_data[raydium::AMM_PC_VAULT_OFFSET..][..32].copy_from_slice(pc_vault.key.as_ref());
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            accounts.truncate(11);
+            accounts.extend([
+                account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, pool_data),
+                coin_vault,
+                pc_vault,
+                program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+                program(token::ID),
+                program(system_program::ID),
+            ]);
+            let (intent_info, treasury) = (accounts[2].clone(), accounts[7].clone());
+            let pool_info = raydium::RaydiumPoolInfo::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            let result = run::<ExecuteSwapIntentRaydium, _>(accounts, |ctx| intentfi::execute_swap_intent_raydium(ctx, pool_info));
+            (result, load::<IntentAccount>(&intent_info), token_balance(&treasury))
+        };
+        
+        // 997,000 into a 50M-deep pool moves it ~1.96%, twice the 1% cap
+        assert_eq!(raydium::calculate_price_impact_bps(997_000, 50_000_000), 195);
+        let (result, shallow, treasury_balance) = buy_from_pool(50_000_000);
+        assert_eq!(result.unwrap_err(), IntentError::PriceImpactTooHigh.into());
+        assert!(shallow.status == IntentStatus::Pending);
+        assert_eq!(treasury_balance, 0);
+        // The same buy barely moves a 1B-deep pool
+        let (result, deep, treasury_balance) = buy_from_pool(1_000_000_000);
+        result.unwrap();
+        assert!(deep.status == IntentStatus::Executed);
+        assert_eq!(treasury_balance, intent.protocol_fee);
+        
+        // Through Jupiter the quote's own impact is what's capped
+        intent.selected_swap_protocol = SwapProtocol::Jupiter;
+        for (price_impact_bps, executes) in [(101, false), (100, true)] {
+            let accounts = jupiter_execution(&intent, &protocol);
+            let intent_info = accounts[2].clone();
+            let mut route = jupiter_route(&intent, 1);
+            route.price_impact_bps = price_impact_bps;
+            let result = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route));
+            assert_eq!(result.is_ok(), executes, "{price_impact_bps} bps impact");
+            assert_eq!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed, executes);
+        }
+    }
     
-    #[account(mut)]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn raydium_swaps_price_against_the_pools_own_vaults() {
+        at_time(START + 60);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.selected_swap_protocol = SwapProtocol::Raydium;
+        let protocol = protocol();
+        let (reserve_in, reserve_out) = (50_000_000_000, 100_000_000_000);
+        let coin_vault = token_account(Pubkey::new_unique(), intent.from_mint, reserve_in);
+        let pc_vault = token_account(Pubkey::new_unique(), intent.to_mint, reserve_out);
+        let mut pool_data = vec![0u8; raydium::AMM_PC_VAULT_OFFSET + 32];
+        pool_data[raydium::AMM_COIN_VAULT_OFFSET..][..32].copy_from_slice(coin_vault.key.as_ref());
+        pool_data[raydium::AMM_PC_VAULT_OFFSET..][..32].copy_from_slice(pc_vault.key.as_ref());
+        let pool = account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, pool_data);
+        
+        let execute = |pool: &AccountInfo<'static>, coin_vault: &AccountInfo<'static>| {
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            accounts.truncate(11);
+            accounts.extend([
+                pool.clone(),
+                coin_vault.clone(),
+                pc_vault.clone(),
+                program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+                program(token::ID),
+                program(system_program::ID),
+            ]);
+            let intent_info = accounts[2].clone();
+            // The keeper claims a nearly free pool with the vaults of some other pair
+            let mut pool_info = raydium::RaydiumPoolInfo::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            pool_info.pool_coin_token_account = Pubkey::new_unique();
+            pool_info.pool_pc_token_account = Pubkey::new_unique();
+            pool_info.pool_coin_amount = 1_000_000_000;
+            pool_info.pool_pc_amount = u64::MAX / 2;
+            run::<ExecuteSwapIntentRaydium, _>(accounts, |ctx| intentfi::execute_swap_intent_raydium(ctx, pool_info))
+                .map(|()| load::<IntentAccount>(&intent_info))
+        };
+        
+        // Vaults the pool doesn't record, or a pool Raydium doesn't own, are refused
+        let fake_vault = token_account(Pubkey::new_unique(), intent.from_mint, u64::MAX / 2);
+        assert_eq!(execute(&pool, &fake_vault).err(), Some(IntentError::InvalidPool.into()));
+        let mut fake_pool = pool.clone();
+        fake_pool.owner = Box::leak(Box::new(Pubkey::new_unique()));
+        assert_eq!(execute(&fake_pool, &coin_vault).err(), Some(IntentError::InvalidPool.into()));
+        
+        let Ok(executed) = execute(&pool, &coin_vault) else { panic!("honest vaults execute") };
+        assert!(executed.status == IntentStatus::Executed);
+        let net_amount = intent.amount - intent.protocol_fee;
+        let expected_out = raydium::calculate_raydium_output(net_amount, reserve_in, reserve_out, 25, 10000).unwrap();
+        assert_eq!((executed.cumulative_in, executed.cumulative_out), (net_amount, expected_out));
+    }
     
-    #[account(mut)]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn repays_reduce_only_the_owners_debt_and_charge_the_fee_on_what_was_repaid() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let owner = Pubkey::new_unique();
+        let obligation = |obligation_owner: Pubkey| {
+            let mut data = vec![0u8; 1300];
+            data[OBLIGATION_OWNER_OFFSET..][..32].copy_from_slice(obligation_owner.as_ref());
+            account(Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID, 1_000_000, data)
+        };
+        let execute = |obligation: AccountInfo<'static>| {
+            let mut intent = pending_intent(owner);
+            intent.intent_type = IntentType::Repay;
+            intent.to_mint = intent.from_mint;
+            intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+            intent.pool_id = Some(obligation.key());
+            let supply = token_account(Pubkey::new_unique(), intent.from_mint, 0);
+            let mut reserve_data = vec![0u8; 619];
+            reserve_data[RESERVE_LIQUIDITY_MINT_OFFSET..][..32].copy_from_slice(intent.from_mint.as_ref());
+            reserve_data[RESERVE_LIQUIDITY_SUPPLY_OFFSET..][..32].copy_from_slice(supply.key.as_ref());
+            let mut clock_data = vec![0u8; 40];
+            clock_data[32..].copy_from_slice(&(START + 60).to_le_bytes());
+            let swap = jupiter_execution(&intent, &protocol);
+            let mut accounts = swap[..6].to_vec();
+            accounts.extend([
+                swap[7].clone(),
+                swap[8].clone(),
+                swap[9].clone(),
+                obligation,
+                account(Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID, 1_000_000, reserve_data),
+                supply.clone(),
+                account(Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID, 1_000_000, Vec::new()),
+                program(solend::SOLEND_PROGRAM_ID),
+                account(anchor_lang::solana_program::sysvar::clock::ID, anchor_lang::solana_program::sysvar::ID, 1, clock_data),
+                program(token::ID),
+                program(system_program::ID),
+            ]);
+            let (intent_info, source, treasury) = (accounts[2].clone(), accounts[5].clone(), accounts[6].clone());
+            run::<ExecuteRepayIntent, _>(accounts, intentfi::execute_repay_intent)
+                .map(|()| (load::<IntentAccount>(&intent_info), [&source, &supply, &treasury].map(token_balance)))
+        };
+        
+        // Someone else's obligation can't be paid down with the owner's tokens
+        assert_eq!(execute(obligation(Pubkey::new_unique())).err(), Some(IntentError::InvalidObligation.into()));
+        
+        // Half the intent covers the whole debt, so the fee is on that half
+        OUTSTANDING_DEBT.with(|debt| debt.set(500_000));
+        let Ok((repaid, [source, supply, treasury])) = execute(obligation(owner)) else {
+            panic!("the owner's obligation is repayable")
+        };
+        assert!(repaid.status == IntentStatus::Executed);
+        assert_eq!(OUTSTANDING_DEBT.with(Cell::get), 0);
+        assert_eq!(repaid.protocol_fee, 1_500);
+        assert_eq!((source, supply, treasury), (1_000_000 - 500_000 - 1_500, 500_000, 1_500));
+        let events = emitted::<DebtRepaid>();
+        assert_eq!(events.len(), 1);
+        assert_eq!((events[0].amount_repaid, events[0].protocol_fee), (500_000, 1_500));
+    }
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn execution_price_is_the_volume_weighted_average_of_the_fills() {
+        let mut intent = pending_intent(Pubkey::new_unique());
+        // 1k in for 2k out, then 3k in for 3k out: 5k out over 4k in, not the 1.5 mean of the prices
+        record_fill(&mut intent, 1_000, 2_000).unwrap();
+        assert_eq!(intent.execution_price, Some(2 * PRICE_SCALE as u64));
+        record_fill(&mut intent, 3_000, 3_000).unwrap();
+        assert_eq!((intent.cumulative_in, intent.cumulative_out), (4_000, 5_000));
+        assert_eq!(intent.execution_price, Some(1_250_000_000));
+        assert_eq!(record_fill(&mut intent, u64::MAX, 0).err(), Some(IntentError::InvalidAmount.into()));
+        
+        // A whole Jupiter execution is one fill of the net amount at the simulated 95% rate
+        at_time(START + 60);
+        let intent = pending_intent(Pubkey::new_unique());
+        let accounts = jupiter_execution(&intent, &protocol());
+        let intent_info = accounts[2].clone();
+        let route = jupiter_route(&intent, 1);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        let executed = load::<IntentAccount>(&intent_info);
+        assert_eq!((executed.cumulative_in, executed.cumulative_out), (997_000, 947_150));
+        assert_eq!(executed.execution_price, Some(950_000_000));
+    }
     
-    #[account(mut)]
-    pub treasury_fee_account: Account<'info, TokenAccount>,
+    #[test]
+    fn fee_floor_applies_when_the_bps_fee_rounds_to_zero() {
+        assert_eq!(calculate_protocol_fee(100, 30, 0).unwrap(), 0);
+        assert_eq!(calculate_protocol_fee(100, 30, 5).unwrap(), 5);
+        // Above the floor the bps fee stands
+        assert_eq!(calculate_protocol_fee(1_000_000, 30, 5).unwrap(), 3_000);
+    }
     
-    // Solend-specific accounts
-    /// CHECK: Solend reserve account
-    pub solend_reserve: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn fee_floor_never_takes_the_whole_amount() {
+        assert_eq!(calculate_protocol_fee(5, 30, 5).unwrap_err(), IntentError::AmountBelowFeeFloor.into());
+        assert_eq!(calculate_protocol_fee(4, 30, 5).unwrap_err(), IntentError::AmountBelowFeeFloor.into());
+        assert_eq!(calculate_protocol_fee(6, 30, 5).unwrap(), 5);
+    }
     
-    /// CHECK: Solend lending market
-    pub solend_lending_market: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn fee_split_rounding_dust_goes_to_the_treasury() {
+        let beneficiaries = [
+            FeeBeneficiary { token_account: Pubkey::new_unique(), bps: 3333 },
+            FeeBeneficiary { token_account: Pubkey::new_unique(), bps: 3333 },
+            FeeBeneficiary { token_account: Pubkey::new_unique(), bps: 3334 },
+        ];
+        assert_eq!(calculate_fee_split(10_000, &beneficiaries), (vec![3_333, 3_333, 3_334], 0));
+        assert_eq!(calculate_fee_split(100, &beneficiaries), (vec![33, 33, 33], 1));
+        assert_eq!(calculate_fee_split(1, &beneficiaries), (vec![0, 0, 0], 1));
+    }
     
-    /// CHECK: Solend destination liquidity account
-    pub solend_destination_liquidity: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn fee_split_applies_once_the_config_is_initialized() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut empty = Vec::new();
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut empty, &crate::ID, false, 0);
+        assert!(load_fee_split(&info).unwrap().is_none());
+        
+        let config = FeeSplitConfig {
+            authority: Pubkey::new_unique(),
+            beneficiaries: vec![FeeBeneficiary { token_account: Pubkey::new_unique(), bps: 10000 }],
+            bump: 255,
+        };
+        let mut lamports = 0;
+        let mut data = serialized(&config);
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert_eq!(load_fee_split(&info).unwrap().unwrap().beneficiaries.len(), 1);
+        
+        // A look-alike owned by another program is not the config
+        let other_owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = serialized(&config);
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &other_owner, false, 0);
+        assert_eq!(load_fee_split(&info).err(), Some(IntentError::Unauthorized.into()));
+    }
     
-    /// CHECK: Solend collateral mint
-    pub solend_collateral_mint: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn treasury_drift_is_reported_per_mint() {
+        let key = Pubkey::new_unique();
+        let ledger = FeeLedger { mint: Pubkey::new_unique(), opening_balance: 1_000, fees_collected: 0, bump: 255 };
+        let mut lamports = 0;
+        let mut data = serialized(&ledger);
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        
+        record_treasury_fee(&info, 300).unwrap();
+        record_treasury_fee(&info, 200).unwrap();
+        let ledger = FeeLedger::try_deserialize(&mut &info.data.borrow()[..]).unwrap();
+        assert_eq!(ledger.fees_collected, 500);
+        assert_eq!(ledger.discrepancy(1_500), 0);
+        
+        // 50 tokens lost to a fee-on-transfer mint, or 20 sent in by hand
+        assert_eq!(ledger.discrepancy(1_450), -50);
+        assert_eq!(ledger.discrepancy(1_520), 20);
+    }
     
-    /// CHECK: User's collateral token account
-    pub user_collateral_account: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn lend_intents_deposit_into_the_registered_reserve() {
+        let mint = Pubkey::new_unique();
+        let solend = LendingMarket {
+            protocol: LendingProtocol::Solend,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let port = LendingMarket {
+            protocol: LendingProtocol::PortFinance,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let registry = LendingMarketRegistry { markets: vec![solend.clone(), port.clone()], bump: 255 };
+        
+        // Small deposits prefer Port Finance, as the unregistered router would
+        let selected = LendingRouter::choose_registered_lending_protocol(&registry.markets, &mint, 1_000_000).unwrap();
+        assert_eq!(selected.protocol, LendingProtocol::PortFinance);
+        assert_eq!(selected.reserve, port.reserve);
+        
+        let mut intent = blank_intent();
+        intent.from_mint = mint;
+        intent.selected_lending_protocol = Some(selected.protocol.clone());
+        intent.pool_id = Some(selected.reserve);
+        assert_eq!(expected_lend_reserve(&registry, &intent, &LendingProtocol::PortFinance).unwrap(), port.reserve);
+        // Falling back to Solend still has to use Solend's registered reserve
+        assert_eq!(expected_lend_reserve(&registry, &intent, &LendingProtocol::Solend).unwrap(), solend.reserve);
+        
+        // Nothing registered for the mint, nothing to route to
+        let other_mint = Pubkey::new_unique();
+        assert!(LendingRouter::choose_registered_lending_protocol(&registry.markets, &other_mint, 1_000_000).is_none());
+        let only_port = LendingMarketRegistry { markets: vec![port], bump: 255 };
+        assert_eq!(
+            expected_lend_reserve(&only_port, &intent, &LendingProtocol::Solend).err(),
+            Some(IntentError::LendingMarketNotRegistered.into())
+        );
+    }
     
-    /// CHECK: Solend program
-    #[account(address = solend::SOLEND_PROGRAM_ID)]
-    pub solend_program: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn port_intents_switch_to_solend_only_for_more_than_the_min_spread() {
+        at_time(START + 60);
+        let mint = Pubkey::new_unique();
+        let market = LendingMarket {
+            protocol: LendingProtocol::Solend,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let port_market = LendingMarket {
+            protocol: LendingProtocol::PortFinance,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let registry = LendingMarketRegistry {
+            markets: vec![market.clone(), port_market.clone()],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        // Port pays nothing on its idle reserve, so the intent looks to Solend paying `solend_apy`
+        let reselect = |protocol: &ProtocolState, solend_apy: u16| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            (intent.intent_type, intent.from_mint, intent.to_mint) = (IntentType::Lend, mint, mint);
+            intent.selected_lending_protocol = Some(LendingProtocol::PortFinance);
+            intent.min_apy = Some(20);
+            let destination = token_account(Pubkey::new_unique(), mint, 0);
+            let mut original_reserve = port_finance::PortReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            original_reserve.liquidity.mint_pubkey = mint;
+            // Fully borrowed, Solend lenders earn 70% of the max borrow rate
+            let mut reserve = solend::SolendReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            (reserve.liquidity.mint_pubkey, reserve.liquidity.supply_pubkey) = (mint, destination.key());
+            reserve.liquidity.borrowed_amount_wads = 1_000;
+            reserve.config.optimal_utilization_rate = 80;
+            reserve.config.max_borrow_rate = (solend_apy * 100 / 70) as u8;
+            let mut accounts = lend_execution(&intent, protocol, &registry);
+            accounts.extend([
+                reserve_account(market.reserve, solend::SOLEND_PROGRAM_ID, &reserve),
+                account(market.market, Pubkey::new_unique(), 1_000_000, Vec::new()),
+                destination,
+                absent(),
+                absent(),
+                program(solend::SOLEND_PROGRAM_ID),
+                reserve_account(port_market.reserve, port_finance::PORT_FINANCE_PROGRAM_ID, &original_reserve),
+                program(token::ID),
+            ]);
+            let intent_info = accounts[2].clone();
+            let result = run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend);
+            (result, load::<IntentAccount>(&intent_info))
+        };
+        let mut protocol = protocol();
+        assert_eq!(protocol.min_apy_spread_bps, DEFAULT_MIN_APY_SPREAD_BPS);
+        
+        // 42 bps over Port meets the intent's minimum but isn't worth switching for
+        let (result, intent) = reselect(&protocol, 42);
+        assert_eq!(result.unwrap_err(), IntentError::ApySpreadTooSmall.into());
+        assert!(intent.status == IntentStatus::Pending);
+        assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::PortFinance));
+        assert!(emitted::<ProtocolReselected>().is_empty());
+        
+        // 70 bps clears the 50 bps spread, so the intent moves to Solend
+        let (result, intent) = reselect(&protocol, 70);
+        result.unwrap();
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::Solend));
+        let reselected = emitted::<ProtocolReselected>();
+        assert_eq!((reselected[0].old_apy, reselected[0].new_apy), (0, 70));
+        
+        // Without a required spread, any gain that meets the minimum switches
+        protocol.min_apy_spread_bps = 0;
+        let (result, intent) = reselect(&protocol, 42);
+        result.unwrap();
+        assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::Solend));
+    }
     
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct ExecuteLendIntentPort<'info> {
-    #[account(mut)]
-    pub user: Signer<'info>,
+    #[test]
+    fn solend_intents_fall_back_to_port_only_once_solend_misses_the_min_apy() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mint = Pubkey::new_unique();
+        let market = LendingMarket {
+            protocol: LendingProtocol::PortFinance,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let solend_market = LendingMarket {
+            protocol: LendingProtocol::Solend,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let registry = LendingMarketRegistry {
+            markets: vec![market.clone(), solend_market.clone()],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        // Fully borrowed reserves pay lenders 70% (Solend) or 75% (Port) of the max borrow rate
+        let reserve = |max_borrow_rate: u8| {
+            let mut solend_reserve = solend::SolendReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            solend_reserve.liquidity.mint_pubkey = mint;
+            solend_reserve.liquidity.borrowed_amount_wads = 1_000;
+            solend_reserve.config.max_borrow_rate = max_borrow_rate;
+            let mut port_reserve = port_finance::PortReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            port_reserve.liquidity.mint_pubkey = mint;
+            port_reserve.liquidity.borrowed_amount = 1_000;
+            port_reserve.config.max_borrow_rate = max_borrow_rate;
+            (solend_reserve, port_reserve)
+        };
+        // Solend's registered reserve, as the Solend program keeps it
+        let registered = |reserve: &solend::SolendReserve| reserve_account(solend_market.reserve, solend::SOLEND_PROGRAM_ID, reserve);
+        let fall_back = |original_reserve: Option<AccountInfo<'static>>, port_max_borrow_rate: u8| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            (intent.intent_type, intent.from_mint, intent.to_mint) = (IntentType::Lend, mint, mint);
+            intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+            intent.min_apy = Some(20);
+            let supply = token_account(Pubkey::new_unique(), mint, 0);
+            let mut port_reserve = reserve(port_max_borrow_rate).1;
+            port_reserve.liquidity.supply_pubkey = supply.key();
+            let mut accounts = lend_execution(&intent, &protocol, &registry);
+            accounts.extend([
+                reserve_account(market.reserve, port_finance::PORT_FINANCE_PROGRAM_ID, &port_reserve),
+                supply.clone(),
+                account(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000, Vec::new()),
+                absent(),
+                program(port_finance::PORT_FINANCE_PROGRAM_ID),
+                original_reserve.unwrap_or_else(absent),
+                program(token::ID),
+            ]);
+            let intent_info = accounts[2].clone();
+            let result = run::<ExecuteLendIntentPort, _>(accounts, intentfi::execute_lend_intent_port);
+            (result, load::<IntentAccount>(&intent_info), token_balance(&supply))
+        };
+        
+        // Without Solend's reserve, or while Solend still meets the minimum, the intent stays put
+        for original_reserve in [None, Some(registered(&reserve(100).0))] {
+            let (result, intent, deposited) = fall_back(original_reserve, 100);
+            assert_eq!(result.unwrap_err(), IntentError::WrongProtocol.into());
+            assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::Solend));
+            assert_eq!(deposited, 0);
+        }
+        // Solend's APY is only read from its registered reserve, never from a keeper's stand-in
+        let unregistered = reserve_account(Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID, &reserve(0).0);
+        assert_eq!(fall_back(Some(unregistered), 100).0.unwrap_err(), IntentError::LendingMarketNotRegistered.into());
+        let forged = reserve_account(solend_market.reserve, Pubkey::new_unique(), &reserve(0).0);
+        assert_eq!(fall_back(Some(forged), 100).0.unwrap_err(), IntentError::InvalidLendReserve.into());
+        // Solend's reserve has to be for the intent's mint
+        let mut foreign = reserve(0).0;
+        foreign.liquidity.mint_pubkey = Pubkey::new_unique();
+        assert_eq!(fall_back(Some(registered(&foreign)), 100).0.unwrap_err(), IntentError::InvalidAmount.into());
+        // Port has to meet the minimum itself
+        assert_eq!(fall_back(Some(registered(&reserve(0).0)), 0).0.unwrap_err(), IntentError::APYTooLow.into());
+        assert!(emitted::<ProtocolReselected>().is_empty());
+        
+        // Solend paying nothing, Port paying 75 bps: the intent moves to Port and deposits there
+        let (result, intent, deposited) = fall_back(Some(registered(&reserve(0).0)), 100);
+        result.unwrap();
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!(intent.selected_lending_protocol, Some(LendingProtocol::PortFinance));
+        assert_eq!(intent.execution_apy, Some(75));
+        assert_eq!(deposited, intent.amount - intent.protocol_fee);
+        let reselected = emitted::<ProtocolReselected>();
+        assert_eq!(reselected.len(), 1);
+        assert!(reselected[0].old_protocol == LendingProtocol::Solend && reselected[0].new_protocol == LendingProtocol::PortFinance);
+        assert_eq!((reselected[0].old_apy, reselected[0].new_apy), (0, 75));
+    }
     
-    #[account(
-        mut,
-        constraint = intent_account.authority == user.key()
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn swaps_and_lends_count_their_whole_input_toward_volume_without_overflowing() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let intent = pending_intent(Pubkey::new_unique());
+        let accounts = jupiter_execution(&intent, &protocol);
+        let user_state = accounts[4].clone();
+        let route = jupiter_route(&intent, 1);
+        run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route)).unwrap();
+        assert_eq!(load::<UserAccount>(&user_state).total_volume, intent.amount);
+        
+        let mint = Pubkey::new_unique();
+        let reserve = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket { protocol: LendingProtocol::Solend, mint, reserve, market: Pubkey::new_unique() }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let mut intent = pending_intent(Pubkey::new_unique());
+        (intent.intent_type, intent.from_mint, intent.to_mint) = (IntentType::Lend, mint, mint);
+        intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+        // Lends the intent for an owner who has already traded `total_volume`
+        let lend = |total_volume: u64| {
+            let mut accounts = lend_execution(&intent, &protocol, &registry);
+            let mut owner = load::<UserAccount>(&accounts[4]);
+            owner.total_volume = total_volume;
+            accounts[4] = user_account(&owner);
+            let supply = token_account(Pubkey::new_unique(), mint, 0);
+            accounts.extend([
+                lend_reserve(reserve, solend::SOLEND_PROGRAM_ID, mint, &supply),
+                account(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000, Vec::new()),
+                supply,
+                absent(),
+                absent(),
+                program(solend::SOLEND_PROGRAM_ID),
+                absent(),
+                program(token::ID),
+            ]);
+            let (user_state, source) = (accounts[4].clone(), accounts[7].clone());
+            let result = run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend);
+            (result, load::<UserAccount>(&user_state).total_volume, token_balance(&source))
+        };
+        
+        // A counter that can't take the lend refuses it rather than wrapping
+        let (result, volume, source) = lend(u64::MAX - 1);
+        assert_eq!(result.unwrap_err(), IntentError::InvalidAmount.into());
+        assert_eq!((volume, source), (u64::MAX - 1, intent.amount));
+        
+        let (result, volume, source) = lend(intent.amount);
+        result.unwrap();
+        assert_eq!((volume, source), (2 * intent.amount, 0));
+    }
     
-    #[account(mut)]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn flash_swaps_repay_the_registered_reserve_within_the_same_instruction() {
+        at_time(START + 60);
+        let mut protocol = protocol();
+        let intent = pending_intent(Pubkey::new_unique());
+        let (reserve, market_authority) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket { protocol: LendingProtocol::Solend, mint: intent.from_mint, reserve, market: Pubkey::new_unique() }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let liquidity = 100 * intent.amount;
+        let borrowed = 10 * intent.amount;
+        // Flash-borrows `borrowed` from the reserve at `reserve_key` charging `fee_wad`; with
+        // `keeper_supply` the keeper names its own account in place of the reserve's supply
+        let flash_swap = |protocol: &ProtocolState, reserve_key: Pubkey, fee_wad: u64, keeper_supply: bool| {
+            let supply = token_account(market_authority, intent.from_mint, liquidity);
+            let mut reserve_data = solend::SolendReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            (reserve_data.liquidity.mint_pubkey, reserve_data.liquidity.supply_pubkey) = (intent.from_mint, supply.key());
+            reserve_data.config.fees.flash_loan_fee_wad = fee_wad;
+            let passed_supply = if keeper_supply { token_account(intent.authority, intent.from_mint, liquidity) } else { supply };
+            let fee_receiver = token_account(Pubkey::new_unique(), intent.from_mint, 0);
+            let mut instructions = vec![0u8; 16];
+            instructions[14..].copy_from_slice(&1u16.to_le_bytes());
+            let swap = jupiter_execution(&intent, protocol);
+            let mut accounts = swap[..11].to_vec();
+            accounts.extend([
+                state(pda(&[b"lending_market_registry"]).0, &registry),
+                reserve_account(reserve_key, solend::SOLEND_PROGRAM_ID, &reserve_data),
+                passed_supply.clone(),
+                fee_receiver.clone(),
+                token_account(Pubkey::new_unique(), intent.from_mint, 0),
+                account(Pubkey::new_unique(), solend::SOLEND_PROGRAM_ID, 1_000_000, Vec::new()),
+                wallet(market_authority),
+                account(anchor_lang::solana_program::sysvar::instructions::ID, anchor_lang::solana_program::sysvar::ID, 1_000_000, instructions),
+                program(solend::SOLEND_PROGRAM_ID),
+                swap[11].clone(),
+                program(token::ID),
+            ]);
+            let (intent_info, source, treasury) = (accounts[2].clone(), accounts[5].clone(), accounts[7].clone());
+            let route = jupiter_route(&intent, 1);
+            let result = run::<ExecuteSwapWithFlashLoan, _>(accounts, |ctx| {
+                intentfi::execute_swap_with_flash_loan(ctx, borrowed, route)
+            });
+            let balances = [&source, &passed_supply, &fee_receiver, &treasury].map(token_balance);
+            (result, load::<IntentAccount>(&intent_info), balances)
+        };
+        let fee_wad = 3_000_000_000_000_000; // 0.3%
+        
+        // Off until the admin turns flash loans on
+        let (result, intent_state, _) = flash_swap(&protocol, reserve, fee_wad, false);
+        assert_eq!(result.unwrap_err(), IntentError::FlashLoansDisabled.into());
+        assert!(intent_state.status == IntentStatus::Pending);
+        protocol.flash_loans_enabled = true;
+        
+        // Only the input token's registered reserve lends, out of its own supply
+        let (result, _, [source, supply, ..]) = flash_swap(&protocol, Pubkey::new_unique(), fee_wad, false);
+        assert_eq!(result.unwrap_err(), IntentError::LendingMarketNotRegistered.into());
+        assert_eq!((source, supply), (intent.amount, liquidity));
+        let (result, _, [source, supply, ..]) = flash_swap(&protocol, reserve, fee_wad, true);
+        assert_eq!(result.unwrap_err(), IntentError::InvalidLendReserve.into());
+        assert_eq!((source, supply), (intent.amount, liquidity));
+        
+        // A loan the user can't pay back with its fee reverts the whole execution
+        let (result, intent_state, _) = flash_swap(&protocol, reserve, 100 * fee_wad, false);
+        assert!(result.is_err());
+        assert!(intent_state.status == IntentStatus::Pending);
+        assert!(emitted::<FlashSwapExecuted>().is_empty());
+        
+        let (result, intent_state, [source, supply, fee_receiver, treasury]) = flash_swap(&protocol, reserve, fee_wad, false);
+        result.unwrap();
+        assert!(intent_state.status == IntentStatus::Executed && !intent_state.is_executing);
+        let (net_amount, flash_fee) = (intent.amount - intent.protocol_fee, borrowed * 3 / 1_000);
+        // The reserve is whole again, the user paid the flash fee out of what stayed behind
+        assert_eq!((supply, fee_receiver, treasury), (liquidity, flash_fee, intent.protocol_fee));
+        assert_eq!(source, net_amount - flash_fee);
+        assert_eq!(intent_state.cumulative_in, net_amount + borrowed);
+        let executed = emitted::<FlashSwapExecuted>();
+        assert_eq!(executed.len(), 1);
+        assert_eq!((executed[0].amount_in, executed[0].borrowed), (net_amount, borrowed));
+        assert_eq!((executed[0].repaid, executed[0].flash_fee), (borrowed + flash_fee, flash_fee));
+        assert_eq!(executed[0].protocol_fee, intent.protocol_fee);
+    }
     
-    #[account(mut)]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn auto_lend_swaps_deposit_their_output_into_the_registered_reserve() {
+        at_time(START);
+        let owner = wallet(Pubkey::new_unique());
+        let user_state = user_account(&user(owner.key()));
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        // A stable pair Meteora would take is routed to Jupiter, which carries the lend leg
+        let (usdc, usdt) = (Pubkey::new_from_array([1; 32]), Pubkey::new_from_array([2; 32]));
+        let create = |min_apy: u16| {
+            let accounts = swap_creation(&owner, &protocol_state, &user_state);
+            let intent_info = accounts[3].clone();
+            let params = SwapIntentParams { auto_lend: true, min_apy: Some(min_apy), ..swap_params(usdc, usdt, 1_000_000) };
+            run::<CreateSwapIntent, _>(accounts, |ctx| intentfi::create_swap_intent(ctx, params)).map(|_| load::<IntentAccount>(&intent_info))
+        };
+        assert_eq!(create(protocol.max_realistic_apy + 1).err(), Some(IntentError::UnrealisticApy.into()));
+        let created = create(50).unwrap();
+        assert!(created.auto_lend && created.min_apy == Some(50));
+        assert!(matches!(created.selected_swap_protocol, SwapProtocol::Jupiter));
+        
+        at_time(START + 60);
+        let mut intent = pending_intent(Pubkey::new_unique());
+        (intent.auto_lend, intent.min_apy) = (true, Some(50));
+        let reserve = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket { protocol: LendingProtocol::Solend, mint: intent.to_mint, reserve, market: Pubkey::new_unique() }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        // Swaps into the Solend reserve at `reserve_key` paying `apy`; with `keeper_supply`
+        // the keeper names its own account in place of the reserve's supply
+        let swap = |reserve_key: Pubkey, apy: u16, keeper_supply: bool| {
+            let supply = token_account(Pubkey::new_unique(), intent.to_mint, 0);
+            // Fully borrowed, Solend lenders earn 70% of the max borrow rate
+            let mut reserve_data = solend::SolendReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            (reserve_data.liquidity.mint_pubkey, reserve_data.liquidity.supply_pubkey) = (intent.to_mint, supply.key());
+            reserve_data.liquidity.borrowed_amount_wads = 1_000;
+            reserve_data.config.optimal_utilization_rate = 80;
+            reserve_data.config.max_borrow_rate = (apy * 100 / 70) as u8;
+            let passed_supply = if keeper_supply { token_account(intent.authority, intent.to_mint, 0) } else { supply };
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            // The simulated route moves no tokens, so the output is already in place
+            accounts[6] = token_account(intent.authority, intent.to_mint, intent.amount);
+            accounts[16] = state(pda(&[b"lending_market_registry"]).0, &registry);
+            accounts[17] = reserve_account(reserve_key, solend::SOLEND_PROGRAM_ID, &reserve_data);
+            accounts[18] = passed_supply.clone();
+            let (intent_info, destination) = (accounts[2].clone(), accounts[6].clone());
+            let route = jupiter_route(&intent, 1);
+            let result = run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route));
+            (result, load::<IntentAccount>(&intent_info), token_balance(&destination), token_balance(&passed_supply))
+        };
+        
+        // Without the lend leg's accounts the swap can't run at all
+        let route = jupiter_route(&intent, 1);
+        let err = run::<ExecuteSwapIntentJupiter, _>(jupiter_execution(&intent, &protocol), |ctx| {
+            intentfi::execute_swap_intent_jupiter(ctx, route)
+        })
+        .unwrap_err();
+        assert_eq!(err, IntentError::MissingExecutionAccounts.into());
+        
+        let (result, ..) = swap(Pubkey::new_unique(), 70, false);
+        assert_eq!(result.unwrap_err(), IntentError::LendingMarketNotRegistered.into());
+        let (result, _, _, keeper_balance) = swap(reserve, 70, true);
+        assert_eq!(result.unwrap_err(), IntentError::InvalidLendReserve.into());
+        assert_eq!(keeper_balance, 0);
+        
+        // A reserve paying under the intent's floor reverts the swap along with the deposit
+        let (result, intent_after, output, supplied) = swap(reserve, 14, false);
+        assert_eq!(result.unwrap_err(), IntentError::APYTooLow.into());
+        assert!(intent_after.status == IntentStatus::Pending);
+        assert_eq!((output, supplied), (intent.amount, 0));
+        assert!(emitted::<SwapIntentExecuted>().is_empty() && emitted::<LendIntentExecuted>().is_empty());
+        
+        let (result, executed, output, supplied) = swap(reserve, 70, false);
+        result.unwrap();
+        assert!(executed.status == IntentStatus::Executed);
+        assert_eq!((executed.selected_lending_protocol, executed.execution_apy), (Some(LendingProtocol::Solend), Some(70)));
+        let swapped = emitted::<SwapIntentExecuted>();
+        let lent = emitted::<LendIntentExecuted>();
+        assert_eq!((swapped.len(), lent.len()), (1, 1));
+        let amount_out = swapped[0].amount_out;
+        assert_eq!((output, supplied), (intent.amount - amount_out, amount_out));
+        assert_eq!((lent[0].mint, lent[0].amount, lent[0].apy), (intent.to_mint, amount_out, 70));
+        assert!(matches!(lent[0].protocol, LendingProtocol::Solend));
+        assert_eq!(lent[0].protocol_fee, 0);
+    }
     
-    #[account(mut)]
-    pub user_token_account: Account<'info, TokenAccount>,
+    #[test]
+    fn a_failed_solend_deposit_leaves_no_fee_behind() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mint = Pubkey::new_unique();
+        let market = LendingMarket {
+            protocol: LendingProtocol::Solend,
+            mint,
+            reserve: Pubkey::new_unique(),
+            market: Pubkey::new_unique(),
+        };
+        let registry = LendingMarketRegistry {
+            markets: vec![market.clone()],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        
+        // Deposits the owner's intent into a destination liquidity account for `destination_mint`
+        let execute = |destination_mint: Pubkey| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            intent.intent_type = IntentType::Lend;
+            intent.from_mint = mint;
+            intent.to_mint = mint;
+            intent.selected_lending_protocol = Some(LendingProtocol::Solend);
+            intent.pool_id = Some(market.reserve);
+            let destination = token_account(Pubkey::new_unique(), destination_mint, 0);
+            let mut accounts = lend_execution(&intent, &protocol, &registry);
+            accounts.extend([
+                lend_reserve(market.reserve, solend::SOLEND_PROGRAM_ID, mint, &destination),
+                account(market.market, Pubkey::new_unique(), 1_000_000, Vec::new()),
+                destination.clone(),
+                absent(),
+                absent(),
+                program(solend::SOLEND_PROGRAM_ID),
+                absent(),
+                program(token::ID),
+            ]);
+            let (intent_info, source, treasury) = (accounts[2].clone(), accounts[7].clone(), accounts[8].clone());
+            let result = run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend);
+            (result, load::<IntentAccount>(&intent_info), [&source, &destination, &treasury].map(token_balance))
+        };
+        
+        // A deposit the reserve refuses fails the call before any fee moves
+        let (result, intent, [source, destination, treasury]) = execute(Pubkey::new_unique());
+        assert!(result.is_err());
+        assert_eq!((source, destination, treasury), (intent.amount, 0, 0));
+        
+        let (result, intent, [source, destination, treasury]) = execute(mint);
+        assert!(result.is_ok());
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!((source, destination, treasury), (0, intent.amount - intent.protocol_fee, intent.protocol_fee));
+    }
     
-    #[account(mut)]
-    pub treasury_fee_account: Account<'info, TokenAccount>,
+    #[test]
+    fn lend_deposits_only_reach_the_registered_reserves_own_supply() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mint = Pubkey::new_unique();
+        let (solend_reserve, port_reserve) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let registry = LendingMarketRegistry {
+            markets: vec![
+                LendingMarket { protocol: LendingProtocol::Solend, mint, reserve: solend_reserve, market: Pubkey::new_unique() },
+                LendingMarket { protocol: LendingProtocol::PortFinance, mint, reserve: port_reserve, market: Pubkey::new_unique() },
+            ],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let intent = |lending_protocol: LendingProtocol| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            (intent.intent_type, intent.from_mint, intent.to_mint) = (IntentType::Lend, mint, mint);
+            intent.selected_lending_protocol = Some(lending_protocol);
+            intent
+        };
+        // The keeper names `destination`; the reserve, owned by `lending_program`, lends out of `supply`
+        let solend = |lending_program: Pubkey, supply: &AccountInfo<'static>, destination: &AccountInfo<'static>| {
+            let intent = intent(LendingProtocol::Solend);
+            let mut accounts = lend_execution(&intent, &protocol, &registry);
+            accounts.extend([
+                lend_reserve(solend_reserve, lending_program, mint, supply),
+                account(Pubkey::new_unique(), Pubkey::new_unique(), 1_000_000, Vec::new()),
+                destination.clone(),
+                absent(),
+                absent(),
+                program(solend::SOLEND_PROGRAM_ID),
+                absent(),
+                program(token::ID),
+            ]);
+            let (intent_info, source) = (accounts[2].clone(), accounts[7].clone());
+            let result = run::<ExecuteLendIntentSolend, _>(accounts, intentfi::execute_lend_intent_solend);
+            (result, load::<IntentAccount>(&intent_info), token_balance(&source))
+        };
+        let port = |lending_program: Pubkey, supply: &AccountInfo<'static>, destination: &AccountInfo<'static>| {
+            let intent = intent(LendingProtocol::PortFinance);
+            let mut accounts = lend_execution(&intent, &protocol, &registry);
+            accounts.extend([
+                lend_reserve(port_reserve, lending_program, mint, supply),
+                destination.clone(),
+                account(Pubkey::new_unique(), port_finance::PORT_FINANCE_PROGRAM_ID, 1_000_000, Vec::new()),
+                absent(),
+                program(port_finance::PORT_FINANCE_PROGRAM_ID),
+                absent(),
+                program(token::ID),
+            ]);
+            let (intent_info, source) = (accounts[2].clone(), accounts[7].clone());
+            let result = run::<ExecuteLendIntentPort, _>(accounts, intentfi::execute_lend_intent_port);
+            (result, load::<IntentAccount>(&intent_info), token_balance(&source))
+        };
+        
+        for (execute, lending_program) in [
+            (&solend as &dyn Fn(Pubkey, &AccountInfo<'static>, &AccountInfo<'static>) -> _, solend::SOLEND_PROGRAM_ID),
+            (&port, port_finance::PORT_FINANCE_PROGRAM_ID),
+        ] {
+            let supply = token_account(Pubkey::new_unique(), mint, 0);
+            let keeper_account = token_account(Pubkey::new_unique(), mint, 0);
+            // A keeper's own account in place of the reserve's supply gets nothing
+            let (result, intent, source) = execute(lending_program, &supply, &keeper_account);
+            assert_eq!(result.unwrap_err(), IntentError::InvalidLendReserve.into());
+            assert!(intent.status == IntentStatus::Pending);
+            assert_eq!((source, token_balance(&keeper_account)), (intent.amount, 0));
+            // Nor does a look-alike reserve the lending program doesn't own pointing at the keeper's account
+            let (result, _, source) = execute(Pubkey::new_unique(), &keeper_account, &keeper_account);
+            assert_eq!(result.unwrap_err(), IntentError::InvalidLendReserve.into());
+            assert_eq!((source, token_balance(&keeper_account)), (intent.amount, 0));
+            
+            let (result, intent, source) = execute(lending_program, &supply, &supply);
+            result.unwrap();
+            assert!(intent.status == IntentStatus::Executed);
+            assert_eq!((source, token_balance(&supply)), (0, intent.amount - intent.protocol_fee));
+        }
+    }
     
-    // Port Finance-specific accounts
-    /// CHECK: Port Finance reserve
-    pub port_reserve: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn port_lends_record_the_reserve_apy_even_with_a_staking_pool_passed() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let mint = Pubkey::new_unique();
+        let reserve = Pubkey::new_unique();
+        let registry = LendingMarketRegistry {
+            markets: vec![LendingMarket {
+                protocol: LendingProtocol::PortFinance,
+                mint,
+                reserve,
+                market: Pubkey::new_unique(),
+            }],
+            bump: pda(&[b"lending_market_registry"]).1,
+        };
+        let mut intent = pending_intent(Pubkey::new_unique());
+        intent.intent_type = IntentType::Lend;
+        (intent.from_mint, intent.to_mint) = (mint, mint);
+        intent.selected_lending_protocol = Some(LendingProtocol::PortFinance);
+        intent.pool_id = Some(reserve);
+        intent.min_apy = Some(100);
+        
+        // A fully borrowed reserve pays 75% of its 2% max borrow rate
+        let supply = token_account(Pubkey::new_unique(), mint, 0);
+        let mut reserve_data = port_finance::PortReserve::deserialize(&mut &[0u8; 1024][..]).unwrap();
+        (reserve_data.liquidity.mint_pubkey, reserve_data.liquidity.supply_pubkey) = (mint, supply.key());
+        reserve_data.liquidity.borrowed_amount = 1_000;
+        reserve_data.config.max_borrow_rate = 200;
+        let reserve_apy = port_finance::calculate_port_apy(&reserve_data).unwrap();
+        assert_eq!(reserve_apy, 150);
+        let mut accounts = lend_execution(&intent, &protocol, &registry);
+        accounts.extend([
+            reserve_account(reserve, port_finance::PORT_FINANCE_PROGRAM_ID, &reserve_data),
+            supply,
+            account(Pubkey::new_unique(), port_finance::PORT_FINANCE_PROGRAM_ID, 1_000_000, Vec::new()),
+            absent(),
+            program(port_finance::PORT_FINANCE_PROGRAM_ID),
+            absent(),
+            program(token::ID),
+        ]);
+        let intent_info = accounts[2].clone();
+        run::<ExecuteLendIntentPort, _>(accounts, intentfi::execute_lend_intent_port).unwrap();
+        
+        // Nothing is staked, so nothing above the reserve rate is recorded or reported
+        let executed = load::<IntentAccount>(&intent_info);
+        assert!(executed.status == IntentStatus::Executed);
+        assert_eq!(executed.execution_apy, Some(reserve_apy));
+        let events = emitted::<LendIntentExecuted>();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].apy, reserve_apy);
+        assert_eq!(events[0].net_apy, net_effective_apy(reserve_apy, executed.fee_bps, executed.amount));
+    }
     
-    /// CHECK: Port Finance staking pool
-    pub port_staking_pool: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn circuit_breaker_trips_the_pair_on_an_anomalous_price() {
+        let mut circuit_breaker = CircuitBreaker {
+            from_mint: Pubkey::new_unique(),
+            to_mint: Pubkey::new_unique(),
+            max_deviation_bps: 500,
+            last_price: 0,
+            tripped: false,
+            bump: 255,
+        };
+        
+        // The first execution only records the reference
+        assert_eq!(circuit_breaker.observe(1_000_000), None);
+        assert_eq!(circuit_breaker.last_price, 1_000_000);
+        // A normal 3% move rolls the reference forward
+        assert_eq!(circuit_breaker.observe(1_030_000), None);
+        assert_eq!(circuit_breaker.last_price, 1_030_000);
+        
+        // A 50% crash trips the pair and keeps the old reference
+        assert_eq!(circuit_breaker.observe(515_000), Some(5_000));
+        assert!(circuit_breaker.tripped);
+        assert_eq!(circuit_breaker.last_price, 1_030_000);
+    }
     
-    /// CHECK: Port Finance LP token account
-    pub port_lp_account: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn raydium_executions_at_an_anomalous_pool_price_halt_only_their_pair() {
+        at_time(START + 60);
+        let (from_mint, to_mint) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let circuit_breaker = state(
+            pda(&[b"circuit_breaker", from_mint.as_ref(), to_mint.as_ref()]).0,
+            &CircuitBreaker {
+                from_mint,
+                to_mint,
+                max_deviation_bps: 500,
+                last_price: 2 * PRICE_SCALE as u64,
+                tripped: false,
+                bump: pda(&[b"circuit_breaker", from_mint.as_ref(), to_mint.as_ref()]).1,
+            },
+        );
+        
+        // Executes an intent on the pair against a pool holding `reserve_out` per 50k `from_mint`
+        let execute = |reserve_out: u64| {
+            let mut intent = pending_intent(Pubkey::new_unique());
+            (intent.from_mint, intent.to_mint) = (from_mint, to_mint);
+            intent.selected_swap_protocol = SwapProtocol::Raydium;
+            let coin_vault = token_account(Pubkey::new_unique(), from_mint, 50_000_000_000);
+            let pc_vault = token_account(Pubkey::new_unique(), to_mint, reserve_out);
+            let mut pool_data = vec![0u8; raydium::AMM_PC_VAULT_OFFSET + 32];
+            pool_data[raydium::AMM_COIN_VAULT_OFFSET..][..32].copy_from_slice(coin_vault.key.as_ref());
+            pool_data[raydium::AMM_PC_VAULT_OFFSET..][..32].copy_from_slice(pc_vault.key.as_ref());
+            let mut accounts = jupiter_execution(&intent, &protocol);
+            accounts.truncate(11);
+            accounts[3] = protocol_state.clone();
+            accounts[10] = circuit_breaker.clone();
+            accounts.extend([
+                account(Pubkey::new_unique(), raydium::RAYDIUM_AMM_PROGRAM_ID, 1_000_000, pool_data),
+                coin_vault,
+                pc_vault,
+                program(raydium::RAYDIUM_AMM_PROGRAM_ID),
+                program(token::ID),
+                program(system_program::ID),
+            ]);
+            let (intent_info, source) = (accounts[2].clone(), accounts[5].clone());
+            let pool_info = raydium::RaydiumPoolInfo::deserialize(&mut &[0u8; 1024][..]).unwrap();
+            run::<ExecuteSwapIntentRaydium, _>(accounts, |ctx| intentfi::execute_swap_intent_raydium(ctx, pool_info)).unwrap();
+            (load::<IntentAccount>(&intent_info), token_balance(&source))
+        };
+        
+        // A 2% move trades and rolls the reference forward
+        let (normal, source_left) = execute(102_000_000_000);
+        assert!(normal.status == IntentStatus::Executed);
+        assert_eq!(normal.cumulative_in, normal.amount - normal.protocol_fee);
+        assert!(source_left < normal.amount);
+        assert_eq!(load::<CircuitBreaker>(&circuit_breaker).last_price, 2_040_000_000);
+        assert!(emitted::<CircuitBreakerTripped>().is_empty());
+        
+        // A halving of the pool price trips the pair and leaves the intent untouched
+        let (anomalous, source_left) = execute(51_000_000_000);
+        assert!(anomalous.status == IntentStatus::Pending && !anomalous.is_executing);
+        assert_eq!(source_left, anomalous.amount);
+        let tripped = emitted::<CircuitBreakerTripped>();
+        assert_eq!(tripped.len(), 1);
+        assert_eq!((tripped[0].reference_price, tripped[0].observed_price), (2_040_000_000, 1_020_000_000));
+        assert_eq!(tripped[0].deviation_bps, 5_000);
+        let halted = load::<CircuitBreaker>(&circuit_breaker);
+        assert!(halted.tripped);
+        assert_eq!(halted.last_price, 2_040_000_000);
+        // The rest of the protocol keeps running
+        assert!(!load::<ProtocolState>(&protocol_state).is_paused);
+    }
     
-    /// CHECK: Port Finance program
-    #[account(address = port_finance::PORT_FINANCE_PROGRAM_ID)]
-    pub port_program: Option<UncheckedAccount<'info>>,
+    #[test]
+    fn tripped_pairs_refuse_further_executions() {
+        let key = Pubkey::new_unique();
+        let circuit_breaker = CircuitBreaker {
+            from_mint: Pubkey::new_unique(),
+            to_mint: Pubkey::new_unique(),
+            max_deviation_bps: 500,
+            last_price: 1_000_000,
+            tripped: true,
+            bump: 255,
+        };
+        let mut lamports = 0;
+        let mut data = serialized(&circuit_breaker);
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        
+        let intent_key = Pubkey::new_unique();
+        let mut intent_lamports = 0;
+        let mut intent_data = serialized(&blank_intent());
+        let intent_info = AccountInfo::new(&intent_key, false, true, &mut intent_lamports, &mut intent_data, &crate::ID, false, 0);
+        let mut intent = Account::<IntentAccount>::try_from(&intent_info).unwrap();
+        
+        // Even a caller-quoted route that never feeds the reference is halted
+        assert_eq!(check_circuit_breaker(&info, &mut intent, None).err(), Some(IntentError::PairHalted.into()));
+        assert_eq!(
+            check_circuit_breaker(&info, &mut intent, Some((1_000, 1_000))).err(),
+            Some(IntentError::PairHalted.into())
+        );
+    }
     
-    pub token_program: Program<'info, Token>,
-}
-
-#[derive(Accounts)]
-pub struct CreateBuyIntent<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn inline_buys_use_the_intent_slippage_not_its_price_impact() {
+        let mut intent = blank_intent();
+        intent.intent_type = IntentType::Buy;
+        intent.target_price = Some(1_500_000);
+        intent.max_slippage = 50;
+        intent.max_price_impact = Some(300);
+        
+        // Condition pre-met at creation, so the buy executes inline
+        assert!(buy_condition_met(&intent, 1_400_000));
+        assert!(buy_condition_met(&intent, 1_500_000));
+        assert!(!buy_condition_met(&intent, 1_500_001));
+        
+        let swap_params = jupiter_swap_params(&intent, 1_000_000);
+        assert_eq!(swap_params.amount, 1_000_000);
+        assert_eq!(swap_params.slippage_bps, 50);
+        assert_eq!(swap_params.max_price_impact, Some(300));
+        assert_eq!(swap_params.platform_fee_bps, 0);
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
+    #[test]
+    fn two_asset_rebalance_sells_the_overweight_asset_into_the_other() {
+        // 700 USDC of SOL and 300 USDC of USDC against a 50/50 target
+        let legs = plan_rebalance_legs(&[700_000_000, 300_000_000], &[5000, 5000]);
+        assert_eq!(legs, vec![(0, 1, 200_000_000)]);
+        
+        // Already balanced portfolios need no legs
+        assert!(plan_rebalance_legs(&[500_000_000, 500_000_000], &[5000, 5000]).is_empty());
+        
+        let mut balances = vec![7_000_000_000, 300_000_000];
+        apply_rebalance_leg(&mut balances, 0, 1, 2_000_000_000, 199_000_000).unwrap();
+        assert_eq!(balances, vec![5_000_000_000, 499_000_000]);
+        // A leg can't sell more than the tracked balance, nor overflow the bought one
+        assert_eq!(
+            apply_rebalance_leg(&mut balances, 0, 1, 5_000_000_001, 0).unwrap_err(),
+            IntentError::RebalanceBalanceMismatch.into()
+        );
+        let mut full = vec![1, u64::MAX];
+        assert!(apply_rebalance_leg(&mut full, 0, 1, 1, 1).is_err());
+    }
     
-    #[account(
-        mut,
-        seeds = [b"user_account", authority.key().as_ref()],
-        bump = user_account.bump
-    )]
-    pub user_account: Account<'info, UserAccount>,
+    #[test]
+    fn two_asset_rebalance_intents_trade_toward_their_weights_for_one_fee() {
+        at_time(START);
+        let protocol = protocol();
+        let protocol_state = protocol_account(&protocol);
+        let owner = Pubkey::new_unique();
+        let user_state = user_account(&user(owner));
+        let (mint_a, mint_b) = (Pubkey::new_unique(), Pubkey::new_unique());
+        // 3.5 A at 2.00 USDC and 3 B at 1.00 USDC: a 70/30 portfolio worth 10 USDC
+        let (account_a, account_b) = (token_account(owner, mint_a, 3_500_000), token_account(owner, mint_b, 3_000_000));
+        let priced = |mint: Pubkey, price: i64| {
+            let oracle_key = Pubkey::new_unique();
+            let mut oracle_data = vec![0u8; 240];
+            oracle_data[..4].copy_from_slice(&oracle::PYTH_MAGIC.to_le_bytes());
+            oracle_data[oracle::PYTH_EXPO_OFFSET..][..4].copy_from_slice(&(-8i32).to_le_bytes());
+            oracle_data[oracle::PYTH_TIMESTAMP_OFFSET..][..8].copy_from_slice(&START.to_le_bytes());
+            oracle_data[oracle::PYTH_AGG_PRICE_OFFSET..][..8].copy_from_slice(&price.to_le_bytes());
+            oracle_data[oracle::PYTH_AGG_STATUS_OFFSET..][..4].copy_from_slice(&1u32.to_le_bytes());
+            let (feed_key, bump) = pda(&[b"price_feed", mint.as_ref()]);
+            let feed = PriceFeed { mint, price_source: PriceSource::Pyth, oracle: oracle_key, bump };
+            [self::mint(mint, None, 0), state(feed_key, &feed), account(oracle_key, oracle::PYTH_ORACLE_PROGRAM_ID, 0, oracle_data)]
+        };
+        let [mint_a_info, feed_a, oracle_a] = priced(mint_a, 200_000_000);
+        let [mint_b_info, feed_b, oracle_b] = priced(mint_b, 100_000_000);
+        
+        let intent_key = pda(&[b"intent", owner.as_ref(), &1u64.to_le_bytes()]).0;
+        let weights_key = pda(&[b"rebalance", intent_key.as_ref()]).0;
+        let create = |weights: Vec<RebalanceWeight>| {
+            let intent = uncreated(intent_key, 8 + IntentAccount::INIT_SPACE);
+            let rebalance_weights = uncreated(weights_key, 8 + RebalanceWeights::INIT_SPACE);
+            let infos = vec![
+                wallet(owner),
+                protocol_state.clone(),
+                user_state.clone(),
+                intent.clone(),
+                rebalance_weights.clone(),
+                program(system_program::ID),
+                account_a.clone(),
+                account_b.clone(),
+            ];
+            let params = RebalanceIntentParams {
+                weights,
+                max_slippage: 100,
+                price_source: PriceSource::Pyth,
+                max_route_fee: None,
+                max_price_impact: None,
+                not_before: None,
+                client_order_id: None,
+                keeper_tip_lamports: 0,
+            };
+            run::<CreateRebalanceIntent, _>(infos, |ctx| intentfi::create_rebalance_intent(ctx, params)).map(|_| (intent, rebalance_weights))
+        };
+        let weights = |bps_a: u16, bps_b: u16| {
+            vec![RebalanceWeight { mint: mint_a, target_bps: bps_a }, RebalanceWeight { mint: mint_b, target_bps: bps_b }]
+        };
+        
+        // Weights have to cover exactly the whole portfolio
+        assert_eq!(create(weights(5000, 4999)).err(), Some(IntentError::InvalidRebalanceWeights.into()));
+        assert_eq!(create(weights(5000, 5001)).err(), Some(IntentError::InvalidRebalanceWeights.into()));
+        let (intent, rebalance_weights) = create(weights(5000, 5000)).unwrap();
+        let snapshot: Vec<u64> = load::<RebalanceWeights>(&rebalance_weights).targets.iter().map(|t| t.balance).collect();
+        assert_eq!(snapshot, [3_500_000, 3_000_000]);
+        
+        let treasury = protocol.treasury_authority;
+        let treasury_account = token_account_at(treasury_fee_ata(&treasury, &mint_a), treasury, mint_a, 0);
+        let execute = |intent: &AccountInfo<'static>, routes: Vec<jupiter::JupiterSwapData>| {
+            let infos = vec![
+                wallet(Pubkey::new_unique()),
+                wallet(owner),
+                intent.clone(),
+                rebalance_weights.clone(),
+                protocol_state.clone(),
+                user_state.clone(),
+                treasury_account.clone(),
+                uninitialized(pda(&[b"fee_split_config"]).0),
+                uninitialized(pda(&[b"fee_ledger", mint_a.as_ref()]).0),
+                program(jupiter::JUPITER_PROGRAM_ID),
+                program(token::ID),
+                program(system_program::ID),
+                account_a.clone(),
+                mint_a_info.clone(),
+                feed_a.clone(),
+                oracle_a.clone(),
+                account_b.clone(),
+                mint_b_info.clone(),
+                feed_b.clone(),
+                oracle_b.clone(),
+            ];
+            run::<ExecuteRebalanceIntent, _>(infos, |ctx| intentfi::execute_rebalance_intent(ctx, routes))
+        };
+        // Reaching 50/50 sells 2 USDC of A, i.e. 1 A, for an oracle-implied 2 B
+        let leg = |quoted_out_amount: u64| {
+            let mut sell = blank_intent();
+            (sell.from_mint, sell.to_mint, sell.amount) = (mint_a, mint_b, 1_000_000);
+            jupiter::JupiterSwapData { quoted_out_amount, ..jupiter_route(&sell, 1) }
+        };
+        // Failed executions are tried on copies, as they leave the intent marked executing
+        let copy = || state(intent_key, &load::<IntentAccount>(&intent));
+        assert_eq!(execute(&copy(), vec![leg(1_990_000), leg(1_990_000)]).unwrap_err(), IntentError::RebalanceRouteMismatch.into());
+        assert_eq!(execute(&copy(), vec![leg(1_970_000)]).unwrap_err(), IntentError::SlippageExceeded.into());
+        
+        execute(&intent, vec![leg(1_990_000)]).unwrap();
+        let executed = emitted::<RebalanceExecuted>();
+        assert_eq!(executed.len(), 1);
+        let legs = &executed[0].legs;
+        assert_eq!(legs.len(), 1);
+        // The simulated Jupiter swap pays out 95% of the input
+        assert_eq!((legs[0].from_mint, legs[0].to_mint), (mint_a, mint_b));
+        assert_eq!((legs[0].amount_in, legs[0].amount_out, legs[0].notional), (1_000_000, 950_000, 2_000_000));
+        // One 0.3% fee on the 2 USDC traded, charged in A
+        assert_eq!((executed[0].total_notional, executed[0].protocol_fee), (2_000_000, 3_000));
+        assert_eq!(token_balance(&treasury_account), 3_000);
+        assert_eq!(token_balance(&account_a), 3_497_000);
+        
+        let balances: Vec<u64> = load::<RebalanceWeights>(&rebalance_weights).targets.iter().map(|t| t.balance).collect();
+        assert_eq!(balances, [2_497_000, 3_950_000]);
+        let intent = load::<IntentAccount>(&intent);
+        assert!(intent.status == IntentStatus::Executed);
+        assert_eq!(intent.amount, 1_000_000);
+        assert!(!intent.is_executing);
+        assert_eq!(load::<UserAccount>(&user_state).active_intents, 0);
+    }
     
-    #[account(
-        init,
-        payer = authority,
-        space = 8 + 32 + 1 + 1 + 32 + 32 + 8 + 8 + 2 + 2 + 8 + 2 + 8 + 8 + 1 + 32 + 32 + 8 + 8 + 8 + 8 + 1,
-        seeds = [b"intent", authority.key().as_ref(), &(user_account.total_intents_created + 1).to_le_bytes()],
-        bump
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn rebalance_prices_come_from_the_mints_registered_feed() {
+        let now: i64 = 1_700_000_000;
+        let mint = Pubkey::new_unique();
+        let oracle_key = Pubkey::new_unique();
+        let (feed_key, bump) = Pubkey::find_program_address(&[b"price_feed", mint.as_ref()], &crate::ID);
+        let feed = PriceFeed { mint, price_source: PriceSource::Pyth, oracle: oracle_key, bump };
+        
+        // 150.00 USDC at expo -8, published 5 seconds ago
+        let mut oracle_data = vec![0u8; 240];
+        oracle_data[..4].copy_from_slice(&oracle::PYTH_MAGIC.to_le_bytes());
+        oracle_data[oracle::PYTH_EXPO_OFFSET..][..4].copy_from_slice(&(-8i32).to_le_bytes());
+        oracle_data[oracle::PYTH_TIMESTAMP_OFFSET..][..8].copy_from_slice(&(now - 5).to_le_bytes());
+        oracle_data[oracle::PYTH_AGG_PRICE_OFFSET..][..8].copy_from_slice(&15_000_000_000i64.to_le_bytes());
+        oracle_data[oracle::PYTH_AGG_STATUS_OFFSET..][..4].copy_from_slice(&1u32.to_le_bytes());
+        let mut other_data = oracle_data.clone();
+        let (mut oracle_lamports, mut feed_lamports, mut other_lamports) = (0, 0, 0);
+        let oracle_account = AccountInfo::new(&oracle_key, false, false, &mut oracle_lamports, &mut oracle_data, &oracle::PYTH_ORACLE_PROGRAM_ID, false, 0);
+        let mut feed_data = serialized(&feed);
+        let feed_account = AccountInfo::new(&feed_key, false, false, &mut feed_lamports, &mut feed_data, &crate::ID, false, 0);
+        
+        assert_eq!(rebalance_asset_price(&feed_account, &oracle_account, &mint, now).unwrap(), 150_000_000);
+        // Another mint's feed, or a different oracle than the registered one, is refused
+        assert_eq!(
+            rebalance_asset_price(&feed_account, &oracle_account, &Pubkey::new_unique(), now).unwrap_err(),
+            IntentError::InvalidPriceFeed.into()
+        );
+        let other_oracle_key = Pubkey::new_unique();
+        let other_oracle = AccountInfo::new(&other_oracle_key, false, false, &mut other_lamports, &mut other_data, &oracle::PYTH_ORACLE_PROGRAM_ID, false, 0);
+        assert_eq!(
+            rebalance_asset_price(&feed_account, &other_oracle, &mint, now).unwrap_err(),
+            IntentError::InvalidPriceFeed.into()
+        );
+        // The registered oracle still has to be fresh
+        assert_eq!(
+            rebalance_asset_price(&feed_account, &oracle_account, &mint, now + 3_600).unwrap_err(),
+            IntentError::StaleOraclePrice.into()
+        );
+    }
     
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct CancelIntent<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn keepers_move_owner_tokens_through_the_delegated_protocol_pda() {
+        let (owner_key, protocol_state_key) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let (mut owner_lamports, mut state_lamports) = (0, 0);
+        let (mut owner_data, mut state_data) = (vec![], vec![]);
+        let system_program = anchor_lang::system_program::ID;
+        let protocol_state = AccountInfo::new(&protocol_state_key, false, true, &mut state_lamports, &mut state_data, &crate::ID, false, 0);
+        let mut owner = AccountInfo::new(&owner_key, false, true, &mut owner_lamports, &mut owner_data, &system_program, false, 0);
+        
+        let keeper_run = OwnerAuthority { owner: owner.clone(), protocol_state: protocol_state.clone(), protocol_state_bump: 255 };
+        assert_eq!(keeper_run.authority().key(), protocol_state_key);
+        
+        owner.is_signer = true;
+        let owner_run = OwnerAuthority { owner, protocol_state, protocol_state_bump: 255 };
+        assert_eq!(owner_run.authority().key(), owner_key);
+    }
     
-    #[account(
-        mut,
-        constraint = intent_account.authority == authority.key()
-    )]
-    pub intent_account: Account<'info, IntentAccount>,
+    #[test]
+    fn allowed_executor_restricts_keepers_but_not_the_owner() {
+        let mut intent = blank_intent();
+        intent.authority = Pubkey::new_unique();
+        let (allowed, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        
+        assert!(executor_allowed(&intent, &other));
+        
+        intent.allowed_executor = Some(allowed);
+        assert!(executor_allowed(&intent, &allowed));
+        let owner = intent.authority;
+        assert!(executor_allowed(&intent, &owner));
+        assert!(!executor_allowed(&intent, &other));
+    }
     
-    #[account(mut)]
-    pub user_account: Account<'info, UserAccount>,
-}
-
-#[derive(Accounts)]
-pub struct PauseProtocol<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn only_the_designated_keeper_executes_a_restricted_intent() {
+        at_time(START + 60);
+        let protocol = protocol();
+        let owner = Pubkey::new_unique();
+        let intent = pending_intent(owner);
+        let accounts = jupiter_execution(&intent, &protocol);
+        let (intent_info, protocol_state, source, treasury) =
+            (accounts[2].clone(), accounts[3].clone(), accounts[5].clone(), accounts[7].clone());
+        let delegation = vec![wallet(owner), source.clone(), protocol_state, program(token::ID)];
+        run::<ManageExecutionDelegation, _>(delegation, |ctx| intentfi::delegate_for_execution(ctx, intent.amount)).unwrap();
+        let designate = |signer: Pubkey, executor: Pubkey| {
+            let infos = vec![wallet(signer), intent_info.clone()];
+            run::<SetAllowedExecutor, _>(infos, |ctx| intentfi::set_allowed_executor(ctx, Some(executor)))
+        };
+        let execute_as = |keeper: Pubkey| {
+            let mut accounts = accounts.clone();
+            accounts[0] = wallet(keeper);
+            accounts[1].is_signer = false;
+            let route = jupiter_route(&intent, 1);
+            run::<ExecuteSwapIntentJupiter, _>(accounts, |ctx| intentfi::execute_swap_intent_jupiter(ctx, route))
+        };
+        
+        let (designated, other) = (Pubkey::new_unique(), Pubkey::new_unique());
+        assert_eq!(designate(other, other).unwrap_err(), IntentError::Unauthorized.into());
+        designate(owner, designated).unwrap();
+        let updates = emitted::<AllowedExecutorUpdated>();
+        assert_eq!((updates[0].intent_id, updates[0].allowed_executor), (intent_info.key(), Some(designated)));
+        
+        assert_eq!(execute_as(other).unwrap_err(), IntentError::ExecutorNotAllowed.into());
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+        assert_eq!(token_balance(&treasury), 0);
+        
+        execute_as(designated).unwrap();
+        assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Executed);
+        assert_eq!(token_balance(&treasury), intent.protocol_fee);
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
-}
-
-#[derive(Accounts)]
-pub struct UnpauseProtocol<'info> {
-    #[account(mut)]
-    pub authority: Signer<'info>,
+    #[test]
+    fn admin_instructions_refuse_anyone_but_the_authority() {
+        at_time(START);
+        let protocol = protocol();
+        assert_eq!(protocol.config_version, CONFIG_VERSION);
+        let protocol_state = protocol_account(&protocol);
+        type AdminCall = fn(Vec<AccountInfo<'static>>) -> Result<()>;
+        let admin_calls: [AdminCall; 12] = [
+            |infos| run::<PauseProtocol, _>(infos, intentfi::pause_protocol),
+            |infos| run::<UnpauseProtocol, _>(infos, intentfi::unpause_protocol),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_min_pool_liquidity(ctx, 1)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_min_fee_tokens(ctx, 1)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_expiry_bounty(ctx, 1)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_max_realistic_apy(ctx, 5_000)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_min_apy_spread(ctx, 10)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_flash_loans_enabled(ctx, true)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_max_execution_attempts(ctx, 5)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::set_type_fee(ctx, IntentType::Swap, 50)),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::add_fee_exempt(ctx, Pubkey::new_from_array([7; 32]))),
+            |infos| run::<UpdateProtocolConfig, _>(infos, |ctx| intentfi::remove_fee_exempt(ctx, Pubkey::new_from_array([7; 32]))),
+        ];
+        
+        for admin_call in admin_calls {
+            let before = protocol_state.data.borrow().to_vec();
+            let stranger = vec![wallet(Pubkey::new_unique()), protocol_state.clone()];
+            assert_eq!(admin_call(stranger).unwrap_err(), IntentError::Unauthorized.into());
+            assert_eq!(protocol_state.data.borrow().to_vec(), before);
+            admin_call(vec![wallet(protocol.authority), protocol_state.clone()]).unwrap();
+        }
+        let updated = load::<ProtocolState>(&protocol_state);
+        assert_eq!((updated.min_pool_liquidity, updated.max_execution_attempts, updated.fee_bps_by_type[0]), (1, 5, 50));
+        assert!(!updated.is_paused && updated.fee_exempt.is_empty() && updated.flash_loans_enabled);
+    }
     
-    #[account(
-        mut,
-        seeds = [b"protocol_state"],
-        bump = protocol_state.bump
-    )]
-    pub protocol_state: Account<'info, ProtocolState>,
-}
-
-// Events
-#[event]
-pub struct SwapIntentExecuted {
-    pub intent_id: Pubkey,
-    pub user: Pubkey,
-    pub protocol: SwapProtocol,
-    pub from_mint: Pubkey,
-    pub to_mint: Pubkey,
-    pub amount_in: u64,
-    pub amount_out: u64,
-    pub protocol_fee: u64,
-}
-
-#[event]
-pub struct LendIntentExecuted {
-    pub intent_id: Pubkey,
-    pub user: Pubkey,
-    pub mint: Pubkey,
-    pub amount: u64,
-    pub apy: u16,
-    pub protocol: LendingProtocol,
-    pub protocol_fee: u64,
-}
-
-// Error Codes
-#[error_code]
-pub enum IntentError {
-    #[msg("Too many active intents")]
-    TooManyActiveIntents,
-    #[msg("Protocol is paused")]
-    ProtocolPaused,
-    #[msg("Invalid amount")]
-    InvalidAmount,
-    #[msg("Slippage too high")]
-    SlippageTooHigh,
-    #[msg("Rugproof check failed")]
-    RugproofCheckFailed,
-    #[msg("Intent is not pending")]
-    IntentNotPending,
-    #[msg("Intent has expired")]
-    IntentExpired,
-    #[msg("Slippage exceeded")]
-    SlippageExceeded,
-    #[msg("Invalid APY")]
-    InvalidAPY,
-    #[msg("APY too low")]
-    APYTooLow,
-    #[msg("Unauthorized")]
-    Unauthorized,
-    #[msg("Wrong protocol selected")]
-    WrongProtocol,
-}
-
-fn perform_rugproof_check(mint: &Pubkey) -> Result<u8> {
-    // Real rugproof check would analyze:
-    // - Token metadata and verification
-    // - Liquidity pool size and age
-    // - Developer wallet distributions
-    // - Trading volume and holders count
-    // For now, return a score based on mint characteristics
+    #[test]
+    fn lending_cap_reserves_up_to_its_max() {
+        let mut cap = lending_cap(1_000, 400);
+        cap.reserve(600).unwrap();
+        assert_eq!(cap.current_total, 1_000);
+        assert_eq!(cap.reserve(1).unwrap_err(), IntentError::LendingCapReached.into());
+        
+        cap.release(600).unwrap();
+        assert_eq!(cap.current_total, 400);
+        assert_eq!(cap.release(401).unwrap_err(), IntentError::LendingCapUnderflow.into());
+    }
     
-    let score = if mint.to_bytes()[0] < 50 {
-        95 // High score for certain patterns
-    } else if mint.to_bytes()[0] < 100 {
-        85 // Medium score  
-    } else {
-        75 // Lower score for other patterns
-    };
+    #[test]
+    fn releasing_an_unreserved_intent_leaves_the_cap_alone() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = serialized(&lending_cap(1_000, 700));
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        
+        // Created before the cap was configured, so nothing was held for it
+        let mut intent = blank_intent();
+        intent.amount = 500;
+        release_lending_cap(&info, &mut intent).unwrap();
+        assert_eq!(LendingCap::try_deserialize(&mut &info.data.borrow()[..]).unwrap().current_total, 700);
+        
+        intent.lending_cap_reserved = true;
+        release_lending_cap(&info, &mut intent).unwrap();
+        assert_eq!(LendingCap::try_deserialize(&mut &info.data.borrow()[..]).unwrap().current_total, 200);
+        assert!(!intent.lending_cap_reserved);
+        
+        // A second release is a no-op rather than freeing capacity twice
+        release_lending_cap(&info, &mut intent).unwrap();
+        assert_eq!(LendingCap::try_deserialize(&mut &info.data.borrow()[..]).unwrap().current_total, 200);
+    }
     
-    msg!("🛡️ Rugproof score for {}: {}", mint, score);
-    Ok(score)
+    #[test]
+    fn reserving_against_an_uninitialized_cap_reserves_nothing() {
+        let key = Pubkey::new_unique();
+        let mut lamports = 0;
+        let mut data = Vec::new();
+        let info = AccountInfo::new(&key, false, true, &mut lamports, &mut data, &crate::ID, false, 0);
+        assert!(!reserve_lending_cap(&info, 500).unwrap());
+    }
 }
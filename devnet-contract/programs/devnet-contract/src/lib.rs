@@ -1,8 +1,15 @@
 use anchor_lang::prelude::*;
-use anchor_spl::token::{self, Token, TokenAccount, Transfer, Mint};
+use anchor_spl::token::{self, Token, TokenAccount, Transfer};
 
 declare_id!("2UPCMZ2LESPx8wU83wdng3Yjhx2yxRLEkEDYDkNUg1jd");
 
+// Simulated output may differ from the net input by at most this factor either way
+// (wide enough to cover decimal gaps between mints)
+pub const MAX_SIMULATED_OUTPUT_RATIO: u64 = 1_000_000;
+
+// Intents must outlive their creation by at least this many seconds
+pub const MIN_INTENT_LIFETIME: i64 = 60;
+
 #[program]
 pub mod devnet_contract {
     use super::*;
@@ -71,6 +78,7 @@ pub mod devnet_contract {
         intent_account.max_slippage = Some(max_slippage);
         intent_account.created_at = Clock::get()?.unix_timestamp;
         intent_account.expires_at = Clock::get()?.unix_timestamp + 3600; // 1 hour
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
         intent_account.bump = ctx.bumps.intent_account;
         
         user_account.active_intents += 1;
@@ -101,6 +109,14 @@ pub mod devnet_contract {
         let protocol_fee = intent_account.protocol_fee;
         let net_amount = intent_account.amount.checked_sub(protocol_fee).unwrap();
         
+        // The simulated output feeds analytics, so reject zero or absurd values
+        require!(expected_output > 0, ErrorCode::InvalidAmount);
+        require!(
+            expected_output <= net_amount.saturating_mul(MAX_SIMULATED_OUTPUT_RATIO)
+                && expected_output.saturating_mul(MAX_SIMULATED_OUTPUT_RATIO) >= net_amount,
+            ErrorCode::ImplausibleOutput
+        );
+        
         // Transfer protocol fee to treasury
         let fee_transfer = Transfer {
             from: ctx.accounts.user_source_token.to_account_info(),
@@ -179,6 +195,7 @@ pub mod devnet_contract {
         intent_account.min_apy = Some(min_apy);
         intent_account.created_at = Clock::get()?.unix_timestamp;
         intent_account.expires_at = Clock::get()?.unix_timestamp + 7200; // 2 hours
+        check_intent_lifetime(intent_account.created_at, intent_account.expires_at)?;
         intent_account.bump = ctx.bumps.intent_account;
         
         user_account.active_intents += 1;
@@ -502,6 +519,15 @@ pub struct LendIntentExecuted {
     pub protocol_fee: u64,
 }
 
+// Refuse intents that would be born expired, or live less than MIN_INTENT_LIFETIME
+fn check_intent_lifetime(created_at: i64, expires_at: i64) -> Result<()> {
+    require!(
+        expires_at.checked_sub(created_at).is_some_and(|lifetime| lifetime > MIN_INTENT_LIFETIME),
+        ErrorCode::InvalidExpiry
+    );
+    Ok(())
+}
+
 // Error Codes
 #[error_code]
 pub enum ErrorCode {
@@ -521,4 +547,247 @@ pub enum ErrorCode {
     APYTooLow,
     #[msg("Unauthorized")]
     Unauthorized,
+    #[msg("Expected output is implausible for the input amount")]
+    ImplausibleOutput,
+    #[msg("Expiry leaves the intent less than the minimum lifetime")]
+    InvalidExpiry,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::{
+        clock::Clock, entrypoint::ProgramResult, instruction::Instruction, program_pack::Pack, program_stubs,
+    };
+    use anchor_lang::{Bumps, Discriminator};
+    use anchor_spl::token::spl_token;
+    use std::{cell::{Cell, RefCell}, collections::BTreeSet, sync::Once};
+    
+    const START: i64 = 1_700_000_000;
+    
+    thread_local! {
+        static NOW: Cell<i64> = const { Cell::new(START) };
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+    
+    /// Host stand-in for the runtime: a per-thread clock, default rent, recorded events, and
+    /// the token program behind CPIs
+    struct TestRuntime;
+    
+    impl program_stubs::SyscallStubs for TestRuntime {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: NOW.with(Cell::get), ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            0
+        }
+        
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+        
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+        }
+        
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            // Hand the callee its accounts in instruction order
+            let accounts: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| account_infos.iter().find(|info| *info.key == meta.pubkey).unwrap().clone())
+                .collect();
+            assert_eq!(instruction.program_id, spl_token::ID);
+            spl_token::processor::Processor::process(&instruction.program_id, &accounts, &instruction.data)
+        }
+    }
+    
+    /// Install the runtime stand-in and set the time this test's thread sees
+    fn at_time(now: i64) {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestRuntime));
+        });
+        NOW.with(|clock| clock.set(now));
+    }
+    
+    /// Events of type `E` emitted on this thread since the last call
+    fn emitted<E: Discriminator + AnchorDeserialize>() -> Vec<E> {
+        EVENTS.with(|events| {
+            let mut events = events.borrow_mut();
+            let (matching, rest) = events.drain(..).partition::<Vec<_>, _>(|data| data.starts_with(&E::DISCRIMINATOR));
+            *events = rest;
+            matching.iter().map(|data| E::deserialize(&mut &data[8..]).unwrap()).collect()
+        })
+    }
+    
+    fn account(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            true,
+            Box::leak(Box::new(lamports)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+    
+    fn wallet(key: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(key, anchor_lang::system_program::ID, 10_000_000_000, Vec::new());
+        info.is_signer = true;
+        info
+    }
+    
+    fn program(id: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(id, anchor_lang::solana_program::bpf_loader_upgradeable::ID, 1, Vec::new());
+        info.executable = true;
+        info
+    }
+    
+    /// A program account holding `value`, with headroom for fields that grow
+    fn state<T: AccountSerialize>(key: Pubkey, value: &T) -> AccountInfo<'static> {
+        let mut data = Vec::new();
+        value.try_serialize(&mut data).unwrap();
+        data.resize(data.len() + 256, 0);
+        account(key, crate::ID, 10_000_000, data)
+    }
+    
+    fn token_account(owner: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(Pubkey::new_unique(), token::ID, 2_039_280, data)
+    }
+    
+    fn token_balance(info: &AccountInfo) -> u64 {
+        spl_token::state::Account::unpack(&info.data.borrow()).unwrap().amount
+    }
+    
+    fn load<T: AccountDeserialize>(info: &AccountInfo) -> T {
+        T::try_deserialize(&mut &info.data.borrow()[..]).unwrap()
+    }
+    
+    /// Validate `infos` as `T` and run `handler` the way the entrypoint would, writing
+    /// the accounts back when it succeeds
+    fn run<T, F>(infos: Vec<AccountInfo<'static>>, handler: F) -> Result<()>
+    where
+        T: Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+        T::Bumps: Default,
+        F: FnOnce(Context<'_, '_, 'static, 'static, T>) -> Result<()>,
+    {
+        let mut infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+        let mut bumps = T::Bumps::default();
+        let mut accounts = T::try_accounts(&crate::ID, &mut infos, &[], &mut bumps, &mut BTreeSet::new())?;
+        handler(Context::new(&crate::ID, &mut accounts, infos, bumps))?;
+        accounts.exit(&crate::ID)
+    }
+    
+    /// Protocol state as initialize_protocol leaves it
+    fn protocol() -> ProtocolState {
+        ProtocolState {
+            authority: Pubkey::new_unique(),
+            treasury_authority: Pubkey::new_unique(),
+            protocol_fee_bps: 30,
+            total_intents_created: 1,
+            total_intents_executed: 0,
+            is_paused: false,
+            bump: Pubkey::find_program_address(&[b"protocol_state"], &crate::ID).1,
+        }
+    }
+    
+    /// A user with one pending intent
+    fn user(authority: Pubkey) -> UserAccount {
+        UserAccount {
+            authority,
+            active_intents: 1,
+            total_intents_created: 1,
+            total_volume: 0,
+            bump: Pubkey::find_program_address(&[b"user_account", authority.as_ref()], &crate::ID).1,
+        }
+    }
+    
+    /// A pending 1_000_000 token swap created at START, as create_swap_intent leaves it
+    fn pending_swap(authority: Pubkey, from_mint: Pubkey) -> IntentAccount {
+        IntentAccount {
+            authority,
+            intent_type: IntentType::Swap,
+            status: IntentStatus::Pending,
+            from_mint,
+            to_mint: Pubkey::new_unique(),
+            amount: 1_000_000,
+            protocol_fee: 3_000,
+            max_slippage: Some(100),
+            min_apy: None,
+            execution_output: None,
+            execution_apy: None,
+            created_at: START,
+            expires_at: START + 3600,
+            executed_at: None,
+            cancelled_at: None,
+            bump: Pubkey::find_program_address(&[b"intent", authority.as_ref(), &1u64.to_le_bytes()], &crate::ID).1,
+        }
+    }
+    
+    /// Accounts for `owner` executing `intent`, funded with exactly its amount. The simulated
+    /// swap pays out in the input token, so the destination holds `from_mint` too
+    fn swap_execution(owner: Pubkey, intent: &IntentAccount) -> Vec<AccountInfo<'static>> {
+        vec![
+            wallet(owner),
+            state(Pubkey::new_unique(), intent),
+            state(Pubkey::find_program_address(&[b"protocol_state"], &crate::ID).0, &protocol()),
+            state(Pubkey::new_unique(), &user(owner)),
+            token_account(owner, intent.from_mint, intent.amount),
+            token_account(owner, intent.from_mint, 0),
+            token_account(Pubkey::new_unique(), intent.from_mint, 0),
+            program(token::ID),
+        ]
+    }
+    
+    #[test]
+    fn swaps_need_a_plausible_nonzero_expected_output() {
+        at_time(START + 60);
+        let owner = Pubkey::new_unique();
+        let intent = pending_swap(owner, Pubkey::new_unique());
+        
+        for (expected_output, error) in [(0, ErrorCode::InvalidAmount), (997_000 * MAX_SIMULATED_OUTPUT_RATIO + 1, ErrorCode::ImplausibleOutput)] {
+            let accounts = swap_execution(owner, &intent);
+            let (intent_info, source) = (accounts[1].clone(), accounts[4].clone());
+            let result = run::<ExecuteSwapIntent, _>(accounts, |ctx| devnet_contract::execute_swap_intent(ctx, expected_output));
+            assert_eq!(result.unwrap_err(), error.into());
+            assert!(load::<IntentAccount>(&intent_info).status == IntentStatus::Pending);
+            assert_eq!(token_balance(&source), intent.amount);
+        }
+        assert!(emitted::<SwapIntentExecuted>().is_empty());
+        
+        let accounts = swap_execution(owner, &intent);
+        let (intent_info, destination, treasury) = (accounts[1].clone(), accounts[5].clone(), accounts[6].clone());
+        run::<ExecuteSwapIntent, _>(accounts, |ctx| devnet_contract::execute_swap_intent(ctx, 2_000_000)).unwrap();
+        let executed = load::<IntentAccount>(&intent_info);
+        assert!(executed.status == IntentStatus::Executed);
+        assert_eq!(executed.execution_output, Some(2_000_000));
+        assert_eq!((token_balance(&destination), token_balance(&treasury)), (997_000, 3_000));
+        assert_eq!(emitted::<SwapIntentExecuted>()[0].amount_out, 2_000_000);
+    }
+    
+    #[test]
+    fn intents_born_expired_or_too_short_lived_are_refused() {
+        for expires_at in [START - 1, START, START + MIN_INTENT_LIFETIME] {
+            assert_eq!(check_intent_lifetime(START, expires_at).unwrap_err(), ErrorCode::InvalidExpiry.into());
+        }
+        assert_eq!(check_intent_lifetime(i64::MAX, i64::MIN).unwrap_err(), ErrorCode::InvalidExpiry.into());
+        check_intent_lifetime(START, START + MIN_INTENT_LIFETIME + 1).unwrap();
+    }
 }
@@ -1,15 +1,48 @@
 use anchor_lang::prelude::*;
+use anchor_lang::system_program;
+use anchor_lang::solana_program::{
+    instruction::{AccountMeta, Instruction},
+    program::invoke,
+};
 use anchor_spl::{
     associated_token::AssociatedToken,
     metadata::{
         create_metadata_accounts_v3, mpl_token_metadata::types::DataV2, CreateMetadataAccountsV3,
         Metadata as Metaplex,
     },
-    token::{self, Mint, Token, TokenAccount, MintTo},
+    token::{self, Mint, Token, TokenAccount, MintTo, Transfer},
 };
 
 declare_id!("5y2X9WML5ttrWrxzUfGrLSxbXfEcKTyV1dDyw2jXW1Zg");
 
+// Max launches claimable in one claim_tokens_batch call (compute budget)
+pub const MAX_BATCH_CLAIMS: usize = 5;
+// Max milestone unlocks per launch (bounds LaunchState size)
+pub const MAX_MILESTONES: usize = 4;
+// Token metadata string limits (Metaplex's own caps), bounding LaunchState size
+pub const MAX_TOKEN_NAME_LEN: usize = 32;
+pub const MAX_TOKEN_SYMBOL_LEN: usize = 10;
+pub const MAX_TOKEN_URI_LEN: usize = 200;
+// Wallets tracked on each launch's top-contributor leaderboard
+pub const MAX_TOP_CONTRIBUTORS: usize = 10;
+// Default bounds on launch_duration, adjustable by the launchpad authority
+pub const DEFAULT_MIN_LAUNCH_DURATION: i64 = 3600; // 1 hour
+pub const DEFAULT_MAX_LAUNCH_DURATION: i64 = 86400 * 30; // 30 days
+// Launches must stay open at least this long, whatever the configured duration bounds
+pub const MIN_LAUNCH_LIFETIME: i64 = 60;
+// Longest a launch can be scheduled ahead of its contribution window
+pub const MAX_START_DELAY: i64 = 86400 * 30; // 30 days
+// Launches a creator may have open (not yet finalized) at once, adjustable by the authority
+pub const DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR: u32 = 3;
+// launch_index of launches created before indexed seeds, which keep their unindexed address
+pub const LEGACY_LAUNCH_INDEX: u64 = u64::MAX;
+// Most recent launch keys kept in each creator's CreatorLaunches index
+pub const MAX_INDEXED_LAUNCHES_PER_CREATOR: usize = 16;
+// Upper bound on the platform fee taken from raised funds
+pub const MAX_PLATFORM_FEE_BPS: u16 = 1000; // 10%
+// Jupiter aggregator, used to sell claimed tokens for stables
+pub const JUPITER_PROGRAM_ID: Pubkey = pubkey!("JUP6LkbZbjS1jKKwapdHNy74zcZ3tLUZoi5QNyVTaV4");
+
 #[program]
 pub mod launchpad_contract {
     use super::*;
@@ -20,6 +53,8 @@ pub mod launchpad_contract {
         platform_fee_bps: u16,
         treasury_authority: Pubkey,
     ) -> Result<()> {
+        require!(platform_fee_bps <= MAX_PLATFORM_FEE_BPS, ErrorCode::InvalidPlatformFee);
+        
         let launchpad_state = &mut ctx.accounts.launchpad_state;
         launchpad_state.authority = ctx.accounts.authority.key();
         launchpad_state.treasury_authority = treasury_authority;
@@ -27,6 +62,11 @@ pub mod launchpad_contract {
         launchpad_state.total_launches = 0;
         launchpad_state.total_raised = 0;
         launchpad_state.is_paused = false;
+        launchpad_state.min_launch_duration = DEFAULT_MIN_LAUNCH_DURATION;
+        launchpad_state.max_launch_duration = DEFAULT_MAX_LAUNCH_DURATION;
+        launchpad_state.max_tvl = u64::MAX;
+        launchpad_state.current_tvl = 0;
+        launchpad_state.max_active_launches_per_creator = DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR;
         launchpad_state.bump = ctx.bumps.launchpad_state;
         
         msg!("🚀 Token Launchpad initialized!");
@@ -43,14 +83,48 @@ pub mod launchpad_contract {
         let launch_state = &mut ctx.accounts.launch_state;
         
         require!(!launchpad_state.is_paused, ErrorCode::LaunchpadPaused);
+        require!(
+            launch_params.token_name.len() <= MAX_TOKEN_NAME_LEN
+                && launch_params.token_symbol.len() <= MAX_TOKEN_SYMBOL_LEN
+                && launch_params.token_uri.len() <= MAX_TOKEN_URI_LEN,
+            ErrorCode::TokenMetadataTooLong
+        );
         require!(launch_params.soft_cap > 0, ErrorCode::InvalidSoftCap);
         require!(launch_params.hard_cap > launch_params.soft_cap, ErrorCode::InvalidHardCap);
         require!(launch_params.token_price > 0, ErrorCode::InvalidTokenPrice);
         require!(launch_params.min_contribution > 0, ErrorCode::InvalidMinContribution);
         require!(launch_params.max_contribution >= launch_params.min_contribution, ErrorCode::InvalidMaxContribution);
-        require!(launch_params.launch_duration > 0, ErrorCode::InvalidLaunchDuration);
+        require!(
+            launch_params.launch_duration >= launchpad_state.min_launch_duration
+                && launch_params.launch_duration <= launchpad_state.max_launch_duration,
+            ErrorCode::InvalidLaunchDuration
+        );
+        require!(
+            launch_params.start_delay_seconds >= 0 && launch_params.start_delay_seconds <= MAX_START_DELAY,
+            ErrorCode::InvalidStartDelay
+        );
+        validate_milestones(&launch_params.milestones)?;
+        
+        let creator_stats = &mut ctx.accounts.creator_stats;
+        require!(
+            creator_stats.active_launches < launchpad_state.max_active_launches_per_creator,
+            ErrorCode::TooManyActiveLaunches
+        );
+        creator_stats.creator = ctx.accounts.creator.key();
+        creator_stats.active_launches += 1;
+        let launch_index = creator_stats.total_launches;
+        creator_stats.total_launches = creator_stats.total_launches.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        creator_stats.bump = ctx.bumps.creator_stats;
+        
+        // Index the launch under its creator so dashboards can discover it from one account
+        let creator_launches = &mut ctx.accounts.creator_launches;
+        creator_launches.creator = ctx.accounts.creator.key();
+        index_launch(&mut creator_launches.launches, launch_state.key());
+        creator_launches.bump = ctx.bumps.creator_launches;
         
         let current_time = Clock::get()?.unix_timestamp;
+        // Scheduled launches open later so everyone learns the start time up front
+        let launch_start = current_time + launch_params.start_delay_seconds;
         
         // Initialize launch state
         launch_state.creator = ctx.accounts.creator.key();
@@ -64,16 +138,38 @@ pub mod launchpad_contract {
         launch_state.tokens_for_sale = launch_params.tokens_for_sale;
         launch_state.min_contribution = launch_params.min_contribution;
         launch_state.max_contribution = launch_params.max_contribution;
-        launch_state.launch_start = current_time;
-        launch_state.launch_end = current_time + launch_params.launch_duration;
+        launch_state.max_contributors = launch_params.max_contributors;
+        launch_state.launch_start = launch_start;
+        launch_state.launch_end = launch_start + launch_params.launch_duration;
+        require!(
+            launch_state.launch_end > launch_state.launch_start + MIN_LAUNCH_LIFETIME,
+            ErrorCode::InvalidExpiry
+        );
         launch_state.total_raised = 0;
         launch_state.total_contributors = 0;
         launch_state.tokens_sold = 0;
         launch_state.status = LaunchStatus::Active;
+        launch_state.milestones = launch_params.milestones.clone();
+        launch_state.withdrawn_amount = 0;
+        // Lock in the current fee so later changes don't apply to this launch
+        launch_state.platform_fee_bps = launchpad_state.platform_fee_bps;
+        launch_state.raise_mint = launch_params.raise_mint;
+        launch_state.top_contributors = [TopContributor::default(); MAX_TOP_CONTRIBUTORS];
+        launch_state.launch_index = launch_index;
         launch_state.bump = ctx.bumps.launch_state;
         
         // Update global state
-        launchpad_state.total_launches += 1;
+        launchpad_state.total_launches = launchpad_state.total_launches.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
+        
+        // The mint is initialized alongside the launch, under the launch PDA's authority
+        emit!(TokenMintCreated {
+            launch_id: launch_state.key(),
+            creator: ctx.accounts.creator.key(),
+            token_mint: ctx.accounts.token_mint.key(),
+            name: launch_params.token_name.clone(),
+            symbol: launch_params.token_symbol.clone(),
+            uri: launch_params.token_uri.clone(),
+        });
         
         emit!(TokenLaunchCreated {
             launch_id: launch_state.key(),
@@ -84,6 +180,7 @@ pub mod launchpad_contract {
             soft_cap: launch_params.soft_cap,
             hard_cap: launch_params.hard_cap,
             token_price: launch_params.token_price,
+            launch_start,
             launch_end: launch_state.launch_end,
         });
         
@@ -94,18 +191,19 @@ pub mod launchpad_contract {
         Ok(())
     }
 
-    /// Create token mint with metadata
+    /// Attach metadata to the launch's token mint
     pub fn create_token_mint(
         ctx: Context<CreateTokenMint>,
-        _decimals: u8,
         name: String,
         symbol: String,
         uri: String,
     ) -> Result<()> {
         let creator_key = ctx.accounts.creator.key();
+        let launch_index = ctx.accounts.launch_state.index_seed();
         let seeds = &[
             b"launch_state",
             creator_key.as_ref(),
+            &launch_index[..],
             &[ctx.bumps.launch_state],
         ];
         let signer = &[&seeds[..]];
@@ -157,47 +255,89 @@ pub mod launchpad_contract {
         require!(current_time >= launch_state.launch_start, ErrorCode::LaunchNotStarted);
         require!(current_time <= launch_state.launch_end, ErrorCode::LaunchEnded);
         require!(amount >= launch_state.min_contribution, ErrorCode::ContributionTooLow);
-        require!(
-            contributor_state.total_contributed + amount <= launch_state.max_contribution,
-            ErrorCode::ContributionTooHigh
-        );
-        require!(
-            launch_state.total_raised + amount <= launch_state.hard_cap,
-            ErrorCode::HardCapReached
-        );
+        let new_contributed = checked_total(contributor_state.total_contributed, amount)?;
+        require!(new_contributed <= launch_state.max_contribution, ErrorCode::ContributionTooHigh);
+        let new_raised = checked_total(launch_state.total_raised, amount)?;
+        require!(new_raised <= launch_state.hard_cap, ErrorCode::HardCapReached);
+        let new_tvl = launchpad_state.current_tvl.checked_add(amount).ok_or(ErrorCode::MathOverflow)?;
+        require!(new_tvl <= launchpad_state.max_tvl, ErrorCode::TvlCapReached);
         
         // Calculate tokens to receive
         let tokens_to_receive = amount
             .checked_mul(10_u64.pow(ctx.accounts.token_mint.decimals as u32))
-            .unwrap()
+            .ok_or(ErrorCode::MathOverflow)?
             .checked_div(launch_state.token_price)
-            .unwrap();
+            .ok_or(ErrorCode::MathOverflow)?;
+        let new_tokens_sold = checked_total(launch_state.tokens_sold, tokens_to_receive)?;
+        require!(new_tokens_sold <= launch_state.tokens_for_sale, ErrorCode::NotEnoughTokens);
         
-        require!(
-            launch_state.tokens_sold + tokens_to_receive <= launch_state.tokens_for_sale,
-            ErrorCode::NotEnoughTokens
-        );
+        // For devnet testing, we'll just track SOL contributions without actually holding SOL
+        // In production, you'd use a proper vault system. SPL raises move tokens into the
+        // launch PDA's vault token account.
+        if let Some(raise_mint) = launch_state.raise_mint {
+            let (Some(contributor_raise_account), Some(raise_vault), Some(token_program)) = (
+                ctx.accounts.contributor_raise_account.as_ref(),
+                ctx.accounts.raise_vault.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingRaiseAccounts);
+            };
+            validate_raise_vault(raise_vault, launch_state.key(), raise_mint)?;
+            require!(contributor_raise_account.mint == raise_mint, ErrorCode::RaiseMintMismatch);
+            
+            token::transfer(
+                CpiContext::new(
+                    token_program.to_account_info(),
+                    Transfer {
+                        from: contributor_raise_account.to_account_info(),
+                        to: raise_vault.to_account_info(),
+                        authority: ctx.accounts.contributor.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+        }
         
-        // For devnet testing, we'll just track contributions without actually holding SOL
-        // In production, you'd use a proper vault system
+        // Cap the number of distinct wallets, existing contributors can still top up
+        let is_new_contributor = contributor_state.total_contributed == 0;
+        if let Some(max_contributors) = launch_state.max_contributors {
+            require!(
+                !is_new_contributor || launch_state.total_contributors < max_contributors,
+                ErrorCode::ContributorLimitReached
+            );
+        }
         
         // Update contributor state
-        let is_new_contributor = contributor_state.total_contributed == 0;
         contributor_state.contributor = ctx.accounts.contributor.key();
         contributor_state.launch = launch_state.key();
-        contributor_state.total_contributed += amount;
-        contributor_state.tokens_owed += tokens_to_receive;
+        contributor_state.total_contributed = new_contributed;
+        contributor_state.tokens_owed = contributor_state.tokens_owed.checked_add(tokens_to_receive).ok_or(ErrorCode::MathOverflow)?;
         contributor_state.claimed = false;
         
         // Update launch state
-        launch_state.total_raised += amount;
-        launch_state.tokens_sold += tokens_to_receive;
+        launch_state.total_raised = new_raised;
+        launch_state.tokens_sold = new_tokens_sold;
         if is_new_contributor {
-            launch_state.total_contributors += 1;
+            launch_state.total_contributors = launch_state.total_contributors.checked_add(1).ok_or(ErrorCode::MathOverflow)?;
         }
         
         // Update global state
-        launchpad_state.total_raised += amount;
+        launchpad_state.total_raised = checked_total(launchpad_state.total_raised, amount)?;
+        launchpad_state.current_tvl = new_tvl;
+        
+        // Keep the bounded leaderboard sorted by total contributed
+        if let Some(rank) = update_leaderboard(
+            &mut launch_state.top_contributors,
+            ctx.accounts.contributor.key(),
+            contributor_state.total_contributed,
+        ) {
+            emit!(LeaderboardUpdated {
+                launch_id: launch_state.key(),
+                contributor: ctx.accounts.contributor.key(),
+                total_contributed: contributor_state.total_contributed,
+                rank,
+            });
+        }
         
         emit!(ContributionMade {
             launch_id: launch_state.key(),
@@ -224,6 +364,13 @@ pub mod launchpad_contract {
         );
         
         // Determine if launch was successful
+        let reason = if launch_state.total_raised >= launch_state.hard_cap {
+            FinalizeReason::HardCapReached
+        } else if launch_state.total_raised >= launch_state.soft_cap {
+            FinalizeReason::SoftCapMetAtExpiry
+        } else {
+            FinalizeReason::FailedBelowSoftCap
+        };
         if launch_state.total_raised >= launch_state.soft_cap {
             launch_state.status = LaunchStatus::Successful;
             msg!("🎉 Launch successful! Raised {} SOL", launch_state.total_raised);
@@ -233,9 +380,16 @@ pub mod launchpad_contract {
                 launch_state.total_raised, launch_state.soft_cap);
         }
         
+        // Free the creator's active-launch slot either way
+        let creator_stats = &mut ctx.accounts.creator_stats;
+        creator_stats.creator = launch_state.creator;
+        creator_stats.active_launches = creator_stats.active_launches.saturating_sub(1);
+        creator_stats.bump = ctx.bumps.creator_stats;
+        
         emit!(LaunchFinalized {
             launch_id: launch_state.key(),
             success: launch_state.status == LaunchStatus::Successful,
+            reason,
             total_raised: launch_state.total_raised,
             tokens_sold: launch_state.tokens_sold,
         });
@@ -243,6 +397,94 @@ pub mod launchpad_contract {
         Ok(())
     }
 
+    /// Extend an active launch's end time (creator only)
+    pub fn extend_launch(ctx: Context<ExtendLaunch>, additional_seconds: i64) -> Result<()> {
+        let launch_state = &mut ctx.accounts.launch_state;
+        let launchpad_state = &ctx.accounts.launchpad_state;
+        
+        require!(launch_state.creator == ctx.accounts.creator.key(), ErrorCode::Unauthorized);
+        require!(launch_state.status == LaunchStatus::Active, ErrorCode::LaunchNotActive);
+        require!(Clock::get()?.unix_timestamp < launch_state.launch_end, ErrorCode::LaunchEnded);
+        require!(additional_seconds > 0, ErrorCode::InvalidLaunchDuration);
+        
+        // Total duration stays within the launchpad's configured maximum
+        let new_launch_end = launch_state.launch_end.checked_add(additional_seconds).ok_or(ErrorCode::MathOverflow)?;
+        require!(
+            new_launch_end - launch_state.launch_start <= launchpad_state.max_launch_duration,
+            ErrorCode::InvalidLaunchDuration
+        );
+        
+        let old_launch_end = launch_state.launch_end;
+        launch_state.launch_end = new_launch_end;
+        
+        emit!(LaunchExtended {
+            launch_id: launch_state.key(),
+            old_launch_end,
+            new_launch_end,
+        });
+        
+        msg!("⏳ Launch extended by {}s, now ends at {}", additional_seconds, new_launch_end);
+        Ok(())
+    }
+
+    /// Emit a launch's progress toward its soft and hard caps
+    pub fn view_launch_progress(ctx: Context<ViewLaunchProgress>) -> Result<()> {
+        let launch_state = &ctx.accounts.launch_state;
+        let soft_cap_bps_filled = cap_filled_bps(launch_state.total_raised, launch_state.soft_cap);
+        let hard_cap_bps_filled = cap_filled_bps(launch_state.total_raised, launch_state.hard_cap);
+        
+        emit!(LaunchProgress {
+            launch_id: launch_state.key(),
+            total_raised: launch_state.total_raised,
+            soft_cap: launch_state.soft_cap,
+            hard_cap: launch_state.hard_cap,
+            soft_cap_bps_filled,
+            hard_cap_bps_filled,
+        });
+        
+        msg!("📈 Launch progress: {}% of soft cap, {}% of hard cap", soft_cap_bps_filled as f64 / 100.0, hard_cap_bps_filled as f64 / 100.0);
+        Ok(())
+    }
+
+    /// Emit whether a contributor can claim a refund and how much, using the same
+    /// checks as claim_refund
+    pub fn view_refund(ctx: Context<ViewRefund>) -> Result<()> {
+        let launch_state = &ctx.accounts.launch_state;
+        
+        // No contributor state means the wallet never contributed
+        let contributor_state = {
+            let data = ctx.accounts.contributor_state.try_borrow_data()?;
+            if data.is_empty() {
+                None
+            } else {
+                Some(ContributorState::try_deserialize(&mut &data[..])?)
+            }
+        };
+        let amount = contributor_state.as_ref().map_or(0, |state| state.total_contributed);
+        
+        let reason = if launch_state.status != LaunchStatus::Failed {
+            RefundReason::LaunchNotFailed
+        } else if contributor_state.as_ref().is_some_and(|state| state.claimed) {
+            RefundReason::AlreadyClaimed
+        } else if amount == 0 {
+            RefundReason::NothingContributed
+        } else {
+            RefundReason::Eligible
+        };
+        let eligible = reason == RefundReason::Eligible;
+        
+        emit!(RefundEligibility {
+            launch_id: launch_state.key(),
+            contributor: ctx.accounts.contributor.key(),
+            eligible,
+            amount: if eligible { amount } else { 0 },
+            reason: reason.clone(),
+        });
+        
+        msg!("💸 Refund for {}: {:?} ({} contributed)", ctx.accounts.contributor.key(), reason, amount);
+        Ok(())
+    }
+
     /// Claim tokens after successful launch
     pub fn claim_tokens(ctx: Context<ClaimTokens>) -> Result<()> {
         let launch_state = &ctx.accounts.launch_state;
@@ -252,9 +494,11 @@ pub mod launchpad_contract {
         require!(!contributor_state.claimed, ErrorCode::AlreadyClaimed);
         require!(contributor_state.tokens_owed > 0, ErrorCode::NoTokensOwed);
         
+        let launch_index = launch_state.index_seed();
         let seeds = &[
             b"launch_state",
             launch_state.creator.as_ref(),
+            &launch_index[..],
             &[launch_state.bump],
         ];
         let signer = &[&seeds[..]];
@@ -285,19 +529,204 @@ pub mod launchpad_contract {
         Ok(())
     }
 
+    /// Claim tokens from several successful launches in one transaction
+    ///
+    /// Remaining accounts are passed in groups of four per launch:
+    /// (launch_state, contributor_state, token_mint, contributor_token_account)
+    pub fn claim_tokens_batch<'info>(
+        ctx: Context<'_, '_, 'info, 'info, ClaimTokensBatch<'info>>,
+    ) -> Result<()> {
+        batch_claim_groups(ctx.remaining_accounts.len())?;
+        let groups = ctx.remaining_accounts.chunks_exact(4);
+        
+        let contributor = ctx.accounts.contributor.key();
+        let mut launches_claimed: u32 = 0;
+        let mut total_tokens_claimed: u64 = 0;
+        
+        for group in groups {
+            let launch_state: Account<LaunchState> = Account::try_from(&group[0])?;
+            let mut contributor_state: Account<ContributorState> = Account::try_from(&group[1])?;
+            let token_mint = &group[2];
+            let contributor_token_account: Account<TokenAccount> = Account::try_from(&group[3])?;
+            
+            require!(contributor_state.contributor == contributor, ErrorCode::Unauthorized);
+            require!(contributor_state.launch == launch_state.key(), ErrorCode::InvalidBatchAccounts);
+            require!(token_mint.key() == launch_state.token_mint, ErrorCode::InvalidBatchAccounts);
+            require!(
+                contributor_token_account.mint == launch_state.token_mint
+                    && contributor_token_account.owner == contributor,
+                ErrorCode::InvalidBatchAccounts
+            );
+            
+            // Skip anything that isn't claimable rather than failing the whole batch
+            if launch_state.status != LaunchStatus::Successful
+                || contributor_state.claimed
+                || contributor_state.tokens_owed == 0
+            {
+                continue;
+            }
+            
+            let launch_index = launch_state.index_seed();
+            let seeds = &[
+                b"launch_state",
+                launch_state.creator.as_ref(),
+                &launch_index[..],
+                &[launch_state.bump],
+            ];
+            let signer = &[&seeds[..]];
+            
+            let mint_ctx = CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                MintTo {
+                    mint: token_mint.clone(),
+                    to: contributor_token_account.to_account_info(),
+                    authority: launch_state.to_account_info(),
+                },
+                signer,
+            );
+            
+            token::mint_to(mint_ctx, contributor_state.tokens_owed)?;
+            
+            contributor_state.claimed = true;
+            contributor_state.exit(ctx.program_id)?;
+            
+            launches_claimed += 1;
+            total_tokens_claimed = checked_total(total_tokens_claimed, contributor_state.tokens_owed)?;
+            
+            emit!(TokensClaimed {
+                launch_id: launch_state.key(),
+                contributor,
+                tokens_claimed: contributor_state.tokens_owed,
+            });
+        }
+        
+        emit!(BatchClaimed {
+            contributor,
+            launches_claimed,
+            total_tokens_claimed,
+        });
+        
+        msg!("🪙 Batch claim: {} tokens across {} launches", total_tokens_claimed, launches_claimed);
+        
+        Ok(())
+    }
+
+    /// Claim tokens and immediately sell them into the market for a stable token
+    ///
+    /// `route_data` is a Jupiter route instruction built off-chain; its accounts are
+    /// passed as remaining accounts. The contributor must receive at least `min_out`.
+    pub fn claim_as_stable<'info>(
+        ctx: Context<'_, '_, '_, 'info, ClaimAsStable<'info>>,
+        min_out: u64,
+        route_data: Vec<u8>,
+    ) -> Result<()> {
+        let launch_state = &ctx.accounts.launch_state;
+        let contributor_state = &ctx.accounts.contributor_state;
+        
+        require!(launch_state.status == LaunchStatus::Successful, ErrorCode::LaunchNotSuccessful);
+        require!(!contributor_state.claimed, ErrorCode::AlreadyClaimed);
+        require!(contributor_state.tokens_owed > 0, ErrorCode::NoTokensOwed);
+        // A zero floor would let any route, however bad, settle the claim
+        require!(min_out > 0, ErrorCode::InvalidMinOut);
+        
+        let tokens_owed = contributor_state.tokens_owed;
+        let launch_index = launch_state.index_seed();
+        let seeds = &[
+            b"launch_state",
+            launch_state.creator.as_ref(),
+            &launch_index[..],
+            &[launch_state.bump],
+        ];
+        let signer = &[&seeds[..]];
+        
+        // Mint tokens to contributor, then sell them through Jupiter
+        let mint_ctx = CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            MintTo {
+                mint: ctx.accounts.token_mint.to_account_info(),
+                to: ctx.accounts.contributor_token_account.to_account_info(),
+                authority: ctx.accounts.launch_state.to_account_info(),
+            },
+            signer,
+        );
+        
+        token::mint_to(mint_ctx, tokens_owed)?;
+        
+        let stable_before = ctx.accounts.contributor_stable_account.amount;
+        
+        let route_ix = Instruction {
+            program_id: JUPITER_PROGRAM_ID,
+            accounts: ctx.remaining_accounts
+                .iter()
+                .map(|account| AccountMeta {
+                    pubkey: account.key(),
+                    is_signer: account.is_signer,
+                    is_writable: account.is_writable,
+                })
+                .collect(),
+            data: route_data,
+        };
+        let mut route_accounts = ctx.remaining_accounts.to_vec();
+        route_accounts.push(ctx.accounts.jupiter_program.to_account_info());
+        invoke(&route_ix, &route_accounts)?;
+        
+        // Slippage protection is enforced on what actually landed in the stable account
+        ctx.accounts.contributor_stable_account.reload()?;
+        // A route that took stable out of the account received less than nothing
+        let stable_received = ctx.accounts.contributor_stable_account.amount
+            .checked_sub(stable_before)
+            .ok_or(ErrorCode::SlippageExceeded)?;
+        require!(stable_received >= min_out, ErrorCode::SlippageExceeded);
+        
+        ctx.accounts.contributor_state.claimed = true;
+        
+        emit!(TokensClaimedAsStable {
+            launch_id: ctx.accounts.launch_state.key(),
+            contributor: ctx.accounts.contributor.key(),
+            tokens_claimed: tokens_owed,
+            stable_mint: ctx.accounts.stable_mint.key(),
+            stable_received,
+        });
+        
+        msg!("💵 {} tokens claimed as {} stable by {}", tokens_owed, stable_received, ctx.accounts.contributor.key());
+        
+        Ok(())
+    }
+
     /// Claim refund after failed launch
     pub fn claim_refund(ctx: Context<ClaimRefund>) -> Result<()> {
         let launch_state = &ctx.accounts.launch_state;
         let contributor_state = &mut ctx.accounts.contributor_state;
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
         
         require!(launch_state.status == LaunchStatus::Failed, ErrorCode::LaunchNotFailed);
         require!(!contributor_state.claimed, ErrorCode::AlreadyClaimed);
         require!(contributor_state.total_contributed > 0, ErrorCode::NoRefundOwed);
         
-        // For devnet testing, we'll just mark as refunded
+        // For devnet testing, SOL refunds are just marked as refunded
         // In production, you'd transfer SOL back from vault
+        if let Some(raise_mint) = launch_state.raise_mint {
+            let (Some(contributor_raise_account), Some(raise_vault), Some(token_program)) = (
+                ctx.accounts.contributor_raise_account.as_ref(),
+                ctx.accounts.raise_vault.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingRaiseAccounts);
+            };
+            validate_raise_vault(raise_vault, launch_state.key(), raise_mint)?;
+            require!(contributor_raise_account.mint == raise_mint, ErrorCode::RaiseMintMismatch);
+            
+            transfer_from_raise_vault(
+                token_program,
+                raise_vault,
+                contributor_raise_account,
+                launch_state,
+                contributor_state.total_contributed,
+            )?;
+        }
         
         contributor_state.claimed = true;
+        launchpad_state.current_tvl = launchpad_state.current_tvl.saturating_sub(contributor_state.total_contributed);
         
         emit!(RefundClaimed {
             launch_id: launch_state.key(),
@@ -312,34 +741,244 @@ pub mod launchpad_contract {
 
     /// Withdraw raised funds (creator only, after successful launch)
     pub fn withdraw_funds(ctx: Context<WithdrawFunds>) -> Result<()> {
-        let launch_state = &ctx.accounts.launch_state;
-        let launchpad_state = &ctx.accounts.launchpad_state;
+        let launch_state = &mut ctx.accounts.launch_state;
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
         
         require!(launch_state.status == LaunchStatus::Successful, ErrorCode::LaunchNotSuccessful);
         require!(launch_state.creator == ctx.accounts.creator.key(), ErrorCode::Unauthorized);
         
-        let total_amount = launch_state.total_raised;
-        let platform_fee = (total_amount as u128)
-            .checked_mul(launchpad_state.platform_fee_bps as u128)
-            .unwrap()
-            .checked_div(10000)
-            .unwrap() as u64;
-        let creator_amount = total_amount - platform_fee;
+        // Only the fraction unlocked by elapsed milestones can be released
+        let (unlocked_amount, platform_fee, creator_amount) =
+            withdrawal_split(launch_state, Clock::get()?.unix_timestamp);
+        let total_amount = platform_fee + creator_amount;
+        require!(total_amount > 0, ErrorCode::NothingToWithdraw);
         
-        // For devnet testing, we'll just emit the withdrawal event
+        launch_state.withdrawn_amount = unlocked_amount;
+        launchpad_state.current_tvl = launchpad_state.current_tvl.saturating_sub(total_amount);
+        
+        // For devnet testing, SOL withdrawals just emit the event
         // In production, you'd transfer actual SOL from vault
+        if let Some(raise_mint) = launch_state.raise_mint {
+            let (Some(raise_vault), Some(creator_raise_account), Some(treasury_raise_account), Some(token_program)) = (
+                ctx.accounts.raise_vault.as_ref(),
+                ctx.accounts.creator_raise_account.as_ref(),
+                ctx.accounts.treasury_raise_account.as_ref(),
+                ctx.accounts.token_program.as_ref(),
+            ) else {
+                return err!(ErrorCode::MissingRaiseAccounts);
+            };
+            validate_raise_vault(raise_vault, launch_state.key(), raise_mint)?;
+            require!(creator_raise_account.mint == raise_mint, ErrorCode::RaiseMintMismatch);
+            require!(treasury_raise_account.mint == raise_mint, ErrorCode::RaiseMintMismatch);
+            require!(
+                treasury_raise_account.owner == launchpad_state.treasury_authority,
+                ErrorCode::InvalidTreasury
+            );
+            
+            transfer_from_raise_vault(token_program, raise_vault, creator_raise_account, launch_state, creator_amount)?;
+            if platform_fee > 0 {
+                transfer_from_raise_vault(token_program, raise_vault, treasury_raise_account, launch_state, platform_fee)?;
+            }
+        }
         
         emit!(FundsWithdrawn {
             launch_id: launch_state.key(),
             creator: ctx.accounts.creator.key(),
             amount_withdrawn: creator_amount,
             platform_fee,
+            total_withdrawn: launch_state.withdrawn_amount,
         });
         
         msg!("💰 Funds withdrawn: {} SOL to creator, {} SOL platform fee", creator_amount, platform_fee);
         
         Ok(())
     }
+
+    /// Emit what withdraw_funds would pay out right now, computed the same way, without
+    /// moving funds
+    pub fn view_withdrawal(ctx: Context<ViewWithdrawal>) -> Result<()> {
+        let launch_state = &ctx.accounts.launch_state;
+        let launch_successful = launch_state.status == LaunchStatus::Successful;
+        
+        let (platform_fee, creator_amount) = if launch_successful {
+            let (_, platform_fee, creator_amount) = withdrawal_split(launch_state, Clock::get()?.unix_timestamp);
+            (platform_fee, creator_amount)
+        } else {
+            (0, 0)
+        };
+        
+        emit!(WithdrawalPreview {
+            launch_id: launch_state.key(),
+            launch_successful,
+            total_raised: launch_state.total_raised,
+            already_withdrawn: launch_state.withdrawn_amount,
+            platform_fee_bps: launch_state.platform_fee_bps,
+            platform_fee,
+            creator_amount,
+        });
+        
+        msg!("🧾 Withdrawal preview: {} to creator, {} platform fee", creator_amount, platform_fee);
+        Ok(())
+    }
+
+    /// Rotate the treasury receiving platform fees (admin only)
+    pub fn set_launchpad_treasury(
+        ctx: Context<SetLaunchpadTreasury>,
+        new_authority: Pubkey,
+    ) -> Result<()> {
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
+        require!(launchpad_state.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        
+        let old_authority = launchpad_state.treasury_authority;
+        launchpad_state.treasury_authority = new_authority;
+        
+        emit!(LaunchpadTreasuryUpdated {
+            old_authority,
+            new_authority,
+        });
+        
+        msg!("🏦 Launchpad treasury updated: {} → {}", old_authority, new_authority);
+        Ok(())
+    }
+
+    /// Set the allowed range for launch durations (admin only)
+    pub fn set_launch_duration_bounds(
+        ctx: Context<UpdateLaunchpadConfig>,
+        min_launch_duration: i64,
+        max_launch_duration: i64,
+    ) -> Result<()> {
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
+        require!(launchpad_state.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        require!(
+            min_launch_duration > 0 && max_launch_duration >= min_launch_duration,
+            ErrorCode::InvalidLaunchDuration
+        );
+        
+        launchpad_state.min_launch_duration = min_launch_duration;
+        launchpad_state.max_launch_duration = max_launch_duration;
+        
+        msg!("⏱️ Launch duration bounds: {}s - {}s", min_launch_duration, max_launch_duration);
+        Ok(())
+    }
+
+    /// Change the platform fee applied to launches created from now on (admin only)
+    pub fn set_platform_fee(ctx: Context<UpdateLaunchpadConfig>, new_bps: u16) -> Result<()> {
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
+        require!(launchpad_state.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        require!(new_bps <= MAX_PLATFORM_FEE_BPS, ErrorCode::InvalidPlatformFee);
+        
+        let old_bps = launchpad_state.platform_fee_bps;
+        launchpad_state.platform_fee_bps = new_bps;
+        
+        emit!(PlatformFeeUpdated {
+            old_bps,
+            new_bps,
+        });
+        
+        msg!("💰 Platform fee updated: {}% → {}%", old_bps as f64 / 100.0, new_bps as f64 / 100.0);
+        Ok(())
+    }
+
+    /// Cap the total funds held across all launches (admin only)
+    pub fn set_max_tvl(ctx: Context<UpdateLaunchpadConfig>, max_tvl: u64) -> Result<()> {
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
+        require!(launchpad_state.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        
+        launchpad_state.max_tvl = max_tvl;
+        
+        msg!("🧱 Launchpad TVL cap set to {} SOL ({} currently held)", max_tvl, launchpad_state.current_tvl);
+        Ok(())
+    }
+
+    /// Cap how many unfinalized launches a single creator may have (admin only)
+    pub fn set_max_active_launches_per_creator(
+        ctx: Context<UpdateLaunchpadConfig>,
+        max_active_launches: u32,
+    ) -> Result<()> {
+        let launchpad_state = &mut ctx.accounts.launchpad_state;
+        require!(launchpad_state.authority == ctx.accounts.authority.key(), ErrorCode::Unauthorized);
+        require!(max_active_launches > 0, ErrorCode::InvalidLaunchCap);
+        
+        launchpad_state.max_active_launches_per_creator = max_active_launches;
+        
+        msg!("🚦 Max active launches per creator set to {}", max_active_launches);
+        Ok(())
+    }
+
+    /// Rewrite a launchpad state created before the current layout, filling the newer
+    /// settings with initialize_launchpad's defaults (authority only)
+    pub fn migrate_launchpad_state(ctx: Context<MigrateLaunchpadState>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        migrate_account(
+            &ctx.accounts.launchpad_state,
+            &ctx.accounts.authority,
+            &ctx.accounts.system_program,
+            |old: LaunchpadStateV0| {
+                require!(old.authority == authority, ErrorCode::Unauthorized);
+                Ok(LaunchpadState {
+                    authority: old.authority,
+                    treasury_authority: old.treasury_authority,
+                    platform_fee_bps: old.platform_fee_bps,
+                    total_launches: old.total_launches,
+                    total_raised: old.total_raised,
+                    is_paused: old.is_paused,
+                    min_launch_duration: DEFAULT_MIN_LAUNCH_DURATION,
+                    max_launch_duration: DEFAULT_MAX_LAUNCH_DURATION,
+                    max_tvl: u64::MAX,
+                    current_tvl: 0, // Older launches never held funds in the launch PDA
+                    max_active_launches_per_creator: DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR,
+                    bump: old.bump,
+                })
+            },
+        )?;
+        
+        msg!("🛠️ Launchpad state migrated");
+        Ok(())
+    }
+
+    /// Rewrite a launch created before the current layout. It keeps its unindexed
+    /// address, raises SOL and releases everything at success. Anyone may pay for this
+    /// so contributors aren't stuck behind an absent creator
+    pub fn migrate_launch(ctx: Context<MigrateLaunch>) -> Result<()> {
+        let platform_fee_bps = ctx.accounts.launchpad_state.platform_fee_bps;
+        migrate_account(
+            &ctx.accounts.launch_state,
+            &ctx.accounts.payer,
+            &ctx.accounts.system_program,
+            |old: LaunchStateV0| {
+                Ok(LaunchState {
+                    creator: old.creator,
+                    token_mint: old.token_mint,
+                    token_name: old.token_name,
+                    token_symbol: old.token_symbol,
+                    token_uri: old.token_uri,
+                    soft_cap: old.soft_cap,
+                    hard_cap: old.hard_cap,
+                    token_price: old.token_price,
+                    tokens_for_sale: old.tokens_for_sale,
+                    min_contribution: old.min_contribution,
+                    max_contribution: old.max_contribution,
+                    max_contributors: None,
+                    launch_start: old.launch_start,
+                    launch_end: old.launch_end,
+                    total_raised: old.total_raised,
+                    total_contributors: old.total_contributors,
+                    tokens_sold: old.tokens_sold,
+                    status: old.status,
+                    milestones: Vec::new(),
+                    withdrawn_amount: 0,
+                    platform_fee_bps,
+                    raise_mint: None,
+                    top_contributors: [TopContributor::default(); MAX_TOP_CONTRIBUTORS],
+                    launch_index: LEGACY_LAUNCH_INDEX,
+                    bump: old.bump,
+                })
+            },
+        )?;
+        
+        msg!("🛠️ Launch {} migrated", ctx.accounts.launch_state.key());
+        Ok(())
+    }
 }
 
 // Structs
@@ -355,9 +994,26 @@ pub struct LaunchParams {
     pub min_contribution: u64,  // Minimum SOL contribution
     pub max_contribution: u64,  // Maximum SOL contribution per user
     pub launch_duration: i64,   // Duration in seconds
+    pub start_delay_seconds: i64, // Contributions open this long after creation, 0 opens immediately
+    pub max_contributors: Option<u32>, // Maximum number of distinct contributors
+    pub milestones: Vec<Milestone>, // Creator fund unlocks, empty releases everything at success
+    pub raise_mint: Option<Pubkey>, // SPL token raised instead of SOL, prices then in its smallest unit
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, InitSpace)]
+pub struct Milestone {
+    pub unlock_time: i64,
+    pub bps: u16, // Share of raised funds unlocked at this time
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default, InitSpace)]
+pub struct TopContributor {
+    pub wallet: Pubkey,
+    pub amount: u64, // Total contributed, 0 marks an empty slot
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct LaunchpadState {
     pub authority: Pubkey,
     pub treasury_authority: Pubkey,
@@ -365,11 +1021,87 @@ pub struct LaunchpadState {
     pub total_launches: u64,
     pub total_raised: u64,
     pub is_paused: bool,
+    pub min_launch_duration: i64,
+    pub max_launch_duration: i64,
+    pub max_tvl: u64, // Global cap on funds held across launches
+    pub current_tvl: u64, // Contributions not yet withdrawn or refunded
+    pub max_active_launches_per_creator: u32,
     pub bump: u8,
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct LaunchState {
+    pub creator: Pubkey,
+    pub token_mint: Pubkey,
+    #[max_len(MAX_TOKEN_NAME_LEN)]
+    pub token_name: String,
+    #[max_len(MAX_TOKEN_SYMBOL_LEN)]
+    pub token_symbol: String,
+    #[max_len(MAX_TOKEN_URI_LEN)]
+    pub token_uri: String,
+    pub soft_cap: u64,
+    pub hard_cap: u64,
+    pub token_price: u64,
+    pub tokens_for_sale: u64,
+    pub min_contribution: u64,
+    pub max_contribution: u64,
+    pub max_contributors: Option<u32>,
+    pub launch_start: i64,
+    pub launch_end: i64,
+    pub total_raised: u64,
+    pub total_contributors: u32,
+    pub tokens_sold: u64,
+    pub status: LaunchStatus,
+    #[max_len(MAX_MILESTONES)]
+    pub milestones: Vec<Milestone>,
+    pub withdrawn_amount: u64, // Gross amount already released to the creator
+    pub platform_fee_bps: u16, // Launchpad fee at creation time
+    pub raise_mint: Option<Pubkey>, // None raises SOL
+    pub top_contributors: [TopContributor; MAX_TOP_CONTRIBUTORS], // Sorted, highest first
+    pub launch_index: u64, // Creator's launch number, part of the PDA seeds
+    pub bump: u8,
+}
+
+impl LaunchState {
+    /// The launch index as it appears in this launch's PDA seeds
+    pub fn index_seed(&self) -> Vec<u8> {
+        launch_index_seed(self.launch_index)
+    }
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CreatorStats {
+    pub creator: Pubkey,
+    pub active_launches: u32, // Created but not yet finalized
+    pub total_launches: u64, // Launches ever created; the next launch's index
+    pub bump: u8,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct CreatorLaunches {
+    pub creator: Pubkey,
+    #[max_len(MAX_INDEXED_LAUNCHES_PER_CREATOR)]
+    pub launches: Vec<Pubkey>, // Most recent launches, oldest first
+    pub bump: u8,
+}
+
+// Original account layouts, read only by the migrate_* instructions
+#[derive(AnchorDeserialize)]
+pub struct LaunchpadStateV0 {
+    pub authority: Pubkey,
+    pub treasury_authority: Pubkey,
+    pub platform_fee_bps: u16,
+    pub total_launches: u64,
+    pub total_raised: u64,
+    pub is_paused: bool,
+    pub bump: u8,
+}
+
+#[derive(AnchorDeserialize)]
+pub struct LaunchStateV0 {
     pub creator: Pubkey,
     pub token_mint: Pubkey,
     pub token_name: String,
@@ -391,6 +1123,7 @@ pub struct LaunchState {
 }
 
 #[account]
+#[derive(InitSpace)]
 pub struct ContributorState {
     pub contributor: Pubkey,
     pub launch: Pubkey,
@@ -399,13 +1132,28 @@ pub struct ContributorState {
     pub claimed: bool,
 }
 
-#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq)]
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, PartialEq, Eq, InitSpace)]
 pub enum LaunchStatus {
     Active,
     Successful,
     Failed,
 }
 
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum RefundReason {
+    Eligible,
+    LaunchNotFailed,
+    AlreadyClaimed,
+    NothingContributed,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, PartialEq, Eq)]
+pub enum FinalizeReason {
+    HardCapReached,
+    SoftCapMetAtExpiry,
+    FailedBelowSoftCap,
+}
+
 // Context Structs
 #[derive(Accounts)]
 pub struct InitializeLaunchpad<'info> {
@@ -415,7 +1163,7 @@ pub struct InitializeLaunchpad<'info> {
     #[account(
         init,
         payer = authority,
-        space = 8 + 32 + 32 + 2 + 8 + 8 + 1 + 1,
+        space = 8 + LaunchpadState::INIT_SPACE,
         seeds = [b"launchpad_state"],
         bump
     )]
@@ -436,31 +1184,34 @@ pub struct CreateTokenLaunch<'info> {
     )]
     pub launchpad_state: Account<'info, LaunchpadState>,
     
+    #[account(
+        init_if_needed,
+        payer = creator,
+        space = 8 + CreatorStats::INIT_SPACE,
+        seeds = [b"creator_stats", creator.key().as_ref()],
+        bump
+    )]
+    pub creator_stats: Account<'info, CreatorStats>,
+    
     #[account(
         init,
         payer = creator,
-        space = 8 + 32 + 32 + 100 + 20 + 200 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 8 + 4 + 8 + 1 + 1,
-        seeds = [b"launch_state", creator.key().as_ref()],
+        space = 8 + LaunchState::INIT_SPACE,
+        seeds = [b"launch_state", creator.key().as_ref(), &creator_stats.total_launches.to_le_bytes()],
         bump
     )]
     pub launch_state: Account<'info, LaunchState>,
     
-    pub token_mint: Account<'info, Mint>,
-    
-    pub system_program: Program<'info, System>,
-}
-
-#[derive(Accounts)]
-pub struct CreateTokenMint<'info> {
-    #[account(mut)]
-    pub creator: Signer<'info>,
-    
     #[account(
-        seeds = [b"launch_state", creator.key().as_ref()],
+        init_if_needed,
+        payer = creator,
+        space = 8 + CreatorLaunches::INIT_SPACE,
+        seeds = [b"creator_launches", creator.key().as_ref()],
         bump
     )]
-    pub launch_state: Account<'info, LaunchState>,
+    pub creator_launches: Account<'info, CreatorLaunches>,
     
+    // Created under the launch PDA so the launch can mint what it sells
     #[account(
         init,
         payer = creator,
@@ -470,6 +1221,25 @@ pub struct CreateTokenMint<'info> {
     )]
     pub token_mint: Account<'info, Mint>,
     
+    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct CreateTokenMint<'info> {
+    #[account(mut)]
+    pub creator: Signer<'info>,
+    
+    #[account(
+        seeds = [b"launch_state", creator.key().as_ref(), &launch_state.index_seed()],
+        bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+    
+    #[account(address = launch_state.token_mint @ ErrorCode::TokenMintMismatch)]
+    pub token_mint: Account<'info, Mint>,
+    
     /// CHECK: This is not dangerous because we don't read or write from this account
     #[account(mut)]
     pub metadata: UncheckedAccount<'info>,
@@ -487,7 +1257,7 @@ pub struct ContributeToLaunch<'info> {
     
     #[account(
         mut,
-        seeds = [b"launch_state", launch_state.creator.as_ref()],
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
         bump = launch_state.bump
     )]
     pub launch_state: Account<'info, LaunchState>,
@@ -495,7 +1265,7 @@ pub struct ContributeToLaunch<'info> {
     #[account(
         init_if_needed,
         payer = contributor,
-        space = 8 + 32 + 32 + 8 + 8 + 1,
+        space = 8 + ContributorState::INIT_SPACE,
         seeds = [b"contributor", launch_state.key().as_ref(), contributor.key().as_ref()],
         bump
     )]
@@ -510,16 +1280,94 @@ pub struct ContributeToLaunch<'info> {
     
     pub token_mint: Account<'info, Mint>,
     
+    // SPL raise accounts, only required when the launch has a raise_mint
+    #[account(mut)]
+    pub contributor_raise_account: Option<Account<'info, TokenAccount>>,
+    
+    /// Launch PDA's token account for the raise mint
+    #[account(mut)]
+    pub raise_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
+    
     pub system_program: Program<'info, System>,
 }
 
 #[derive(Accounts)]
 pub struct FinalizeLaunch<'info> {
+    #[account(mut)]
     pub authority: Signer<'info>,
     
     #[account(
         mut,
-        seeds = [b"launch_state", launch_state.creator.as_ref()],
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
+        bump = launch_state.bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+    
+    // Created here for launches that predate per-creator tracking
+    #[account(
+        init_if_needed,
+        payer = authority,
+        space = 8 + CreatorStats::INIT_SPACE,
+        seeds = [b"creator_stats", launch_state.creator.as_ref()],
+        bump
+    )]
+    pub creator_stats: Account<'info, CreatorStats>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExtendLaunch<'info> {
+    pub creator: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"launch_state", creator.key().as_ref(), &launch_state.index_seed()],
+        bump = launch_state.bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+    
+    #[account(
+        seeds = [b"launchpad_state"],
+        bump = launchpad_state.bump
+    )]
+    pub launchpad_state: Account<'info, LaunchpadState>,
+}
+
+#[derive(Accounts)]
+pub struct ViewLaunchProgress<'info> {
+    #[account(
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
+        bump = launch_state.bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+}
+
+#[derive(Accounts)]
+pub struct ViewRefund<'info> {
+    #[account(
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
+        bump = launch_state.bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+    
+    /// CHECK: Wallet whose refund is being previewed
+    pub contributor: UncheckedAccount<'info>,
+    
+    /// CHECK: Contributor state PDA, left uninitialized when the wallet never contributed
+    #[account(
+        seeds = [b"contributor", launch_state.key().as_ref(), contributor.key().as_ref()],
+        bump
+    )]
+    pub contributor_state: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct ViewWithdrawal<'info> {
+    #[account(
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
         bump = launch_state.bump
     )]
     pub launch_state: Account<'info, LaunchState>,
@@ -531,7 +1379,42 @@ pub struct ClaimTokens<'info> {
     pub contributor: Signer<'info>,
     
     #[account(
-        seeds = [b"launch_state", launch_state.creator.as_ref()],
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
+        bump = launch_state.bump
+    )]
+    pub launch_state: Account<'info, LaunchState>,
+    
+    #[account(
+        mut,
+        seeds = [b"contributor", launch_state.key().as_ref(), contributor.key().as_ref()],
+        bump
+    )]
+    pub contributor_state: Account<'info, ContributorState>,
+    
+    #[account(mut)]
+    pub token_mint: Account<'info, Mint>,
+    
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        associated_token::mint = token_mint,
+        associated_token::authority = contributor,
+        constraint = contributor_token_account.mint == launch_state.token_mint @ ErrorCode::TokenMintMismatch,
+    )]
+    pub contributor_token_account: Account<'info, TokenAccount>,
+    
+    pub token_program: Program<'info, Token>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimAsStable<'info> {
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+    
+    #[account(
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
         bump = launch_state.bump
     )]
     pub launch_state: Account<'info, LaunchState>,
@@ -551,21 +1434,44 @@ pub struct ClaimTokens<'info> {
         payer = contributor,
         associated_token::mint = token_mint,
         associated_token::authority = contributor,
+        constraint = contributor_token_account.mint == launch_state.token_mint @ ErrorCode::TokenMintMismatch,
     )]
     pub contributor_token_account: Account<'info, TokenAccount>,
     
+    pub stable_mint: Account<'info, Mint>,
+    
+    #[account(
+        init_if_needed,
+        payer = contributor,
+        associated_token::mint = stable_mint,
+        associated_token::authority = contributor,
+    )]
+    pub contributor_stable_account: Account<'info, TokenAccount>,
+    
+    /// CHECK: Jupiter program
+    #[account(address = JUPITER_PROGRAM_ID)]
+    pub jupiter_program: UncheckedAccount<'info>,
+    
     pub token_program: Program<'info, Token>,
     pub associated_token_program: Program<'info, AssociatedToken>,
     pub system_program: Program<'info, System>,
 }
 
+#[derive(Accounts)]
+pub struct ClaimTokensBatch<'info> {
+    #[account(mut)]
+    pub contributor: Signer<'info>,
+    
+    pub token_program: Program<'info, Token>,
+}
+
 #[derive(Accounts)]
 pub struct ClaimRefund<'info> {
     #[account(mut)]
     pub contributor: Signer<'info>,
     
     #[account(
-        seeds = [b"launch_state", launch_state.creator.as_ref()],
+        seeds = [b"launch_state", launch_state.creator.as_ref(), &launch_state.index_seed()],
         bump = launch_state.bump
     )]
     pub launch_state: Account<'info, LaunchState>,
@@ -576,6 +1482,23 @@ pub struct ClaimRefund<'info> {
         bump
     )]
     pub contributor_state: Account<'info, ContributorState>,
+    
+    #[account(
+        mut,
+        seeds = [b"launchpad_state"],
+        bump = launchpad_state.bump
+    )]
+    pub launchpad_state: Account<'info, LaunchpadState>,
+    
+    // SPL raise accounts, only required when the launch has a raise_mint
+    #[account(mut)]
+    pub contributor_raise_account: Option<Account<'info, TokenAccount>>,
+    
+    /// Launch PDA's token account for the raise mint
+    #[account(mut)]
+    pub raise_vault: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
 }
 
 #[derive(Accounts)]
@@ -584,20 +1507,106 @@ pub struct WithdrawFunds<'info> {
     pub creator: Signer<'info>,
     
     #[account(
-        seeds = [b"launch_state", creator.key().as_ref()],
+        mut,
+        seeds = [b"launch_state", creator.key().as_ref(), &launch_state.index_seed()],
         bump = launch_state.bump
     )]
     pub launch_state: Account<'info, LaunchState>,
     
     #[account(
+        mut,
         seeds = [b"launchpad_state"],
         bump = launchpad_state.bump
     )]
     pub launchpad_state: Account<'info, LaunchpadState>,
     
-    #[account(mut)]
+    #[account(
+        mut,
+        address = launchpad_state.treasury_authority @ ErrorCode::InvalidTreasury
+    )]
     /// CHECK: Treasury account for platform fees
     pub treasury: UncheckedAccount<'info>,
+    
+    // SPL raise accounts, only required when the launch has a raise_mint
+    /// Launch PDA's token account for the raise mint
+    #[account(mut)]
+    pub raise_vault: Option<Account<'info, TokenAccount>>,
+    
+    #[account(mut)]
+    pub creator_raise_account: Option<Account<'info, TokenAccount>>,
+    
+    /// Treasury's token account for the raise mint, receives the platform fee
+    #[account(mut)]
+    pub treasury_raise_account: Option<Account<'info, TokenAccount>>,
+    
+    pub token_program: Option<Program<'info, Token>>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateLaunchpadConfig<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"launchpad_state"],
+        bump = launchpad_state.bump
+    )]
+    pub launchpad_state: Account<'info, LaunchpadState>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLaunchpadState<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+    
+    /// CHECK: Still in the original layout, so it can't load as LaunchpadState; migrate_account checks the discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"launchpad_state"],
+        bump
+    )]
+    pub launchpad_state: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateLaunch<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+    
+    #[account(
+        seeds = [b"launchpad_state"],
+        bump = launchpad_state.bump
+    )]
+    pub launchpad_state: Account<'info, LaunchpadState>,
+    
+    /// CHECK: Only seeds the legacy launch address
+    pub creator: UncheckedAccount<'info>,
+    
+    /// CHECK: Still in the original layout, so it can't load as LaunchState; migrate_account checks the discriminator
+    #[account(
+        mut,
+        owner = crate::ID,
+        seeds = [b"launch_state", creator.key().as_ref()],
+        bump
+    )]
+    pub launch_state: UncheckedAccount<'info>,
+    
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetLaunchpadTreasury<'info> {
+    pub authority: Signer<'info>,
+    
+    #[account(
+        mut,
+        seeds = [b"launchpad_state"],
+        bump = launchpad_state.bump
+    )]
+    pub launchpad_state: Account<'info, LaunchpadState>,
 }
 
 // Events
@@ -611,9 +1620,20 @@ pub struct TokenLaunchCreated {
     pub soft_cap: u64,
     pub hard_cap: u64,
     pub token_price: u64,
+    pub launch_start: i64,
     pub launch_end: i64,
 }
 
+#[event]
+pub struct TokenMintCreated {
+    pub launch_id: Pubkey,
+    pub creator: Pubkey,
+    pub token_mint: Pubkey,
+    pub name: String,
+    pub symbol: String,
+    pub uri: String,
+}
+
 #[event]
 pub struct ContributionMade {
     pub launch_id: Pubkey,
@@ -623,10 +1643,56 @@ pub struct ContributionMade {
     pub total_raised: u64,
 }
 
+#[event]
+pub struct LeaderboardUpdated {
+    pub launch_id: Pubkey,
+    pub contributor: Pubkey,
+    pub total_contributed: u64,
+    pub rank: u8, // 0 is the top contributor
+}
+
+#[event]
+pub struct LaunchExtended {
+    pub launch_id: Pubkey,
+    pub old_launch_end: i64,
+    pub new_launch_end: i64,
+}
+
+#[event]
+pub struct LaunchProgress {
+    pub launch_id: Pubkey,
+    pub total_raised: u64,
+    pub soft_cap: u64,
+    pub hard_cap: u64,
+    pub soft_cap_bps_filled: u16,
+    pub hard_cap_bps_filled: u16,
+}
+
+#[event]
+pub struct WithdrawalPreview {
+    pub launch_id: Pubkey,
+    pub launch_successful: bool, // Amounts are 0 unless the launch succeeded
+    pub total_raised: u64,
+    pub already_withdrawn: u64,
+    pub platform_fee_bps: u16,
+    pub platform_fee: u64,
+    pub creator_amount: u64,
+}
+
+#[event]
+pub struct RefundEligibility {
+    pub launch_id: Pubkey,
+    pub contributor: Pubkey,
+    pub eligible: bool,
+    pub amount: u64, // Refundable amount, 0 unless eligible
+    pub reason: RefundReason,
+}
+
 #[event]
 pub struct LaunchFinalized {
     pub launch_id: Pubkey,
     pub success: bool,
+    pub reason: FinalizeReason,
     pub total_raised: u64,
     pub tokens_sold: u64,
 }
@@ -638,6 +1704,22 @@ pub struct TokensClaimed {
     pub tokens_claimed: u64,
 }
 
+#[event]
+pub struct BatchClaimed {
+    pub contributor: Pubkey,
+    pub launches_claimed: u32,
+    pub total_tokens_claimed: u64,
+}
+
+#[event]
+pub struct TokensClaimedAsStable {
+    pub launch_id: Pubkey,
+    pub contributor: Pubkey,
+    pub tokens_claimed: u64,
+    pub stable_mint: Pubkey,
+    pub stable_received: u64,
+}
+
 #[event]
 pub struct RefundClaimed {
     pub launch_id: Pubkey,
@@ -651,9 +1733,22 @@ pub struct FundsWithdrawn {
     pub creator: Pubkey,
     pub amount_withdrawn: u64,
     pub platform_fee: u64,
+    pub total_withdrawn: u64,
 }
 
-// Error Codes
+#[event]
+pub struct LaunchpadTreasuryUpdated {
+    pub old_authority: Pubkey,
+    pub new_authority: Pubkey,
+}
+
+#[event]
+pub struct PlatformFeeUpdated {
+    pub old_bps: u16,
+    pub new_bps: u16,
+}
+
+// Error Codes
 #[error_code]
 pub enum ErrorCode {
     #[msg("Launchpad is paused")]
@@ -698,4 +1793,1664 @@ pub enum ErrorCode {
     NoRefundOwed,
     #[msg("Unauthorized")]
     Unauthorized,
-} 
\ No newline at end of file
+    #[msg("Contributor limit reached")]
+    ContributorLimitReached,
+    #[msg("Invalid batch accounts")]
+    InvalidBatchAccounts,
+    #[msg("Batch too large")]
+    BatchTooLarge,
+    #[msg("Invalid milestones")]
+    InvalidMilestones,
+    #[msg("Nothing to withdraw")]
+    NothingToWithdraw,
+    #[msg("Treasury account does not match launchpad treasury")]
+    InvalidTreasury,
+    #[msg("Slippage tolerance exceeded")]
+    SlippageExceeded,
+    #[msg("Math overflow")]
+    MathOverflow,
+    #[msg("Launchpad TVL cap reached")]
+    TvlCapReached,
+    #[msg("Platform fee exceeds the maximum")]
+    InvalidPlatformFee,
+    #[msg("SPL raise accounts missing")]
+    MissingRaiseAccounts,
+    #[msg("Raise vault is not the launch's token account for the raise mint")]
+    InvalidRaiseVault,
+    #[msg("Token account mint does not match the launch's raise mint")]
+    RaiseMintMismatch,
+    #[msg("Start delay is negative or too far in the future")]
+    InvalidStartDelay,
+    #[msg("Token account mint does not match the launch's token mint")]
+    TokenMintMismatch,
+    #[msg("Too many milestones")]
+    TooManyMilestones,
+    #[msg("Token name, symbol or URI is too long")]
+    TokenMetadataTooLong,
+    #[msg("Creator has too many active launches")]
+    TooManyActiveLaunches,
+    #[msg("Active launch cap must be positive")]
+    InvalidLaunchCap,
+    #[msg("Launch would end before its minimum lifetime")]
+    InvalidExpiry,
+    #[msg("Account already uses the current layout")]
+    AlreadyMigrated,
+    #[msg("Minimum stable out must be positive")]
+    InvalidMinOut,
+}
+
+/// Rewrite an account still in an older layout as `New`, growing it to `New::INIT_SPACE`
+/// with the payer topping up rent. Accounts already at full size are left alone
+fn migrate_account<'info, Old: AnchorDeserialize, New: AccountSerialize + anchor_lang::Discriminator + Space>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    upgrade: impl FnOnce(Old) -> Result<New>,
+) -> Result<()> {
+    let new_len = 8 + New::INIT_SPACE;
+    let old = {
+        let data = account.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == New::DISCRIMINATOR,
+            anchor_lang::error::ErrorCode::AccountDiscriminatorMismatch
+        );
+        require!(data.len() < new_len, ErrorCode::AlreadyMigrated);
+        Old::deserialize(&mut &data[8..])?
+    };
+    let migrated = upgrade(old)?;
+    
+    let rent_shortfall = Rent::get()?.minimum_balance(new_len).saturating_sub(account.lamports());
+    if rent_shortfall > 0 {
+        let cpi_ctx = CpiContext::new(
+            system_program.to_account_info(),
+            system_program::Transfer {
+                from: payer.to_account_info(),
+                to: account.clone(),
+            },
+        );
+        system_program::transfer(cpi_ctx, rent_shortfall)?;
+    }
+    account.realloc(new_len, true)?;
+    migrated.try_serialize(&mut &mut account.try_borrow_mut_data()?[..])
+}
+
+fn validate_raise_vault(raise_vault: &Account<TokenAccount>, launch_key: Pubkey, raise_mint: Pubkey) -> Result<()> {
+    require!(
+        raise_vault.owner == launch_key && raise_vault.mint == raise_mint,
+        ErrorCode::InvalidRaiseVault
+    );
+    Ok(())
+}
+
+/// Move raise tokens out of a launch's vault, signed by the launch PDA
+fn transfer_from_raise_vault<'info>(
+    token_program: &Program<'info, Token>,
+    raise_vault: &Account<'info, TokenAccount>,
+    to: &Account<'info, TokenAccount>,
+    launch_state: &Account<'info, LaunchState>,
+    amount: u64,
+) -> Result<()> {
+    let launch_index = launch_state.index_seed();
+    let seeds = &[
+        b"launch_state",
+        launch_state.creator.as_ref(),
+        &launch_index[..],
+        &[launch_state.bump],
+    ];
+    let signer = &[&seeds[..]];
+    
+    token::transfer(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            Transfer {
+                from: raise_vault.to_account_info(),
+                to: to.to_account_info(),
+                authority: launch_state.to_account_info(),
+            },
+            signer,
+        ),
+        amount,
+    )
+}
+
+/// Number of (launch_state, contributor_state, token_mint, token_account) groups in a
+/// batch claim's remaining accounts, within MAX_BATCH_CLAIMS
+fn batch_claim_groups(account_count: usize) -> Result<usize> {
+    require!(
+        account_count > 0 && account_count.is_multiple_of(4),
+        ErrorCode::InvalidBatchAccounts
+    );
+    require!(account_count / 4 <= MAX_BATCH_CLAIMS, ErrorCode::BatchTooLarge);
+    Ok(account_count / 4)
+}
+
+/// Add to a running raise total, failing with MathOverflow instead of panicking
+fn checked_total(total: u64, amount: u64) -> Result<u64> {
+    total.checked_add(amount).ok_or_else(|| error!(ErrorCode::MathOverflow))
+}
+
+fn validate_milestones(milestones: &[Milestone]) -> Result<()> {
+    if milestones.is_empty() {
+        return Ok(());
+    }
+    require!(milestones.len() <= MAX_MILESTONES, ErrorCode::TooManyMilestones);
+    require!(
+        milestones.windows(2).all(|pair| pair[0].unlock_time < pair[1].unlock_time),
+        ErrorCode::InvalidMilestones
+    );
+    let total_bps: u32 = milestones.iter().map(|m| m.bps as u32).sum();
+    require!(total_bps == 10000, ErrorCode::InvalidMilestones);
+    Ok(())
+}
+
+/// Address of a creator's `launch_index`-th launch
+pub fn launch_state_address(creator: &Pubkey, launch_index: u64) -> (Pubkey, u8) {
+    Pubkey::find_program_address(&[b"launch_state", creator.as_ref(), &launch_index_seed(launch_index)], &crate::ID)
+}
+
+/// Launch PDA seed for `launch_index`; legacy launches have none
+pub fn launch_index_seed(launch_index: u64) -> Vec<u8> {
+    if launch_index == LEGACY_LAUNCH_INDEX {
+        Vec::new()
+    } else {
+        launch_index.to_le_bytes().to_vec()
+    }
+}
+
+/// Record a new launch in a creator's index, dropping the oldest once it is full. Older
+/// launches stay reachable through `launch_state_address` up to `CreatorStats::total_launches`
+pub fn index_launch(launches: &mut Vec<Pubkey>, launch: Pubkey) {
+    if launches.len() >= MAX_INDEXED_LAUNCHES_PER_CREATOR {
+        launches.remove(0);
+    }
+    launches.push(launch);
+}
+
+/// Cumulative share of raised funds unlocked at `now`, in basis points
+pub fn unlocked_milestone_bps(milestones: &[Milestone], now: i64) -> u16 {
+    if milestones.is_empty() {
+        return 10000;
+    }
+    milestones
+        .iter()
+        .filter(|m| m.unlock_time <= now)
+        .map(|m| m.bps)
+        .sum()
+} 
+
+/// Funds withdraw_funds can release at `now`: (unlocked total, platform fee, creator amount).
+/// The fee and creator amount split whatever is unlocked but not yet withdrawn.
+pub fn withdrawal_split(launch_state: &LaunchState, now: i64) -> (u64, u64, u64) {
+    let unlocked_bps = unlocked_milestone_bps(&launch_state.milestones, now);
+    let unlocked_amount = (launch_state.total_raised as u128)
+        .checked_mul(unlocked_bps as u128)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap() as u64;
+    let total_amount = unlocked_amount.saturating_sub(launch_state.withdrawn_amount);
+    
+    let platform_fee = (total_amount as u128)
+        .checked_mul(launch_state.platform_fee_bps as u128)
+        .unwrap()
+        .checked_div(10000)
+        .unwrap() as u64;
+    (unlocked_amount, platform_fee, total_amount - platform_fee)
+}
+
+/// Share of `cap` raised so far in basis points, capped at 100% (a zero cap counts as filled)
+pub fn cap_filled_bps(total_raised: u64, cap: u64) -> u16 {
+    (total_raised as u128)
+        .checked_mul(10000)
+        .unwrap()
+        .checked_div(cap as u128)
+        .unwrap_or(10000)
+        .min(10000) as u16
+}
+
+/// Insert or move `wallet` on the sorted leaderboard, evicting the smallest entry when full.
+/// Returns the wallet's new rank if the board changed
+pub fn update_leaderboard(board: &mut [TopContributor], wallet: Pubkey, amount: u64) -> Option<u8> {
+    if board.is_empty() || amount == 0 {
+        return None;
+    }
+    let mut idx = match board.iter().position(|e| e.amount > 0 && e.wallet == wallet) {
+        Some(i) => i,
+        None => {
+            let last = board.len() - 1;
+            if board[last].amount >= amount {
+                return None;
+            }
+            last
+        }
+    };
+    board[idx] = TopContributor { wallet, amount };
+    // Bubble up past smaller entries; earlier contributors keep ties
+    while idx > 0 && board[idx - 1].amount < amount {
+        board.swap(idx - 1, idx);
+        idx -= 1;
+    }
+    Some(idx as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::solana_program::{
+        clock::Clock,
+        entrypoint::{ProgramResult, MAX_PERMITTED_DATA_INCREASE},
+        program_option::COption,
+        program_pack::Pack,
+        program_stubs,
+        program_utils::limited_deserialize,
+        system_instruction::SystemInstruction,
+        system_program,
+    };
+    use anchor_lang::{Bumps, Discriminator};
+    use anchor_spl::token::spl_token;
+    use std::{cell::{Cell, RefCell}, collections::BTreeSet, sync::Once};
+
+    const START: i64 = 1_700_000_000;
+
+    thread_local! {
+        static NOW: Cell<i64> = const { Cell::new(START) };
+        static JUPITER_FILL: Cell<u64> = const { Cell::new(0) };
+        static EVENTS: RefCell<Vec<Vec<u8>>> = const { RefCell::new(Vec::new()) };
+    }
+
+    /// Host stand-in for the runtime: a per-thread clock, default rent, recorded events, and
+    /// the system and token programs behind CPIs. Jupiter routes pay JUPITER_FILL tokens into the route's
+    /// first account. CPIs to any other program succeed without effect.
+    struct TestRuntime;
+
+    impl program_stubs::SyscallStubs for TestRuntime {
+        fn sol_get_clock_sysvar(&self, var_addr: *mut u8) -> u64 {
+            let clock = Clock { unix_timestamp: NOW.with(Cell::get), ..Clock::default() };
+            unsafe { *(var_addr as *mut Clock) = clock };
+            0
+        }
+
+        fn sol_get_rent_sysvar(&self, var_addr: *mut u8) -> u64 {
+            unsafe { *(var_addr as *mut Rent) = Rent::default() };
+            0
+        }
+
+        fn sol_log_data(&self, fields: &[&[u8]]) {
+            EVENTS.with(|events| events.borrow_mut().push(fields.concat()));
+        }
+
+        fn sol_invoke_signed(
+            &self,
+            instruction: &Instruction,
+            account_infos: &[AccountInfo],
+            _signers_seeds: &[&[&[u8]]],
+        ) -> ProgramResult {
+            // Hand the callee its accounts in instruction order, with PDA signatures applied
+            let accounts: Vec<AccountInfo> = instruction
+                .accounts
+                .iter()
+                .map(|meta| {
+                    let mut info = account_infos.iter().find(|info| *info.key == meta.pubkey).unwrap().clone();
+                    info.is_signer |= meta.is_signer;
+                    info
+                })
+                .collect();
+            if instruction.program_id == spl_token::ID {
+                return spl_token::processor::Processor::process(&instruction.program_id, &accounts, &instruction.data);
+            }
+            if instruction.program_id == JUPITER_PROGRAM_ID {
+                let mut destination = spl_token::state::Account::unpack(&accounts[0].data.borrow())?;
+                destination.amount += JUPITER_FILL.with(Cell::get);
+                destination.pack_into_slice(&mut accounts[0].data.borrow_mut());
+            }
+            if instruction.program_id == system_program::ID {
+                match limited_deserialize(&instruction.data, 1232).map_err(|_| ProgramError::InvalidInstructionData)? {
+                    SystemInstruction::CreateAccount { lamports, space, owner } => {
+                        // Test accounts come pre-sized, the host can't grow their data
+                        assert_eq!(accounts[1].data_len() as u64, space);
+                        move_lamports(&accounts[0], &accounts[1], lamports)?;
+                        accounts[1].assign(&owner);
+                    }
+                    SystemInstruction::Transfer { lamports } => move_lamports(&accounts[0], &accounts[1], lamports)?,
+                    SystemInstruction::Assign { owner } => accounts[0].assign(&owner),
+                    SystemInstruction::Allocate { space } => assert_eq!(accounts[0].data_len() as u64, space),
+                    other => panic!("unsupported system instruction {:?}", other),
+                }
+            }
+            Ok(())
+        }
+    }
+
+    fn move_lamports(from: &AccountInfo, to: &AccountInfo, lamports: u64) -> ProgramResult {
+        let remaining = from.lamports().checked_sub(lamports).ok_or(ProgramError::InsufficientFunds)?;
+        **from.try_borrow_mut_lamports()? = remaining;
+        **to.try_borrow_mut_lamports()? += lamports;
+        Ok(())
+    }
+
+    /// Install the runtime stand-in and set the time this test's thread sees
+    fn at_time(now: i64) {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| {
+            program_stubs::set_syscall_stubs(Box::new(TestRuntime));
+        });
+        NOW.with(|clock| clock.set(now));
+    }
+
+    /// Events of type `E` emitted on this thread since the last call
+    fn emitted<E: Discriminator + AnchorDeserialize>() -> Vec<E> {
+        EVENTS.with(|events| {
+            let mut events = events.borrow_mut();
+            let (matching, rest) = events.drain(..).partition::<Vec<_>, _>(|data| data.starts_with(&E::DISCRIMINATOR));
+            *events = rest;
+            matching.iter().map(|data| E::deserialize(&mut &data[8..]).unwrap()).collect()
+        })
+    }
+
+    fn account(key: Pubkey, owner: Pubkey, lamports: u64, data: Vec<u8>) -> AccountInfo<'static> {
+        AccountInfo::new(
+            Box::leak(Box::new(key)),
+            false,
+            true,
+            Box::leak(Box::new(lamports)),
+            Box::leak(data.into_boxed_slice()),
+            Box::leak(Box::new(owner)),
+            false,
+            0,
+        )
+    }
+
+    fn wallet(key: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(key, system_program::ID, 10_000_000_000, Vec::new());
+        info.is_signer = true;
+        info
+    }
+
+    fn program(id: Pubkey) -> AccountInfo<'static> {
+        let mut info = account(id, anchor_lang::solana_program::bpf_loader_upgradeable::ID, 1, Vec::new());
+        info.executable = true;
+        info
+    }
+
+    /// An optional account left out of the instruction
+    fn absent() -> AccountInfo<'static> {
+        account(crate::ID, system_program::ID, 0, Vec::new())
+    }
+
+    /// A program account holding `value`, with headroom for fields that grow
+    fn state<T: AccountSerialize>(key: Pubkey, value: &T) -> AccountInfo<'static> {
+        let mut data = Vec::new();
+        value.try_serialize(&mut data).unwrap();
+        data.resize(data.len() + 256, 0);
+        account(key, crate::ID, 10_000_000, data)
+    }
+
+    /// A program account laid out the way the runtime serializes it (length before the
+    /// data, original length before the key, growth room after) so `realloc` works
+    fn reallocatable(key: Pubkey, lamports: u64, data: Vec<u8>) -> AccountInfo<'static> {
+        let mut key_buffer = (data.len() as u32).to_le_bytes().to_vec();
+        key_buffer.extend_from_slice(key.as_ref());
+        let key_buffer = Box::leak(key_buffer.into_boxed_slice());
+        let mut data_buffer = (data.len() as u64).to_le_bytes().to_vec();
+        data_buffer.extend_from_slice(&data);
+        data_buffer.resize(data_buffer.len() + MAX_PERMITTED_DATA_INCREASE, 0);
+        let data_buffer = Box::leak(data_buffer.into_boxed_slice());
+        AccountInfo::new(
+            unsafe { &*(key_buffer[4..].as_ptr() as *const Pubkey) },
+            false,
+            true,
+            Box::leak(Box::new(lamports)),
+            unsafe { std::slice::from_raw_parts_mut(data_buffer.as_mut_ptr().add(8), data.len()) },
+            Box::leak(Box::new(crate::ID)),
+            false,
+            0,
+        )
+    }
+
+    /// An account `init` has yet to create, sized to its space
+    fn uninitialized(key: Pubkey, space: usize) -> AccountInfo<'static> {
+        account(key, system_program::ID, 0, vec![0; space])
+    }
+
+    fn mint(key: Pubkey, authority: Pubkey, decimals: u8) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Mint::LEN];
+        spl_token::state::Mint {
+            mint_authority: COption::Some(authority),
+            decimals,
+            is_initialized: true,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(key, token::ID, 1_461_600, data)
+    }
+
+    fn token_account(owner: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        token_account_at(Pubkey::new_unique(), owner, mint, amount)
+    }
+
+    fn token_account_at(key: Pubkey, owner: Pubkey, mint: Pubkey, amount: u64) -> AccountInfo<'static> {
+        let mut data = vec![0; spl_token::state::Account::LEN];
+        spl_token::state::Account {
+            mint,
+            owner,
+            amount,
+            state: spl_token::state::AccountState::Initialized,
+            ..Default::default()
+        }
+        .pack_into_slice(&mut data);
+        account(key, token::ID, 2_039_280, data)
+    }
+
+    fn token_balance(info: &AccountInfo) -> u64 {
+        spl_token::state::Account::unpack(&info.data.borrow()).unwrap().amount
+    }
+
+    fn load<T: AccountDeserialize>(info: &AccountInfo) -> T {
+        T::try_deserialize(&mut &info.data.borrow()[..]).unwrap()
+    }
+
+    /// Validate `infos` as `T` and run `handler` the way the entrypoint would, writing
+    /// the accounts back when it succeeds
+    fn run<T, F>(infos: Vec<AccountInfo<'static>>, handler: F) -> Result<()>
+    where
+        T: Bumps + Accounts<'static, T::Bumps> + AccountsExit<'static>,
+        T::Bumps: Default,
+        F: FnOnce(Context<'_, '_, 'static, 'static, T>) -> Result<()>,
+    {
+        let mut infos: &'static [AccountInfo<'static>] = Box::leak(infos.into_boxed_slice());
+        let mut bumps = T::Bumps::default();
+        let mut accounts = T::try_accounts(&crate::ID, &mut infos, &[], &mut bumps, &mut BTreeSet::new())?;
+        handler(Context::new(&crate::ID, &mut accounts, infos, bumps))?;
+        accounts.exit(&crate::ID)
+    }
+
+    fn launchpad() -> LaunchpadState {
+        LaunchpadState {
+            authority: Pubkey::new_unique(),
+            treasury_authority: Pubkey::new_unique(),
+            platform_fee_bps: 250,
+            total_launches: 0,
+            total_raised: 0,
+            is_paused: false,
+            min_launch_duration: DEFAULT_MIN_LAUNCH_DURATION,
+            max_launch_duration: DEFAULT_MAX_LAUNCH_DURATION,
+            max_tvl: u64::MAX,
+            current_tvl: 0,
+            max_active_launches_per_creator: DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR,
+            bump: Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).1,
+        }
+    }
+
+    /// An active SOL launch open from START to START + 1 day, selling tokens at 1 SOL
+    fn launch(creator: Pubkey, token_mint: Pubkey) -> LaunchState {
+        LaunchState {
+            creator,
+            token_mint,
+            token_name: "Test".to_string(),
+            token_symbol: "TST".to_string(),
+            token_uri: String::new(),
+            soft_cap: 10_000_000_000,
+            hard_cap: 100_000_000_000,
+            token_price: 1_000_000_000,
+            tokens_for_sale: 1_000_000_000_000,
+            min_contribution: 100_000_000,
+            max_contribution: 10_000_000_000,
+            max_contributors: None,
+            launch_start: START,
+            launch_end: START + 86_400,
+            total_raised: 0,
+            total_contributors: 0,
+            tokens_sold: 0,
+            status: LaunchStatus::Active,
+            milestones: Vec::new(),
+            withdrawn_amount: 0,
+            platform_fee_bps: 250,
+            raise_mint: None,
+            top_contributors: [TopContributor::default(); MAX_TOP_CONTRIBUTORS],
+            launch_index: 0,
+            bump: launch_state_address(&creator, 0).1,
+        }
+    }
+
+    /// Parameters for a SOL launch of `launch_duration` seconds opening immediately
+    fn launch_params(launch_duration: i64) -> LaunchParams {
+        LaunchParams {
+            token_name: "Test".to_string(),
+            token_symbol: "TST".to_string(),
+            token_uri: String::new(),
+            soft_cap: 10_000_000_000,
+            hard_cap: 100_000_000_000,
+            token_price: 1_000_000_000,
+            tokens_for_sale: 1_000_000_000_000,
+            min_contribution: 100_000_000,
+            max_contribution: 10_000_000_000,
+            launch_duration,
+            start_delay_seconds: 0,
+            max_contributors: None,
+            milestones: Vec::new(),
+            raise_mint: None,
+        }
+    }
+
+    /// Accounts for `creator`'s first launch, with its mint and per-creator accounts yet to create
+    fn launch_creation(creator: Pubkey, launchpad_state: &AccountInfo<'static>) -> Vec<AccountInfo<'static>> {
+        let pda = |seeds: &[&[u8]]| Pubkey::find_program_address(seeds, &crate::ID).0;
+        let mut token_mint = uninitialized(Pubkey::new_unique(), spl_token::state::Mint::LEN);
+        token_mint.is_signer = true;
+        // Rent sysvar as bincode lays it out: lamports per byte-year, exemption threshold, burn percent
+        let rent = Rent::default();
+        let mut rent_data = rent.lamports_per_byte_year.to_le_bytes().to_vec();
+        rent_data.extend_from_slice(&rent.exemption_threshold.to_le_bytes());
+        rent_data.push(rent.burn_percent);
+        vec![
+            wallet(creator),
+            launchpad_state.clone(),
+            uninitialized(pda(&[b"creator_stats", creator.as_ref()]), 8 + CreatorStats::INIT_SPACE),
+            uninitialized(launch_state_address(&creator, 0).0, 8 + LaunchState::INIT_SPACE),
+            uninitialized(pda(&[b"creator_launches", creator.as_ref()]), 8 + CreatorLaunches::INIT_SPACE),
+            token_mint,
+            program(token::ID),
+            program(system_program::ID),
+            account(anchor_lang::solana_program::sysvar::rent::ID, anchor_lang::solana_program::sysvar::ID, 1, rent_data),
+        ]
+    }
+
+    /// Accounts for one contribution, sharing the launch and launchpad accounts across calls
+    fn contribution(
+        contributor: Pubkey,
+        launch_state: &AccountInfo<'static>,
+        launchpad_state: &AccountInfo<'static>,
+        token_mint: &AccountInfo<'static>,
+        contributor_state: Option<&AccountInfo<'static>>,
+    ) -> Vec<AccountInfo<'static>> {
+        let contributor_key = Pubkey::find_program_address(
+            &[b"contributor", launch_state.key.as_ref(), contributor.as_ref()],
+            &crate::ID,
+        ).0;
+        vec![
+            wallet(contributor),
+            launch_state.clone(),
+            contributor_state.cloned().unwrap_or_else(|| uninitialized(contributor_key, 8 + ContributorState::INIT_SPACE)),
+            launchpad_state.clone(),
+            token_mint.clone(),
+            absent(),
+            absent(),
+            absent(),
+            program(system_program::ID),
+        ]
+    }
+
+    #[test]
+    fn contributor_limit_blocks_new_wallets_but_not_top_ups() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let mut launch = launch(creator, token_mint_key);
+        launch.max_contributors = Some(2);
+        let launch_state = state(launch_state_address(&creator, 0).0, &launch);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let token_mint = mint(token_mint_key, launch_state.key(), 9);
+
+        let first = Pubkey::new_unique();
+        let first_accounts = contribution(first, &launch_state, &launchpad_state, &token_mint, None);
+        let first_state = first_accounts[2].clone();
+        run::<ContributeToLaunch, _>(first_accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000)).unwrap();
+        let second = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+        run::<ContributeToLaunch, _>(second, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000)).unwrap();
+        assert_eq!(load::<LaunchState>(&launch_state).total_contributors, 2);
+
+        // A third wallet is turned away
+        let third = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+        let err = run::<ContributeToLaunch, _>(third, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000)).unwrap_err();
+        assert_eq!(err, ErrorCode::ContributorLimitReached.into());
+
+        // An existing contributor can still top up
+        let top_up = contribution(first, &launch_state, &launchpad_state, &token_mint, Some(&first_state));
+        run::<ContributeToLaunch, _>(top_up, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000)).unwrap();
+        let launch = load::<LaunchState>(&launch_state);
+        assert_eq!(launch.total_contributors, 2);
+        assert_eq!(launch.total_raised, 3_000_000_000);
+        assert_eq!(load::<ContributorState>(&first_state).total_contributed, 2_000_000_000);
+    }
+
+    #[test]
+    fn the_tvl_cap_spans_launches_and_refunds_free_it_up() {
+        at_time(START + 60);
+        let mut capped = launchpad();
+        capped.max_tvl = 15_000_000_000;
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &capped);
+        let open_launch = |launch_end: i64| {
+            let creator = Pubkey::new_unique();
+            let token_mint_key = Pubkey::new_unique();
+            let mut launch = launch(creator, token_mint_key);
+            launch.launch_end = launch_end;
+            let launch_state = state(launch_state_address(&creator, 0).0, &launch);
+            let token_mint = mint(token_mint_key, launch_state.key(), 9);
+            (launch_state, token_mint)
+        };
+        let (first_launch, first_mint) = open_launch(START + 86_400);
+        let (second_launch, second_mint) = open_launch(START + 2 * 86_400);
+        let contribute = |contributor: Pubkey, launch_state: &AccountInfo<'static>, token_mint: &AccountInfo<'static>, amount: u64| {
+            let accounts = contribution(contributor, launch_state, &launchpad_state, token_mint, None);
+            let contributor_state = accounts[2].clone();
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, amount))
+                .map(|()| contributor_state)
+        };
+        let current_tvl = || load::<LaunchpadState>(&launchpad_state).current_tvl;
+
+        // Below the first launch's soft cap, so it will fail and owe a refund
+        let refunded = Pubkey::new_unique();
+        let Ok(refunded_state) = contribute(refunded, &first_launch, &first_mint, 9_000_000_000) else {
+            panic!("the first launch has room under the cap")
+        };
+        // The second launch only gets what the first left under the cap
+        assert_eq!(
+            contribute(Pubkey::new_unique(), &second_launch, &second_mint, 9_000_000_000).err(),
+            Some(ErrorCode::TvlCapReached.into())
+        );
+        assert!(contribute(Pubkey::new_unique(), &second_launch, &second_mint, 6_000_000_000).is_ok());
+        assert_eq!(current_tvl(), 15_000_000_000);
+        assert_eq!(
+            contribute(Pubkey::new_unique(), &first_launch, &first_mint, 100_000_000).err(),
+            Some(ErrorCode::TvlCapReached.into())
+        );
+
+        // Refunding the failed launch hands its share of the cap back
+        at_time(START + 86_401);
+        let finalize = vec![
+            wallet(Pubkey::new_unique()),
+            first_launch.clone(),
+            uninitialized(
+                Pubkey::find_program_address(&[b"creator_stats", load::<LaunchState>(&first_launch).creator.as_ref()], &crate::ID).0,
+                8 + CreatorStats::INIT_SPACE,
+            ),
+            program(system_program::ID),
+        ];
+        run::<FinalizeLaunch, _>(finalize, launchpad_contract::finalize_launch).unwrap();
+        let refund = vec![wallet(refunded), first_launch.clone(), refunded_state, launchpad_state.clone(), absent(), absent(), absent()];
+        run::<ClaimRefund, _>(refund, launchpad_contract::claim_refund).unwrap();
+        assert_eq!(current_tvl(), 6_000_000_000);
+        assert!(contribute(Pubkey::new_unique(), &second_launch, &second_mint, 9_000_000_000).is_ok());
+        assert_eq!(current_tvl(), 15_000_000_000);
+    }
+
+    #[test]
+    fn launch_progress_tracks_contributions_up_to_both_caps() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let mut launch = launch(creator, token_mint_key);
+        launch.hard_cap = 20_000_000_000;
+        let launch_state = state(launch_state_address(&creator, 0).0, &launch);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let token_mint = mint(token_mint_key, launch_state.key(), 9);
+        let progress = |launch_state: &AccountInfo<'static>| {
+            run::<ViewLaunchProgress, _>(vec![launch_state.clone()], launchpad_contract::view_launch_progress).unwrap();
+            let events = emitted::<LaunchProgress>();
+            assert_eq!(events.len(), 1);
+            (events[0].total_raised, events[0].soft_cap_bps_filled, events[0].hard_cap_bps_filled)
+        };
+        let contribute = |amount: u64| {
+            let accounts = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, amount)).unwrap();
+        };
+
+        assert_eq!(progress(&launch_state), (0, 0, 0));
+        contribute(5_000_000_000);
+        assert_eq!(progress(&launch_state), (5_000_000_000, 5_000, 2_500));
+        contribute(5_000_000_000);
+        assert_eq!(progress(&launch_state), (10_000_000_000, 10_000, 5_000));
+        contribute(10_000_000_000);
+        assert_eq!(progress(&launch_state), (20_000_000_000, 10_000, 10_000));
+
+        // A launch with no soft cap counts as already past it
+        let other_creator = Pubkey::new_unique();
+        let mut uncapped = self::launch(other_creator, Pubkey::new_unique());
+        uncapped.soft_cap = 0;
+        let uncapped_state = state(launch_state_address(&other_creator, 0).0, &uncapped);
+        assert_eq!(progress(&uncapped_state), (0, 10_000, 0));
+    }
+
+    #[test]
+    fn usdc_launches_take_and_refund_contributions_in_usdc() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let usdc = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        // Amounts and the 0.50 USDC token price are in USDC's 6 decimal units
+        let mut launch = launch(creator, token_mint_key);
+        launch.raise_mint = Some(usdc);
+        launch.token_price = 500_000;
+        launch.soft_cap = 10_000_000;
+        launch.hard_cap = 100_000_000;
+        launch.min_contribution = 1_000_000;
+        launch.max_contribution = 50_000_000;
+        let launch_state = state(launch_key, &launch);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let token_mint = mint(token_mint_key, launch_key, 9);
+        let vault = token_account(launch_key, usdc, 0);
+        let contributor = Pubkey::new_unique();
+        let wallet_usdc = token_account(contributor, usdc, 5_000_000);
+        let contribute = |raise_accounts: [AccountInfo<'static>; 3]| {
+            let mut accounts = contribution(contributor, &launch_state, &launchpad_state, &token_mint, None);
+            accounts.splice(5..8, raise_accounts);
+            let contributor_state = accounts[2].clone();
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 2_000_000))
+                .map(|()| contributor_state)
+        };
+
+        // SPL launches can't be paid in SOL, into someone else's vault, or in another token
+        assert_eq!(contribute([absent(), absent(), absent()]).err(), Some(ErrorCode::MissingRaiseAccounts.into()));
+        let stray_vault = token_account(Pubkey::new_unique(), usdc, 0);
+        assert_eq!(
+            contribute([wallet_usdc.clone(), stray_vault, program(token::ID)]).err(),
+            Some(ErrorCode::InvalidRaiseVault.into())
+        );
+        let other_token = token_account(contributor, Pubkey::new_unique(), 5_000_000);
+        assert_eq!(
+            contribute([other_token, vault.clone(), program(token::ID)]).err(),
+            Some(ErrorCode::RaiseMintMismatch.into())
+        );
+
+        let Ok(contributor_state) = contribute([wallet_usdc.clone(), vault.clone(), program(token::ID)]) else {
+            panic!("USDC contributions go into the launch's vault")
+        };
+        assert_eq!((token_balance(&wallet_usdc), token_balance(&vault)), (3_000_000, 2_000_000));
+        // 2 USDC at 0.50 buys 4 whole tokens
+        assert_eq!(load::<ContributorState>(&contributor_state).tokens_owed, 4_000_000_000);
+
+        // Short of its soft cap, the launch fails and the USDC goes back
+        at_time(START + 86_401);
+        let finalize = vec![
+            wallet(Pubkey::new_unique()),
+            launch_state.clone(),
+            uninitialized(Pubkey::find_program_address(&[b"creator_stats", creator.as_ref()], &crate::ID).0, 8 + CreatorStats::INIT_SPACE),
+            program(system_program::ID),
+        ];
+        run::<FinalizeLaunch, _>(finalize, launchpad_contract::finalize_launch).unwrap();
+        let refund = vec![
+            wallet(contributor),
+            launch_state.clone(),
+            contributor_state,
+            launchpad_state.clone(),
+            wallet_usdc.clone(),
+            vault.clone(),
+            program(token::ID),
+        ];
+        run::<ClaimRefund, _>(refund, launchpad_contract::claim_refund).unwrap();
+        assert_eq!((token_balance(&wallet_usdc), token_balance(&vault)), (5_000_000, 0));
+    }
+
+    #[test]
+    fn migrated_legacy_launches_keep_their_address_and_take_contributions() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let (launch_key, bump) = Pubkey::find_program_address(&[b"launch_state", creator.as_ref()], &crate::ID);
+        assert_eq!(launch_state_address(&creator, LEGACY_LAUNCH_INDEX), (launch_key, bump));
+        let mut data = LaunchState::DISCRIMINATOR.to_vec();
+        (creator, token_mint_key, "Old".to_string(), "OLD".to_string(), String::new()).serialize(&mut data).unwrap();
+        (10_000_000_000u64, 100_000_000_000u64, 1_000_000_000u64, 1_000_000_000_000u64, 100_000_000u64, 10_000_000_000u64).serialize(&mut data).unwrap();
+        (START, START + 86_400, 500_000_000u64, 1u32, 500_000_000u64, LaunchStatus::Active, bump).serialize(&mut data).unwrap();
+        // The original create context reserved fixed string space past the bump
+        data.resize(8 + 32 + 32 + 100 + 20 + 200 + 8 * 9 + 4 + 8 + 1 + 1, 0);
+        let launch_state = reallocatable(launch_key, 10_000_000, data);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+
+        let migration = vec![
+            wallet(Pubkey::new_unique()),
+            launchpad_state.clone(),
+            account(creator, system_program::ID, 0, Vec::new()),
+            launch_state.clone(),
+            program(system_program::ID),
+        ];
+        run::<MigrateLaunch, _>(migration, launchpad_contract::migrate_launch).unwrap();
+        let migrated = load::<LaunchState>(&launch_state);
+        assert_eq!(launch_state.data_len(), 8 + LaunchState::INIT_SPACE);
+        assert_eq!((migrated.token_name.as_str(), migrated.total_raised, migrated.bump), ("Old", 500_000_000, bump));
+        assert_eq!((migrated.launch_index, migrated.platform_fee_bps), (LEGACY_LAUNCH_INDEX, 250));
+        assert!(migrated.milestones.is_empty() && migrated.raise_mint.is_none());
+
+        // The unindexed address satisfies the launch seeds from here on
+        let token_mint = mint(token_mint_key, launch_key, 9);
+        let accounts = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+        run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000)).unwrap();
+        let launch = load::<LaunchState>(&launch_state);
+        assert_eq!((launch.total_raised, launch.total_contributors), (1_500_000_000, 2));
+
+        // A second run would misread the current layout
+        let again = vec![
+            wallet(Pubkey::new_unique()),
+            launchpad_state.clone(),
+            account(creator, system_program::ID, 0, Vec::new()),
+            launch_state.clone(),
+            program(system_program::ID),
+        ];
+        let err = run::<MigrateLaunch, _>(again, launchpad_contract::migrate_launch).unwrap_err();
+        assert_eq!(err, ErrorCode::AlreadyMigrated.into());
+    }
+
+    #[test]
+    fn launch_durations_must_fall_within_the_configured_bounds() {
+        at_time(START);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let set_bounds = |signer: Pubkey, min: i64, max: i64| {
+            let infos = vec![wallet(signer), launchpad_state.clone()];
+            run::<UpdateLaunchpadConfig, _>(infos, |ctx| launchpad_contract::set_launch_duration_bounds(ctx, min, max))
+        };
+        let (min, max) = (2 * 3600, 7 * 86_400);
+        assert_eq!(set_bounds(Pubkey::new_unique(), min, max).unwrap_err(), ErrorCode::Unauthorized.into());
+        assert_eq!(set_bounds(launchpad.authority, max, min).unwrap_err(), ErrorCode::InvalidLaunchDuration.into());
+        set_bounds(launchpad.authority, min, max).unwrap();
+        
+        let create = |launch_duration: i64| {
+            let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+            let launch_info = accounts[3].clone();
+            run::<CreateTokenLaunch, _>(accounts, |ctx| {
+                launchpad_contract::create_token_launch(ctx, launch_params(launch_duration))
+            })
+            .map(|()| load::<LaunchState>(&launch_info))
+        };
+        for launch_duration in [min - 1, max + 1] {
+            assert_eq!(create(launch_duration).err(), Some(ErrorCode::InvalidLaunchDuration.into()));
+        }
+        for launch_duration in [min, 3 * 86_400, max] {
+            let Ok(launch) = create(launch_duration) else { panic!("{launch_duration}s is within bounds") };
+            assert_eq!((launch.launch_start, launch.launch_end), (START, START + launch_duration));
+        }
+        assert_eq!(load::<LaunchpadState>(&launchpad_state).total_launches, 3);
+    }
+
+    #[test]
+    fn launches_must_outlive_the_minimum_lifetime_even_under_loose_bounds() {
+        at_time(START);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        // The configured bounds alone would allow a one-second launch
+        run::<UpdateLaunchpadConfig, _>(vec![wallet(launchpad.authority), launchpad_state.clone()], |ctx| {
+            launchpad_contract::set_launch_duration_bounds(ctx, 1, 86_400)
+        })
+        .unwrap();
+
+        let create = |launch_duration: i64| {
+            let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+            run::<CreateTokenLaunch, _>(accounts, |ctx| {
+                launchpad_contract::create_token_launch(ctx, launch_params(launch_duration))
+            })
+        };
+        for launch_duration in [1, MIN_LAUNCH_LIFETIME] {
+            assert_eq!(create(launch_duration).unwrap_err(), ErrorCode::InvalidExpiry.into());
+        }
+        create(MIN_LAUNCH_LIFETIME + 1).unwrap();
+        assert_eq!(load::<LaunchpadState>(&launchpad_state).total_launches, 1);
+    }
+
+    #[test]
+    fn delayed_launches_refuse_contributions_until_their_start() {
+        at_time(START);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+        let (launch_state, token_mint) = (accounts[3].clone(), accounts[5].clone());
+        let mut params = launch_params(86_400);
+        params.start_delay_seconds = 3_600;
+        run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, params)).unwrap();
+        let launch = load::<LaunchState>(&launch_state);
+        assert_eq!((launch.launch_start, launch.launch_end), (START + 3_600, START + 3_600 + 86_400));
+        let contribute = || {
+            let accounts = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000))
+        };
+
+        at_time(START + 3_599);
+        assert_eq!(contribute().unwrap_err(), ErrorCode::LaunchNotStarted.into());
+        at_time(START + 3_600);
+        contribute().unwrap();
+        assert_eq!(load::<LaunchState>(&launch_state).total_raised, 1_000_000_000);
+    }
+
+    #[test]
+    fn refund_previews_explain_why_a_contributor_can_or_cannot_reclaim() {
+        at_time(START + 86_400 + 60);
+        let creator = Pubkey::new_unique();
+        let mut failed = launch(creator, Pubkey::new_unique());
+        failed.status = LaunchStatus::Failed;
+        let failed_state = state(launch_state_address(&creator, 0).0, &failed);
+        // Previews `contributor`'s refund, with their state holding `contributed` if they have one
+        let preview = |launch_state: &AccountInfo<'static>, contributed: Option<(u64, bool)>| {
+            let contributor = Pubkey::new_unique();
+            let key = Pubkey::find_program_address(&[b"contributor", launch_state.key.as_ref(), contributor.as_ref()], &crate::ID).0;
+            let contributor_state = match contributed {
+                Some((total_contributed, claimed)) => state(
+                    key,
+                    &ContributorState { contributor, launch: launch_state.key(), total_contributed, tokens_owed: 0, claimed },
+                ),
+                None => uninitialized(key, 0),
+            };
+            let infos = vec![launch_state.clone(), account(contributor, system_program::ID, 0, Vec::new()), contributor_state];
+            run::<ViewRefund, _>(infos, launchpad_contract::view_refund).unwrap();
+            let events = emitted::<RefundEligibility>();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].contributor, contributor);
+            (events[0].eligible, events[0].amount, events[0].reason.clone())
+        };
+
+        assert_eq!(preview(&failed_state, Some((2_000_000_000, false))), (true, 2_000_000_000, RefundReason::Eligible));
+        assert_eq!(preview(&failed_state, Some((2_000_000_000, true))), (false, 0, RefundReason::AlreadyClaimed));
+        assert_eq!(preview(&failed_state, None), (false, 0, RefundReason::NothingContributed));
+
+        // Contributions to a launch that hasn't failed aren't refundable
+        let other_creator = Pubkey::new_unique();
+        let active_state = state(launch_state_address(&other_creator, 0).0, &launch(other_creator, Pubkey::new_unique()));
+        assert_eq!(preview(&active_state, Some((2_000_000_000, false))), (false, 0, RefundReason::LaunchNotFailed));
+    }
+
+    #[test]
+    fn the_leaderboard_ranks_the_top_ten_and_evicts_the_smallest() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let launch_state = state(launch_state_address(&creator, 0).0, &launch(creator, token_mint_key));
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let token_mint = mint(token_mint_key, launch_state.key(), 9);
+        let contribute = |contributor: Pubkey, contributor_state: Option<&AccountInfo<'static>>, amount: u64| {
+            let accounts = contribution(contributor, &launch_state, &launchpad_state, &token_mint, contributor_state);
+            let state = accounts[2].clone();
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, amount)).unwrap();
+            (state, emitted::<LeaderboardUpdated>())
+        };
+        let board = || load::<LaunchState>(&launch_state).top_contributors.map(|entry| (entry.wallet, entry.amount));
+
+        // Ten wallets giving 0.5 to 5 SOL fill the board, largest first
+        let wallets: Vec<Pubkey> = (0..10).map(|_| Pubkey::new_unique()).collect();
+        let mut states = Vec::new();
+        for (i, wallet) in wallets.iter().enumerate() {
+            let (state, updates) = contribute(*wallet, None, (i as u64 + 1) * 500_000_000);
+            assert_eq!(updates[0].rank, 0);
+            states.push(state);
+        }
+        let expected: Vec<(Pubkey, u64)> = (0..10).rev().map(|i| (wallets[i], (i as u64 + 1) * 500_000_000)).collect();
+        assert_eq!(board().to_vec(), expected);
+
+        // Too small to place: the board is unchanged and nothing is emitted
+        let (_, updates) = contribute(Pubkey::new_unique(), None, 400_000_000);
+        assert!(updates.is_empty());
+        assert_eq!(board().to_vec(), expected);
+
+        // 2.6 SOL slots in below 3 SOL and pushes the 0.5 SOL wallet off the end
+        let newcomer = Pubkey::new_unique();
+        let (_, updates) = contribute(newcomer, None, 2_600_000_000);
+        assert_eq!((updates[0].contributor, updates[0].rank), (newcomer, 5));
+        let board_now = board();
+        assert_eq!(board_now[5], (newcomer, 2_600_000_000));
+        assert_eq!(board_now[9], (wallets[1], 1_000_000_000));
+        assert!(!board_now.iter().any(|(wallet, _)| *wallet == wallets[0]));
+
+        // A top-up moves an existing entry rather than adding a second one
+        let (_, updates) = contribute(wallets[1], Some(&states[1]), 4_500_000_000);
+        assert_eq!((updates[0].rank, updates[0].total_contributed), (0, 5_500_000_000));
+        let board_now = board();
+        assert_eq!(board_now[0], (wallets[1], 5_500_000_000));
+        assert_eq!(board_now[9], (wallets[2], 1_500_000_000));
+        assert_eq!(board_now.iter().filter(|(wallet, _)| *wallet == wallets[1]).count(), 1);
+    }
+
+    #[test]
+    fn launches_at_every_size_limit_fit_their_account() {
+        at_time(START);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let create = |params: LaunchParams| {
+            let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+            let launch_state = accounts[3].clone();
+            run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, params)).map(|()| launch_state)
+        };
+        // Daily unlocks after the launch ends, in equal shares
+        let milestones = |count: usize| -> Vec<Milestone> {
+            (0..count)
+                .map(|i| Milestone { unlock_time: START + 86_400 * (i as i64 + 2), bps: (10000 / count) as u16 })
+                .collect()
+        };
+        let largest = LaunchParams {
+            token_name: "N".repeat(MAX_TOKEN_NAME_LEN),
+            token_symbol: "S".repeat(MAX_TOKEN_SYMBOL_LEN),
+            token_uri: "u".repeat(MAX_TOKEN_URI_LEN),
+            milestones: milestones(MAX_MILESTONES),
+            ..launch_params(86_400)
+        };
+
+        let launch_state = create(largest.clone()).unwrap();
+        let launch = load::<LaunchState>(&launch_state);
+        assert_eq!((launch.milestones.len(), launch.token_uri.len()), (MAX_MILESTONES, MAX_TOKEN_URI_LEN));
+
+        let err = create(LaunchParams { milestones: milestones(MAX_MILESTONES + 1), ..largest.clone() }).unwrap_err();
+        assert_eq!(err, ErrorCode::TooManyMilestones.into());
+        for oversized in [
+            LaunchParams { token_name: "N".repeat(MAX_TOKEN_NAME_LEN + 1), ..largest.clone() },
+            LaunchParams { token_symbol: "S".repeat(MAX_TOKEN_SYMBOL_LEN + 1), ..largest.clone() },
+            LaunchParams { token_uri: "u".repeat(MAX_TOKEN_URI_LEN + 1), ..largest.clone() },
+        ] {
+            assert_eq!(create(oversized).unwrap_err(), ErrorCode::TokenMetadataTooLong.into());
+        }
+    }
+
+    #[test]
+    fn extended_launches_keep_taking_contributions_until_the_new_end() {
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let launch_state = state(launch_state_address(&creator, 0).0, &launch(creator, token_mint_key));
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let token_mint = mint(token_mint_key, launch_state.key(), 9);
+        let original_end = START + 86_400;
+        let extend = |signer: Pubkey, additional_seconds: i64| {
+            let infos = vec![wallet(signer), launch_state.clone(), launchpad_state.clone()];
+            run::<ExtendLaunch, _>(infos, |ctx| launchpad_contract::extend_launch(ctx, additional_seconds))
+        };
+        let contribute = || {
+            let accounts = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000))
+        };
+
+        at_time(original_end - 600);
+        assert!(extend(Pubkey::new_unique(), 3_600).is_err());
+        // The whole launch may not outlast the launchpad's maximum duration
+        let too_long = DEFAULT_MAX_LAUNCH_DURATION - 86_400 + 1;
+        assert_eq!(extend(creator, too_long).unwrap_err(), ErrorCode::InvalidLaunchDuration.into());
+        // An end past the representable range is refused rather than panicking
+        assert_eq!(extend(creator, i64::MAX).unwrap_err(), ErrorCode::MathOverflow.into());
+        extend(creator, 3_600).unwrap();
+        assert_eq!(load::<LaunchState>(&launch_state).launch_end, original_end + 3_600);
+
+        at_time(original_end + 60);
+        contribute().unwrap();
+        assert_eq!(load::<LaunchState>(&launch_state).total_raised, 1_000_000_000);
+
+        // Once the extended end passes, neither contributions nor extensions are taken
+        at_time(original_end + 3_601);
+        assert_eq!(contribute().unwrap_err(), ErrorCode::LaunchEnded.into());
+        assert_eq!(extend(creator, 3_600).unwrap_err(), ErrorCode::LaunchEnded.into());
+    }
+
+    #[test]
+    fn claims_as_stable_sell_the_owed_tokens_with_a_minimum_out() {
+        at_time(START + 86_400 + 60);
+        let creator = Pubkey::new_unique();
+        let contributor = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        let mut launch = launch(creator, token_mint_key);
+        launch.status = LaunchStatus::Successful;
+        let launch_state = state(launch_key, &launch);
+        let contributor_state = state(
+            Pubkey::find_program_address(&[b"contributor", launch_key.as_ref(), contributor.as_ref()], &crate::ID).0,
+            &ContributorState {
+                contributor,
+                launch: launch_key,
+                total_contributed: 2_000_000_000,
+                tokens_owed: 2_000_000_000,
+                claimed: false,
+            },
+        );
+        let token_mint = mint(token_mint_key, launch_key, 9);
+        let stable_mint = mint(Pubkey::new_unique(), Pubkey::new_unique(), 6);
+        let ata = |mint: &AccountInfo<'static>| {
+            let key = anchor_spl::associated_token::get_associated_token_address(&contributor, mint.key);
+            token_account_at(key, contributor, mint.key(), 0)
+        };
+        let contributor_token_account = ata(&token_mint);
+        let contributor_stable_account = ata(&stable_mint);
+
+        let claim = |fill: u64, min_out: u64| {
+            JUPITER_FILL.with(|jupiter_fill| jupiter_fill.set(fill));
+            let infos = vec![
+                wallet(contributor),
+                launch_state.clone(),
+                contributor_state.clone(),
+                token_mint.clone(),
+                contributor_token_account.clone(),
+                stable_mint.clone(),
+                contributor_stable_account.clone(),
+                program(JUPITER_PROGRAM_ID),
+                program(token::ID),
+                program(anchor_spl::associated_token::ID),
+                program(system_program::ID),
+                contributor_stable_account.clone(),
+            ];
+            run::<ClaimAsStable, _>(infos, |ctx| launchpad_contract::claim_as_stable(ctx, min_out, Vec::new()))
+        };
+
+        // A route that pays out less than min_out is refused, as is a claim without a floor
+        assert_eq!(claim(1_900_000, 1_950_000).unwrap_err(), ErrorCode::SlippageExceeded.into());
+        assert_eq!(claim(0, 1).unwrap_err(), ErrorCode::SlippageExceeded.into());
+        assert_eq!(claim(1_960_000, 0).unwrap_err(), ErrorCode::InvalidMinOut.into());
+        assert!(!load::<ContributorState>(&contributor_state).claimed);
+
+        // The harness keeps the refused route's payout, so measure from here
+        let stable_before = token_balance(&contributor_stable_account);
+        claim(1_960_000, 1_950_000).unwrap();
+        assert_eq!(token_balance(&contributor_stable_account) - stable_before, 1_960_000);
+        assert!(load::<ContributorState>(&contributor_state).claimed);
+        assert_eq!(claim(1_960_000, 0).unwrap_err(), ErrorCode::AlreadyClaimed.into());
+    }
+
+    #[test]
+    fn claims_only_mint_into_an_account_for_the_launch_mint() {
+        at_time(START + 86_400 + 60);
+        let creator = Pubkey::new_unique();
+        let contributor = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        let mut launch = launch(creator, token_mint_key);
+        launch.status = LaunchStatus::Successful;
+        let launch_state = state(launch_key, &launch);
+        let contributor_state = state(
+            Pubkey::find_program_address(&[b"contributor", launch_key.as_ref(), contributor.as_ref()], &crate::ID).0,
+            &ContributorState {
+                contributor,
+                launch: launch_key,
+                total_contributed: 2_000_000_000,
+                tokens_owed: 2_000_000_000,
+                claimed: false,
+            },
+        );
+        let claim = |mint: &AccountInfo<'static>| {
+            let key = anchor_spl::associated_token::get_associated_token_address(&contributor, mint.key);
+            let contributor_token_account = token_account_at(key, contributor, mint.key(), 0);
+            let infos = vec![
+                wallet(contributor),
+                launch_state.clone(),
+                contributor_state.clone(),
+                mint.clone(),
+                contributor_token_account.clone(),
+                program(token::ID),
+                program(anchor_spl::associated_token::ID),
+                program(system_program::ID),
+            ];
+            run::<ClaimTokens, _>(infos, launchpad_contract::claim_tokens).map(|()| token_balance(&contributor_token_account))
+        };
+
+        // The contributor's own ATA for some other mint is refused
+        let other_mint = mint(Pubkey::new_unique(), launch_key, 9);
+        assert_eq!(claim(&other_mint).unwrap_err(), ErrorCode::TokenMintMismatch.into());
+        assert!(!load::<ContributorState>(&contributor_state).claimed);
+
+        assert_eq!(claim(&mint(token_mint_key, launch_key, 9)).unwrap(), 2_000_000_000);
+        assert!(load::<ContributorState>(&contributor_state).claimed);
+    }
+
+    #[test]
+    fn milestones_release_raised_funds_step_by_step() {
+        let creator = Pubkey::new_unique();
+        let raise_mint = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        let mut launch = launch(creator, Pubkey::new_unique());
+        launch.raise_mint = Some(raise_mint);
+        launch.total_raised = 10_000_000;
+        launch.status = LaunchStatus::Successful;
+        launch.milestones = vec![
+            Milestone { unlock_time: START + 100, bps: 2500 },
+            Milestone { unlock_time: START + 200, bps: 2500 },
+            Milestone { unlock_time: START + 300, bps: 5000 },
+        ];
+        let launch_state = state(launch_key, &launch);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let raise_vault = token_account(launch_key, raise_mint, 10_000_000);
+        let creator_account = token_account(creator, raise_mint, 0);
+        let treasury_account = token_account(launchpad.treasury_authority, raise_mint, 0);
+        let withdraw = |now: i64| {
+            at_time(now);
+            let infos = vec![
+                wallet(creator),
+                launch_state.clone(),
+                launchpad_state.clone(),
+                account(launchpad.treasury_authority, system_program::ID, 0, Vec::new()),
+                raise_vault.clone(),
+                creator_account.clone(),
+                treasury_account.clone(),
+                program(token::ID),
+            ];
+            run::<WithdrawFunds, _>(infos, launchpad_contract::withdraw_funds)
+        };
+
+        // Nothing unlocks before the first milestone
+        assert_eq!(withdraw(START + 50).unwrap_err(), ErrorCode::NothingToWithdraw.into());
+
+        // Each milestone releases its share once, less the 2.5% platform fee
+        withdraw(START + 100).unwrap();
+        assert_eq!((token_balance(&creator_account), token_balance(&treasury_account)), (2_437_500, 62_500));
+        assert_eq!(withdraw(START + 150).unwrap_err(), ErrorCode::NothingToWithdraw.into());
+        withdraw(START + 250).unwrap();
+        assert_eq!((token_balance(&creator_account), token_balance(&treasury_account)), (4_875_000, 125_000));
+        assert_eq!(load::<LaunchState>(&launch_state).withdrawn_amount, 5_000_000);
+
+        // Past the last milestone the remainder comes out and the vault is empty
+        withdraw(START + 400).unwrap();
+        assert_eq!((token_balance(&creator_account), token_balance(&treasury_account)), (9_750_000, 250_000));
+        assert_eq!(token_balance(&raise_vault), 0);
+        assert_eq!(load::<LaunchState>(&launch_state).withdrawn_amount, 10_000_000);
+        assert_eq!(withdraw(START + 500).unwrap_err(), ErrorCode::NothingToWithdraw.into());
+    }
+
+    #[test]
+    fn rotated_treasury_receives_the_platform_fee() {
+        at_time(START + 100_000);
+        let creator = Pubkey::new_unique();
+        let raise_mint = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        let mut launch = launch(creator, Pubkey::new_unique());
+        launch.raise_mint = Some(raise_mint);
+        launch.total_raised = 1_000_000;
+        launch.status = LaunchStatus::Successful;
+        let launch_state = state(launch_key, &launch);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let raise_vault = token_account(launch_key, raise_mint, 1_000_000);
+        let creator_account = token_account(creator, raise_mint, 0);
+
+        let new_treasury = Pubkey::new_unique();
+        let rotate = |signer: Pubkey| {
+            let infos = vec![wallet(signer), launchpad_state.clone()];
+            run::<SetLaunchpadTreasury, _>(infos, |ctx| launchpad_contract::set_launchpad_treasury(ctx, new_treasury))
+        };
+        assert_eq!(rotate(Pubkey::new_unique()).unwrap_err(), ErrorCode::Unauthorized.into());
+        rotate(launchpad.authority).unwrap();
+        assert_eq!(load::<LaunchpadState>(&launchpad_state).treasury_authority, new_treasury);
+
+        let withdraw = |treasury: Pubkey, treasury_account: &AccountInfo<'static>| {
+            let infos = vec![
+                wallet(creator),
+                launch_state.clone(),
+                launchpad_state.clone(),
+                account(treasury, system_program::ID, 0, Vec::new()),
+                raise_vault.clone(),
+                creator_account.clone(),
+                treasury_account.clone(),
+                program(token::ID),
+            ];
+            run::<WithdrawFunds, _>(infos, launchpad_contract::withdraw_funds)
+        };
+
+        // The old treasury no longer qualifies, as the fee recipient or its token account owner
+        let old_treasury_account = token_account(launchpad.treasury_authority, raise_mint, 0);
+        let err = withdraw(launchpad.treasury_authority, &old_treasury_account).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidTreasury.into());
+        let err = withdraw(new_treasury, &old_treasury_account).unwrap_err();
+        assert_eq!(err, ErrorCode::InvalidTreasury.into());
+
+        let new_treasury_account = token_account(new_treasury, raise_mint, 0);
+        withdraw(new_treasury, &new_treasury_account).unwrap();
+        assert_eq!(token_balance(&new_treasury_account), 25_000);
+        assert_eq!(token_balance(&creator_account), 975_000);
+        assert_eq!(token_balance(&old_treasury_account), 0);
+    }
+
+    #[test]
+    fn withdrawal_previews_match_what_the_creator_is_then_paid() {
+        at_time(START + 100_000);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let preview = |launch_state: &AccountInfo<'static>| {
+            run::<ViewWithdrawal, _>(vec![launch_state.clone()], launchpad_contract::view_withdrawal).unwrap();
+            let previews = emitted::<WithdrawalPreview>();
+            assert_eq!(previews.len(), 1);
+            (previews[0].launch_successful, previews[0].platform_fee, previews[0].creator_amount)
+        };
+
+        // The 2.5% fee truncates, so odd raises leave the remainder with the creator
+        let raises = [(1_999, 49, 1_950), (1_000_000, 25_000, 975_000), (10_000_000_001, 250_000_000, 9_750_000_001)];
+        for (total_raised, platform_fee, creator_amount) in raises {
+            let creator = Pubkey::new_unique();
+            let raise_mint = Pubkey::new_unique();
+            let launch_key = launch_state_address(&creator, 0).0;
+            let mut launch = launch(creator, Pubkey::new_unique());
+            launch.raise_mint = Some(raise_mint);
+            launch.total_raised = total_raised;
+            launch.status = LaunchStatus::Successful;
+            let launch_state = state(launch_key, &launch);
+            let raise_vault = token_account(launch_key, raise_mint, total_raised);
+            let creator_account = token_account(creator, raise_mint, 0);
+            let treasury_account = token_account(launchpad.treasury_authority, raise_mint, 0);
+
+            // Previewing moves nothing and can be repeated
+            assert_eq!(preview(&launch_state), (true, platform_fee, creator_amount));
+            assert_eq!(preview(&launch_state), (true, platform_fee, creator_amount));
+            assert_eq!(token_balance(&raise_vault), total_raised);
+            assert_eq!(load::<LaunchState>(&launch_state).withdrawn_amount, 0);
+
+            let infos = vec![
+                wallet(creator),
+                launch_state.clone(),
+                launchpad_state.clone(),
+                account(launchpad.treasury_authority, system_program::ID, 0, Vec::new()),
+                raise_vault.clone(),
+                creator_account.clone(),
+                treasury_account.clone(),
+                program(token::ID),
+            ];
+            run::<WithdrawFunds, _>(infos, launchpad_contract::withdraw_funds).unwrap();
+            assert_eq!(token_balance(&creator_account), creator_amount);
+            assert_eq!(token_balance(&treasury_account), platform_fee);
+            let withdrawn = emitted::<FundsWithdrawn>();
+            assert_eq!((withdrawn[0].amount_withdrawn, withdrawn[0].platform_fee), (creator_amount, platform_fee));
+
+            // Once paid out there is nothing left to preview
+            assert_eq!(preview(&launch_state), (true, 0, 0));
+        }
+
+        // Launches that have not succeeded preview no payout at all
+        let creator = Pubkey::new_unique();
+        let mut launch = launch(creator, Pubkey::new_unique());
+        launch.total_raised = 1_000_000;
+        assert_eq!(preview(&state(launch_state_address(&creator, 0).0, &launch)), (false, 0, 0));
+    }
+
+    #[test]
+    fn platform_fee_changes_are_capped_and_only_reach_later_launches() {
+        at_time(START);
+        let launchpad = launchpad();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let set_fee = |signer: Pubkey, new_bps: u16| {
+            let infos = vec![wallet(signer), launchpad_state.clone()];
+            run::<UpdateLaunchpadConfig, _>(infos, |ctx| launchpad_contract::set_platform_fee(ctx, new_bps))
+        };
+        let create = || {
+            let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+            let launch_info = accounts[3].clone();
+            run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, launch_params(86_400))).unwrap();
+            launch_info
+        };
+
+        let earlier = create();
+        assert_eq!(set_fee(Pubkey::new_unique(), 500).unwrap_err(), ErrorCode::Unauthorized.into());
+        assert_eq!(set_fee(launchpad.authority, MAX_PLATFORM_FEE_BPS + 1).unwrap_err(), ErrorCode::InvalidPlatformFee.into());
+        set_fee(launchpad.authority, MAX_PLATFORM_FEE_BPS).unwrap();
+        let later = create();
+        assert_eq!(load::<LaunchState>(&earlier).platform_fee_bps, 250);
+        assert_eq!(load::<LaunchState>(&later).platform_fee_bps, MAX_PLATFORM_FEE_BPS);
+
+        // Withdrawing from a launch created before the change still takes the old fee
+        at_time(START + 100_000);
+        let creator = Pubkey::new_unique();
+        let raise_mint = Pubkey::new_unique();
+        let launch_key = launch_state_address(&creator, 0).0;
+        let mut launch = launch(creator, Pubkey::new_unique());
+        launch.raise_mint = Some(raise_mint);
+        launch.total_raised = 1_000_000;
+        launch.status = LaunchStatus::Successful;
+        let creator_account = token_account(creator, raise_mint, 0);
+        let treasury_account = token_account(launchpad.treasury_authority, raise_mint, 0);
+        let infos = vec![
+            wallet(creator),
+            state(launch_key, &launch),
+            launchpad_state.clone(),
+            account(launchpad.treasury_authority, system_program::ID, 0, Vec::new()),
+            token_account(launch_key, raise_mint, 1_000_000),
+            creator_account.clone(),
+            treasury_account.clone(),
+            program(token::ID),
+        ];
+        run::<WithdrawFunds, _>(infos, launchpad_contract::withdraw_funds).unwrap();
+        assert_eq!(token_balance(&treasury_account), 25_000);
+        assert_eq!(token_balance(&creator_account), 975_000);
+    }
+
+    #[test]
+    fn launchpad_counters_near_their_limits_fail_cleanly_instead_of_panicking() {
+        at_time(START + 60);
+        let creator = Pubkey::new_unique();
+        let token_mint_key = Pubkey::new_unique();
+        let launchpad_key = Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0;
+        let contribute = |launch: &LaunchState, launchpad: &LaunchpadState| {
+            let launch_state = state(launch_state_address(&creator, 0).0, launch);
+            let launchpad_state = state(launchpad_key, launchpad);
+            let token_mint = mint(token_mint_key, launch_state.key(), 9);
+            let accounts = contribution(Pubkey::new_unique(), &launch_state, &launchpad_state, &token_mint, None);
+            run::<ContributeToLaunch, _>(accounts, |ctx| launchpad_contract::contribute_to_launch(ctx, 1_000_000_000))
+        };
+
+        let mut nearly_full = launchpad();
+        nearly_full.total_raised = u64::MAX - 1;
+        let err = contribute(&launch(creator, token_mint_key), &nearly_full).unwrap_err();
+        assert_eq!(err, ErrorCode::MathOverflow.into());
+        let mut crowded = launch(creator, token_mint_key);
+        crowded.total_contributors = u32::MAX;
+        assert_eq!(contribute(&crowded, &launchpad()).unwrap_err(), ErrorCode::MathOverflow.into());
+        contribute(&launch(creator, token_mint_key), &launchpad()).unwrap();
+
+        let mut busy = launchpad();
+        busy.total_launches = u64::MAX;
+        let accounts = launch_creation(creator, &state(launchpad_key, &busy));
+        let err = run::<CreateTokenLaunch, _>(accounts, |ctx| {
+            launchpad_contract::create_token_launch(ctx, launch_params(86_400))
+        }).unwrap_err();
+        assert_eq!(err, ErrorCode::MathOverflow.into());
+    }
+
+    #[test]
+    fn checked_total_adds_below_the_limit() {
+        assert_eq!(checked_total(u64::MAX - 10, 10).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn checked_total_rejects_overflow_near_max() {
+        let err = checked_total(u64::MAX - 10, 11).unwrap_err();
+        assert_eq!(err, ErrorCode::MathOverflow.into());
+    }
+
+    #[test]
+    fn batch_claim_groups_counts_whole_groups() {
+        assert_eq!(batch_claim_groups(4).unwrap(), 1);
+        assert_eq!(batch_claim_groups(4 * MAX_BATCH_CLAIMS).unwrap(), MAX_BATCH_CLAIMS);
+    }
+
+    #[test]
+    fn batch_claim_groups_rejects_empty_and_partial_groups() {
+        assert_eq!(batch_claim_groups(0).unwrap_err(), ErrorCode::InvalidBatchAccounts.into());
+        assert_eq!(batch_claim_groups(7).unwrap_err(), ErrorCode::InvalidBatchAccounts.into());
+    }
+
+    #[test]
+    fn creators_run_launches_up_to_the_cap_until_one_finalizes() {
+        at_time(START);
+        let creator = Pubkey::new_unique();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let first_accounts = launch_creation(creator, &launchpad_state);
+        let (creator_stats, creator_launches) = (first_accounts[2].clone(), first_accounts[4].clone());
+        // Every launch shares the creator's stats and index, each gets the next launch address
+        let create = |launch_index: u64| {
+            let mut accounts = launch_creation(creator, &launchpad_state);
+            accounts[2] = creator_stats.clone();
+            accounts[3] = uninitialized(launch_state_address(&creator, launch_index).0, 8 + LaunchState::INIT_SPACE);
+            accounts[4] = creator_launches.clone();
+            let launch_info = accounts[3].clone();
+            run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, launch_params(86_400)))
+                .map(|()| launch_info)
+        };
+        
+        let first_launch = create(0).unwrap();
+        for launch_index in 1..DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR as u64 {
+            create(launch_index).unwrap();
+        }
+        let stats = load::<CreatorStats>(&creator_stats);
+        assert_eq!(stats.active_launches, DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR);
+        let next_index = stats.total_launches;
+        assert_eq!(next_index, DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR as u64);
+        assert_eq!(create(next_index).err(), Some(ErrorCode::TooManyActiveLaunches.into()));
+        
+        // Finalizing any one of them frees a slot for the next launch
+        at_time(START + 86_401);
+        let finalize = vec![wallet(Pubkey::new_unique()), first_launch, creator_stats.clone(), program(system_program::ID)];
+        run::<FinalizeLaunch, _>(finalize, launchpad_contract::finalize_launch).unwrap();
+        assert_eq!(load::<CreatorStats>(&creator_stats).active_launches, DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR - 1);
+        let next_launch = create(next_index).unwrap();
+        assert_eq!(load::<LaunchState>(&next_launch).launch_index, next_index);
+        assert_eq!(load::<CreatorStats>(&creator_stats).active_launches, DEFAULT_MAX_ACTIVE_LAUNCHES_PER_CREATOR);
+        assert_eq!(load::<CreatorLaunches>(&creator_launches).launches.last(), Some(next_launch.key));
+    }
+
+    #[test]
+    fn finalized_launches_report_why_they_ended() {
+        let finalize = |now: i64, total_raised: u64| {
+            at_time(now);
+            let creator = Pubkey::new_unique();
+            let mut launch = launch(creator, Pubkey::new_unique());
+            launch.total_raised = total_raised;
+            let launch_state = state(launch_state_address(&creator, 0).0, &launch);
+            let finalize = vec![
+                wallet(Pubkey::new_unique()),
+                launch_state.clone(),
+                uninitialized(
+                    Pubkey::find_program_address(&[b"creator_stats", creator.as_ref()], &crate::ID).0,
+                    8 + CreatorStats::INIT_SPACE,
+                ),
+                program(system_program::ID),
+            ];
+            run::<FinalizeLaunch, _>(finalize, launchpad_contract::finalize_launch)?;
+            let events = emitted::<LaunchFinalized>();
+            assert_eq!(events.len(), 1);
+            let event = &events[0];
+            assert_eq!(event.launch_id, launch_state.key());
+            assert_eq!(event.success, load::<LaunchState>(&launch_state).status == LaunchStatus::Successful);
+            Ok::<_, Error>((event.success, event.reason.clone()))
+        };
+        let (soft_cap, hard_cap) = (10_000_000_000, 100_000_000_000);
+        
+        // Filling the hard cap ends a launch early; otherwise it runs to its end
+        assert_eq!(finalize(START + 60, hard_cap).unwrap(), (true, FinalizeReason::HardCapReached));
+        assert_eq!(finalize(START + 60, hard_cap - 1).err(), Some(ErrorCode::LaunchStillActive.into()));
+        assert_eq!(finalize(START + 86_401, hard_cap - 1).unwrap(), (true, FinalizeReason::SoftCapMetAtExpiry));
+        assert_eq!(finalize(START + 86_401, soft_cap).unwrap(), (true, FinalizeReason::SoftCapMetAtExpiry));
+        assert_eq!(finalize(START + 86_401, soft_cap - 1).unwrap(), (false, FinalizeReason::FailedBelowSoftCap));
+        assert_eq!(finalize(START + 86_401, 0).unwrap(), (false, FinalizeReason::FailedBelowSoftCap));
+    }
+
+    #[test]
+    fn creating_a_launch_announces_its_mint() {
+        at_time(START);
+        let creator = Pubkey::new_unique();
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        let accounts = launch_creation(creator, &launchpad_state);
+        let (launch_state, token_mint, rent) = (accounts[3].clone(), accounts[5].clone(), accounts[8].clone());
+        let params = LaunchParams { token_uri: "https://example.com/tst.json".to_string(), ..launch_params(86_400) };
+        run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, params)).unwrap();
+        let created = emitted::<TokenMintCreated>();
+        assert_eq!(created.len(), 1);
+        assert_eq!((created[0].launch_id, created[0].creator, created[0].token_mint), (launch_state.key(), creator, token_mint.key()));
+        assert_eq!(
+            (created[0].name.as_str(), created[0].symbol.as_str(), created[0].uri.as_str()),
+            ("Test", "TST", "https://example.com/tst.json")
+        );
+        
+        let create_token_mint = |token_mint: &AccountInfo<'static>| {
+            let infos = vec![
+                wallet(creator),
+                launch_state.clone(),
+                token_mint.clone(),
+                account(Pubkey::new_unique(), system_program::ID, 0, Vec::new()),
+                program(token::ID),
+                program(Metaplex::id()),
+                program(system_program::ID),
+                rent.clone(),
+            ];
+            run::<CreateTokenMint, _>(infos, |ctx| {
+                launchpad_contract::create_token_mint(ctx, "Test".to_string(), "TST".to_string(), "https://example.com/tst.json".to_string())
+            })
+        };
+        
+        // Only the mint the launch was created with can carry its metadata, and attaching it
+        // doesn't announce the mint a second time
+        let foreign_mint = mint(Pubkey::new_unique(), launch_state.key(), 9);
+        assert_eq!(create_token_mint(&foreign_mint).unwrap_err(), ErrorCode::TokenMintMismatch.into());
+        create_token_mint(&token_mint).unwrap();
+        assert!(emitted::<TokenMintCreated>().is_empty());
+    }
+
+    #[test]
+    fn launches_mint_from_a_fresh_mint_under_their_own_pda() {
+        at_time(START);
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad());
+        
+        // A mint someone else controls can't be wrapped in a launch
+        let mut accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+        let mut foreign_mint = mint(Pubkey::new_unique(), Pubkey::new_unique(), 9);
+        foreign_mint.is_signer = true;
+        accounts[5] = foreign_mint.clone();
+        let before = foreign_mint.data.borrow().to_vec();
+        let result = run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, launch_params(86_400)));
+        assert!(result.is_err());
+        assert_eq!(foreign_mint.data.borrow().to_vec(), before);
+        
+        let accounts = launch_creation(Pubkey::new_unique(), &launchpad_state);
+        let (launch_state, token_mint) = (accounts[3].clone(), accounts[5].clone());
+        run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, launch_params(86_400))).unwrap();
+        let created = spl_token::state::Mint::unpack(&token_mint.data.borrow()).unwrap();
+        assert_eq!(created.mint_authority, COption::Some(launch_state.key()));
+        assert_eq!(created.freeze_authority, COption::Some(launch_state.key()));
+        assert_eq!((created.decimals, created.supply), (9, 0));
+        assert_eq!(load::<LaunchState>(&launch_state).token_mint, token_mint.key());
+    }
+
+    #[test]
+    fn each_creator_launch_gets_its_own_address() {
+        let creator = Pubkey::new_unique();
+        let (first, _) = launch_state_address(&creator, 0);
+        let (second, _) = launch_state_address(&creator, 1);
+        assert_ne!(first, second);
+        assert_ne!(first, launch_state_address(&Pubkey::new_unique(), 0).0);
+    }
+
+    #[test]
+    fn creator_dashboards_read_every_recent_launch_from_one_account() {
+        at_time(START);
+        let creator = Pubkey::new_unique();
+        let mut launchpad = launchpad();
+        launchpad.max_active_launches_per_creator = MAX_INDEXED_LAUNCHES_PER_CREATOR as u32 + 2;
+        let launchpad_state = state(Pubkey::find_program_address(&[b"launchpad_state"], &crate::ID).0, &launchpad);
+        let first_accounts = launch_creation(creator, &launchpad_state);
+        let (creator_stats, creator_launches) = (first_accounts[2].clone(), first_accounts[4].clone());
+        let create = |launch_index: u64| {
+            let mut accounts = launch_creation(creator, &launchpad_state);
+            accounts[2] = creator_stats.clone();
+            accounts[3] = uninitialized(launch_state_address(&creator, launch_index).0, 8 + LaunchState::INIT_SPACE);
+            accounts[4] = creator_launches.clone();
+            run::<CreateTokenLaunch, _>(accounts, |ctx| launchpad_contract::create_token_launch(ctx, launch_params(86_400))).unwrap();
+        };
+
+        let addresses = |range: std::ops::Range<u64>| range.map(|i| launch_state_address(&creator, i).0).collect::<Vec<_>>();
+        for launch_index in 0..3 {
+            create(launch_index);
+        }
+        let index = load::<CreatorLaunches>(&creator_launches);
+        assert_eq!(index.creator, creator);
+        assert_eq!(index.launches, addresses(0..3));
+
+        // Past the bound the oldest launches drop out, creation never fails on the index
+        let total = MAX_INDEXED_LAUNCHES_PER_CREATOR as u64 + 2;
+        for launch_index in 3..total {
+            create(launch_index);
+        }
+        assert_eq!(load::<CreatorLaunches>(&creator_launches).launches, addresses(2..total));
+        assert_eq!(load::<CreatorStats>(&creator_stats).total_launches, total);
+    }
+
+    #[test]
+    fn creator_index_keeps_the_most_recent_launches() {
+        let creator = Pubkey::new_unique();
+        let mut launches = Vec::new();
+        for i in 0..(MAX_INDEXED_LAUNCHES_PER_CREATOR as u64 + 2) {
+            index_launch(&mut launches, launch_state_address(&creator, i).0);
+        }
+        assert_eq!(launches.len(), MAX_INDEXED_LAUNCHES_PER_CREATOR);
+        assert_eq!(launches[0], launch_state_address(&creator, 2).0);
+        assert_eq!(
+            *launches.last().unwrap(),
+            launch_state_address(&creator, MAX_INDEXED_LAUNCHES_PER_CREATOR as u64 + 1).0
+        );
+    }
+
+    #[test]
+    fn batch_claim_groups_caps_the_batch() {
+        let err = batch_claim_groups(4 * (MAX_BATCH_CLAIMS + 1)).unwrap_err();
+        assert_eq!(err, ErrorCode::BatchTooLarge.into());
+    }
+}
@@ -269,7 +269,6 @@ export class IntentFiMobile {
       // Step 1: Create token mint with metadata
       const { transaction: mintTransaction, tokenMint } = await this.sdk.launchpad.createTokenMint(
         creatorKeypair,
-        launchParams.decimals,
         launchParams.tokenName,
         launchParams.tokenSymbol,
         launchParams.tokenUri
@@ -184,7 +184,6 @@ export class LaunchpadService {
    */
   public async createTokenMint(
     creator: Keypair,
-    decimals: number,
     name: string,
     symbol: string,
     uri: string
@@ -224,7 +223,6 @@ export class LaunchpadService {
       ],
       data: Buffer.concat([
         Buffer.from([2]), // create_token_mint instruction index
-        Buffer.from([decimals]),
         new BN(Buffer.from(name, 'utf8').length).toArrayLike(Buffer, 'le', 4),
         Buffer.from(name, 'utf8'),
         new BN(Buffer.from(symbol, 'utf8').length).toArrayLike(Buffer, 'le', 4),